@@ -0,0 +1,81 @@
+//! Parsing and writing large documents, as servers and clients passing
+//! bulk data around do. Run with `cargo bench`.
+
+extern crate criterion;
+extern crate xml_rpc;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::collections::HashMap;
+use std::hint::black_box;
+use xml_rpc::{parse, Call, ToXml, Value};
+
+// A listing of `rows` records, each a small struct of every scalar type.
+fn records(rows: usize) -> Vec<Value> {
+    let rows = (0..rows).map(|row| {
+        let mut record = HashMap::new();
+        record.insert("id".to_owned(), Value::Int(row as i32));
+        record.insert("name".to_owned(), Value::String(format!("record {}", row)));
+        record.insert("note".to_owned(), Value::String("a < b & c".to_owned()));
+        record.insert("score".to_owned(), Value::Double(row as f64 / 7.0));
+        record.insert("active".to_owned(), Value::Bool(row % 2 == 0));
+        record.insert(
+            "updated".to_owned(),
+            Value::DateTime("20240117T14:08:55".to_owned()),
+        );
+        Value::Struct(record)
+    });
+    vec![Value::Array(rows.collect())]
+}
+
+fn upload(bytes: usize) -> Call {
+    Call {
+        name: "files.upload".to_owned(),
+        params: vec![
+            Value::String("report.pdf".to_owned()),
+            Value::Base64((0..bytes).map(|byte| byte as u8).collect()),
+        ],
+    }
+}
+
+fn parsing(c: &mut Criterion) {
+    let response = Ok(records(10_000)).to_xml();
+    let call = upload(1 << 20).to_xml();
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(response.len() as u64));
+    group.bench_function("records", |b| {
+        b.iter(|| parse::response(black_box(response.as_bytes())).unwrap())
+    });
+    group.bench_function("records_streaming", |b| {
+        b.iter(|| parse::response_streaming(black_box(response.as_bytes())).unwrap())
+    });
+    group.throughput(Throughput::Bytes(call.len() as u64));
+    group.bench_function("upload", |b| {
+        b.iter(|| parse::call(black_box(call.as_bytes())).unwrap())
+    });
+    group.finish();
+}
+
+fn writing(c: &mut Criterion) {
+    let response = Ok(records(10_000));
+    let call = upload(1 << 20);
+    let mut out = Vec::new();
+    let mut group = c.benchmark_group("write");
+    group.throughput(Throughput::Bytes(response.to_xml().len() as u64));
+    group.bench_function("records", |b| {
+        b.iter(|| {
+            out.clear();
+            black_box(&response).write_xml(&mut out).unwrap();
+        })
+    });
+    group.throughput(Throughput::Bytes(call.to_xml().len() as u64));
+    group.bench_function("upload", |b| {
+        b.iter(|| {
+            out.clear();
+            black_box(&call).write_xml(&mut out).unwrap();
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parsing, writing);
+criterion_main!(benches);
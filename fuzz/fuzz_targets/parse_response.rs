@@ -0,0 +1,8 @@
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate xml_rpc;
+
+fuzz_target!(|data: &[u8]| {
+    xml_rpc::fuzz::parse_response(data);
+});
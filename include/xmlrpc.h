@@ -0,0 +1,73 @@
+/* C interface to the xml-rpc crate, built with the `ffi` feature. */
+
+#ifndef XMLRPC_H
+#define XMLRPC_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The kinds of `xmlrpc_value`. A call whose result holds a value of any other
+ * kind is answered with a fault. */
+typedef enum xmlrpc_kind {
+    XMLRPC_INT = 0,
+    XMLRPC_BOOL = 1,
+    XMLRPC_STRING = 2,
+    XMLRPC_DOUBLE = 3,
+    XMLRPC_DATETIME = 4,
+    XMLRPC_BASE64 = 5,
+    XMLRPC_ARRAY = 6,
+    XMLRPC_STRUCT = 7,
+    XMLRPC_NIL = 8,
+    XMLRPC_I8 = 9
+} xmlrpc_kind;
+
+/* Only the fields used by `kind` are meaningful: `int_value` for ints and
+ * bools, `double_value` for doubles, `data` and `len` for strings,
+ * date-times, base64 and i8s, and `items` and `count` for arrays and
+ * structs, whose member names are in `names`. An i8 is written out as a
+ * decimal string. */
+typedef struct xmlrpc_value {
+    int kind; /* an `xmlrpc_kind` */
+    int32_t int_value;
+    double double_value;
+    const char *data;
+    size_t len;
+    const struct xmlrpc_value *items;
+    const char *const *names;
+    size_t count;
+} xmlrpc_value;
+
+/* Returns 0 and fills `result` on success. Any other return value is sent as
+ * the fault code, with `result` holding the message as a string, if any.
+ * Parameters are only valid until the method returns. */
+typedef int (*xmlrpc_method)(void *user_data, const xmlrpc_value *params,
+                             size_t count, xmlrpc_value *result);
+
+/* Frees whatever the method allocated for `result`, once it was copied. */
+typedef void (*xmlrpc_release)(void *user_data, xmlrpc_value *result);
+
+typedef struct xmlrpc_server xmlrpc_server;
+typedef struct xmlrpc_running xmlrpc_running;
+
+xmlrpc_server *xmlrpc_server_new(void);
+void xmlrpc_server_free(xmlrpc_server *server);
+
+/* `method` may run on several threads at once. `release` may be NULL. */
+int xmlrpc_server_register(xmlrpc_server *server, const char *name,
+                           xmlrpc_method method, xmlrpc_release release,
+                           void *user_data);
+
+/* Takes ownership of `server`. Returns NULL if binding `addr` failed. */
+xmlrpc_running *xmlrpc_server_run(xmlrpc_server *server, const char *addr);
+uint16_t xmlrpc_server_port(const xmlrpc_running *running);
+void xmlrpc_server_stop(xmlrpc_running *running);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
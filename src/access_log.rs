@@ -0,0 +1,192 @@
+//! One line per HTTP request, in the formats web servers log in.
+
+use std::io::Write;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+type RecordHandler = Box<dyn Fn(&AccessRecord) + Send + Sync>;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// The layout of access log lines.
+///
+/// Both end with three fields of their own: the quoted XML-RPC method, the
+/// size of the request body and the time taken in microseconds, as in
+/// `"system.listMethods" 104 352`. Fields that are not known are `-`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// `host ident user [time] "request line" status bytes`.
+    Common,
+    /// The Common Log Format followed by the quoted `Referer` and
+    /// `User-Agent` headers.
+    Combined,
+}
+
+/// One request, as passed to an access log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccessRecord {
+    pub peer: Option<IpAddr>,
+    /// When the request head arrived.
+    pub received: SystemTime,
+    /// The HTTP method, like `POST`.
+    pub http_method: String,
+    /// The request target, query included.
+    pub path: String,
+    /// The HTTP version, like `HTTP/1.1`, where the backend reports it.
+    pub protocol: Option<String>,
+    /// The XML-RPC method called, or `None` if the request was not a call
+    /// that parsed.
+    pub method: Option<String>,
+    pub status: u16,
+    /// The declared length of the request body.
+    pub request_bytes: Option<u64>,
+    /// The length of the response body.
+    pub response_bytes: u64,
+    /// From the arrival of the request head until the response was handed
+    /// to the backend. With rouille, which writes responses itself, this
+    /// does not include sending the body.
+    pub duration: Duration,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl AccessRecord {
+    /// The record as a log line, without the line ending.
+    pub fn line(&self, format: LogFormat) -> String {
+        let mut request = format!("{} {}", self.http_method, self.path);
+        if let Some(ref protocol) = self.protocol {
+            request.push(' ');
+            request.push_str(protocol);
+        }
+        let mut line = format!(
+            "{} - - [{}] {} {} {}",
+            self.peer
+                .map_or_else(|| String::from("-"), |peer| peer.to_string()),
+            format_time(self.received),
+            quoted(Some(&request)),
+            self.status,
+            match self.response_bytes {
+                0 => String::from("-"),
+                bytes => bytes.to_string(),
+            },
+        );
+        if format == LogFormat::Combined {
+            line.push(' ');
+            line.push_str(&quoted(self.referer.as_ref()));
+            line.push(' ');
+            line.push_str(&quoted(self.user_agent.as_ref()));
+        }
+        line.push(' ');
+        line.push_str(&quoted(self.method.as_ref()));
+        line.push(' ');
+        line.push_str(
+            &self
+                .request_bytes
+                .map_or_else(|| String::from("-"), |bytes| bytes.to_string()),
+        );
+        let micros = self.duration.as_secs() * 1_000_000 + u64::from(self.duration.subsec_micros());
+        line.push(' ');
+        line.push_str(&micros.to_string());
+        line
+    }
+}
+
+enum Sink {
+    Writer(LogFormat, Mutex<Box<dyn Write + Send>>),
+    Callback(RecordHandler),
+}
+
+/// Where a server logs the requests it answers.
+///
+/// Set on a server with `Server::set_access_log`.
+pub struct AccessLog {
+    sink: Sink,
+}
+
+impl AccessLog {
+    /// Writes a line per request to `out`, like a file or `io::stderr()`.
+    /// Failed writes are ignored, so a full disk does not fail requests.
+    pub fn writer<W: Write + Send + 'static>(format: LogFormat, out: W) -> AccessLog {
+        AccessLog {
+            sink: Sink::Writer(format, Mutex::new(Box::new(out))),
+        }
+    }
+
+    /// Calls `handler` with every request, e.g. to pass
+    /// `record.line(LogFormat::Combined)` to a logging library.
+    pub fn callback<F>(handler: F) -> AccessLog
+    where
+        F: Fn(&AccessRecord) + Send + Sync + 'static,
+    {
+        AccessLog {
+            sink: Sink::Callback(Box::new(handler)),
+        }
+    }
+
+    pub(crate) fn log(&self, record: &AccessRecord) {
+        match self.sink {
+            Sink::Writer(format, ref out) => {
+                let mut line = record.line(format);
+                line.push('\n');
+                // Each line goes out in one write, so lines from
+                // concurrent requests do not interleave.
+                let mut out = out.lock().unwrap_or_else(|err| err.into_inner());
+                let _ = out.write_all(line.as_bytes()).and_then(|()| out.flush());
+            }
+            Sink::Callback(ref handler) => handler(record),
+        }
+    }
+}
+
+// Quotes `text`, escaping quotes, backslashes and control characters as
+// Apache does. `None` is written as `"-"`.
+fn quoted(text: Option<&String>) -> String {
+    let text = match text {
+        Some(text) => text,
+        None => return String::from("\"-\""),
+    };
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\x{:02x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// `time` in UTC as `10/Oct/2000:13:55:36 +0000`.
+fn format_time(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+    // Converts days since the epoch to a date in the proleptic Gregorian
+    // calendar, counting from 0000-03-01 so leap days end each 4-year cycle.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,73 @@
+use actix_web::body::BoxBody;
+use actix_web::dev::Payload;
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
+use actix_web::{FromRequest, HttpRequest, HttpResponse, Responder, ResponseError};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use super::error::FmtError;
+use super::xmlfmt::value::ToXml;
+use super::xmlfmt::{parse, Call, Fault, Response};
+
+/// Extracts the call from the request body.
+impl FromRequest for Call {
+    type Error = actix_web::Error;
+    type Future = CallFuture;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> CallFuture {
+        CallFuture(Box::pin(Bytes::from_request(req, payload)))
+    }
+}
+
+/// Resolves to the call once the whole body has been read.
+pub struct CallFuture(Pin<Box<<Bytes as FromRequest>::Future>>);
+
+impl Future for CallFuture {
+    type Output = Result<Call, actix_web::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let body = match self.0.as_mut().poll(cx) {
+            Poll::Ready(body) => body?,
+            Poll::Pending => return Poll::Pending,
+        };
+        Poll::Ready(parse::call(&body[..]).map_err(|err| InvalidCall(err).into()))
+    }
+}
+
+/// A request body that is not an XML-RPC call. It is answered with a
+/// parse error fault.
+#[derive(Debug, Error)]
+#[error("Failed to parse request: {0}")]
+pub struct InvalidCall(#[source] pub FmtError);
+
+impl ResponseError for InvalidCall {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+
+    fn error_response(&self) -> HttpResponse<BoxBody> {
+        let fault = Fault::new(Fault::PARSE_ERROR, self.to_string());
+        Reply(Err(fault)).http_response()
+    }
+}
+
+/// Sends a response back as XML-RPC.
+pub struct Reply(pub Response);
+
+impl Reply {
+    fn http_response(&self) -> HttpResponse<BoxBody> {
+        HttpResponse::Ok()
+            .content_type("text/xml")
+            .body(self.0.to_xml())
+    }
+}
+
+impl Responder for Reply {
+    type Body = BoxBody;
+
+    fn respond_to(self, _req: &HttpRequest) -> HttpResponse<BoxBody> {
+        self.http_response()
+    }
+}
@@ -0,0 +1,529 @@
+//! A client usable from any async runtime.
+//!
+//! The client only encodes calls and decodes replies. Sending the HTTP POST
+//! is left to a `Transport`, so it works the same on tokio, async-std or
+//! smol, with whatever connection reuse the transport provides. With the
+//! `tokio` feature, `TokioTransport` is one for Tokio.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std;
+#[cfg(feature = "tokio")]
+use std::collections::HashMap;
+#[cfg(feature = "tokio")]
+use std::future;
+use std::future::Future;
+#[cfg(feature = "tokio")]
+use std::io;
+use std::marker::PhantomData;
+#[cfg(feature = "tokio")]
+use std::mem;
+use std::pin::Pin;
+#[cfg(feature = "tokio")]
+use std::str;
+#[cfg(feature = "tokio")]
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+#[cfg(feature = "tokio")]
+use tokio_lib::io::{AsyncRead, AsyncWrite, ReadBuf};
+#[cfg(feature = "tokio")]
+use tokio_lib::net::TcpStream;
+
+use super::client::summarize_reply;
+use super::error::{Error, Result};
+use super::tracecontext::{self, TraceContext, TRACEPARENT, TRACESTATE};
+use super::xmlfmt::value::ToXml;
+use super::xmlfmt::{from_params, into_params, parse, Call, Fault, Params, Response};
+use Url;
+
+pub struct HttpRequest {
+    pub url: Url,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+pub type TransportFuture = Pin<Box<dyn Future<Output = Result<HttpResponse>> + Send>>;
+
+/// Sends HTTP requests on some runtime's sockets.
+pub trait Transport: Send + Sync {
+    /// Should fail only if no complete reply was received; replies with an
+    /// error status are reported by the client.
+    fn post(&self, request: HttpRequest) -> TransportFuture;
+}
+
+pub struct AsyncClient<T> {
+    transport: T,
+    trace_context: Option<TraceContext>,
+}
+
+impl<T: Transport> AsyncClient<T> {
+    pub fn new(transport: T) -> AsyncClient<T> {
+        AsyncClient {
+            transport,
+            trace_context: None,
+        }
+    }
+
+    /// Sends `context` with every call instead of that of the request being
+    /// handled on the calling thread.
+    pub fn set_trace_context(&mut self, context: Option<TraceContext>) {
+        self.trace_context = context;
+    }
+
+    pub fn call_value<Tkey>(&self, uri: &Url, name: Tkey, params: Params) -> ResponseFuture
+    where
+        Tkey: Into<String>,
+    {
+        let body = Call {
+            name: name.into(),
+            params,
+        }
+        .to_xml();
+        let mut headers = vec![("Content-Type".to_owned(), "text/xml".to_owned())];
+        if let Some(context) = self.trace_context.clone().or_else(tracecontext::current) {
+            headers.push((TRACEPARENT.to_owned(), context.traceparent));
+            if let Some(state) = context.tracestate {
+                headers.push((TRACESTATE.to_owned(), state));
+            }
+        }
+        ResponseFuture(self.transport.post(HttpRequest {
+            url: uri.clone(),
+            headers,
+            body: body.into_bytes(),
+        }))
+    }
+
+    /// Resolves to `Ok(Err(fault))` for faults, like `Client::call`.
+    pub fn call<Tkey, Treq, Tres>(&self, uri: &Url, name: Tkey, req: Treq) -> CallFuture<Tres>
+    where
+        Tkey: Into<String>,
+        Treq: Serialize,
+        Tres: DeserializeOwned,
+    {
+        let state = match into_params(&req) {
+            Ok(params) => Ok(self.call_value(uri, name, params)),
+            Err(err) => Err(Some(err.into())),
+        };
+        CallFuture {
+            state,
+            output: PhantomData,
+        }
+    }
+}
+
+fn decode_reply(reply: HttpResponse) -> Result<Response> {
+    let header = |name: &str| {
+        reply
+            .headers
+            .iter()
+            .find(|&(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.clone())
+    };
+    if reply.status < 200 || reply.status >= 300 {
+        return Err(Error::Status(summarize_reply(
+            reply.status,
+            header,
+            &reply.body,
+        )));
+    }
+    let content_type = header("Content-Type");
+    let parsed = match parse::content_charset(content_type.as_deref()) {
+        #[cfg(feature = "encoding")]
+        Some(charset) => parse::response_in_charset(reply.body.as_slice(), charset),
+        _ => parse::response(reply.body.as_slice()),
+    };
+    parsed.map_err(|err| Error::InvalidResponse {
+        reply: summarize_reply(reply.status, header, &reply.body),
+        source: err,
+    })
+}
+
+pub struct ResponseFuture(TransportFuture);
+
+impl Future for ResponseFuture {
+    type Output = Result<Response>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<Response>> {
+        self.0
+            .as_mut()
+            .poll(cx)
+            .map(|reply| reply.and_then(decode_reply))
+    }
+}
+
+pub struct CallFuture<T> {
+    // Holds the encoding error until the first poll.
+    state: std::result::Result<ResponseFuture, Option<Error>>,
+    output: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Future for CallFuture<T> {
+    type Output = Result<std::result::Result<T, Fault>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let response = match self.get_mut().state {
+            Ok(ref mut response) => response,
+            Err(ref mut err) => {
+                let err = err.take().expect("CallFuture polled after completion");
+                return Poll::Ready(Err(err));
+            }
+        };
+        Pin::new(response).poll(cx).map(|response| match response {
+            Ok(Ok(v)) => from_params(v).map(Ok).map_err(Into::into),
+            Ok(Err(v)) => Ok(Err(v)),
+            Err(v) => Err(v),
+        })
+    }
+}
+
+#[cfg(feature = "tokio")]
+type Connecting = Pin<Box<dyn Future<Output = io::Result<TcpStream>> + Send>>;
+
+/// Sends calls over plain HTTP on Tokio, keeping connections open between
+/// calls to the same host. Clones share their connections.
+///
+/// `https` URLs are refused.
+#[cfg(feature = "tokio")]
+#[derive(Clone)]
+pub struct TokioTransport {
+    idle: Arc<Mutex<HashMap<String, Vec<TcpStream>>>>,
+    max_idle_per_host: usize,
+}
+
+#[cfg(feature = "tokio")]
+impl Default for TokioTransport {
+    fn default() -> TokioTransport {
+        TokioTransport {
+            idle: Arc::default(),
+            max_idle_per_host: 8,
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl TokioTransport {
+    pub fn new() -> TokioTransport {
+        TokioTransport::default()
+    }
+
+    /// Sets how many idle connections are kept per host. Defaults to 8.
+    pub fn set_max_idle_per_host(&mut self, max: usize) {
+        self.max_idle_per_host = max;
+    }
+
+    fn checkout(&self, host: &str) -> Option<TcpStream> {
+        lock(&self.idle).get_mut(host).and_then(Vec::pop)
+    }
+
+    fn checkin(&self, host: String, stream: TcpStream) {
+        let mut idle = lock(&self.idle);
+        let streams = idle.entry(host).or_default();
+        if streams.len() < self.max_idle_per_host {
+            streams.push(stream);
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+#[cfg(feature = "tokio")]
+impl Transport for TokioTransport {
+    fn post(&self, request: HttpRequest) -> TransportFuture {
+        if request.url.scheme() != "http" {
+            let err = io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} URLs are not supported", request.url.scheme()),
+            );
+            return Box::pin(future::ready(Err(Error::Connect(err))));
+        }
+        let host = request.url.host_str().unwrap_or("").to_owned();
+        let port = request.url.port_or_known_default().unwrap_or(80);
+        let authority = format!("{}:{}", host, port);
+        let mut target = request.url.path().to_owned();
+        if let Some(query) = request.url.query() {
+            target.push('?');
+            target.push_str(query);
+        }
+        let mut head = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\n",
+            target,
+            authority,
+            request.body.len()
+        );
+        for (name, value) in &request.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        let mut out = head.into_bytes();
+        out.extend_from_slice(&request.body);
+        let (state, reused) = match self.checkout(&authority) {
+            Some(stream) => (Exchange::writing(stream), true),
+            None => (Exchange::connect(&authority), false),
+        };
+        Box::pin(Exchange {
+            transport: self.clone(),
+            authority,
+            out,
+            reused,
+            state,
+        })
+    }
+}
+
+#[cfg(feature = "tokio")]
+enum ExchangeState {
+    Connecting(Connecting),
+    Writing { stream: TcpStream, written: usize },
+    Reading { stream: TcpStream, buffer: Vec<u8> },
+    Done,
+}
+
+// One request and its reply, over a pooled connection or a new one.
+#[cfg(feature = "tokio")]
+struct Exchange {
+    transport: TokioTransport,
+    authority: String,
+    out: Vec<u8>,
+    // Whether the connection was kept from an earlier call, which the
+    // server may have closed since.
+    reused: bool,
+    state: ExchangeState,
+}
+
+#[cfg(feature = "tokio")]
+impl Exchange {
+    fn connect(authority: &str) -> ExchangeState {
+        ExchangeState::Connecting(Box::pin(TcpStream::connect(authority.to_owned())))
+    }
+
+    fn writing(stream: TcpStream) -> ExchangeState {
+        ExchangeState::Writing { stream, written: 0 }
+    }
+
+    // Retries on a new connection if a kept one failed before replying.
+    fn failed(&mut self, err: io::Error, replied: bool) -> Option<Error> {
+        if self.reused && !replied {
+            self.reused = false;
+            self.state = Exchange::connect(&self.authority);
+            return None;
+        }
+        Some(Error::Io(err))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Future for Exchange {
+    type Output = Result<HttpResponse>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<HttpResponse>> {
+        let this = &mut *self;
+        loop {
+            let state = mem::replace(&mut this.state, ExchangeState::Done);
+            this.state = match state {
+                ExchangeState::Connecting(mut connecting) => match connecting.as_mut().poll(cx) {
+                    Poll::Ready(Ok(stream)) => {
+                        let _ = stream.set_nodelay(true);
+                        Exchange::writing(stream)
+                    }
+                    Poll::Ready(Err(err)) => return Poll::Ready(Err(Error::Connect(err))),
+                    Poll::Pending => {
+                        this.state = ExchangeState::Connecting(connecting);
+                        return Poll::Pending;
+                    }
+                },
+                ExchangeState::Writing {
+                    mut stream,
+                    mut written,
+                } => {
+                    while written < this.out.len() {
+                        match Pin::new(&mut stream).poll_write(cx, &this.out[written..]) {
+                            Poll::Ready(Ok(0)) => {
+                                let err = io::Error::from(io::ErrorKind::WriteZero);
+                                match this.failed(err, false) {
+                                    Some(err) => return Poll::Ready(Err(err)),
+                                    None => break,
+                                }
+                            }
+                            Poll::Ready(Ok(sent)) => written += sent,
+                            Poll::Ready(Err(err)) => match this.failed(err, false) {
+                                Some(err) => return Poll::Ready(Err(err)),
+                                None => break,
+                            },
+                            Poll::Pending => {
+                                this.state = ExchangeState::Writing { stream, written };
+                                return Poll::Pending;
+                            }
+                        }
+                    }
+                    if written < this.out.len() {
+                        // Reconnecting.
+                        continue;
+                    }
+                    ExchangeState::Reading {
+                        stream,
+                        buffer: Vec::new(),
+                    }
+                }
+                ExchangeState::Reading {
+                    mut stream,
+                    mut buffer,
+                } => {
+                    let mut chunk = [0; 8192];
+                    let mut read = ReadBuf::new(&mut chunk);
+                    let closed = match Pin::new(&mut stream).poll_read(cx, &mut read) {
+                        Poll::Ready(Ok(())) => read.filled().is_empty(),
+                        Poll::Ready(Err(err)) => match this.failed(err, !buffer.is_empty()) {
+                            Some(err) => return Poll::Ready(Err(err)),
+                            None => continue,
+                        },
+                        Poll::Pending => {
+                            this.state = ExchangeState::Reading { stream, buffer };
+                            return Poll::Pending;
+                        }
+                    };
+                    buffer.extend_from_slice(read.filled());
+                    if closed && buffer.is_empty() {
+                        let err = io::Error::from(io::ErrorKind::UnexpectedEof);
+                        match this.failed(err, false) {
+                            Some(err) => return Poll::Ready(Err(err)),
+                            None => continue,
+                        }
+                    }
+                    match take_reply(&buffer, closed) {
+                        Ok(Some((reply, keep_alive))) => {
+                            if keep_alive {
+                                let authority = this.authority.clone();
+                                this.transport.checkin(authority, stream);
+                            }
+                            return Poll::Ready(Ok(reply));
+                        }
+                        Ok(None) if closed => {
+                            let err = io::Error::from(io::ErrorKind::UnexpectedEof);
+                            return Poll::Ready(Err(Error::Io(err)));
+                        }
+                        Ok(None) => ExchangeState::Reading { stream, buffer },
+                        Err(err) => return Poll::Ready(Err(Error::Io(err))),
+                    }
+                }
+                ExchangeState::Done => panic!("Exchange polled after completion"),
+            };
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid HTTP reply: {}", what),
+    )
+}
+
+// The reply in `buffer` once it is all there, and whether the connection
+// can carry another request. `closed` is whether the server closed the
+// connection after `buffer`.
+#[cfg(feature = "tokio")]
+fn take_reply(buffer: &[u8], closed: bool) -> io::Result<Option<(HttpResponse, bool)>> {
+    let end = match buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+        Some(end) => end,
+        None => return Ok(None),
+    };
+    let head = str::from_utf8(&buffer[..end]).map_err(|_| invalid("head is not UTF-8"))?;
+    let mut lines = head.split("\r\n");
+    let mut start = lines.next().unwrap_or("").splitn(3, ' ');
+    let version = start.next().unwrap_or("");
+    let status = start
+        .next()
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| invalid("no status"))?;
+    let mut headers = Vec::new();
+    for line in lines {
+        let colon = line.find(':').ok_or_else(|| invalid("malformed header"))?;
+        headers.push((
+            line[..colon].trim().to_owned(),
+            line[colon + 1..].trim().to_owned(),
+        ));
+    }
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|&(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    };
+    let close = header("Connection").map_or(version != "HTTP/1.1", |value| {
+        value.eq_ignore_ascii_case("close")
+    });
+    let chunked = header("Transfer-Encoding")
+        .is_some_and(|value| value.to_ascii_lowercase().contains("chunked"));
+    let length = match header("Content-Length") {
+        Some(length) => Some(
+            length
+                .parse::<usize>()
+                .map_err(|_| invalid("bad Content-Length"))?,
+        ),
+        None => None,
+    };
+    let rest = &buffer[end + 4..];
+    let (body, delimited) = if chunked {
+        match dechunk(rest)? {
+            Some(body) => (body, true),
+            None => return Ok(None),
+        }
+    } else if let Some(length) = length {
+        if rest.len() < length {
+            return Ok(None);
+        }
+        (rest[..length].to_vec(), true)
+    } else if closed {
+        (rest.to_vec(), false)
+    } else {
+        return Ok(None);
+    };
+    let reply = HttpResponse {
+        status,
+        headers,
+        body,
+    };
+    Ok(Some((reply, delimited && !close && !closed)))
+}
+
+// Decodes a chunked body, or returns `None` until its last chunk is in.
+#[cfg(feature = "tokio")]
+fn dechunk(mut data: &[u8]) -> io::Result<Option<Vec<u8>>> {
+    let mut body = Vec::new();
+    loop {
+        let line = match data.windows(2).position(|window| window == b"\r\n") {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+        let size = str::from_utf8(&data[..line])
+            .ok()
+            .and_then(|size| usize::from_str_radix(size.split(';').next()?.trim(), 16).ok())
+            .ok_or_else(|| invalid("bad chunk size"))?;
+        data = &data[line + 2..];
+        if size == 0 {
+            // Trailers, if any, end with an empty line like the head.
+            return Ok(
+                if data.starts_with(b"\r\n") || data.windows(4).any(|w| w == b"\r\n\r\n") {
+                    Some(body)
+                } else {
+                    None
+                },
+            );
+        }
+        if data.len() < size + 2 {
+            return Ok(None);
+        }
+        body.extend_from_slice(&data[..size]);
+        data = &data[size + 2..];
+    }
+}
@@ -0,0 +1,149 @@
+//! Credentials a server requires of every HTTP request.
+
+use base64;
+use std::collections::{HashMap, HashSet};
+
+type TokenVerifier = Box<dyn Fn(&str) -> bool + Send + Sync>;
+type UserVerifier = Box<dyn Fn(&str, &str) -> bool + Send + Sync>;
+
+enum Scheme {
+    // A shared secret in the named header. In `Authorization`, it follows
+    // `Bearer `.
+    Token {
+        header: String,
+        verify: TokenVerifier,
+    },
+    Basic(UserVerifier),
+}
+
+/// Checks the credentials of requests before they are read, answering those
+/// without valid ones with `401 Unauthorized` and a `WWW-Authenticate`
+/// challenge.
+///
+/// Set on a server with `Server::set_auth`.
+pub struct Auth {
+    scheme: Scheme,
+    realm: String,
+}
+
+// Takes as long for every `given` of the same length, so timing does not
+// reveal how much of a secret was guessed right.
+fn secrets_match(given: &str, secret: &str) -> bool {
+    given.len() == secret.len()
+        && given
+            .bytes()
+            .zip(secret.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+impl Auth {
+    /// Requires the `header` of each request, like `X-Api-Key`, to hold a
+    /// token that `verify` accepts.
+    pub fn token<K, F>(header: K, verify: F) -> Auth
+    where
+        K: Into<String>,
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        Auth {
+            scheme: Scheme::Token {
+                header: header.into(),
+                verify: Box::new(verify),
+            },
+            realm: String::from("xml-rpc"),
+        }
+    }
+
+    /// Requires the `header` of each request to hold one of `tokens`.
+    pub fn tokens<K, I>(header: K, tokens: I) -> Auth
+    where
+        K: Into<String>,
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let tokens = tokens.into_iter().map(Into::into).collect::<HashSet<_>>();
+        Auth::token(header, move |given| {
+            tokens.iter().any(|token| secrets_match(given, token))
+        })
+    }
+
+    /// Requires `Basic` credentials whose user name and password `verify`
+    /// accepts.
+    pub fn basic<F>(verify: F) -> Auth
+    where
+        F: Fn(&str, &str) -> bool + Send + Sync + 'static,
+    {
+        Auth {
+            scheme: Scheme::Basic(Box::new(verify)),
+            realm: String::from("xml-rpc"),
+        }
+    }
+
+    /// Requires `Basic` credentials matching `users`, passwords keyed by
+    /// user name.
+    pub fn users(users: HashMap<String, String>) -> Auth {
+        Auth::basic(move |user, password| {
+            users
+                .get(user)
+                .is_some_and(|secret| secrets_match(password, secret))
+        })
+    }
+
+    /// Sets the realm named in challenges. Defaults to `xml-rpc`.
+    pub fn set_realm<K: Into<String>>(&mut self, realm: K) {
+        self.realm = realm.into();
+    }
+
+    /// Whether a request carries valid credentials, given a lookup of its
+    /// headers by name.
+    pub(crate) fn check<F>(&self, header: F) -> bool
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        match self.scheme {
+            Scheme::Token {
+                header: ref name,
+                ref verify,
+            } => {
+                let value = match header(name) {
+                    Some(value) => value,
+                    None => return false,
+                };
+                if name.eq_ignore_ascii_case("Authorization") {
+                    credentials(&value, "Bearer").is_some_and(verify)
+                } else {
+                    verify(value.trim())
+                }
+            }
+            Scheme::Basic(ref verify) => header("Authorization")
+                .as_ref()
+                .and_then(|value| credentials(value, "Basic"))
+                .and_then(|encoded| base64::decode(encoded).ok())
+                .and_then(|decoded| String::from_utf8(decoded).ok())
+                .is_some_and(|decoded| match decoded.find(':') {
+                    Some(colon) => verify(&decoded[..colon], &decoded[colon + 1..]),
+                    None => false,
+                }),
+        }
+    }
+
+    /// The value of the `WWW-Authenticate` header sent with a 401.
+    pub(crate) fn challenge(&self) -> String {
+        let scheme = match self.scheme {
+            Scheme::Token { .. } => "Bearer",
+            Scheme::Basic(_) => "Basic",
+        };
+        format!("{} realm=\"{}\"", scheme, self.realm.replace('"', "'"))
+    }
+}
+
+// The credentials of an `Authorization` header value using `scheme`.
+fn credentials<'a>(value: &'a str, scheme: &str) -> Option<&'a str> {
+    let value = value.trim();
+    let split = value.find(' ')?;
+    if value[..split].eq_ignore_ascii_case(scheme) {
+        Some(value[split + 1..].trim())
+    } else {
+        None
+    }
+}
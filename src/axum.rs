@@ -0,0 +1,29 @@
+use axum_lib::body::Bytes;
+use axum_lib::http::{header, HeaderMap, StatusCode};
+use axum_lib::response::{IntoResponse, Response};
+use std::future::{ready, Ready};
+use std::sync::Arc;
+
+use super::server::Server;
+use super::xmlfmt::parse;
+
+/// Turns the server into an axum handler, to be mounted with e.g.
+/// `Router::new().route("/RPC2", post(into_handler(server)))`.
+///
+/// Calls are dispatched on the runtime's worker thread, so slow handlers
+/// block it.
+pub fn into_handler(
+    server: Server,
+) -> impl Fn(HeaderMap, Bytes) -> Ready<Response> + Clone + Send + Sync + 'static {
+    let server = Arc::new(server);
+    move |headers: HeaderMap, body: Bytes| {
+        let content_type = headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok());
+        let charset = parse::content_charset(content_type);
+        ready(match server.handle_body(&body[..], charset) {
+            Some(body) => ([(header::CONTENT_TYPE, "text/xml")], body).into_response(),
+            None => StatusCode::BAD_REQUEST.into_response(),
+        })
+    }
+}
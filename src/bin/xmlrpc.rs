@@ -0,0 +1,199 @@
+extern crate base64;
+extern crate serde_json;
+extern crate xml_rpc;
+
+use std::env;
+use std::fs::File;
+use std::io;
+use std::process;
+use xml_rpc::codegen;
+use xml_rpc::jsonrpc::{from_json, to_json};
+use xml_rpc::{parse, Client, Url, Value};
+
+const USAGE: &str = "Usage: xmlrpc [--json] URL METHOD [TYPE:VALUE]...
+       xmlrpc --generate URL [STRUCT]
+       xmlrpc --doctor [FILE]
+
+The second form prints a typed client module for the methods the server
+describes through system.listMethods and system.methodSignature. The third
+lists what is wrong with a call or response read from FILE or standard
+input, along with the values that could still be read.
+
+Types: i4 (or int), i8, boolean, string, double, dateTime.iso8601, base64, nil
+(as in nil:), and json for arrays and structs written as JSON. Untyped
+arguments are strings.";
+
+fn parse_arg(arg: &str) -> Result<Value, String> {
+    let (kind, value) = match arg.find(':') {
+        Some(at) => (&arg[..at], &arg[at + 1..]),
+        None => return Ok(Value::String(arg.to_owned())),
+    };
+    let invalid = || format!("Invalid {} argument: {:?}", kind, value);
+    Ok(match kind {
+        "i4" | "int" => Value::Int(value.parse().map_err(|_| invalid())?),
+        "i8" => Value::Int64(value.parse().map_err(|_| invalid())?),
+        "boolean" => match value {
+            "1" | "true" => Value::Bool(true),
+            "0" | "false" => Value::Bool(false),
+            _ => return Err(invalid()),
+        },
+        "string" => Value::String(value.to_owned()),
+        "double" => Value::Double(value.parse().map_err(|_| invalid())?),
+        "dateTime.iso8601" => Value::DateTime(value.to_owned()),
+        "base64" => Value::Base64(base64::decode(value).map_err(|_| invalid())?),
+        "nil" => Value::Nil,
+        "json" => serde_json::from_str(value)
+            .map_err(|err| err.to_string())
+            .and_then(from_json)
+            .map_err(|err| format!("Invalid json argument: {}", err))?,
+        // Something like `http://host` that merely contains a colon.
+        _ => Value::String(arg.to_owned()),
+    })
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn write_xml(out: &mut String, value: &Value, depth: usize) {
+    let indent = "  ".repeat(depth);
+    match *value {
+        Value::Int(v) => out.push_str(&format!("{}<i4>{}</i4>\n", indent, v)),
+        Value::Int64(v) => out.push_str(&format!("{}<i8>{}</i8>\n", indent, v)),
+        Value::Bool(v) => out.push_str(&format!("{}<boolean>{}</boolean>\n", indent, i32::from(v))),
+        Value::String(ref v) => {
+            out.push_str(&format!("{}<string>{}</string>\n", indent, escape(v)))
+        }
+        Value::Double(v) => out.push_str(&format!("{}<double>{}</double>\n", indent, v)),
+        Value::DateTime(ref v) => out.push_str(&format!(
+            "{}<dateTime.iso8601>{}</dateTime.iso8601>\n",
+            indent,
+            escape(v)
+        )),
+        Value::Base64(ref v) => out.push_str(&format!(
+            "{}<base64>{}</base64>\n",
+            indent,
+            base64::encode(v)
+        )),
+        Value::Array(ref items) => {
+            out.push_str(&format!("{}<array><data>\n", indent));
+            for item in items {
+                write_xml(out, item, depth + 1);
+            }
+            out.push_str(&format!("{}</data></array>\n", indent));
+        }
+        Value::Struct(ref members) => {
+            out.push_str(&format!("{}<struct>\n", indent));
+            let mut names = members.keys().collect::<Vec<_>>();
+            names.sort();
+            for name in names {
+                out.push_str(&format!(
+                    "{}  <member><name>{}</name>\n",
+                    indent,
+                    escape(name)
+                ));
+                write_xml(out, &members[name], depth + 2);
+                out.push_str(&format!("{}  </member>\n", indent));
+            }
+            out.push_str(&format!("{}</struct>\n", indent));
+        }
+        Value::Nil => out.push_str(&format!("{}<nil/>\n", indent)),
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(2);
+}
+
+fn generate(args: &[String]) {
+    if args.is_empty() || args.len() > 2 {
+        fail(USAGE);
+    }
+    let url = Url::parse(&args[0]).unwrap_or_else(|err| fail(&format!("Invalid URL: {}", err)));
+    let name = args.get(1).map_or("Api", |name| name.as_str());
+    let mut client = Client::new().unwrap_or_else(|err| fail(&err.to_string()));
+    let methods =
+        codegen::introspect(&mut client, &url).unwrap_or_else(|err| fail(&err.to_string()));
+    print!("{}", codegen::generate(name, &methods));
+}
+
+fn doctor(args: &[String]) {
+    let diagnosis = match args.len() {
+        0 => parse::recover(io::stdin()),
+        1 => {
+            let file = File::open(&args[0])
+                .unwrap_or_else(|err| fail(&format!("Cannot open {}: {}", args[0], err)));
+            parse::recover(file)
+        }
+        _ => fail(USAGE),
+    }
+    .unwrap_or_else(|err| fail(&err.to_string()));
+    if let Some(ref name) = diagnosis.name {
+        println!("Method: {}", name);
+    }
+    for value in &diagnosis.values {
+        let mut text = String::new();
+        write_xml(&mut text, value, 0);
+        print!("{}", text);
+    }
+    for problem in &diagnosis.problems {
+        eprintln!("Problem: {}", problem);
+    }
+    if !diagnosis.is_healthy() {
+        process::exit(1);
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1).collect::<Vec<_>>();
+    if args.first().is_some_and(|arg| arg == "--generate") {
+        return generate(&args[1..]);
+    }
+    if args.first().is_some_and(|arg| arg == "--doctor") {
+        return doctor(&args[1..]);
+    }
+    let json = args.first().is_some_and(|arg| arg == "--json");
+    if json {
+        args.remove(0);
+    }
+    if args.len() < 2 || args[0] == "--help" {
+        fail(USAGE);
+    }
+    let url = Url::parse(&args[0]).unwrap_or_else(|err| fail(&format!("Invalid URL: {}", err)));
+    let params = args[2..]
+        .iter()
+        .map(|arg| parse_arg(arg))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|err| fail(&err));
+
+    let mut client = Client::new().unwrap_or_else(|err| fail(&err.to_string()));
+    let response = client
+        .call_value(&url, args[1].clone(), params)
+        .unwrap_or_else(|err| fail(&err.to_string()));
+
+    let (value, failed) = match response {
+        Ok(mut params) if params.len() == 1 => (params.remove(0), false),
+        Ok(params) => (Value::Array(params), false),
+        Err(fault) => {
+            let mut members = ::std::collections::HashMap::new();
+            members.insert("faultCode".to_owned(), Value::Int(fault.code));
+            members.insert("faultString".to_owned(), Value::String(fault.message));
+            (Value::Struct(members), true)
+        }
+    };
+    if json {
+        let text = serde_json::to_string_pretty(&to_json(&value))
+            .unwrap_or_else(|err| fail(&err.to_string()));
+        println!("{}", text);
+    } else {
+        let mut text = String::new();
+        write_xml(&mut text, &value, 0);
+        print!("{}", text);
+    }
+    if failed {
+        process::exit(1);
+    }
+}
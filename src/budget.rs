@@ -0,0 +1,143 @@
+//! A cap on the request body bytes held in memory across all connections.
+
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub(crate) struct MemoryBudget {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: usize) -> MemoryBudget {
+        MemoryBudget {
+            limit,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    /// Holds `bytes`, or returns false if they do not fit.
+    pub fn take(&self, bytes: usize) -> bool {
+        let mut used = self.used.load(Ordering::Relaxed);
+        loop {
+            if bytes > self.limit - used {
+                return false;
+            }
+            match self.used.compare_exchange_weak(
+                used,
+                used + bytes,
+                Ordering::AcqRel,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(current) => used = current,
+            }
+        }
+    }
+
+    /// Gives back `bytes` held with `take`.
+    pub fn give_back(&self, bytes: usize) {
+        self.used.fetch_sub(bytes, Ordering::AcqRel);
+    }
+
+    /// Holds the `declared` length of `body` up front, or `None` if it does
+    /// not fit. Bodies of unknown length are charged as they are read.
+    pub fn admit<R: Read>(&self, declared: Option<u64>, body: R) -> Option<Charged<'_, R>> {
+        let held = match declared {
+            Some(length) if length > self.limit as u64 => return None,
+            Some(length) => length as usize,
+            None => 0,
+        };
+        if !self.take(held) {
+            return None;
+        }
+        Some(Charged {
+            budget: Some(self),
+            body,
+            held,
+            read: 0,
+            max: None,
+            exhausted: false,
+            too_large: false,
+        })
+    }
+}
+
+/// A request body whose bytes count against a `MemoryBudget` until dropped.
+///
+/// Bytes are counted as they come off the connection, which stands in for
+/// both buffered bodies and the calls parsed from them.
+pub(crate) struct Charged<'a, R> {
+    budget: Option<&'a MemoryBudget>,
+    body: R,
+    held: usize,
+    read: usize,
+    // The most bytes read before giving up on the body.
+    max: Option<u64>,
+    exhausted: bool,
+    too_large: bool,
+}
+
+impl<'a, R> Charged<'a, R> {
+    pub fn unbounded(body: R) -> Charged<'a, R> {
+        Charged {
+            budget: None,
+            body,
+            held: 0,
+            read: 0,
+            max: None,
+            exhausted: false,
+            too_large: false,
+        }
+    }
+
+    /// Stops reading once more than `max` bytes have come in.
+    pub fn capped(mut self, max: Option<u64>) -> Charged<'a, R> {
+        self.max = max;
+        self
+    }
+
+    /// The status to refuse the request with if reading stopped short: 503
+    /// if the body outgrew the budget, 413 if it went past its cap.
+    pub fn refusal(&self) -> Option<u16> {
+        if self.exhausted {
+            Some(503)
+        } else if self.too_large {
+            Some(413)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, R: Read> Read for Charged<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.body.read(buf)?;
+        if let Some(max) = self.max {
+            if (self.read + read) as u64 > max {
+                self.too_large = true;
+                return Err(io::Error::other("Request body exceeds the size limit"));
+            }
+        }
+        if let Some(budget) = self.budget {
+            let total = self.read + read;
+            if total > self.held {
+                if !budget.take(total - self.held) {
+                    self.exhausted = true;
+                    return Err(io::Error::other("Request bodies exceed the memory budget"));
+                }
+                self.held = total;
+            }
+        }
+        self.read += read;
+        Ok(read)
+    }
+}
+
+impl<'a, R> Drop for Charged<'a, R> {
+    fn drop(&mut self) {
+        if let Some(budget) = self.budget {
+            budget.give_back(self.held);
+        }
+    }
+}
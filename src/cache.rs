@@ -0,0 +1,80 @@
+//! Serialized responses of methods registered as cacheable.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::xmlfmt::value::ToXml;
+use super::xmlfmt::{Response, WireProfile};
+
+// Past this many entries, expired ones are swept out before adding more,
+// and new ones are dropped if none have expired.
+const MAX_ENTRIES: usize = 1024;
+
+/// A response serialized once, to be sent many times. The response is kept
+/// too, for middleware to be handed.
+pub struct Serialized {
+    pub response: Response,
+    pub xml: String,
+}
+
+impl Serialized {
+    pub fn new(response: Response, profile: &WireProfile) -> Serialized {
+        Serialized {
+            xml: response.to_xml_as(profile),
+            response,
+        }
+    }
+}
+
+struct Entry {
+    body: Arc<Serialized>,
+    expires: Instant,
+}
+
+#[derive(Default)]
+pub struct ResponseCache {
+    ttls: HashMap<String, Duration>,
+    // Keyed by the serialized call, method name included.
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl ResponseCache {
+    pub fn set_ttl(&mut self, method: String, ttl: Duration) {
+        self.ttls.insert(method, ttl);
+    }
+
+    pub fn is_cacheable(&self, method: &str) -> bool {
+        self.ttls.contains_key(method)
+    }
+
+    pub fn get(&self, key: &str) -> Option<Arc<Serialized>> {
+        let entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        match entries.get(key) {
+            Some(entry) if entry.expires > Instant::now() => Some(Arc::clone(&entry.body)),
+            _ => None,
+        }
+    }
+
+    pub fn insert(&self, method: &str, key: String, body: Arc<Serialized>) {
+        let ttl = match self.ttls.get(method) {
+            Some(&ttl) => ttl,
+            None => return,
+        };
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(|err| err.into_inner());
+        if entries.len() >= MAX_ENTRIES {
+            entries.retain(|_, entry| entry.expires > now);
+            if entries.len() >= MAX_ENTRIES {
+                return;
+            }
+        }
+        entries.insert(
+            key,
+            Entry {
+                body,
+                expires: now + ttl,
+            },
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Aborting client calls from another thread.
+
+use hyper;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::{Shutdown, SocketAddr};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+type Closer = Box<dyn FnMut() + Send>;
+
+#[derive(Default)]
+struct State {
+    cancelled: bool,
+    next_id: u64,
+    // The connections open through the clients the token is set on.
+    connections: HashMap<u64, Closer>,
+}
+
+/// Cancels the calls of the clients it is set on, from any thread.
+///
+/// Cancelling closes the connection of the call in flight, which then fails
+/// with `Error::Cancelled` as soon as its socket wakes up. A token stays
+/// cancelled: later calls fail at once until the client is given a new one.
+/// Connecting is not interrupted, so a call still connecting fails once
+/// connected or once its connect timeout expires.
+///
+/// Set on a client with `Client::set_cancel_token`.
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    state: Arc<Mutex<State>>,
+}
+
+impl fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CancelToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        let mut state = self.lock();
+        state.cancelled = true;
+        for (_, mut close) in state.connections.drain() {
+            close();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.lock().cancelled
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    // Tracks `stream` until every copy hyper holds is dropped, or fails if
+    // the token is cancelled already.
+    fn track<S>(&self, stream: S) -> io::Result<CancelStream<S>>
+    where
+        S: hyper::net::NetworkStream + Clone,
+    {
+        let mut state = self.lock();
+        if state.cancelled {
+            return Err(cancelled());
+        }
+        let id = state.next_id;
+        state.next_id += 1;
+        let mut closer = stream.clone();
+        state.connections.insert(
+            id,
+            Box::new(move || {
+                let _ = closer.close(Shutdown::Both);
+            }),
+        );
+        Ok(CancelStream {
+            inner: stream,
+            _tracked: Arc::new(Tracked {
+                token: self.clone(),
+                id,
+            }),
+        })
+    }
+}
+
+fn cancelled() -> io::Error {
+    io::Error::new(io::ErrorKind::Interrupted, "call cancelled")
+}
+
+// Stops tracking a connection once hyper is done with it.
+struct Tracked {
+    token: CancelToken,
+    id: u64,
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.token.lock().connections.remove(&self.id);
+    }
+}
+
+// Tracks the connections `inner` makes with `token`.
+pub(crate) struct CancelConnector<C> {
+    inner: C,
+    token: CancelToken,
+}
+
+impl<C> CancelConnector<C> {
+    pub fn new(inner: C, token: CancelToken) -> CancelConnector<C> {
+        CancelConnector { inner, token }
+    }
+}
+
+impl<C> hyper::net::NetworkConnector for CancelConnector<C>
+where
+    C: hyper::net::NetworkConnector,
+    C::Stream: hyper::net::NetworkStream + Send + Clone,
+{
+    type Stream = CancelStream<C::Stream>;
+
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> hyper::Result<Self::Stream> {
+        let stream = self.inner.connect(host, port, scheme)?;
+        Ok(self.token.track(stream)?)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct CancelStream<S> {
+    inner: S,
+    // Dropped along with the last copy of the stream.
+    _tracked: Arc<Tracked>,
+}
+
+impl<S: io::Read> io::Read for CancelStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<S: io::Write> io::Write for CancelStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S: hyper::net::NetworkStream> hyper::net::NetworkStream for CancelStream<S> {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    fn set_read_timeout(&self, duration: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(duration)
+    }
+
+    fn set_write_timeout(&self, duration: Option<Duration>) -> io::Result<()> {
+        self.inner.set_write_timeout(duration)
+    }
+
+    fn close(&mut self, how: Shutdown) -> io::Result<()> {
+        self.inner.close(how)
+    }
+}
@@ -0,0 +1,230 @@
+//! Record and replay of HTTP exchanges, for tests that must not depend on
+//! a live server.
+//!
+//! A `Cassette` wraps the transport of an `AsyncClient`. The first time a
+//! call is made its reply is fetched for real and written to a file in the
+//! cassette's directory; after that the file is replayed instead. Files are
+//! named after the method and a hash of the parameters, so a test may make
+//! its calls in any order, and are plain text meant to be committed.
+
+use std::collections::HashMap;
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use super::async_client::{HttpRequest, HttpResponse, Transport, TransportFuture};
+use super::error::{Error, Result};
+use super::xmlfmt::{parse, Value};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+// FNV-1a, whose output unlike that of `DefaultHasher` is stable across
+// Rust releases, so recordings stay valid.
+fn feed(hash: &mut u64, bytes: &[u8]) {
+    for &b in bytes {
+        *hash ^= u64::from(b);
+        *hash = hash.wrapping_mul(FNV_PRIME);
+    }
+}
+
+fn feed_value(hash: &mut u64, value: &Value) {
+    match *value {
+        Value::Int(v) => {
+            feed(hash, b"i");
+            feed(hash, &v.to_be_bytes());
+        }
+        Value::Int64(v) => {
+            feed(hash, b"l");
+            feed(hash, &v.to_be_bytes());
+        }
+        Value::Bool(v) => feed(hash, if v { b"t" } else { b"f" }),
+        Value::String(ref v) => {
+            feed(hash, b"s");
+            feed(hash, &(v.len() as u64).to_be_bytes());
+            feed(hash, v.as_bytes());
+        }
+        Value::Double(v) => {
+            feed(hash, b"d");
+            feed(hash, &v.to_bits().to_be_bytes());
+        }
+        Value::DateTime(ref v) => {
+            feed(hash, b"D");
+            feed(hash, &(v.len() as u64).to_be_bytes());
+            feed(hash, v.as_bytes());
+        }
+        Value::Base64(ref v) => {
+            feed(hash, b"b");
+            feed(hash, &(v.len() as u64).to_be_bytes());
+            feed(hash, v);
+        }
+        Value::Array(ref items) => {
+            feed(hash, b"a");
+            feed(hash, &(items.len() as u64).to_be_bytes());
+            for item in items {
+                feed_value(hash, item);
+            }
+        }
+        Value::Struct(ref members) => feed_struct(hash, members),
+        Value::Nil => feed(hash, b"n"),
+    }
+}
+
+// Members are hashed in name order, since `HashMap` order is random.
+fn feed_struct(hash: &mut u64, members: &HashMap<String, Value>) {
+    let mut names = members.keys().collect::<Vec<_>>();
+    names.sort();
+    feed(hash, b"m");
+    feed(hash, &(names.len() as u64).to_be_bytes());
+    for name in names {
+        feed(hash, &(name.len() as u64).to_be_bytes());
+        feed(hash, name.as_bytes());
+        feed_value(hash, &members[name]);
+    }
+}
+
+// The file holding the reply to `body`: `<method>-<hash>.http`.
+fn file_name(body: &[u8]) -> String {
+    let mut hash = FNV_OFFSET;
+    let name = match parse::call(body) {
+        Ok(call) => {
+            for param in &call.params {
+                feed_value(&mut hash, param);
+            }
+            call.name
+        }
+        // Not something this crate sent, but it can still be replayed.
+        Err(_) => {
+            feed(&mut hash, body);
+            "unparsed".to_owned()
+        }
+    };
+    let name = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '_' | '-' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+    format!("{}-{:016x}.http", name, hash)
+}
+
+fn encode(reply: &HttpResponse) -> Vec<u8> {
+    let mut data = format!("{}\n", reply.status);
+    for (name, value) in &reply.headers {
+        data.push_str(&format!("{}: {}\n", name, value));
+    }
+    data.push('\n');
+    let mut data = data.into_bytes();
+    data.extend_from_slice(&reply.body);
+    data
+}
+
+fn invalid(path: &Path) -> Error {
+    Error::Io(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Malformed recording {}", path.display()),
+    ))
+}
+
+fn decode(path: &Path, data: &[u8]) -> Result<HttpResponse> {
+    let split = data
+        .windows(2)
+        .position(|window| window == b"\n\n")
+        .ok_or_else(|| invalid(path))?;
+    let head = String::from_utf8_lossy(&data[..split]);
+    let mut lines = head.lines();
+    let status = lines
+        .next()
+        .and_then(|line| line.trim().parse().ok())
+        .ok_or_else(|| invalid(path))?;
+    let headers = lines
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => Some((name.to_owned(), value.trim().to_owned())),
+                _ => None,
+            }
+        })
+        .collect();
+    Ok(HttpResponse {
+        status,
+        headers,
+        body: data[split + 2..].to_vec(),
+    })
+}
+
+fn save(path: &Path, reply: &HttpResponse) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, encode(reply))
+}
+
+pub struct Cassette<T> {
+    dir: PathBuf,
+    transport: T,
+    replay_only: bool,
+}
+
+impl<T: Transport> Cassette<T> {
+    /// Replays the recordings in `dir`, and records calls missing from it
+    /// with `transport`.
+    pub fn new<P: Into<PathBuf>>(dir: P, transport: T) -> Cassette<T> {
+        Cassette {
+            dir: dir.into(),
+            transport,
+            replay_only: false,
+        }
+    }
+
+    /// Fails calls that were not recorded yet instead of making them, e.g.
+    /// on CI machines without access to the real server.
+    pub fn set_replay_only(&mut self, replay_only: bool) {
+        self.replay_only = replay_only;
+    }
+}
+
+impl<T: Transport> Transport for Cassette<T> {
+    fn post(&self, request: HttpRequest) -> TransportFuture {
+        let path = self.dir.join(file_name(&request.body));
+        match fs::read(&path) {
+            Ok(data) => return Box::pin(::std::future::ready(decode(&path, &data))),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Box::pin(::std::future::ready(Err(Error::Io(err)))),
+        }
+        if self.replay_only {
+            let err = io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No recording {}", path.display()),
+            );
+            return Box::pin(::std::future::ready(Err(Error::Io(err))));
+        }
+        Box::pin(Recording {
+            reply: self.transport.post(request),
+            path,
+        })
+    }
+}
+
+// Saves the reply once the real transport delivers it.
+struct Recording {
+    reply: TransportFuture,
+    path: PathBuf,
+}
+
+impl Future for Recording {
+    type Output = Result<HttpResponse>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<HttpResponse>> {
+        let reply = match self.reply.as_mut().poll(cx) {
+            Poll::Ready(Ok(reply)) => reply,
+            other => return other,
+        };
+        // Error replies are recorded too, since the test may expect them.
+        Poll::Ready(save(&self.path, &reply).map(|()| reply).map_err(Error::Io))
+    }
+}
@@ -1,12 +1,49 @@
-use super::error::{Result, ResultExt};
-use super::xmlfmt::{from_params, into_params, parse, Call, Fault, Params, Response};
+use super::cancel::{CancelConnector, CancelToken};
+#[cfg(feature = "gzip")]
+use super::compression::{self, Coding, DecodeError};
+use super::error::{ConnectFailed, Error, FmtError, HttpReply, Result};
+#[cfg(feature = "hmac")]
+use super::hmac::Signer;
+use super::httpproxy::{HttpProxy, Plaintext};
+use super::jobs::Backoff;
+use super::retry::RetryPolicy;
+use super::stats::{ClientStats, Counters, StatsConnector};
+#[cfg(feature = "encoding")]
+use super::xmlfmt::charset;
+use super::xmlfmt::{
+    from_params, into_params, parse, scratch, Call, Fault, Multicall, Params, Response, Value,
+    WireProfile,
+};
+use base64;
+#[cfg(feature = "encoding")]
+use encoding_rs::Encoding;
+use hyper::client::pool::{self, Pool};
+use hyper::client::ProxyConfig;
+use hyper::net::{NetworkConnector, NetworkStream};
 use hyper::{self, Client as HyperClient};
 use serde::{Deserialize, Serialize};
 use std;
+use std::io::{self, Read};
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "socks")]
+use socks::Socks5Proxy;
+#[cfg(any(feature = "tls-native", feature = "tls-rustls"))]
+use tls::TlsConnector;
+use tracecontext::{self, TraceContext, TRACEPARENT, TRACESTATE};
+#[cfg(unix)]
+use unix::UnixHttpStream;
+#[cfg(feature = "chrono")]
+use xmlfmt::datetime::DateTimeOptions;
 use Url;
 
 use hyper::header::Headers;
-header! { (ContentType, "ContentType") => [String] }
 
 pub fn call_value<Tkey>(uri: &Url, name: Tkey, params: Params) -> Result<Response>
 where
@@ -28,43 +65,837 @@ where
     Client::new()?.call(uri, name, req)
 }
 
+const REPLY_HEADERS: &[&str] = &["Content-Type", "Content-Length", "Server"];
+const REPLY_SNIPPET_LEN: usize = 512;
+
+fn describe_reply(response: &hyper::client::Response, body: &[u8]) -> Box<HttpReply> {
+    let header = |name: &str| {
+        response.headers.get_raw(name).map(|values| {
+            values
+                .iter()
+                .map(|value| String::from_utf8_lossy(value).into_owned())
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+    };
+    summarize_reply(response.status.to_u16(), header, body)
+}
+
+// The body of `response` with its `Content-Encoding` undone.
+#[cfg(feature = "gzip")]
+fn decompress(
+    response: &hyper::client::Response,
+    body: Vec<u8>,
+    limit: Option<usize>,
+) -> Result<Vec<u8>> {
+    let invalid = |details: String| Error::InvalidResponse {
+        reply: describe_reply(response, &body),
+        source: FmtError::Parse {
+            context: "Failed to decompress XML-RPC response",
+            details,
+            location: None,
+        },
+    };
+    let coding = match response.headers.get_raw("Content-Encoding") {
+        Some(values) => values
+            .iter()
+            .map(|value| Coding::from_header(&String::from_utf8_lossy(value)))
+            .next()
+            .unwrap_or(Ok(None)),
+        None => Ok(None),
+    };
+    match coding {
+        Ok(Some(coding)) => match coding.decode(&body, limit.unwrap_or(usize::MAX)) {
+            Ok(decoded) => Ok(decoded),
+            Err(DecodeError::TooLarge) => Err(Error::ResponseTooLarge {
+                limit: limit.unwrap_or(usize::MAX),
+            }),
+            Err(DecodeError::Invalid(details)) => Err(invalid(details)),
+        },
+        Ok(None) => Ok(body),
+        Err(details) => Err(invalid(details)),
+    }
+}
+
+pub(crate) fn summarize_reply<F>(status: u16, header: F, body: &[u8]) -> Box<HttpReply>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let headers = REPLY_HEADERS
+        .iter()
+        .filter_map(|&name| header(name).map(|value| (name.to_owned(), value)))
+        .collect();
+    let snippet = &body[..body.len().min(REPLY_SNIPPET_LEN)];
+    Box::new(HttpReply {
+        status,
+        headers,
+        body: String::from_utf8_lossy(snippet).into_owned(),
+    })
+}
+
+fn connect_with_timeout(host: &str, port: u16, timeout: Duration) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any addresses",
+        )
+    }))
+}
+
+fn connect_tcp(host: &str, port: u16, timeout: Option<Duration>) -> io::Result<TcpStream> {
+    let stream = match timeout {
+        Some(timeout) => connect_with_timeout(host, port, timeout),
+        None => TcpStream::connect((host, port)),
+    }
+    .map_err(ConnectFailed::wrap)?;
+    // XML-RPC calls are small request/response pairs, so Nagle's algorithm
+    // only adds latency.
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
+
+// The `Authorization` header value for HTTP Basic authentication.
+fn basic_credentials(user: String, password: String) -> String {
+    format!(
+        "Basic {}",
+        base64::encode(&format!("{}:{}", user, password))
+    )
+}
+
+// How many idle connections to each server are kept for later calls, and
+// for how long.
+#[derive(Clone, Copy)]
+struct PoolSettings {
+    max_idle: usize,
+    idle_timeout: Option<Duration>,
+}
+
+impl Default for PoolSettings {
+    fn default() -> PoolSettings {
+        PoolSettings {
+            max_idle: pool::Config::default().max_idle,
+            idle_timeout: None,
+        }
+    }
+}
+
+impl PoolSettings {
+    fn pool<C>(&self, connector: C) -> HyperClient
+    where
+        C: NetworkConnector + Send + Sync + 'static,
+        C::Stream: NetworkStream + Send + Clone,
+    {
+        let config = pool::Config {
+            max_idle: self.max_idle,
+        };
+        let mut pool = Pool::with_connector(config, connector);
+        pool.set_idle_timeout(self.idle_timeout);
+        HyperClient::with_connector(pool)
+    }
+}
+
+// How TCP connections to servers are made.
+#[derive(Clone, Default)]
+struct Connect {
+    timeout: Option<Duration>,
+    pool: PoolSettings,
+    #[cfg(feature = "socks")]
+    socks: Option<Socks5Proxy>,
+    http_proxy: Option<HttpProxy>,
+}
+
+impl Connect {
+    // The HTTP proxy `http` calls to `host` are sent to, if any.
+    fn http_proxy_for(&self, host: &str) -> Option<&HttpProxy> {
+        self.http_proxy
+            .as_ref()
+            .filter(|proxy| proxy.applies_to(host))
+    }
+
+    fn open(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        // Calls to `http` URLs the proxy applies to are sent to it whole,
+        // through another client, so only `https` ones are tunneled here.
+        if let Some(proxy) = self.http_proxy_for(host) {
+            let mut stream = connect_tcp(&proxy.host, proxy.port, self.timeout)?;
+            proxy
+                .tunnel(&mut stream, host, port, self.timeout)
+                .map_err(ConnectFailed::wrap)?;
+            return Ok(stream);
+        }
+        #[cfg(feature = "socks")]
+        {
+            if let Some(ref proxy) = self.socks {
+                let mut stream = connect_tcp(&proxy.host, proxy.port, self.timeout)?;
+                proxy
+                    .handshake(&mut stream, host, port, self.timeout)
+                    .map_err(ConnectFailed::wrap)?;
+                return Ok(stream);
+            }
+        }
+        connect_tcp(host, port, self.timeout)
+    }
+}
+
+#[cfg(unix)]
+struct UnixConnector(PathBuf);
+
+#[cfg(unix)]
+impl NetworkConnector for UnixConnector {
+    type Stream = UnixHttpStream;
+
+    fn connect(&self, _host: &str, _port: u16, _scheme: &str) -> hyper::Result<UnixHttpStream> {
+        let stream = UnixStream::connect(&self.0).map_err(ConnectFailed::wrap)?;
+        Ok(UnixHttpStream::new(stream))
+    }
+}
+
+// Stands in for the TLS settings so clients are set up the same way with TLS
+// disabled.
+#[cfg(not(any(feature = "tls-native", feature = "tls-rustls")))]
+struct TlsConnector;
+
+#[cfg(not(any(feature = "tls-native", feature = "tls-rustls")))]
+impl TlsConnector {
+    fn new() -> Result<TlsConnector> {
+        Ok(TlsConnector)
+    }
+}
+
+// Pools the connections `connector` makes as `settings` says, counting them
+// in `counters` and tracking them with `cancel`.
+fn pooled<C>(
+    connector: C,
+    settings: PoolSettings,
+    counters: &Arc<Counters>,
+    cancel: Option<&CancelToken>,
+) -> HyperClient
+where
+    C: NetworkConnector + Send + Sync + 'static,
+    C::Stream: NetworkStream + Send + Clone,
+{
+    let connector = StatsConnector::new(connector, Arc::clone(counters));
+    match cancel {
+        Some(token) => settings.pool(CancelConnector::new(connector, token.clone())),
+        None => settings.pool(connector),
+    }
+}
+
+fn hyper_client(
+    socket: Option<&Path>,
+    connect: &Connect,
+    tls: &TlsConnector,
+    counters: &Arc<Counters>,
+    cancel: Option<&CancelToken>,
+) -> HyperClient {
+    #[cfg(unix)]
+    {
+        if let Some(path) = socket {
+            return pooled(
+                UnixConnector(path.to_owned()),
+                connect.pool,
+                counters,
+                cancel,
+            );
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = socket;
+
+    let settings = connect.pool;
+    let connect = connect.clone();
+    let connector = move |host: &str, port: u16, _scheme: &str| connect.open(host, port);
+    #[cfg(any(feature = "tls-native", feature = "tls-rustls"))]
+    let connector = hyper::net::HttpsConnector::with_connector(tls.clone(), connector);
+    #[cfg(not(any(feature = "tls-native", feature = "tls-rustls")))]
+    let _ = tls;
+    pooled(connector, settings, counters, cancel)
+}
+
+// A client sending `http` calls to the HTTP proxy of `connect`, if any.
+// hyper pools its connections to the proxy without an idle timeout.
+fn proxied_client(
+    connect: &Connect,
+    counters: &Arc<Counters>,
+    cancel: Option<&CancelToken>,
+) -> Option<HyperClient> {
+    let mut connect = connect.clone();
+    let proxy = connect.http_proxy.take()?;
+    let config = pool::Config {
+        max_idle: connect.pool.max_idle,
+    };
+    let connector = move |host: &str, port: u16, _scheme: &str| connect.open(host, port);
+    let connector = StatsConnector::new(connector, Arc::clone(counters));
+    Some(match cancel {
+        Some(token) => {
+            let connector = CancelConnector::new(connector, token.clone());
+            let mut proxy = ProxyConfig::new("http", proxy.host, proxy.port, connector, Plaintext);
+            proxy.set_pool_config(Some(config));
+            HyperClient::with_proxy_config(proxy)
+        }
+        None => {
+            let mut proxy = ProxyConfig::new("http", proxy.host, proxy.port, connector, Plaintext);
+            proxy.set_pool_config(Some(config));
+            HyperClient::with_proxy_config(proxy)
+        }
+    })
+}
+
 pub struct Client {
     client: HyperClient,
+    proxied: Option<HyperClient>,
+    socket: Option<PathBuf>,
+    // The client for the socket of the last `unix:` URL called.
+    #[cfg(unix)]
+    url_socket: Option<(PathBuf, HyperClient)>,
+    connect: Connect,
+    tls: TlsConnector,
+    counters: Arc<Counters>,
+    cancel: Option<CancelToken>,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    call_timeout: Option<Duration>,
+    retry: Option<RetryPolicy>,
+    headers: Headers,
+    max_response_size: Option<usize>,
+    #[cfg(feature = "gzip")]
+    compression: bool,
+    #[cfg(feature = "encoding")]
+    output_encoding: Option<&'static Encoding>,
+    trace_context: Option<TraceContext>,
+    #[cfg(feature = "hmac")]
+    signer: Option<Signer>,
+    wire_profile: WireProfile,
+    #[cfg(feature = "chrono")]
+    datetimes: DateTimeOptions,
 }
 
 impl Client {
     pub fn new() -> Result<Client> {
-        let client = HyperClient::new();
-        Ok(Client { client: client })
+        Client::with_socket(None)
+    }
+
+    /// Starts configuring a client, as in
+    /// `Client::builder().call_timeout(Duration::from_secs(30)).build()?`.
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// Sends every call through the Unix socket at `path`. Only the path of
+    /// the URLs passed to calls is used.
+    ///
+    /// Any client also calls `unix:` URLs, such as `unix:///run/app.sock`,
+    /// through the socket they name, at the root path.
+    #[cfg(unix)]
+    pub fn unix<P: Into<PathBuf>>(path: P) -> Result<Client> {
+        Client::with_socket(Some(path.into()))
+    }
+
+    fn with_socket(socket: Option<PathBuf>) -> Result<Client> {
+        let tls = TlsConnector::new()?;
+        let connect = Connect::default();
+        let counters = Arc::default();
+        Ok(Client {
+            client: hyper_client(socket.as_deref(), &connect, &tls, &counters, None),
+            proxied: None,
+            socket,
+            #[cfg(unix)]
+            url_socket: None,
+            connect,
+            tls,
+            counters,
+            cancel: None,
+            read_timeout: None,
+            write_timeout: None,
+            call_timeout: None,
+            retry: None,
+            headers: Headers::new(),
+            max_response_size: None,
+            #[cfg(feature = "gzip")]
+            compression: true,
+            #[cfg(feature = "encoding")]
+            output_encoding: None,
+            trace_context: None,
+            #[cfg(feature = "hmac")]
+            signer: None,
+            wire_profile: WireProfile::default(),
+            #[cfg(feature = "chrono")]
+            datetimes: DateTimeOptions::default(),
+        })
+    }
+
+    fn reconnect(&mut self) {
+        let socket = self.socket.as_deref();
+        self.client = hyper_client(
+            socket,
+            &self.connect,
+            &self.tls,
+            &self.counters,
+            self.cancel.as_ref(),
+        );
+        self.proxied = match self.socket {
+            Some(_) => None,
+            None => proxied_client(&self.connect, &self.counters, self.cancel.as_ref()),
+        };
+        #[cfg(unix)]
+        {
+            self.url_socket = None;
+        }
+    }
+
+    // Sets up the client for calls to a `unix:` URL, which names the socket
+    // to call through, as in `unix:///run/app.sock`.
+    #[cfg(unix)]
+    fn open_url_socket(&mut self, uri: &Url) {
+        let path = Path::new(uri.path());
+        match self.url_socket {
+            Some((ref socket, _)) if socket == path => {}
+            _ => {
+                let client = hyper_client(
+                    Some(path),
+                    &self.connect,
+                    &self.tls,
+                    &self.counters,
+                    self.cancel.as_ref(),
+                );
+                self.url_socket = Some((path.to_owned(), client));
+            }
+        }
+    }
+
+    /// Limits how long establishing a TCP connection may take. Drops pooled
+    /// connections.
+    pub fn set_connect_timeout(&mut self, timeout: Option<Duration>) {
+        self.connect.timeout = timeout;
+        self.reconnect();
+    }
+
+    /// Connects to servers through a SOCKS5 proxy. Drops pooled connections.
+    #[cfg(feature = "socks")]
+    pub fn set_socks_proxy(&mut self, proxy: Option<Socks5Proxy>) {
+        self.connect.socks = proxy;
+        self.reconnect();
+    }
+
+    /// Keeps at most `max_idle` idle connections to each server for later
+    /// calls to reuse, 5 by default. 0 opens a new connection for every
+    /// call. Drops pooled connections.
+    pub fn set_max_idle_connections(&mut self, max_idle: usize) {
+        self.connect.pool.max_idle = max_idle;
+        self.reconnect();
+    }
+
+    /// Closes connections instead of reusing them once they have been idle
+    /// for `timeout`, so they are not reused after servers dropped them.
+    /// Does not apply to connections to HTTP proxies for `http` calls. Drops
+    /// pooled connections.
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) {
+        self.connect.pool.idle_timeout = timeout;
+        self.reconnect();
+    }
+
+    /// Connects to servers through an HTTP proxy, which takes precedence
+    /// over any SOCKS5 proxy. Drops pooled connections.
+    pub fn set_http_proxy(&mut self, proxy: Option<HttpProxy>) {
+        self.connect.http_proxy = proxy;
+        self.reconnect();
+    }
+
+    /// Replaces the TLS settings used for `https` URLs. Drops pooled
+    /// connections.
+    #[cfg(any(feature = "tls-native", feature = "tls-rustls"))]
+    pub fn set_tls(&mut self, tls: TlsConnector) {
+        self.tls = tls;
+        self.reconnect();
+    }
+
+    /// Counts of the connections the client has made and the bytes through
+    /// them, since it was created.
+    pub fn stats(&self) -> ClientStats {
+        self.counters.snapshot()
+    }
+
+    /// Lets `token` cancel calls through the client from other threads.
+    /// Drops pooled connections.
+    pub fn set_cancel_token(&mut self, token: Option<CancelToken>) {
+        self.cancel = token;
+        self.reconnect();
+    }
+
+    /// Limits how long any single read from or write to the server may block.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
+
+    /// Limits how long any single write to the server may block, in place
+    /// of the read timeout.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.write_timeout = timeout;
+    }
+
+    /// Sends header `name` with every call, replacing any value set before.
+    /// Headers the client sets itself, like `Content-Length`, take
+    /// precedence.
+    pub fn set_header<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) {
+        self.headers
+            .set_raw(name.into(), vec![value.into().into_bytes()]);
+    }
+
+    /// Stops sending header `name` set with `set_header`.
+    pub fn remove_header(&mut self, name: &str) {
+        self.headers.remove_raw(name);
+    }
+
+    pub fn set_user_agent<V: Into<String>>(&mut self, user_agent: V) {
+        self.set_header("User-Agent", user_agent);
+    }
+
+    /// Sends `user` and `password` with every call, with HTTP Basic
+    /// authentication. Calls they are refused for fail with
+    /// `Error::Unauthorized`.
+    pub fn set_basic_auth<U: Into<String>, P: Into<String>>(&mut self, user: U, password: P) {
+        self.set_header(
+            "Authorization",
+            basic_credentials(user.into(), password.into()),
+        );
+    }
+
+    /// Sends `token` with every call, as `Authorization: Bearer token`.
+    pub fn set_bearer_auth<T: Into<String>>(&mut self, token: T) {
+        self.set_header("Authorization", format!("Bearer {}", token.into()));
+    }
+
+    /// Fails calls whose response body is larger than `limit` bytes with
+    /// `Error::ResponseTooLarge`, instead of reading all of it.
+    pub fn set_max_response_size(&mut self, limit: Option<usize>) {
+        self.max_response_size = limit;
+    }
+
+    /// Whether to ask for responses compressed with gzip or deflate, which
+    /// are then decompressed before parsing. On by default. The limit of
+    /// `set_max_response_size` applies both before and after decompressing.
+    #[cfg(feature = "gzip")]
+    pub fn set_compression(&mut self, enabled: bool) {
+        self.compression = enabled;
+    }
+
+    /// Writes calls in `encoding` rather than in UTF-8, for servers that
+    /// read nothing else. Characters the encoding lacks are written as
+    /// character references.
+    #[cfg(feature = "encoding")]
+    pub fn set_output_encoding(&mut self, encoding: Option<&'static Encoding>) {
+        self.output_encoding = encoding;
+    }
+
+    /// Limits how long a call may wait on the server after connecting.
+    ///
+    /// The deadline is checked whenever a socket operation times out, so a
+    /// server trickling data can overrun it by up to one read timeout.
+    /// `deadline` overrides it for a single call.
+    pub fn set_call_timeout(&mut self, timeout: Option<Duration>) {
+        self.call_timeout = timeout;
+    }
+
+    /// Sends calls that fail in ways `policy` retries again, waiting
+    /// between attempts. Off by default. See the `retry` module.
+    ///
+    /// The attempts share the call timeout: each gets only what is left of
+    /// it, and no attempt is made once the wait before it would use it up.
+    pub fn set_retry_policy(&mut self, policy: Option<RetryPolicy>) {
+        self.retry = policy;
+    }
+
+    /// Sends `context` with every call instead of that of the request being
+    /// handled on this thread.
+    pub fn set_trace_context(&mut self, context: Option<TraceContext>) {
+        self.trace_context = context;
+    }
+
+    /// Signs the body of every call with `signer`, for servers that check
+    /// signatures.
+    #[cfg(feature = "hmac")]
+    pub fn set_signer(&mut self, signer: Option<Signer>) {
+        self.signer = signer;
+    }
+
+    /// Sets the XML dialect of calls, for servers that only read some of
+    /// XML-RPC.
+    pub fn set_wire_profile(&mut self, profile: WireProfile) {
+        self.wire_profile = profile;
+    }
+
+    /// Sets how the UTC offsets of sent and received datetimes are handled.
+    #[cfg(feature = "chrono")]
+    pub fn set_datetimes(&mut self, options: DateTimeOptions) {
+        self.datetimes = options;
     }
 
     pub fn call_value<Tkey>(&mut self, uri: &Url, name: Tkey, params: Params) -> Result<Response>
     where
         Tkey: Into<String>,
     {
+        self.call_value_by(uri, name.into(), params)
+    }
+
+    /// Gives the call made through the result `timeout` in place of the
+    /// call timeout set on the client, for the odd method much slower or
+    /// faster than the rest, as in
+    /// `client.deadline(Duration::from_secs(300)).call(&uri, "reindex", ())`.
+    pub fn deadline(&mut self, timeout: Duration) -> Deadline<'_> {
+        let default = self.call_timeout.replace(timeout);
+        Deadline {
+            client: self,
+            default,
+        }
+    }
+
+    /// Like `call_value`, but with `timeout` in place of the call timeout
+    /// set on the client. The same as `deadline(timeout).call_value(...)`.
+    pub fn call_value_within<Tkey>(
+        &mut self,
+        uri: &Url,
+        name: Tkey,
+        params: Params,
+        timeout: Duration,
+    ) -> Result<Response>
+    where
+        Tkey: Into<String>,
+    {
+        self.deadline(timeout).call_value(uri, name, params)
+    }
+
+    fn call_value_by(&mut self, uri: &Url, name: String, params: Params) -> Result<Response> {
+        let call_timeout = self.call_timeout;
+        #[cfg(unix)]
+        {
+            if uri.scheme() == "unix" {
+                self.open_url_socket(uri);
+            }
+        }
+        let cancel = self.cancel.clone();
+        let cancelled = || cancel.as_ref().is_some_and(CancelToken::is_cancelled);
+        if cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let started = Instant::now();
+        let before = self.counters.snapshot();
+        let mut params = params;
+        let mut attempt = 1;
+        let result = loop {
+            // Later attempts only get what is left of the call timeout.
+            let left = match call_timeout {
+                Some(limit) => match limit.checked_sub(started.elapsed()) {
+                    Some(left) if left > Duration::from_secs(0) => Some(left),
+                    _ => break Err(Error::CallTimeout),
+                },
+                None => None,
+            };
+            self.set_timeouts(left);
+            // Kept for the next attempt, if there may be one.
+            let spare = match self.retry {
+                Some(ref policy) if policy.allows(&name, attempt) => Some(params.clone()),
+                _ => None,
+            };
+            let result = self.exchange(uri, name.clone(), params);
+            let (policy, spare) = match (self.retry.as_ref(), spare) {
+                (Some(policy), Some(spare)) => (policy, spare),
+                _ => break result,
+            };
+            match result {
+                Err(ref err) if !cancelled() && policy.retries(err) => {}
+                result => break result,
+            }
+            let delay = policy.delay(attempt);
+            // No attempt is made that could not finish before the deadline.
+            if call_timeout.is_some_and(|limit| started.elapsed() + delay >= limit) {
+                break result;
+            }
+            thread::sleep(delay);
+            attempt += 1;
+            params = spare;
+        };
+        self.counters.count_call(&before);
+        match result {
+            Err(_) if cancelled() => Err(Error::Cancelled),
+            Err(Error::ReadTimeout) => match call_timeout {
+                Some(limit) if started.elapsed() >= limit => Err(Error::CallTimeout),
+                _ => Err(Error::ReadTimeout),
+            },
+            result => result,
+        }
+    }
+
+    // Bounds socket operations by `call_timeout` as well as by the read and
+    // write timeouts.
+    fn set_timeouts(&mut self, call_timeout: Option<Duration>) {
+        let io_timeout = |timeout: Option<Duration>| match (timeout, call_timeout) {
+            (Some(timeout), Some(call)) => Some(timeout.min(call)),
+            (timeout, call) => timeout.or(call),
+        };
+        let read_timeout = io_timeout(self.read_timeout);
+        let write_timeout = io_timeout(self.write_timeout.or(self.read_timeout));
+        #[cfg(unix)]
+        let url_socket = self
+            .url_socket
+            .as_mut()
+            .map(|&mut (_, ref mut client)| client);
+        #[cfg(not(unix))]
+        let url_socket = None;
+        for client in Some(&mut self.client)
+            .into_iter()
+            .chain(self.proxied.as_mut())
+            .chain(url_socket)
+        {
+            client.set_read_timeout(read_timeout);
+            client.set_write_timeout(write_timeout);
+        }
+    }
+
+    fn exchange(&mut self, uri: &Url, name: String, params: Params) -> Result<Response> {
         use super::xmlfmt::value::ToXml;
-        let body_str = Call {
-            name: name.into(),
-            params,
+
+        #[cfg(feature = "chrono")]
+        let params = {
+            let mut params = params;
+            self.datetimes.send(&mut params)?;
+            params
+        };
+
+        let mut headers = self.headers.clone();
+        #[cfg(feature = "encoding")]
+        let output_encoding = self.output_encoding.map(Encoding::output_encoding);
+        #[cfg(feature = "encoding")]
+        let content_type = match output_encoding {
+            Some(encoding) => format!("text/xml; charset={}", encoding.name()),
+            None => String::from("text/xml"),
+        };
+        #[cfg(not(feature = "encoding"))]
+        let content_type = String::from("text/xml");
+        headers.set_raw("Content-Type", vec![content_type.into_bytes()]);
+        #[cfg(feature = "gzip")]
+        {
+            if self.compression {
+                headers.set_raw("Accept-Encoding", vec![compression::ACCEPTED.into()]);
+            }
+        }
+        if let Some(context) = self.trace_context.clone().or_else(tracecontext::current) {
+            headers.set_raw(TRACEPARENT, vec![context.traceparent.into_bytes()]);
+            if let Some(state) = context.tracestate {
+                headers.set_raw(TRACESTATE, vec![state.into_bytes()]);
+            }
         }
-        .to_xml();
-        let bytes: &[u8] = body_str.as_bytes();
-        let body = hyper::client::Body::BufBody(bytes, bytes.len());
 
-        let mut headers = Headers::new();
-        headers.set(ContentType("xml".to_owned()));
+        let proxied = match self.proxied {
+            Some(ref proxied) if uri.scheme() == "http" => uri
+                .host_str()
+                .and_then(|host| self.connect.http_proxy_for(host))
+                .map(|proxy| (proxied, proxy)),
+            _ => None,
+        };
+        let client = match proxied {
+            Some((proxied, proxy)) => {
+                if let Some(authorization) = proxy.authorization() {
+                    headers.set_raw("Proxy-Authorization", vec![authorization.into()]);
+                }
+                proxied
+            }
+            None => &self.client,
+        };
+        // Calls through a socket named by the URL go to the root path.
+        #[cfg(unix)]
+        let root = Url::parse("http://localhost/").expect("a valid URL");
+        #[cfg(unix)]
+        let (client, uri) = match self.url_socket {
+            Some((_, ref socket)) if uri.scheme() == "unix" => (socket, &root),
+            _ => (client, uri),
+        };
 
-        let response = self
-            .client
-            .post(uri.as_ref())
-            .headers(headers)
-            .body(body)
-            .send()
-            .chain_err(|| "Failed to run the HTTP request within hyper.")?;
+        let call = Call { name, params };
+        let profile = &self.wire_profile;
+        #[cfg(feature = "hmac")]
+        let signer = self.signer.as_ref();
+        let mut response = scratch::with_bytes(|bytes| {
+            call.write_xml_as(bytes, profile)?;
+            #[cfg(feature = "encoding")]
+            {
+                if let Some(encoding) = output_encoding {
+                    let encoded = charset::encode(&String::from_utf8_lossy(bytes), encoding);
+                    bytes.clear();
+                    bytes.extend_from_slice(&encoded);
+                }
+            }
+            #[cfg(feature = "hmac")]
+            let headers = {
+                let mut headers = headers;
+                if let Some(signer) = signer {
+                    let signature = signer.sign(bytes).into_bytes();
+                    headers.set_raw(signer.header().to_owned(), vec![signature]);
+                }
+                headers
+            };
+            client
+                .post(uri.as_ref())
+                .headers(headers)
+                .body(hyper::client::Body::BufBody(bytes, bytes.len()))
+                .send()
+                .map_err(Error::from)
+        })?;
 
-        parse::response(response).map_err(Into::into)
+        let mut body = Vec::new();
+        match self.max_response_size {
+            Some(limit) => {
+                (&mut response)
+                    .take(limit as u64 + 1)
+                    .read_to_end(&mut body)?;
+                if body.len() > limit {
+                    return Err(Error::ResponseTooLarge { limit });
+                }
+            }
+            None => {
+                response.read_to_end(&mut body)?;
+            }
+        }
+        #[cfg(feature = "gzip")]
+        let body = decompress(&response, body, self.max_response_size)?;
+        if response.status.to_u16() == 401 {
+            return Err(Error::Unauthorized(describe_reply(&response, &body)));
+        }
+        if !response.status.is_success() {
+            return Err(Error::Status(describe_reply(&response, &body)));
+        }
+        let content_type = response
+            .headers
+            .get_raw("Content-Type")
+            .and_then(|values| values.first())
+            .map(|value| String::from_utf8_lossy(value).into_owned());
+        let parsed = match parse::content_charset(content_type.as_deref()) {
+            #[cfg(feature = "encoding")]
+            Some(charset) => parse::response_in_charset(body.as_slice(), charset),
+            _ => parse::response(body.as_slice()),
+        };
+        let reply = parsed.map_err(|err| Error::InvalidResponse {
+            reply: describe_reply(&response, &body),
+            source: err,
+        })?;
+        #[cfg(feature = "chrono")]
+        let reply = match reply {
+            Ok(mut values) => {
+                self.datetimes.receive(&mut values)?;
+                Ok(values)
+            }
+            fault => fault,
+        };
+        Ok(reply)
     }
 
+    /// Faults are returned as `Ok(Err(fault))`, keeping them apart from
+    /// transport and protocol errors. Use `??` to fold them into `Error::Fault`.
     pub fn call<'a, Tkey, Treq, Tres>(
         &mut self,
         uri: &Url,
@@ -82,4 +913,300 @@ impl Client {
             Err(v) => Err(v),
         }
     }
+
+    /// Like `call`, but passes `args`, a struct or map, as the single struct
+    /// parameter that methods taking named parameters expect.
+    pub fn call_named<'a, Tkey, Targs, Tres>(
+        &mut self,
+        uri: &Url,
+        name: Tkey,
+        args: Targs,
+    ) -> Result<std::result::Result<Tres, Fault>>
+    where
+        Tkey: Into<String>,
+        Targs: Serialize,
+        Tres: Deserialize<'a>,
+    {
+        let params = into_params(&args)?;
+        match params.first() {
+            Some(&Value::Struct(_)) if params.len() == 1 => {}
+            _ => {
+                return Err(FmtError::UnsupportedData(
+                    "named parameters must serialize to a struct".into(),
+                )
+                .into())
+            }
+        }
+        match self.call_value(uri, name, params)? {
+            Ok(v) => from_params(v).map(Ok).map_err(Into::into),
+            Err(v) => Ok(Err(v)),
+        }
+    }
+
+    /// Makes the calls in `calls` in one round trip through
+    /// `system.multicall`, returning the response to each in order. Faults
+    /// of the multicall itself, as from servers without it, are returned as
+    /// `Ok(Err(fault))`, like with `call`.
+    pub fn multicall(
+        &mut self,
+        uri: &Url,
+        calls: &Multicall,
+    ) -> Result<std::result::Result<Vec<Response>, Fault>> {
+        let call = calls.to_call();
+        match self.call_value(uri, call.name, call.params)? {
+            Ok(params) => calls.responses(params).map(Ok).map_err(Into::into),
+            Err(fault) => Ok(Err(fault)),
+        }
+    }
+
+    /// Calls job method `name`, then waits for the job to finish and
+    /// returns its result, asking whether it is done as often as `backoff`
+    /// says. See the `jobs` module.
+    pub fn call_job<Tkey>(
+        &mut self,
+        uri: &Url,
+        name: Tkey,
+        params: Params,
+        backoff: Backoff,
+    ) -> Result<Response>
+    where
+        Tkey: Into<String>,
+    {
+        let name = name.into();
+        let id: String = match self.call_value(uri, name.clone(), params)? {
+            Ok(v) => from_params(v)?,
+            Err(fault) => return Ok(Err(fault)),
+        };
+        let status = format!("{}.status", name);
+        let mut delay = backoff.initial;
+        loop {
+            let done = match self.call_value(uri, status.clone(), vec![Value::from(&id[..])])? {
+                Ok(v) => from_params::<String>(v)? == "done",
+                Err(fault) => return Ok(Err(fault)),
+            };
+            if done {
+                break;
+            }
+            thread::sleep(delay);
+            delay = backoff.next(delay);
+        }
+        self.call_value(uri, format!("{}.result", name), vec![Value::String(id)])
+    }
+}
+
+/// A client with the call timeout overridden for one call, made with
+/// `Client::deadline`. The methods stand for those of the same name on
+/// `Client`. For `call_job`, the timeout applies to each of the calls it
+/// makes to start the job, follow it and fetch its result.
+pub struct Deadline<'a> {
+    client: &'a mut Client,
+    // The client's own call timeout, put back when done.
+    default: Option<Duration>,
+}
+
+impl<'a> Deadline<'a> {
+    pub fn call_value<Tkey>(self, uri: &Url, name: Tkey, params: Params) -> Result<Response>
+    where
+        Tkey: Into<String>,
+    {
+        self.client.call_value(uri, name, params)
+    }
+
+    pub fn call<'de, Tkey, Treq, Tres>(
+        self,
+        uri: &Url,
+        name: Tkey,
+        req: Treq,
+    ) -> Result<std::result::Result<Tres, Fault>>
+    where
+        Tkey: Into<String>,
+        Treq: Serialize,
+        Tres: Deserialize<'de>,
+    {
+        self.client.call(uri, name, req)
+    }
+
+    pub fn call_named<'de, Tkey, Targs, Tres>(
+        self,
+        uri: &Url,
+        name: Tkey,
+        args: Targs,
+    ) -> Result<std::result::Result<Tres, Fault>>
+    where
+        Tkey: Into<String>,
+        Targs: Serialize,
+        Tres: Deserialize<'de>,
+    {
+        self.client.call_named(uri, name, args)
+    }
+
+    pub fn multicall(
+        self,
+        uri: &Url,
+        calls: &Multicall,
+    ) -> Result<std::result::Result<Vec<Response>, Fault>> {
+        self.client.multicall(uri, calls)
+    }
+
+    pub fn call_job<Tkey>(
+        self,
+        uri: &Url,
+        name: Tkey,
+        params: Params,
+        backoff: Backoff,
+    ) -> Result<Response>
+    where
+        Tkey: Into<String>,
+    {
+        self.client.call_job(uri, name, params, backoff)
+    }
+}
+
+impl<'a> Drop for Deadline<'a> {
+    fn drop(&mut self) {
+        self.client.call_timeout = self.default;
+    }
+}
+
+/// Settings for a new `Client`, applied to every call it makes. Each
+/// stands for the setter of the same name on `Client`.
+#[derive(Default)]
+pub struct ClientBuilder {
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    call_timeout: Option<Duration>,
+    retry_policy: Option<RetryPolicy>,
+    headers: Vec<(String, String)>,
+    max_response_size: Option<usize>,
+    http_proxy: Option<HttpProxy>,
+    max_idle_connections: Option<usize>,
+    idle_timeout: Option<Duration>,
+}
+
+impl ClientBuilder {
+    pub fn connect_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn read_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    pub fn write_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.write_timeout = Some(timeout);
+        self
+    }
+
+    pub fn call_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.call_timeout = Some(timeout);
+        self
+    }
+
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> ClientBuilder {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Adds a header sent with every call. Setting one twice keeps the
+    /// last value.
+    pub fn header<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> ClientBuilder {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub fn user_agent<V: Into<String>>(self, user_agent: V) -> ClientBuilder {
+        self.header("User-Agent", user_agent)
+    }
+
+    pub fn basic_auth<U: Into<String>, P: Into<String>>(
+        self,
+        user: U,
+        password: P,
+    ) -> ClientBuilder {
+        self.header(
+            "Authorization",
+            basic_credentials(user.into(), password.into()),
+        )
+    }
+
+    pub fn bearer_auth<T: Into<String>>(self, token: T) -> ClientBuilder {
+        self.header("Authorization", format!("Bearer {}", token.into()))
+    }
+
+    pub fn max_response_size(mut self, limit: usize) -> ClientBuilder {
+        self.max_response_size = Some(limit);
+        self
+    }
+
+    pub fn http_proxy(mut self, proxy: HttpProxy) -> ClientBuilder {
+        self.http_proxy = Some(proxy);
+        self
+    }
+
+    pub fn max_idle_connections(mut self, max_idle: usize) -> ClientBuilder {
+        self.max_idle_connections = Some(max_idle);
+        self
+    }
+
+    pub fn idle_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    pub fn build(self) -> Result<Client> {
+        let mut client = Client::new()?;
+        if self.connect_timeout.is_some() {
+            client.connect.timeout = self.connect_timeout;
+        }
+        if let Some(max_idle) = self.max_idle_connections {
+            client.connect.pool.max_idle = max_idle;
+        }
+        if self.idle_timeout.is_some() {
+            client.connect.pool.idle_timeout = self.idle_timeout;
+        }
+        client.connect.http_proxy = self.http_proxy;
+        client.reconnect();
+        client.set_read_timeout(self.read_timeout);
+        client.set_write_timeout(self.write_timeout);
+        client.set_call_timeout(self.call_timeout);
+        client.set_retry_policy(self.retry_policy);
+        for (name, value) in self.headers {
+            client.set_header(name, value);
+        }
+        client.set_max_response_size(self.max_response_size);
+        Ok(client)
+    }
+}
+
+/// Domain errors that well-known server faults can be decoded into.
+pub trait FromFault: Sized {
+    /// Returns `None` for faults this type does not describe.
+    fn from_fault(fault: &Fault) -> Option<Self>;
+}
+
+impl FromFault for Fault {
+    fn from_fault(fault: &Fault) -> Option<Fault> {
+        Some(fault.clone())
+    }
+}
+
+/// Decodes the fault of a `Client::call` result into a typed error.
+pub trait MapFault<T> {
+    /// Faults that `E` does not recognize are returned as `Error::Fault`.
+    fn map_fault<E: FromFault>(self) -> Result<std::result::Result<T, E>>;
+}
+
+impl<T> MapFault<T> for Result<std::result::Result<T, Fault>> {
+    fn map_fault<E: FromFault>(self) -> Result<std::result::Result<T, E>> {
+        match self? {
+            Ok(value) => Ok(Ok(value)),
+            Err(fault) => match E::from_fault(&fault) {
+                Some(err) => Ok(Err(err)),
+                None => Err(Error::Fault(fault)),
+            },
+        }
+    }
 }
@@ -0,0 +1,273 @@
+//! Generates typed Rust clients from a server's introspection methods.
+//!
+//! `introspect` asks a live server for `system.listMethods`,
+//! `system.methodSignature` and `system.methodHelp`, and `generate` turns
+//! the answer into the source of a module with one Rust method per
+//! XML-RPC method.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use super::client::Client;
+use super::error::{Error, Result};
+use super::xmlfmt::{from_params, Params, Value};
+use Url;
+
+/// One signature from `system.methodSignature`, as XML-RPC type names.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signature {
+    pub output: String,
+    pub params: Vec<String>,
+}
+
+/// What a server says about one of its methods.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MethodInfo {
+    pub name: String,
+    /// Empty when the server does not describe the method's signature.
+    pub signatures: Vec<Signature>,
+    pub help: Option<String>,
+}
+
+fn call(client: &mut Client, uri: &Url, name: &str, params: Params) -> Result<Value> {
+    let mut values = client
+        .call_value(uri, name, params)?
+        .map_err(Error::Fault)?;
+    Ok(if values.len() == 1 {
+        values.remove(0)
+    } else {
+        Value::Array(values)
+    })
+}
+
+// Servers answer with a non-array, usually the string "undef", for methods
+// they cannot describe.
+fn signatures(value: Value) -> Vec<Signature> {
+    from_params::<Vec<Vec<String>>>(vec![value])
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|types| !types.is_empty())
+        .map(|mut types| Signature {
+            output: types.remove(0),
+            params: types,
+        })
+        .collect()
+}
+
+/// Describes every method the server at `uri` lists.
+///
+/// Only `system.listMethods` is required. Signatures and help texts are
+/// left empty for methods the server refuses to describe.
+pub fn introspect(client: &mut Client, uri: &Url) -> Result<Vec<MethodInfo>> {
+    let names = call(client, uri, "system.listMethods", Vec::new())?;
+    let names = from_params::<Vec<String>>(vec![names])?;
+    let mut methods = Vec::with_capacity(names.len());
+    for name in names {
+        let params = vec![Value::String(name.clone())];
+        let signatures = match call(client, uri, "system.methodSignature", params.clone()) {
+            Ok(value) => signatures(value),
+            Err(Error::Fault(_)) => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        let help = match call(client, uri, "system.methodHelp", params) {
+            Ok(Value::String(ref help)) if help.trim().is_empty() => None,
+            Ok(Value::String(help)) => Some(help),
+            Ok(_) | Err(Error::Fault(_)) => None,
+            Err(err) => return Err(err),
+        };
+        methods.push(MethodInfo {
+            name,
+            signatures,
+            help,
+        });
+    }
+    Ok(methods)
+}
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+// `examples.getStateName` becomes `examples_get_state_name`.
+fn method_ident(name: &str) -> String {
+    let mut ident = String::new();
+    let mut previous = '_';
+    for c in name.chars() {
+        if !c.is_ascii_alphanumeric() {
+            previous = '_';
+            continue;
+        }
+        if !ident.is_empty()
+            && (previous == '_' || (c.is_ascii_uppercase() && previous.is_ascii_lowercase()))
+            && !ident.ends_with('_')
+        {
+            ident.push('_');
+        }
+        ident.push(c.to_ascii_lowercase());
+        previous = c;
+    }
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    if KEYWORDS.contains(&ident.as_str()) {
+        ident.push('_');
+    }
+    ident
+}
+
+// The Rust type for an XML-RPC type, and how to make a `Value` of it from
+// an expression of that type.
+fn param_type(kind: &str) -> (&'static str, &'static str) {
+    match kind {
+        "int" | "i4" => ("i32", "Value::Int"),
+        "i8" => ("i64", "Value::Int64"),
+        "boolean" => ("bool", "Value::Bool"),
+        "string" => ("String", "Value::String"),
+        "double" => ("f64", "Value::Double"),
+        "dateTime.iso8601" => ("String", "Value::DateTime"),
+        "base64" => ("Vec<u8>", "Value::Base64"),
+        "array" => ("Vec<Value>", "Value::Array"),
+        "struct" => ("HashMap<String, Value>", "Value::Struct"),
+        _ => ("Value", ""),
+    }
+}
+
+// Results that serde cannot decode into a precise type stay a `Value`.
+fn output_type(kind: &str) -> Option<&'static str> {
+    match kind {
+        "int" | "i4" => Some("i32"),
+        "i8" => Some("i64"),
+        "boolean" => Some("bool"),
+        "string" | "dateTime.iso8601" => Some("String"),
+        "double" => Some("f64"),
+        _ => None,
+    }
+}
+
+fn write_method(out: &mut String, ident: &str, method: &MethodInfo) {
+    if let Some(ref help) = method.help {
+        for line in help.trim().lines().map(str::trim_end) {
+            if line.is_empty() {
+                out.push_str("    ///\n");
+            } else {
+                let _ = writeln!(out, "    /// {}", line);
+            }
+        }
+        out.push_str("    ///\n");
+    }
+    for signature in &method.signatures {
+        let _ = writeln!(
+            out,
+            "    /// `{}({}) -> {}`",
+            method.name,
+            signature.params.join(", "),
+            signature.output
+        );
+    }
+    if method.signatures.is_empty() {
+        let _ = writeln!(out, "    /// `{}`, signature unknown", method.name);
+    }
+    if method.signatures.len() > 1 {
+        out.push_str("    ///\n    /// Only the first signature is generated.\n");
+    }
+
+    let signature = match method.signatures.first() {
+        Some(signature) => signature,
+        None => {
+            let _ = writeln!(
+                out,
+                "    pub fn {}(&mut self, params: Params) -> Result<Value> {{\n        \
+                 self.call_value({:?}, params)\n    }}",
+                ident, method.name
+            );
+            return;
+        }
+    };
+    let args = signature
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, kind)| format!(", arg{}: {}", i, param_type(kind).0))
+        .collect::<String>();
+    let values = signature
+        .params
+        .iter()
+        .enumerate()
+        .map(|(i, kind)| format!("{}(arg{})", param_type(kind).1, i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let call = format!("self.call_value({:?}, vec![{}])", method.name, values);
+    let (output, body) = match output_type(&signature.output) {
+        Some(output) => (
+            output,
+            format!(
+                "let value = {}?;\n        from_params(vec![value]).map_err(Into::into)",
+                call
+            ),
+        ),
+        None => ("Value", call),
+    };
+    let _ = writeln!(
+        out,
+        "    pub fn {}(&mut self{}) -> Result<{}> {{\n        {}\n    }}",
+        ident, args, output, body
+    );
+}
+
+/// Writes a module defining `struct_name`, a client with one method per
+/// entry of `methods`.
+///
+/// Methods are named after their XML-RPC names in snake case, and take and
+/// return Rust types following their first signature. Results of types
+/// without a fixed shape, like arrays and structs, are returned as `Value`.
+/// `system.*` methods are left out.
+pub fn generate(struct_name: &str, methods: &[MethodInfo]) -> String {
+    let mut out = String::new();
+    out.push_str(
+        "// Generated from XML-RPC introspection.\n\n\
+         #![allow(dead_code, unused_imports)]\n\n\
+         use std::collections::HashMap;\n\n\
+         use xml_rpc::error::{Error, Result};\n\
+         use xml_rpc::{from_params, Client, Params, Url, Value};\n\n",
+    );
+    let _ = write!(
+        out,
+        "pub struct {name} {{\n    client: Client,\n    uri: Url,\n}}\n\n\
+         impl {name} {{\n    \
+         pub fn new(uri: Url) -> Result<{name}> {{\n        \
+         Ok({name}::with_client(Client::new()?, uri))\n    }}\n\n    \
+         pub fn with_client(client: Client, uri: Url) -> {name} {{\n        \
+         {name} {{ client, uri }}\n    }}\n\n    \
+         fn call_value(&mut self, name: &str, params: Params) -> Result<Value> {{\n        \
+         let mut values = self\n            .client\n            \
+         .call_value(&self.uri, name, params)?\n            \
+         .map_err(Error::Fault)?;\n        \
+         Ok(if values.len() == 1 {{\n            values.remove(0)\n        \
+         }} else {{\n            Value::Array(values)\n        }})\n    }}\n",
+        name = struct_name
+    );
+    let mut taken = ["new", "with_client", "call_value"]
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<HashSet<_>>();
+    for method in methods {
+        if method.name.starts_with("system.") {
+            continue;
+        }
+        let base = method_ident(&method.name);
+        let mut ident = base.clone();
+        let mut suffix = 2;
+        while !taken.insert(ident.clone()) {
+            ident = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        out.push('\n');
+        write_method(&mut out, &ident, method);
+    }
+    out.push_str("}\n");
+    out
+}
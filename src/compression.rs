@@ -0,0 +1,193 @@
+//! Gzip and deflate content codings, for replies that shrink a lot when
+//! compressed, as those full of structs do.
+
+use miniz_oxide::deflate::{compress_to_vec, compress_to_vec_zlib};
+use miniz_oxide::inflate::{
+    decompress_to_vec_with_limit, decompress_to_vec_zlib_with_limit, DecompressError, TINFLStatus,
+};
+
+// What gzip itself defaults to, trading speed for size evenly.
+const LEVEL: u8 = 6;
+
+// The header of a gzip member, with no name, time or extra fields.
+const GZIP_HEADER: [u8; 10] = [0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 255];
+
+// Where the flags of a gzip header say optional fields follow.
+const FHCRC: u8 = 2;
+const FEXTRA: u8 = 4;
+const FNAME: u8 = 8;
+const FCOMMENT: u8 = 16;
+
+/// What to send in `Accept-Encoding`.
+pub(crate) const ACCEPTED: &str = "gzip, deflate";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Coding {
+    Gzip,
+    // The zlib format, which `deflate` means in HTTP.
+    Deflate,
+}
+
+pub(crate) enum DecodeError {
+    // The body would come to more than the limit once decoded.
+    TooLarge,
+    Invalid(String),
+}
+
+impl Coding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Coding::Gzip => "gzip",
+            Coding::Deflate => "deflate",
+        }
+    }
+
+    // The coding a `Content-Encoding` value names, or `None` for the
+    // identity coding. Fails with codings not supported.
+    pub fn from_header(value: &str) -> Result<Option<Coding>, String> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("gzip") || value.eq_ignore_ascii_case("x-gzip") {
+            Ok(Some(Coding::Gzip))
+        } else if value.eq_ignore_ascii_case("deflate") {
+            Ok(Some(Coding::Deflate))
+        } else if value.is_empty() || value.eq_ignore_ascii_case("identity") {
+            Ok(None)
+        } else {
+            Err(format!("unsupported content coding {:?}", value))
+        }
+    }
+
+    // The coding to send a body in to a client that sent `accept` as its
+    // `Accept-Encoding`, preferring gzip when both are as welcome.
+    pub fn accepted(accept: &str) -> Option<Coding> {
+        let mut best = None;
+        for entry in accept.split(',') {
+            let mut parts = entry.split(';');
+            let name = parts.next().unwrap_or("").trim();
+            let quality = parts
+                .filter_map(|param| {
+                    let (key, value) = param.split_at(param.find('=')?);
+                    if key.trim().eq_ignore_ascii_case("q") {
+                        value[1..].trim().parse::<f32>().ok()
+                    } else {
+                        None
+                    }
+                })
+                .next()
+                .unwrap_or(1.0);
+            let coding = match name {
+                "*" => Coding::Gzip,
+                _ => match Coding::from_header(name) {
+                    Ok(Some(coding)) => coding,
+                    _ => continue,
+                },
+            };
+            if quality > 0.0
+                && best.is_none_or(|(_, best_quality)| {
+                    quality > best_quality || (quality == best_quality && coding == Coding::Gzip)
+                })
+            {
+                best = Some((coding, quality));
+            }
+        }
+        best.map(|(coding, _)| coding)
+    }
+
+    pub fn encode(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            Coding::Gzip => {
+                let mut out = GZIP_HEADER.to_vec();
+                out.extend_from_slice(&compress_to_vec(data, LEVEL));
+                out.extend_from_slice(&crc32(data).to_le_bytes());
+                out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                out
+            }
+            Coding::Deflate => compress_to_vec_zlib(data, LEVEL),
+        }
+    }
+
+    // Decodes `data`, refusing to decode more than `limit` bytes.
+    pub fn decode(self, data: &[u8], limit: usize) -> Result<Vec<u8>, DecodeError> {
+        let decoded = match self {
+            Coding::Gzip => {
+                let (deflated, trailer) = gzip_member(data).map_err(DecodeError::Invalid)?;
+                let decoded =
+                    decompress_to_vec_with_limit(deflated, limit).map_err(inflate_error)?;
+                let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+                if crc != crc32(&decoded) {
+                    return Err(DecodeError::Invalid("gzip checksum mismatch".into()));
+                }
+                decoded
+            }
+            Coding::Deflate => {
+                decompress_to_vec_zlib_with_limit(data, limit).map_err(inflate_error)?
+            }
+        };
+        Ok(decoded)
+    }
+}
+
+fn inflate_error(err: DecompressError) -> DecodeError {
+    match err.status {
+        TINFLStatus::HasMoreOutput => DecodeError::TooLarge,
+        status => DecodeError::Invalid(format!("corrupt compressed data ({:?})", status)),
+    }
+}
+
+// The deflated data of a gzip member and its 8-byte trailer.
+fn gzip_member(data: &[u8]) -> Result<(&[u8], &[u8]), String> {
+    let truncated = || String::from("truncated gzip data");
+    if data.len() < GZIP_HEADER.len() + 8 || data[0] != 0x1f || data[1] != 0x8b || data[2] != 8 {
+        return Err("not gzip data".into());
+    }
+    let flags = data[3];
+    let mut rest = &data[GZIP_HEADER.len()..];
+    if flags & FEXTRA != 0 {
+        if rest.len() < 2 {
+            return Err(truncated());
+        }
+        let len = usize::from(rest[0]) | usize::from(rest[1]) << 8;
+        rest = rest.get(2 + len..).ok_or_else(truncated)?;
+    }
+    for &field in &[FNAME, FCOMMENT] {
+        if flags & field != 0 {
+            let end = rest.iter().position(|&b| b == 0).ok_or_else(truncated)?;
+            rest = &rest[end + 1..];
+        }
+    }
+    if flags & FHCRC != 0 {
+        rest = rest.get(2..).ok_or_else(truncated)?;
+    }
+    if rest.len() < 8 {
+        return Err(truncated());
+    }
+    Ok(rest.split_at(rest.len() - 8))
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut crc = n as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[n] = crc;
+        n += 1;
+    }
+    table
+}
+
+static CRC_TABLE: [u32; 256] = crc_table();
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        CRC_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
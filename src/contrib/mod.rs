@@ -0,0 +1,8 @@
+//! Typed bindings for well-known XML-RPC APIs, each behind its own feature.
+
+#[cfg(feature = "contrib-opensubtitles")]
+pub mod opensubtitles;
+#[cfg(feature = "contrib-supervisor")]
+pub mod supervisor;
+#[cfg(feature = "contrib-wordpress")]
+pub mod wordpress;
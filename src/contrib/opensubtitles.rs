@@ -0,0 +1,129 @@
+//! The legacy OpenSubtitles XML-RPC API, served at
+//! `https://api.opensubtitles.org/xml-rpc`.
+
+use super::super::client::Client;
+use super::super::error::{Error, FmtError, Result};
+use super::super::xmlfmt::Fault;
+use Url;
+
+#[derive(Deserialize)]
+struct Status {
+    status: String,
+}
+
+#[derive(Deserialize)]
+struct Session {
+    status: String,
+    token: String,
+}
+
+/// Search criteria. Empty fields are not sent.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SearchQuery {
+    /// Comma separated ISO 639-2 codes, e.g. `"eng,ger"`.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub sublanguageid: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub moviehash: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub moviebytesize: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub imdbid: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub query: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub season: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub episode: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Subtitle {
+    #[serde(rename = "IDSubtitleFile")]
+    pub id: String,
+    #[serde(rename = "SubFileName")]
+    pub file_name: String,
+    #[serde(rename = "SubLanguageID")]
+    pub language: String,
+    #[serde(rename = "SubFormat")]
+    pub format: String,
+    #[serde(rename = "SubDownloadLink")]
+    pub download_link: String,
+    #[serde(rename = "MovieName")]
+    pub movie_name: String,
+    #[serde(rename = "IDMovieImdb")]
+    pub imdb_id: String,
+}
+
+// The server answers `false` instead of an empty array when nothing matches.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Matches {
+    Found(Vec<Subtitle>),
+    Nothing(bool),
+}
+
+#[derive(Deserialize)]
+struct Search {
+    status: String,
+    data: Matches,
+}
+
+// Statuses look like `"200 OK"` or `"401 Unauthorized"`.
+fn check(status: &str) -> Result<()> {
+    if status.starts_with("200") {
+        return Ok(());
+    }
+    let code = status
+        .split_whitespace()
+        .next()
+        .and_then(|code| code.parse().ok())
+        .unwrap_or(0);
+    Err(Error::Fault(Fault::new(code, status)))
+}
+
+pub struct OpenSubtitles {
+    client: Client,
+    uri: Url,
+    token: String,
+}
+
+impl OpenSubtitles {
+    /// Logs in; pass empty credentials for anonymous access.
+    pub fn log_in(
+        uri: Url,
+        username: &str,
+        password: &str,
+        language: &str,
+        user_agent: &str,
+    ) -> Result<OpenSubtitles> {
+        let mut client = Client::new()?;
+        let args = (username, password, language, user_agent);
+        let session: Session = client.call(&uri, "LogIn", args)??;
+        check(&session.status)?;
+        Ok(OpenSubtitles {
+            client,
+            uri,
+            token: session.token,
+        })
+    }
+
+    pub fn search(&mut self, queries: &[SearchQuery]) -> Result<Vec<Subtitle>> {
+        let args = (&self.token, queries);
+        let search: Search = self.client.call(&self.uri, "SearchSubtitles", args)??;
+        check(&search.status)?;
+        match search.data {
+            Matches::Found(subtitles) => Ok(subtitles),
+            Matches::Nothing(false) => Ok(Vec::new()),
+            Matches::Nothing(true) => Err(Error::Parse(FmtError::Decoding(
+                "expected a list of subtitles or false, found true".into(),
+            ))),
+        }
+    }
+
+    pub fn log_out(mut self) -> Result<()> {
+        let status: Status = self.client.call(&self.uri, "LogOut", &self.token)??;
+        check(&status.status)
+    }
+}
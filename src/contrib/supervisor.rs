@@ -0,0 +1,126 @@
+//! The supervisord XML-RPC API, served over its `[inet_http_server]` or
+//! `[unix_http_server]` socket.
+
+use serde::de::DeserializeOwned;
+use std::path::PathBuf;
+
+use super::super::client::Client;
+use super::super::error::Result;
+use super::super::xmlfmt::from_params;
+use Url;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct State {
+    #[serde(rename = "statecode")]
+    pub code: i32,
+    #[serde(rename = "statename")]
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ProcessInfo {
+    pub name: String,
+    pub group: String,
+    pub description: String,
+    /// UNIX timestamps; zero if the event has not happened.
+    pub start: i32,
+    pub stop: i32,
+    pub now: i32,
+    pub state: i32,
+    pub statename: String,
+    pub spawnerr: String,
+    pub exitstatus: i32,
+    pub stdout_logfile: String,
+    pub stderr_logfile: String,
+    pub pid: i32,
+}
+
+/// A chunk of log output, as returned by the `tail*Log` calls.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LogTail {
+    pub bytes: String,
+    /// Where to continue reading on the next call.
+    pub offset: i32,
+    /// More output was written than was returned.
+    pub overflow: bool,
+}
+
+pub struct Supervisor {
+    client: Client,
+    uri: Url,
+}
+
+impl Supervisor {
+    /// Connects over TCP, e.g. to `http://localhost:9001/RPC2`.
+    pub fn new(uri: Url) -> Result<Supervisor> {
+        Ok(Supervisor {
+            client: Client::new()?,
+            uri,
+        })
+    }
+
+    /// Connects through the Unix socket configured in `[unix_http_server]`.
+    #[cfg(unix)]
+    pub fn unix<P: Into<PathBuf>>(path: P) -> Result<Supervisor> {
+        Ok(Supervisor {
+            client: Client::unix(path)?,
+            uri: Url::parse("http://localhost/RPC2").expect("valid URL"),
+        })
+    }
+
+    // `()` would be sent as an empty struct, so calls without arguments are
+    // made with empty params.
+    fn call_without_args<T: DeserializeOwned>(&mut self, name: &str) -> Result<T> {
+        let response = self.client.call_value(&self.uri, name, Vec::new())??;
+        Ok(from_params(response)?)
+    }
+
+    pub fn get_state(&mut self) -> Result<State> {
+        self.call_without_args("supervisor.getState")
+    }
+
+    pub fn get_all_process_info(&mut self) -> Result<Vec<ProcessInfo>> {
+        self.call_without_args("supervisor.getAllProcessInfo")
+    }
+
+    /// `name` is either `process` or `group:process`.
+    pub fn get_process_info(&mut self, name: &str) -> Result<ProcessInfo> {
+        Ok(self
+            .client
+            .call(&self.uri, "supervisor.getProcessInfo", name)??)
+    }
+
+    /// With `wait`, returns once the process has fully started.
+    pub fn start_process(&mut self, name: &str, wait: bool) -> Result<bool> {
+        let args = (name, wait);
+        Ok(self
+            .client
+            .call(&self.uri, "supervisor.startProcess", args)??)
+    }
+
+    /// With `wait`, returns once the process has fully stopped.
+    pub fn stop_process(&mut self, name: &str, wait: bool) -> Result<bool> {
+        let args = (name, wait);
+        Ok(self
+            .client
+            .call(&self.uri, "supervisor.stopProcess", args)??)
+    }
+
+    /// Reads up to `length` bytes of stdout, ending at the log's end when
+    /// `offset` is past it.
+    pub fn tail_stdout(&mut self, name: &str, offset: i32, length: i32) -> Result<LogTail> {
+        let args = (name, offset, length);
+        Ok(self
+            .client
+            .call(&self.uri, "supervisor.tailProcessStdoutLog", args)??)
+    }
+
+    pub fn tail_stderr(&mut self, name: &str, offset: i32, length: i32) -> Result<LogTail> {
+        let args = (name, offset, length);
+        Ok(self
+            .client
+            .call(&self.uri, "supervisor.tailProcessStderrLog", args)??)
+    }
+}
@@ -0,0 +1,132 @@
+//! The WordPress XML-RPC API, served at `https://<site>/xmlrpc.php`.
+
+use super::super::client::Client;
+use super::super::error::Result;
+use Url;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Blog {
+    #[serde(rename = "blogid")]
+    pub blog_id: String,
+    #[serde(rename = "blogName")]
+    pub name: String,
+    pub url: String,
+    pub xmlrpc: String,
+    #[serde(rename = "isAdmin")]
+    pub is_admin: bool,
+}
+
+/// A post as returned by the server. Fields the server left out are empty.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Post {
+    pub post_id: String,
+    pub post_title: String,
+    pub post_status: String,
+    pub post_type: String,
+    pub post_name: String,
+    pub post_author: String,
+    pub post_content: String,
+    pub post_excerpt: String,
+    pub link: String,
+}
+
+/// The fields of a post to create or change. Empty fields are not sent.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PostContent {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub post_type: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub post_status: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub post_title: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub post_content: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub post_excerpt: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub post_name: String,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PostFilter {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub post_type: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub post_status: String,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub number: i32,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub offset: i32,
+}
+
+fn is_zero(v: &i32) -> bool {
+    *v == 0
+}
+
+pub struct WordPress {
+    client: Client,
+    uri: Url,
+    blog_id: i32,
+    username: String,
+    password: String,
+}
+
+impl WordPress {
+    pub fn new<U, P>(uri: Url, username: U, password: P) -> Result<WordPress>
+    where
+        U: Into<String>,
+        P: Into<String>,
+    {
+        Ok(WordPress {
+            client: Client::new()?,
+            uri,
+            blog_id: 1,
+            username: username.into(),
+            password: password.into(),
+        })
+    }
+
+    /// Selects the blog of a multisite install. Defaults to 1.
+    pub fn set_blog_id(&mut self, blog_id: i32) {
+        self.blog_id = blog_id;
+    }
+
+    pub fn get_users_blogs(&mut self) -> Result<Vec<Blog>> {
+        let args = (&self.username, &self.password);
+        Ok(self.client.call(&self.uri, "wp.getUsersBlogs", args)??)
+    }
+
+    pub fn get_post(&mut self, post_id: &str) -> Result<Post> {
+        let args = (self.blog_id, &self.username, &self.password, post_id);
+        Ok(self.client.call(&self.uri, "wp.getPost", args)??)
+    }
+
+    pub fn get_posts(&mut self, filter: &PostFilter) -> Result<Vec<Post>> {
+        let args = (self.blog_id, &self.username, &self.password, filter);
+        Ok(self.client.call(&self.uri, "wp.getPosts", args)??)
+    }
+
+    /// Returns the ID of the new post.
+    pub fn new_post(&mut self, content: &PostContent) -> Result<String> {
+        let args = (self.blog_id, &self.username, &self.password, content);
+        Ok(self.client.call(&self.uri, "wp.newPost", args)??)
+    }
+
+    pub fn edit_post(&mut self, post_id: &str, content: &PostContent) -> Result<bool> {
+        let args = (
+            self.blog_id,
+            &self.username,
+            &self.password,
+            post_id,
+            content,
+        );
+        Ok(self.client.call(&self.uri, "wp.editPost", args)??)
+    }
+
+    pub fn delete_post(&mut self, post_id: &str) -> Result<bool> {
+        let args = (self.blog_id, &self.username, &self.password, post_id);
+        Ok(self.client.call(&self.uri, "wp.deletePost", args)??)
+    }
+}
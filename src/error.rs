@@ -1,14 +1,209 @@
-#![allow(unknown_lints, unused_doc_comments)]
-pub use super::xmlfmt::error::{Error as FmtError, ErrorKind as FmtErrorKind};
+#[cfg(feature = "hyper")]
+use hyper;
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
 
-error_chain! {
-    links {
-        XmlFormat(FmtError, FmtErrorKind);
+pub use super::xmlfmt::error::Error as FmtError;
+use super::xmlfmt::{Fault, IntoFault};
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("I/O failure: {0}")]
+    Io(#[source] io::Error),
+    #[error("Failed to connect to server: {0}")]
+    Connect(#[source] io::Error),
+    #[cfg(feature = "hyper")]
+    #[error("Failed to run the HTTP request within hyper.")]
+    Http(#[source] hyper::Error),
+    #[error(transparent)]
+    Parse(#[from] FmtError),
+    #[error("Server sent an unexpected reply: {0}")]
+    Status(Box<HttpReply>),
+    #[error("Server refused the credentials of the call: {0}")]
+    Unauthorized(Box<HttpReply>),
+    #[error("Failed to parse XML-RPC response ({source}), got {reply}")]
+    InvalidResponse {
+        reply: Box<HttpReply>,
+        #[source]
+        source: FmtError,
+    },
+    #[error("Server returned fault {}: {}", .0.code, .0.message)]
+    Fault(Fault),
+    #[error("Timed out connecting to server")]
+    ConnectTimeout,
+    #[error("Timed out waiting for the server")]
+    ReadTimeout,
+    #[error("Call did not complete within its deadline")]
+    CallTimeout,
+    #[error("Call was cancelled")]
+    Cancelled,
+    #[error("Server response is larger than the limit of {limit} bytes")]
+    ResponseTooLarge { limit: usize },
+    #[error("Failed to set up TLS: {0}")]
+    Tls(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error("Failed to bind XML-RPC server to {addr}: {source}")]
+    Bind {
+        addr: SocketAddr,
+        #[source]
+        source: io::Error,
+    },
+}
+
+/// The parts of an HTTP reply worth showing when it could not be used.
+#[derive(Clone, Debug)]
+pub struct HttpReply {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// The start of the body, decoded lossily as UTF-8.
+    pub body: String,
+}
+
+impl fmt::Display for HttpReply {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HTTP status {}", self.status)?;
+        for (name, value) in &self.headers {
+            write!(f, ", {}: {}", name, value)?;
+        }
+        write!(f, ", body: {:?}", self.body)
+    }
+}
+
+// Marks I/O errors raised while establishing a connection, so they can be
+// told apart after passing through hyper.
+#[derive(Debug, Error)]
+#[error(transparent)]
+pub(crate) struct ConnectFailed(io::Error);
+
+impl ConnectFailed {
+    #[cfg(feature = "client")]
+    pub(crate) fn wrap(err: io::Error) -> io::Error {
+        io::Error::new(err.kind(), ConnectFailed(err))
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        let timed_out = matches!(
+            err.kind(),
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+        );
+        let connecting = err
+            .get_ref()
+            .is_some_and(|inner| inner.is::<ConnectFailed>());
+        match (connecting, timed_out) {
+            (true, true) => Error::ConnectTimeout,
+            (true, false) => Error::Connect(err),
+            (false, true) => Error::ReadTimeout,
+            (false, false) => Error::Io(err),
+        }
+    }
+}
+
+#[cfg(feature = "hyper")]
+impl From<hyper::Error> for Error {
+    fn from(err: hyper::Error) -> Error {
+        match err {
+            hyper::Error::Io(err) => err.into(),
+            err => Error::Http(err),
+        }
+    }
+}
+
+impl From<Fault> for Error {
+    fn from(fault: Fault) -> Error {
+        Error::Fault(fault)
     }
-    errors {
-        BindFail(details: String) {
-            description("Failed to bind XML-RPC server to port")
-            display("Failed to bind XML-RPC server to port: {}", details)
+}
+
+// Lets services that fail with this error pass on remote faults unchanged.
+impl IntoFault for Error {
+    fn into_fault(self) -> Fault {
+        match self {
+            Error::Fault(fault) => fault,
+            err => Fault::from_error(Fault::INTERNAL_ERROR, &err),
         }
     }
 }
+
+impl Error {
+    /// The request never completed an HTTP exchange with the server.
+    pub fn is_transport(&self) -> bool {
+        match *self {
+            Error::Io(_)
+            | Error::Connect(_)
+            | Error::ConnectTimeout
+            | Error::ReadTimeout
+            | Error::CallTimeout => true,
+            #[cfg(feature = "hyper")]
+            Error::Http(_) => true,
+            _ => false,
+        }
+    }
+
+    /// The exchange completed, but the payload was not valid XML-RPC.
+    pub fn is_protocol(&self) -> bool {
+        matches!(
+            *self,
+            Error::Parse(_)
+                | Error::Status(_)
+                | Error::InvalidResponse { .. }
+                | Error::ResponseTooLarge { .. }
+        )
+    }
+
+    /// A connection to the server could not be established.
+    pub fn is_connect(&self) -> bool {
+        matches!(*self, Error::Connect(_) | Error::ConnectTimeout)
+    }
+
+    /// Any of the connect, read or whole-call deadlines expired.
+    pub fn is_timeout(&self) -> bool {
+        matches!(
+            *self,
+            Error::ConnectTimeout | Error::ReadTimeout | Error::CallTimeout
+        )
+    }
+
+    /// The call failed in a way that may succeed if sent again, e.g. the
+    /// connection dropped or the server reported itself overloaded.
+    ///
+    /// This says nothing about whether the method is safe to repeat.
+    pub fn is_retryable(&self) -> bool {
+        match *self {
+            Error::Connect(_) | Error::ConnectTimeout | Error::ReadTimeout | Error::CallTimeout => {
+                true
+            }
+            Error::Io(ref err) => matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::Interrupted
+                    | io::ErrorKind::UnexpectedEof
+            ),
+            Error::Status(ref reply) => matches!(reply.status, 429 | 502 | 503 | 504),
+            _ => false,
+        }
+    }
+
+    /// The server processed the call and answered with a fault.
+    pub fn fault(&self) -> Option<&Fault> {
+        match *self {
+            Error::Fault(ref fault) => Some(fault),
+            _ => None,
+        }
+    }
+}
+
+// Both error types must stay usable with `Box<dyn Error + Send + Sync>`,
+// `anyhow` and async executors.
+#[allow(dead_code)]
+fn assert_error_bounds() {
+    fn bounds<T: ::std::error::Error + Send + Sync + 'static>() {}
+    bounds::<Error>();
+    bounds::<FmtError>();
+}
@@ -0,0 +1,381 @@
+//! C interface for embedding the server, declared in `include/xmlrpc.h`.
+//!
+//! Build it as a shared library with
+//! `cargo rustc --release --features ffi --crate-type cdylib`.
+
+use std::convert::TryFrom;
+use std::ffi::CStr;
+use std::net::SocketAddr;
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use super::server::Server;
+use super::xmlfmt::{Fault, Response, Value};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The kinds of `XmlRpcValue`, numbered as in `xmlrpc.h`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum XmlRpcKind {
+    Int = 0,
+    Bool = 1,
+    String = 2,
+    Double = 3,
+    DateTime = 4,
+    Base64 = 5,
+    Array = 6,
+    Struct = 7,
+    Nil = 8,
+    I8 = 9,
+}
+
+/// Fails with the number itself if C passed a kind this library lacks.
+impl TryFrom<c_int> for XmlRpcKind {
+    type Error = c_int;
+
+    fn try_from(kind: c_int) -> Result<Self, c_int> {
+        Ok(match kind {
+            0 => XmlRpcKind::Int,
+            1 => XmlRpcKind::Bool,
+            2 => XmlRpcKind::String,
+            3 => XmlRpcKind::Double,
+            4 => XmlRpcKind::DateTime,
+            5 => XmlRpcKind::Base64,
+            6 => XmlRpcKind::Array,
+            7 => XmlRpcKind::Struct,
+            8 => XmlRpcKind::Nil,
+            9 => XmlRpcKind::I8,
+            _ => return Err(kind),
+        })
+    }
+}
+
+/// One value of any kind. Only the fields used by `kind` are meaningful:
+/// `int_value` for ints and bools, `double_value` for doubles, `data` and
+/// `len` for strings, date-times, base64 and i8s, and `items` and `count`
+/// for arrays and structs, whose member names are in `names`. An i8 is
+/// written out as a decimal string.
+///
+/// `data` is always NUL-terminated when passed to C, but may hold NULs.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct XmlRpcValue {
+    /// One of `XmlRpcKind`, as a plain number so that C can't smuggle in an
+    /// invalid enum.
+    pub kind: c_int,
+    pub int_value: i32,
+    pub double_value: f64,
+    pub data: *const c_char,
+    pub len: usize,
+    pub items: *const XmlRpcValue,
+    pub names: *const *const c_char,
+    pub count: usize,
+}
+
+/// Returns 0 and fills `result` on success. Any other return value is sent
+/// as the fault code, with `result` holding the message as a string, if any.
+pub type XmlRpcMethod = extern "C" fn(
+    user_data: *mut c_void,
+    params: *const XmlRpcValue,
+    count: usize,
+    result: *mut XmlRpcValue,
+) -> c_int;
+
+/// Frees whatever the method allocated for `result`, once it was copied.
+pub type XmlRpcRelease = extern "C" fn(user_data: *mut c_void, result: *mut XmlRpcValue);
+
+// A value handed to C, with the storage its pointers refer to.
+struct Marshalled {
+    value: XmlRpcValue,
+    _data: Vec<u8>,
+    _items: Vec<XmlRpcValue>,
+    _names: Vec<*const c_char>,
+    _children: Vec<Marshalled>,
+    _keys: Vec<Vec<u8>>,
+}
+
+fn nul_terminated(bytes: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(bytes.len() + 1);
+    data.extend_from_slice(bytes);
+    data.push(0);
+    data
+}
+
+fn blank(kind: XmlRpcKind) -> XmlRpcValue {
+    XmlRpcValue {
+        kind: kind as c_int,
+        int_value: 0,
+        double_value: 0.0,
+        data: ptr::null(),
+        len: 0,
+        items: ptr::null(),
+        names: ptr::null(),
+        count: 0,
+    }
+}
+
+fn marshal(value: &Value) -> Marshalled {
+    let mut out = Marshalled {
+        value: blank(XmlRpcKind::Int),
+        _data: Vec::new(),
+        _items: Vec::new(),
+        _names: Vec::new(),
+        _children: Vec::new(),
+        _keys: Vec::new(),
+    };
+    let decimal;
+    let (kind, bytes): (XmlRpcKind, Option<&[u8]>) = match *value {
+        Value::Int(v) => {
+            out.value.int_value = v;
+            (XmlRpcKind::Int, None)
+        }
+        Value::Int64(v) => {
+            decimal = v.to_string();
+            (XmlRpcKind::I8, Some(decimal.as_bytes()))
+        }
+        Value::Bool(v) => {
+            out.value.int_value = i32::from(v);
+            (XmlRpcKind::Bool, None)
+        }
+        Value::Double(v) => {
+            out.value.double_value = v;
+            (XmlRpcKind::Double, None)
+        }
+        Value::String(ref v) => (XmlRpcKind::String, Some(v.as_bytes())),
+        Value::DateTime(ref v) => (XmlRpcKind::DateTime, Some(v.as_bytes())),
+        Value::Base64(ref v) => (XmlRpcKind::Base64, Some(v)),
+        Value::Array(ref items) => {
+            out._children = items.iter().map(marshal).collect();
+            (XmlRpcKind::Array, None)
+        }
+        Value::Struct(ref members) => {
+            for (key, member) in members {
+                out._keys.push(nul_terminated(key.as_bytes()));
+                out._children.push(marshal(member));
+            }
+            out._names = out._keys.iter().map(|key| key.as_ptr() as _).collect();
+            out.value.names = out._names.as_ptr();
+            (XmlRpcKind::Struct, None)
+        }
+        Value::Nil => (XmlRpcKind::Nil, None),
+    };
+    out.value.kind = kind as c_int;
+    if let Some(bytes) = bytes {
+        out._data = nul_terminated(bytes);
+        out.value.data = out._data.as_ptr() as *const c_char;
+        out.value.len = bytes.len();
+    }
+    if !out._children.is_empty() {
+        out._items = out._children.iter().map(|child| child.value).collect();
+        out.value.items = out._items.as_ptr();
+        out.value.count = out._items.len();
+    }
+    out
+}
+
+unsafe fn bytes(value: &XmlRpcValue) -> &[u8] {
+    if value.data.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(value.data as *const u8, value.len)
+    }
+}
+
+unsafe fn items(value: &XmlRpcValue) -> &[XmlRpcValue] {
+    if value.items.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(value.items, value.count)
+    }
+}
+
+// Fails with the kind of the first value of a kind this library lacks.
+unsafe fn unmarshal(value: &XmlRpcValue) -> Result<Value, c_int> {
+    let text = || String::from_utf8_lossy(bytes(value)).into_owned();
+    Ok(match XmlRpcKind::try_from(value.kind)? {
+        XmlRpcKind::Int => Value::Int(value.int_value),
+        XmlRpcKind::Bool => Value::Bool(value.int_value != 0),
+        XmlRpcKind::Double => Value::Double(value.double_value),
+        XmlRpcKind::String => Value::String(text()),
+        XmlRpcKind::DateTime => Value::DateTime(text()),
+        XmlRpcKind::Base64 => Value::Base64(bytes(value).to_vec()),
+        XmlRpcKind::Array => Value::Array(
+            items(value)
+                .iter()
+                .map(|v| unmarshal(v))
+                .collect::<Result<_, _>>()?,
+        ),
+        XmlRpcKind::Struct => {
+            let names = if value.names.is_null() {
+                &[]
+            } else {
+                slice::from_raw_parts(value.names, value.count)
+            };
+            let members = names
+                .iter()
+                .zip(items(value))
+                .map(|(&name, member)| {
+                    let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+                    unmarshal(member).map(|member| (name, member))
+                })
+                .collect::<Result<_, _>>()?;
+            Value::Struct(members)
+        }
+        XmlRpcKind::Nil => Value::Nil,
+        XmlRpcKind::I8 => text()
+            .parse()
+            .map(Value::Int64)
+            .unwrap_or_else(|_| Value::String(text())),
+    })
+}
+
+// The embedding application vouches for `user_data` being usable from the
+// server's worker threads.
+struct Callback {
+    method: XmlRpcMethod,
+    release: Option<XmlRpcRelease>,
+    user_data: *mut c_void,
+}
+
+unsafe impl Send for Callback {}
+unsafe impl Sync for Callback {}
+
+impl Callback {
+    fn call(&self, params: &[Value]) -> Response {
+        let params = params.iter().map(marshal).collect::<Vec<_>>();
+        let values = params.iter().map(|param| param.value).collect::<Vec<_>>();
+        let mut result = blank(XmlRpcKind::String);
+        let code = (self.method)(self.user_data, values.as_ptr(), values.len(), &mut result);
+        let value = unsafe { unmarshal(&result) };
+        if let Some(release) = self.release {
+            release(self.user_data, &mut result);
+        }
+        let value = match value {
+            Ok(value) => value,
+            Err(kind) => {
+                let message = format!("Method returned a value of unknown kind {}", kind);
+                return Err(Fault::new(Fault::INTERNAL_ERROR, message));
+            }
+        };
+        if code == 0 {
+            return Ok(vec![value]);
+        }
+        let message = match value {
+            Value::String(message) => message,
+            _ => String::new(),
+        };
+        Err(Fault::new(code, message))
+    }
+}
+
+pub struct XmlRpcServer(pub(crate) Server);
+
+pub struct XmlRpcRunning {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+    port: u16,
+}
+
+#[no_mangle]
+pub extern "C" fn xmlrpc_server_new() -> *mut XmlRpcServer {
+    Box::into_raw(Box::new(XmlRpcServer(Server::new())))
+}
+
+/// # Safety
+///
+/// `server` must come from `xmlrpc_server_new` and not have been passed to
+/// `xmlrpc_server_run` or freed already.
+#[no_mangle]
+pub unsafe extern "C" fn xmlrpc_server_free(server: *mut XmlRpcServer) {
+    if !server.is_null() {
+        drop(Box::from_raw(server));
+    }
+}
+
+/// Returns 0, or -1 if `name` is not valid UTF-8.
+///
+/// # Safety
+///
+/// `server` must be a live server and `name` a NUL-terminated string.
+/// `method` may run on several threads at once with the same `user_data`.
+#[no_mangle]
+pub unsafe extern "C" fn xmlrpc_server_register(
+    server: *mut XmlRpcServer,
+    name: *const c_char,
+    method: XmlRpcMethod,
+    release: Option<XmlRpcRelease>,
+    user_data: *mut c_void,
+) -> c_int {
+    let name = match CStr::from_ptr(name).to_str() {
+        Ok(name) => name,
+        Err(_) => return -1,
+    };
+    let callback = Callback {
+        method,
+        release,
+        user_data,
+    };
+    (*server)
+        .0
+        .register_value(name, move |params| callback.call(&params));
+    0
+}
+
+/// Starts serving on `addr`, e.g. `"127.0.0.1:8080"`, in a background
+/// thread. Takes ownership of `server`, and returns NULL if binding failed.
+///
+/// # Safety
+///
+/// `server` must be a live server and `addr` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn xmlrpc_server_run(
+    server: *mut XmlRpcServer,
+    addr: *const c_char,
+) -> *mut XmlRpcRunning {
+    let server = Box::from_raw(server).0;
+    let addr = match CStr::from_ptr(addr).to_str().map(str::parse::<SocketAddr>) {
+        Ok(Ok(addr)) => addr,
+        _ => return ptr::null_mut(),
+    };
+    let bound = match server.bind(&addr) {
+        Ok(bound) => bound,
+        Err(_) => return ptr::null_mut(),
+    };
+    let port = bound.local_addr().port();
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&stop);
+    let thread = thread::spawn(move || {
+        while !stopped.load(Ordering::SeqCst) {
+            bound.poll();
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+    Box::into_raw(Box::new(XmlRpcRunning { stop, thread, port }))
+}
+
+/// The port being served, useful after binding to port 0.
+///
+/// # Safety
+///
+/// `running` must come from `xmlrpc_server_run` and not be stopped yet.
+#[no_mangle]
+pub unsafe extern "C" fn xmlrpc_server_port(running: *const XmlRpcRunning) -> u16 {
+    (*running).port
+}
+
+/// Stops serving and frees the server.
+///
+/// # Safety
+///
+/// `running` must come from `xmlrpc_server_run` and not be stopped yet.
+#[no_mangle]
+pub unsafe extern "C" fn xmlrpc_server_stop(running: *mut XmlRpcRunning) {
+    let running = Box::from_raw(running);
+    running.stop.store(true, Ordering::SeqCst);
+    let _ = running.thread.join();
+}
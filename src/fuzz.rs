@@ -0,0 +1,43 @@
+//! Entry points for the cargo-fuzz targets in `fuzz/`.
+//!
+//! Each one feeds arbitrary bytes to a parser and discards the outcome.
+//! Errors are expected; a panic, a hang or a crash is a bug. Run one with
+//! `cargo fuzz run parse_call` from the repository root.
+
+use std::io::Cursor;
+
+#[cfg(feature = "server")]
+use super::server::Server;
+use super::xmlfmt::parse;
+
+pub fn parse_xml(data: &[u8]) {
+    let _ = parse::xml(Cursor::new(data));
+}
+
+pub fn parse_call(data: &[u8]) {
+    let _ = parse::call(Cursor::new(data));
+}
+
+pub fn parse_response(data: &[u8]) {
+    let _ = parse::response(Cursor::new(data));
+}
+
+pub fn parse_message(data: &[u8]) {
+    let _ = parse::message(Cursor::new(data));
+}
+
+/// Runs a request body through a server with a single method, `echo`, that
+/// returns its parameters, so well-formed calls reach serialization too.
+#[cfg(feature = "server")]
+pub fn handle_request(data: &[u8]) {
+    thread_local! {
+        static SERVER: Server = {
+            let mut server = Server::new();
+            server.register_value("echo", Ok);
+            server
+        };
+    }
+    SERVER.with(|server| {
+        let _ = server.handle_body(Cursor::new(data), None);
+    });
+}
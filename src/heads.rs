@@ -0,0 +1,284 @@
+//! Limits on clients that are slow to send the head of a request, which
+//! could otherwise tie up every server thread by trickling heads in a byte
+//! at a time.
+
+#[cfg(feature = "hyper-server")]
+use hyper;
+use std::collections::HashMap;
+use std::io;
+use std::net::IpAddr;
+#[cfg(feature = "hyper-server")]
+use std::net::{Shutdown, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HeadLimits {
+    // From the first read of a head to its end.
+    pub timeout: Option<Duration>,
+    // Connections of one IP address with a head still coming in.
+    pub per_ip: Option<usize>,
+    // From a connection opening, or its last response, to the end of the
+    // next head.
+    pub idle: Option<Duration>,
+}
+
+pub struct PendingHeads {
+    limits: HeadLimits,
+    counts: Mutex<HashMap<IpAddr, usize>>,
+}
+
+impl PendingHeads {
+    pub fn new(limits: HeadLimits) -> Arc<PendingHeads> {
+        Arc::new(PendingHeads {
+            limits,
+            counts: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // A new connection from `ip`, or `None` if `ip` has too many whose head
+    // is incomplete already.
+    pub fn admit(heads: &Arc<PendingHeads>, ip: IpAddr) -> Option<Pending> {
+        if let Some(max) = heads.limits.per_ip {
+            let mut counts = heads.counts.lock().unwrap_or_else(|err| err.into_inner());
+            let count = counts.entry(ip).or_insert(0);
+            if *count >= max {
+                return None;
+            }
+            *count += 1;
+        }
+        Some(Pending {
+            heads: Arc::clone(heads),
+            ip,
+            deadline: None,
+        })
+    }
+}
+
+/// A connection whose head is incomplete. It stops counting against its IP
+/// address when dropped.
+pub struct Pending {
+    heads: Arc<PendingHeads>,
+    ip: IpAddr,
+    deadline: Option<Instant>,
+}
+
+impl Pending {
+    // How long the next read of the head may take, starting the clock on
+    // the first call. Fails once the head is overdue.
+    pub fn remaining(&mut self) -> io::Result<Option<Duration>> {
+        let timeout = match self.heads.limits.timeout {
+            Some(timeout) => timeout,
+            None => return Ok(None),
+        };
+        let deadline = *self
+            .deadline
+            .get_or_insert_with(|| Instant::now() + timeout);
+        until(deadline, "request head not received in time").map(Some)
+    }
+}
+
+// The time left before `deadline`, or a `TimedOut` error saying `what` once
+// it has passed.
+fn until(deadline: Instant, what: &str) -> io::Result<Duration> {
+    match deadline.checked_duration_since(Instant::now()) {
+        Some(remaining) if remaining > Duration::from_secs(0) => Ok(remaining),
+        _ => Err(io::Error::new(io::ErrorKind::TimedOut, what)),
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        if self.heads.limits.per_ip.is_none() {
+            return;
+        }
+        let mut counts = self
+            .heads
+            .counts
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        if let Some(count) = counts.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(&self.ip);
+            }
+        }
+    }
+}
+
+// The shorter of two read timeouts, `None` being no timeout.
+#[cfg(feature = "hyper-server")]
+fn shorter(a: Option<Duration>, b: Option<Duration>) -> Option<Duration> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// Applies the limits to the request heads of each connection hyper accepts.
+/// The timeout and the count per IP address only apply to the first head;
+/// later ones on a kept-alive connection are bounded by the idle timeout, or
+/// by hyper's keep-alive timeout without one.
+#[cfg(feature = "hyper-server")]
+#[derive(Clone)]
+pub struct GuardedListener<L> {
+    inner: L,
+    heads: Arc<PendingHeads>,
+}
+
+#[cfg(feature = "hyper-server")]
+impl<L> GuardedListener<L> {
+    pub fn new(inner: L, heads: Arc<PendingHeads>) -> GuardedListener<L> {
+        GuardedListener { inner, heads }
+    }
+}
+
+#[cfg(feature = "hyper-server")]
+impl<L> hyper::net::NetworkListener for GuardedListener<L>
+where
+    L: hyper::net::NetworkListener,
+    L::Stream: 'static,
+{
+    type Stream = GuardedStream<L::Stream>;
+
+    fn accept(&mut self) -> hyper::Result<Self::Stream> {
+        loop {
+            let mut stream = self.inner.accept()?;
+            let ip = hyper::net::NetworkStream::peer_addr(&mut stream)?.ip();
+            // Over the limit, the connection is closed unanswered.
+            if let Some(pending) = PendingHeads::admit(&self.heads, ip) {
+                let idle = self.heads.limits.idle;
+                return Ok(GuardedStream {
+                    inner: stream,
+                    idle,
+                    state: Arc::new(Mutex::new(StreamState {
+                        pending: Some(pending),
+                        in_head: true,
+                        idle_deadline: idle.map(|idle| Instant::now() + idle),
+                        newlines: 0,
+                        read_timeout: None,
+                    })),
+                });
+            }
+        }
+    }
+
+    fn local_addr(&mut self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn set_read_timeout(&mut self, duration: Option<Duration>) {
+        self.inner.set_read_timeout(duration);
+    }
+
+    fn set_write_timeout(&mut self, duration: Option<Duration>) {
+        self.inner.set_write_timeout(duration);
+    }
+}
+
+#[cfg(feature = "hyper-server")]
+struct StreamState {
+    // `None` once the first head is in.
+    pending: Option<Pending>,
+    // Whether a head is expected or coming in.
+    in_head: bool,
+    // When the head must be in by, with an idle timeout.
+    idle_deadline: Option<Instant>,
+    // Line ends in a row so far, not counting `\r`. Two end the head.
+    newlines: u8,
+    // The timeout hyper asked for.
+    read_timeout: Option<Duration>,
+}
+
+#[cfg(feature = "hyper-server")]
+#[derive(Clone)]
+pub struct GuardedStream<S> {
+    inner: S,
+    idle: Option<Duration>,
+    // Shared with the clone hyper writes through.
+    state: Arc<Mutex<StreamState>>,
+}
+
+#[cfg(feature = "hyper-server")]
+impl<S: hyper::net::NetworkStream> io::Read for GuardedStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        {
+            let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+            if !state.in_head {
+                return self.inner.read(buf);
+            }
+            let mut timeout = state.read_timeout;
+            if let Some(ref mut pending) = state.pending {
+                timeout = shorter(pending.remaining()?, timeout);
+            }
+            if let Some(deadline) = state.idle_deadline {
+                let remaining = until(deadline, "connection idle for too long")?;
+                timeout = shorter(Some(remaining), timeout);
+            }
+            self.inner.set_read_timeout(timeout)?;
+        }
+        let read = self.inner.read(buf)?;
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        for &byte in &buf[..read] {
+            match byte {
+                b'\n' => state.newlines += 1,
+                b'\r' => {}
+                _ => state.newlines = 0,
+            }
+            if state.newlines == 2 {
+                state.pending = None;
+                state.in_head = false;
+                state.idle_deadline = None;
+                self.inner.set_read_timeout(state.read_timeout)?;
+                break;
+            }
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(feature = "hyper-server")]
+impl<S: hyper::net::NetworkStream> io::Write for GuardedStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(idle) = self.idle {
+            // The connection is idle from the last write of a response
+            // until the next head is in.
+            let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+            state.in_head = true;
+            state.idle_deadline = Some(Instant::now() + idle);
+            state.newlines = 0;
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "hyper-server")]
+impl<S: hyper::net::NetworkStream> hyper::net::NetworkStream for GuardedStream<S> {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    fn set_read_timeout(&self, duration: Option<Duration>) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.read_timeout = duration;
+        if state.in_head {
+            // Set again before each read of the head.
+            return Ok(());
+        }
+        self.inner.set_read_timeout(duration)
+    }
+
+    fn set_write_timeout(&self, duration: Option<Duration>) -> io::Result<()> {
+        self.inner.set_write_timeout(duration)
+    }
+
+    fn close(&mut self, how: Shutdown) -> io::Result<()> {
+        self.inner.close(how)
+    }
+}
@@ -0,0 +1,194 @@
+//! Which peers a server answers, by IP address.
+
+#[cfg(feature = "hyper-server")]
+use hyper;
+use std::fmt;
+#[cfg(feature = "hyper-server")]
+use std::io;
+use std::net::IpAddr;
+#[cfg(feature = "hyper-server")]
+use std::net::SocketAddr;
+use std::str::FromStr;
+#[cfg(feature = "hyper-server")]
+use std::time::Duration;
+
+/// A block of IP addresses in CIDR notation, like `10.0.0.0/8` or
+/// `fd00::/8`. A bare address is a block of one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+/// A string that is not a CIDR block.
+#[derive(Debug, Error)]
+#[error("invalid CIDR block `{0}`")]
+pub struct InvalidIpNet(String);
+
+impl IpNet {
+    /// The block of addresses sharing the first `prefix` bits of `addr`, or
+    /// `None` if `prefix` is longer than the address. Blocks of IPv4
+    /// addresses mapped into IPv6, like `::ffff:10.0.0.0/104`, become the
+    /// IPv4 block, here `10.0.0.0/8`.
+    pub fn new(addr: IpAddr, prefix: u8) -> Option<IpNet> {
+        let bits = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        if prefix > bits {
+            return None;
+        }
+        let mapped = match addr {
+            IpAddr::V6(v6) if prefix >= 96 => v6.to_ipv4_mapped(),
+            _ => None,
+        };
+        if let Some(v4) = mapped {
+            return Some(IpNet {
+                addr: IpAddr::V4(v4),
+                prefix: prefix - 96,
+            });
+        }
+        Some(IpNet { addr, prefix })
+    }
+
+    /// Whether `ip` is in the block. IPv4 addresses mapped into IPv6, as
+    /// dual-stack sockets report IPv4 peers, count as the IPv4 address.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            ip => ip,
+        };
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                prefix_matches(&net.octets(), &ip.octets(), self.prefix)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_matches(&net.octets(), &ip.octets(), self.prefix)
+            }
+            // IPv6 blocks wider than the mapped range, like `::/0`.
+            (IpAddr::V6(net), IpAddr::V4(ip)) => {
+                prefix_matches(&net.octets(), &ip.to_ipv6_mapped().octets(), self.prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_matches(net: &[u8], ip: &[u8], prefix: u8) -> bool {
+    let whole = usize::from(prefix / 8);
+    if net[..whole] != ip[..whole] {
+        return false;
+    }
+    let rest = prefix % 8;
+    if rest == 0 {
+        return true;
+    }
+    let mask = 0xffu8 << (8 - rest);
+    net[whole] & mask == ip[whole] & mask
+}
+
+impl FromStr for IpNet {
+    type Err = InvalidIpNet;
+
+    fn from_str(text: &str) -> Result<IpNet, InvalidIpNet> {
+        let invalid = || InvalidIpNet(text.to_owned());
+        let mut parts = text.trim().splitn(2, '/');
+        let addr = parts
+            .next()
+            .and_then(|addr| addr.parse::<IpAddr>().ok())
+            .ok_or_else(invalid)?;
+        let prefix = match parts.next() {
+            Some(prefix) => prefix.parse().map_err(|_| invalid())?,
+            None if addr.is_ipv4() => 32,
+            None => 128,
+        };
+        IpNet::new(addr, prefix).ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+/// Allow and deny lists of IP address blocks, as in
+/// `IpFilter::new().allow("10.0.0.0/8".parse()?).deny("10.9.0.0/16".parse()?)`.
+///
+/// Denied addresses are refused even if also allowed. With any block
+/// allowed, addresses outside all of them are refused too.
+///
+/// Set on a server with `Server::set_ip_filter`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IpFilter {
+    allowed: Vec<IpNet>,
+    denied: Vec<IpNet>,
+}
+
+impl IpFilter {
+    pub fn new() -> IpFilter {
+        IpFilter::default()
+    }
+
+    pub fn allow(mut self, net: IpNet) -> IpFilter {
+        self.allowed.push(net);
+        self
+    }
+
+    pub fn deny(mut self, net: IpNet) -> IpFilter {
+        self.denied.push(net);
+        self
+    }
+
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        !self.denied.iter().any(|net| net.contains(ip))
+            && (self.allowed.is_empty() || self.allowed.iter().any(|net| net.contains(ip)))
+    }
+}
+
+// Closes connections from peers the filter refuses as soon as they are
+// accepted.
+#[cfg(feature = "hyper-server")]
+#[derive(Clone)]
+pub(crate) struct FilteredListener<L> {
+    inner: L,
+    filter: Option<IpFilter>,
+}
+
+#[cfg(feature = "hyper-server")]
+impl<L> FilteredListener<L> {
+    pub fn new(inner: L, filter: Option<IpFilter>) -> FilteredListener<L> {
+        FilteredListener { inner, filter }
+    }
+}
+
+#[cfg(feature = "hyper-server")]
+impl<L: hyper::net::NetworkListener> hyper::net::NetworkListener for FilteredListener<L> {
+    type Stream = L::Stream;
+
+    fn accept(&mut self) -> hyper::Result<L::Stream> {
+        let filter = match self.filter {
+            Some(ref filter) => filter,
+            None => return self.inner.accept(),
+        };
+        loop {
+            let mut stream = self.inner.accept()?;
+            let peer = hyper::net::NetworkStream::peer_addr(&mut stream)?;
+            if filter.permits(peer.ip()) {
+                return Ok(stream);
+            }
+        }
+    }
+
+    fn local_addr(&mut self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn set_read_timeout(&mut self, duration: Option<Duration>) {
+        self.inner.set_read_timeout(duration);
+    }
+
+    fn set_write_timeout(&mut self, duration: Option<Duration>) {
+        self.inner.set_write_timeout(duration);
+    }
+}
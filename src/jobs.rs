@@ -0,0 +1,145 @@
+//! Long-running methods answered in the background, for calls that would
+//! otherwise hold a connection for minutes.
+//!
+//! A job method registered with `Server::register_job` answers at once with
+//! a job id and runs in a thread of its own. Two companion methods follow
+//! the job: `name.status` answers `"running"` or `"done"`, and `name.result`
+//! answers with what the job returned, once, after which the job is
+//! forgotten. `Client::call_job` does all three, polling with backoff.
+
+#[cfg(feature = "client")]
+use std::cmp;
+#[cfg(feature = "server")]
+use std::collections::HashMap;
+#[cfg(feature = "server")]
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(feature = "server")]
+use std::sync::{Arc, Mutex, MutexGuard};
+#[cfg(feature = "server")]
+use std::thread;
+use std::time::Duration;
+#[cfg(feature = "server")]
+use std::time::Instant;
+
+#[cfg(feature = "server")]
+use super::xmlfmt::Response;
+
+/// How often `Client::call_job` asks whether a job is done: after
+/// `initial` at first, then twice as long each time up to `max`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Backoff {
+        Backoff {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(5),
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+impl Backoff {
+    pub(crate) fn next(&self, delay: Duration) -> Duration {
+        cmp::min(delay * 2, self.max)
+    }
+}
+
+#[cfg(feature = "server")]
+enum Job {
+    Running,
+    // With when it finished, for forgetting results nobody fetches.
+    Done(Response, Instant),
+}
+
+#[cfg(feature = "server")]
+struct State {
+    next_id: u64,
+    jobs: HashMap<String, Job>,
+    retention: Duration,
+}
+
+// The jobs of one server, shared by all its job methods.
+#[cfg(feature = "server")]
+#[derive(Clone)]
+pub(crate) struct Jobs {
+    state: Arc<Mutex<State>>,
+}
+
+#[cfg(feature = "server")]
+impl Default for Jobs {
+    fn default() -> Jobs {
+        Jobs {
+            state: Arc::new(Mutex::new(State {
+                next_id: 1,
+                jobs: HashMap::new(),
+                retention: Duration::from_secs(600),
+            })),
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+impl Jobs {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub fn set_retention(&self, retention: Duration) {
+        self.lock().retention = retention;
+    }
+
+    // Runs `work` in a thread of its own, returning the id of the job.
+    // A panic is answered with `panicked` as the job's result.
+    pub fn start<F>(&self, work: F, panicked: Response) -> String
+    where
+        F: FnOnce() -> Response + Send + 'static,
+    {
+        let id = {
+            let mut state = self.lock();
+            let retention = state.retention;
+            state.jobs.retain(|_, job| match *job {
+                Job::Running => true,
+                Job::Done(_, finished) => finished.elapsed() < retention,
+            });
+            let id = state.next_id.to_string();
+            state.next_id += 1;
+            state.jobs.insert(id.clone(), Job::Running);
+            id
+        };
+        let jobs = self.clone();
+        let job = id.clone();
+        thread::spawn(move || {
+            let response = panic::catch_unwind(AssertUnwindSafe(work)).unwrap_or(panicked);
+            jobs.lock()
+                .jobs
+                .insert(job, Job::Done(response, Instant::now()));
+        });
+        id
+    }
+
+    // Whether job `id` is done, or `None` if there is no such job.
+    pub fn is_done(&self, id: &str) -> Option<bool> {
+        self.lock().jobs.get(id).map(|job| match *job {
+            Job::Running => false,
+            Job::Done(..) => true,
+        })
+    }
+
+    // Takes the result of job `id`: `None` if there is no such job,
+    // `Some(None)` while it is still running.
+    pub fn take(&self, id: &str) -> Option<Option<Response>> {
+        let mut state = self.lock();
+        match state.jobs.remove(id) {
+            None => None,
+            Some(Job::Running) => {
+                state.jobs.insert(id.to_owned(), Job::Running);
+                Some(None)
+            }
+            Some(Job::Done(response, _)) => Some(Some(response)),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! JSON-RPC 2.0 access to the handlers registered on a `Server`.
+//!
+//! Parameters and results are translated to and from XML-RPC values as
+//! described in `xmlfmt::json`, so handlers cannot tell which protocol a
+//! call came in on.
+
+use serde_json::{self, Map, Value as Json};
+use std::convert::TryFrom;
+use std::io;
+
+use super::server::Server;
+use super::xmlfmt::{Call, Fault, Value};
+
+pub(crate) const CONTENT_TYPE: &str = "application/json";
+
+pub fn to_json(value: &Value) -> Json {
+    Json::from(value)
+}
+
+pub fn from_json(json: Json) -> Result<Value, String> {
+    Value::try_from(json).map_err(|err| err.to_string())
+}
+
+fn reply(id: Json, key: &str, value: Json) -> Json {
+    let mut reply = Map::new();
+    reply.insert("jsonrpc".into(), Json::from("2.0"));
+    reply.insert(key.into(), value);
+    reply.insert("id".into(), id);
+    Json::Object(reply)
+}
+
+fn error_reply(id: Json, code: i32, message: &str) -> Json {
+    let mut error = Map::new();
+    error.insert("code".into(), Json::from(code));
+    error.insert("message".into(), Json::from(message));
+    reply(id, "error", Json::Object(error))
+}
+
+// Returns `None` for notifications, which get no reply.
+fn answer(server: &Server, request: Json) -> Option<Json> {
+    let mut request = match request {
+        Json::Object(request) => request,
+        _ => {
+            return Some(error_reply(
+                Json::Null,
+                Fault::INVALID_REQUEST,
+                "Request must be an object",
+            ))
+        }
+    };
+    let id = request.remove("id");
+    let is_notification = id.is_none();
+    let id = id.unwrap_or(Json::Null);
+    let name = match (request.remove("jsonrpc"), request.remove("method")) {
+        (Some(Json::String(ref version)), Some(Json::String(name))) if version == "2.0" => name,
+        _ => {
+            return Some(error_reply(
+                id,
+                Fault::INVALID_REQUEST,
+                "Expected a JSON-RPC 2.0 request with a method name",
+            ))
+        }
+    };
+    let params = match request.remove("params") {
+        None => Ok(Vec::new()),
+        Some(Json::Array(params)) => params.into_iter().map(from_json).collect(),
+        Some(members @ Json::Object(_)) => from_json(members).map(|members| vec![members]),
+        Some(_) => Err("Parameters must be an array or an object".into()),
+    };
+    let response = match params {
+        Ok(params) => server.handle(Call { name, params }),
+        Err(message) => Err(Fault::new(Fault::INVALID_PARAMS, message)),
+    };
+    if is_notification {
+        return None;
+    }
+    Some(match response {
+        Ok(mut params) => {
+            let result = if params.len() == 1 {
+                to_json(&params.remove(0))
+            } else {
+                Json::Array(params.iter().map(to_json).collect())
+            };
+            reply(id, "result", result)
+        }
+        Err(fault) => error_reply(id, fault.code, &fault.message),
+    })
+}
+
+impl Server {
+    /// Answers a JSON-RPC request or batch read from any transport, e.g. a
+    /// listener on a port of its own. Returns `None` if there is nothing to
+    /// reply, because the request only held notifications.
+    ///
+    /// A method returning several values replies with them as an array.
+    pub fn handle_jsonrpc<R: io::Read>(&self, body: R) -> Option<String> {
+        let request = match serde_json::from_reader(body) {
+            Ok(request) => request,
+            Err(err) => {
+                let reply = error_reply(Json::Null, Fault::PARSE_ERROR, &err.to_string());
+                return Some(reply.to_string());
+            }
+        };
+        let reply = match request {
+            Json::Array(ref batch) if batch.is_empty() => Some(error_reply(
+                Json::Null,
+                Fault::INVALID_REQUEST,
+                "Empty batch",
+            )),
+            Json::Array(batch) => {
+                let replies = batch
+                    .into_iter()
+                    .filter_map(|request| answer(self, request))
+                    .collect::<Vec<_>>();
+                if replies.is_empty() {
+                    None
+                } else {
+                    Some(Json::Array(replies))
+                }
+            }
+            request => answer(self, request),
+        };
+        reply.map(|reply| reply.to_string())
+    }
+}
@@ -1,30 +1,199 @@
-#![recursion_limit = "1024"]
-
+#[cfg(feature = "actix-web")]
+extern crate actix_web;
+#[cfg(feature = "anyhow")]
+extern crate anyhow;
+#[cfg(feature = "axum")]
+extern crate axum as axum_lib;
 extern crate base64;
-#[macro_use]
-extern crate error_chain;
+#[cfg(feature = "chrono")]
+extern crate chrono;
+#[cfg(feature = "encoding")]
+pub extern crate encoding_rs;
 extern crate futures;
-#[macro_use]
+#[cfg(feature = "hyper")]
 extern crate hyper;
-#[macro_use]
-extern crate lazy_static;
-extern crate regex;
+extern crate itoa;
+#[cfg(feature = "metrics")]
+extern crate metrics as metrics_lib;
+#[cfg(feature = "gzip")]
+extern crate miniz_oxide;
+#[cfg(feature = "tls-native")]
+extern crate native_tls;
+extern crate quick_xml;
+#[cfg(feature = "hmac")]
+extern crate ring;
+#[cfg(feature = "tls-rustls")]
+extern crate rustls;
+extern crate ryu;
 #[macro_use]
 extern crate serde;
 #[cfg(test)]
 extern crate serde_bytes;
 #[macro_use]
 extern crate serde_derive;
+#[cfg(feature = "server")]
 pub extern crate rouille;
-extern crate serde_xml_rs;
-extern crate xml;
+#[cfg(feature = "serde_json")]
+extern crate serde_json;
+#[cfg(feature = "server")]
+extern crate socket2;
+#[macro_use]
+extern crate thiserror;
+#[cfg(feature = "tokio")]
+extern crate tokio as tokio_lib;
+#[cfg(feature = "tower")]
+extern crate tower_service;
+#[cfg(feature = "tracing")]
+#[macro_use]
+extern crate tracing;
+#[cfg(feature = "tls-rustls")]
+extern crate webpki_roots;
+#[cfg(feature = "macros")]
+extern crate xml_rpc_macros;
 
+#[cfg(feature = "server")]
+pub mod access_log;
+#[cfg(feature = "actix-web")]
+pub mod actix;
+#[cfg(feature = "client")]
+pub mod async_client;
+#[cfg(feature = "server")]
+pub mod auth;
+#[cfg(feature = "axum")]
+pub mod axum;
+#[cfg(feature = "server")]
+mod budget;
+#[cfg(feature = "server")]
+mod cache;
+#[cfg(feature = "client")]
+pub mod cancel;
+#[cfg(feature = "client")]
+pub mod cassette;
+#[cfg(feature = "client")]
 pub mod client;
+#[cfg(feature = "client")]
+pub mod codegen;
+#[cfg(feature = "gzip")]
+#[cfg_attr(not(all(feature = "client", feature = "server")), allow(dead_code))]
+mod compression;
+#[cfg(any(
+    feature = "contrib-opensubtitles",
+    feature = "contrib-supervisor",
+    feature = "contrib-wordpress"
+))]
+pub mod contrib;
 pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[doc(hidden)]
+pub mod fuzz;
+#[cfg(feature = "server")]
+mod heads;
+#[cfg(feature = "hmac")]
+#[cfg_attr(not(all(feature = "client", feature = "server")), allow(dead_code))]
+pub mod hmac;
+#[cfg(feature = "client")]
+pub mod httpproxy;
+#[cfg(feature = "server")]
+pub mod ipfilter;
+#[cfg(any(feature = "client", feature = "server"))]
+pub mod jobs;
+#[cfg(feature = "jsonrpc")]
+pub mod jsonrpc;
+#[cfg(feature = "server")]
+pub mod limits;
+#[cfg(feature = "server")]
+mod listing;
+#[cfg(feature = "server")]
+mod metrics;
+#[cfg(feature = "server")]
+pub mod middleware;
+#[cfg(feature = "server")]
+pub mod observer;
+#[cfg(feature = "client")]
+pub mod proxy;
+#[cfg(feature = "server")]
+mod queue;
+#[cfg(feature = "client")]
+pub mod retry;
+#[cfg(feature = "ros")]
+pub mod ros;
+#[cfg(feature = "server")]
+pub mod routes;
+#[cfg(any(feature = "client", feature = "server"))]
+pub mod scgi;
+#[cfg(feature = "server")]
 pub mod server;
+#[cfg(feature = "server")]
+pub mod shutdown;
+#[cfg(feature = "socks")]
+pub mod socks;
+#[cfg(feature = "client")]
+pub mod stats;
+#[cfg(any(feature = "client", feature = "server"))]
+pub mod test;
+#[cfg(test)]
+mod tests;
+#[cfg(any(feature = "tls-native", feature = "tls-rustls"))]
+pub mod tls;
+#[cfg(all(feature = "tokio", feature = "server"))]
+pub mod tokio;
+#[cfg(feature = "server")]
+mod trace;
+#[cfg(any(feature = "client", feature = "server"))]
+pub mod tracecontext;
+#[cfg(all(unix, any(feature = "client", feature = "hyper-server")))]
+pub mod unix;
 mod xmlfmt;
 
-pub use client::{call, call_value, Client};
+#[cfg(feature = "server")]
+pub use access_log::{AccessLog, AccessRecord, LogFormat};
+#[cfg(feature = "client")]
+pub use async_client::AsyncClient;
+#[cfg(all(feature = "tokio", feature = "client"))]
+pub use async_client::TokioTransport;
+#[cfg(feature = "server")]
+pub use auth::Auth;
+#[cfg(feature = "client")]
+pub use cancel::CancelToken;
+#[cfg(feature = "client")]
+pub use client::{call, call_value, Client, ClientBuilder, Deadline, FromFault, MapFault};
+#[cfg(feature = "client")]
+pub use httpproxy::HttpProxy;
+#[cfg(feature = "client")]
 pub use hyper::Url;
-pub use server::Server;
-pub use xmlfmt::{from_params, into_params, Call, Fault, Params, Response, Value};
+#[cfg(feature = "server")]
+pub use ipfilter::{IpFilter, IpNet};
+#[cfg(feature = "server")]
+pub use limits::ServerLimits;
+#[cfg(feature = "server")]
+pub use middleware::{HttpRequest, Next};
+#[cfg(feature = "metrics")]
+pub use observer::MetricsObserver;
+#[cfg(feature = "server")]
+pub use observer::{Observer, Outcome};
+#[cfg(feature = "client")]
+pub use proxy::ServerProxy;
+#[cfg(feature = "client")]
+pub use retry::{RetryOn, RetryPolicy};
+#[cfg(feature = "server")]
+pub use routes::HttpResponse;
+#[cfg(feature = "server")]
+pub use server::{FaultCodes, MethodInfo, PanicPolicy, Registration, Server, Timings};
+#[cfg(feature = "server")]
+pub use shutdown::{DrainReport, ShutdownHandle};
+#[cfg(feature = "client")]
+pub use stats::ClientStats;
+#[cfg(any(feature = "client", feature = "server"))]
+pub use tracecontext::TraceContext;
+#[cfg(feature = "macros")]
+pub use xml_rpc_macros::xmlrpc_service;
+#[cfg(feature = "encoding")]
+pub use xmlfmt::charset;
+#[cfg(feature = "chrono")]
+pub use xmlfmt::datetime::{self, DateTimeOptions, NaiveDateTimes, SentOffsets};
+pub use xmlfmt::value::ToXml;
+pub use xmlfmt::{
+    from_params, into_params, parse, Call, CallBuilder, Doubles, Fault, FaultCode, IntTag,
+    IntoFault, Layout, Multicall, Params, Response, ResponseExt, Value, WireProfile,
+};
@@ -0,0 +1,70 @@
+//! Bounds on what the server reads of a request, so that malformed or
+//! hostile requests are refused before they cost much memory or time.
+
+use std::time::Duration;
+
+use super::xmlfmt::parse;
+
+/// The limits a server holds requests to, set with `Server::set_limits`.
+/// Each is off when `None`.
+///
+/// Requests with a larger body are answered with 413 Payload Too Large,
+/// those with more or longer headers with 431 Request Header Fields Too
+/// Large, and calls with too many parameters or too deeply nested values
+/// as requests that fail to parse.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ServerLimits {
+    /// The longest request body, in bytes. Bodies sent without a
+    /// Content-Length are cut off once they go past it.
+    pub max_body: Option<u64>,
+    /// The most header fields a request may have.
+    pub max_headers: Option<usize>,
+    /// The most bytes of header names and values a request may have.
+    pub max_header_bytes: Option<usize>,
+    /// How long the head of a request may take to come in, as with
+    /// `Server::set_head_timeout`.
+    pub head_timeout: Option<Duration>,
+    /// The most parameters a call may have.
+    pub max_params: Option<usize>,
+    /// How deep the elements of a call may nest. Calls are never allowed
+    /// to nest more than 256 deep.
+    pub max_depth: usize,
+}
+
+impl Default for ServerLimits {
+    fn default() -> ServerLimits {
+        let parse = parse::Limits::default();
+        ServerLimits {
+            max_body: None,
+            max_headers: None,
+            max_header_bytes: None,
+            head_timeout: None,
+            max_params: parse.max_params,
+            max_depth: parse.max_depth,
+        }
+    }
+}
+
+impl ServerLimits {
+    pub(crate) fn parse(&self) -> parse::Limits {
+        parse::Limits {
+            max_depth: self.max_depth,
+            max_params: self.max_params,
+        }
+    }
+
+    // Whether a body of `declared` length is too long.
+    pub(crate) fn refuses_body(&self, declared: Option<u64>) -> bool {
+        match (declared, self.max_body) {
+            (Some(length), Some(max)) => length > max,
+            _ => false,
+        }
+    }
+
+    // Whether a head with `count` header fields, whose names and values
+    // come to `bytes`, is too large.
+    pub(crate) fn refuses_head(&self, count: usize, bytes: usize) -> bool {
+        self.max_headers.is_some_and(|max| count > max)
+            || self.max_header_bytes.is_some_and(|max| bytes > max)
+    }
+}
@@ -0,0 +1,57 @@
+//! The HTML page listing a server's methods.
+
+use std::fmt::Write;
+
+use super::server::MethodInfo;
+
+pub const CONTENT_TYPE: &str = "text/html; charset=utf-8";
+
+pub fn render(methods: &[(&str, &MethodInfo)]) -> String {
+    let mut out = String::new();
+    // Writing to a `String` cannot fail.
+    let _ = write(&mut out, methods);
+    out
+}
+
+fn write(out: &mut String, methods: &[(&str, &MethodInfo)]) -> ::std::fmt::Result {
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>XML-RPC methods</title></head><body>")?;
+    writeln!(
+        out,
+        "<h1>XML-RPC methods</h1><p>POST calls to this URL.</p>"
+    )?;
+    for &(name, info) in methods {
+        writeln!(out, "<h2><code>{}</code></h2>", escape(name))?;
+        // Signatures list the return type first.
+        for signature in &info.signatures {
+            let (returns, params) = match signature.split_first() {
+                Some(split) => split,
+                None => continue,
+            };
+            let params = params.iter().map(|param| escape(param)).collect::<Vec<_>>();
+            writeln!(
+                out,
+                "<p><code>{} {}({})</code></p>",
+                escape(returns),
+                escape(name),
+                params.join(", ")
+            )?;
+        }
+        if !info.help.is_empty() {
+            writeln!(
+                out,
+                "<p style=\"white-space: pre-wrap\">{}</p>",
+                escape(&info.help)
+            )?;
+        }
+    }
+    writeln!(out, "</body></html>")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Prometheus' default buckets, in seconds.
+const BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Default)]
+struct MethodStats {
+    calls: u64,
+    faults: u64,
+    // Calls that finished within each bucket but not the one before it.
+    buckets: [u64; 11],
+    seconds: f64,
+}
+
+/// Request statistics, rendered in the Prometheus text format.
+pub struct Metrics {
+    pub path: String,
+    methods: Mutex<BTreeMap<String, MethodStats>>,
+    invalid: AtomicUsize,
+    in_flight: AtomicUsize,
+}
+
+/// Counts a request as in flight until dropped.
+pub struct InFlight<'a>(&'a Metrics);
+
+impl<'a> Drop for InFlight<'a> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Metrics {
+    pub fn new(path: String) -> Metrics {
+        Metrics {
+            path,
+            methods: Mutex::new(BTreeMap::new()),
+            invalid: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn start(&self) -> InFlight<'_> {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlight(self)
+    }
+
+    pub fn record_invalid(&self) {
+        self.invalid.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn record(&self, method: &str, elapsed: Duration, fault: bool) {
+        let seconds = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) * 1e-9;
+        // Stats stay consistent even if a panic poisoned the lock.
+        let mut methods = self.methods.lock().unwrap_or_else(|err| err.into_inner());
+        let stats = methods.entry(method.to_owned()).or_default();
+        stats.calls += 1;
+        if fault {
+            stats.faults += 1;
+        }
+        if let Some(bucket) = BUCKETS.iter().position(|&le| seconds <= le) {
+            stats.buckets[bucket] += 1;
+        }
+        stats.seconds += seconds;
+    }
+
+    // `connections` is the number of connections open.
+    pub fn render(&self, connections: usize) -> String {
+        let methods = self.methods.lock().unwrap_or_else(|err| err.into_inner());
+        let mut out = String::new();
+        // Writing to a `String` cannot fail.
+        let _ = self.write(&mut out, &methods, connections);
+        out
+    }
+
+    fn write(
+        &self,
+        out: &mut String,
+        methods: &BTreeMap<String, MethodStats>,
+        connections: usize,
+    ) -> ::std::fmt::Result {
+        writeln!(
+            out,
+            "# HELP xmlrpc_requests_total Calls dispatched, by method."
+        )?;
+        writeln!(out, "# TYPE xmlrpc_requests_total counter")?;
+        for (method, stats) in methods {
+            writeln!(
+                out,
+                "xmlrpc_requests_total{{method=\"{}\"}} {}",
+                escape(method),
+                stats.calls
+            )?;
+        }
+        writeln!(
+            out,
+            "# HELP xmlrpc_faults_total Calls answered with a fault, by method."
+        )?;
+        writeln!(out, "# TYPE xmlrpc_faults_total counter")?;
+        for (method, stats) in methods {
+            writeln!(
+                out,
+                "xmlrpc_faults_total{{method=\"{}\"}} {}",
+                escape(method),
+                stats.faults
+            )?;
+        }
+        writeln!(
+            out,
+            "# HELP xmlrpc_request_duration_seconds Time spent handling calls, by method."
+        )?;
+        writeln!(out, "# TYPE xmlrpc_request_duration_seconds histogram")?;
+        for (method, stats) in methods {
+            let method = escape(method);
+            let mut cumulative = 0;
+            for (le, count) in BUCKETS.iter().zip(stats.buckets.iter()) {
+                cumulative += count;
+                writeln!(
+                    out,
+                    "xmlrpc_request_duration_seconds_bucket{{method=\"{}\",le=\"{}\"}} {}",
+                    method, le, cumulative
+                )?;
+            }
+            writeln!(
+                out,
+                "xmlrpc_request_duration_seconds_bucket{{method=\"{}\",le=\"+Inf\"}} {}",
+                method, stats.calls
+            )?;
+            writeln!(
+                out,
+                "xmlrpc_request_duration_seconds_sum{{method=\"{}\"}} {}",
+                method, stats.seconds
+            )?;
+            writeln!(
+                out,
+                "xmlrpc_request_duration_seconds_count{{method=\"{}\"}} {}",
+                method, stats.calls
+            )?;
+        }
+        writeln!(
+            out,
+            "# HELP xmlrpc_invalid_requests_total Requests that were not valid XML-RPC calls."
+        )?;
+        writeln!(out, "# TYPE xmlrpc_invalid_requests_total counter")?;
+        writeln!(
+            out,
+            "xmlrpc_invalid_requests_total {}",
+            self.invalid.load(Ordering::SeqCst)
+        )?;
+        writeln!(
+            out,
+            "# HELP xmlrpc_requests_in_flight Requests currently being handled."
+        )?;
+        writeln!(out, "# TYPE xmlrpc_requests_in_flight gauge")?;
+        writeln!(
+            out,
+            "xmlrpc_requests_in_flight {}",
+            self.in_flight.load(Ordering::SeqCst)
+        )?;
+        writeln!(
+            out,
+            "# HELP xmlrpc_open_connections Connections open, on backends other than rouille."
+        )?;
+        writeln!(out, "# TYPE xmlrpc_open_connections gauge")?;
+        writeln!(out, "xmlrpc_open_connections {}", connections)
+    }
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,100 @@
+//! Code run around every call the server dispatches, for logging, checking
+//! credentials or measuring calls in one place rather than in each handler.
+//!
+//! Middleware added with `Server::add_middleware` is handed the HTTP request
+//! the call came in, the call, and a `Next` to pass the call on with. The
+//! first added runs outermost. Middleware may answer a call itself, rewrite
+//! it before passing it on, or change the response on its way back.
+
+use std::cell::RefCell;
+use std::net::IpAddr;
+
+use super::xmlfmt::{Call, Response};
+
+pub(crate) type Middleware = Box<dyn Fn(&HttpRequest, Call, Next) -> Response + Send + Sync>;
+
+/// The HTTP request a call came in.
+///
+/// Calls that came in some other way, as through `Server::handle_body`, are
+/// handed a request with no method, path, peer or headers.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub method: String,
+    /// The path, without the query.
+    pub path: String,
+    pub peer: Option<IpAddr>,
+    pub headers: Vec<(String, String)>,
+}
+
+impl HttpRequest {
+    /// The first value of header `name`, which is matched ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|&(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The rest of the middleware, and then the method, a call is passed on to.
+pub struct Next<'a> {
+    chain: &'a [Middleware],
+    request: &'a HttpRequest,
+    dispatch: &'a dyn Fn(Call) -> Response,
+}
+
+impl<'a> Next<'a> {
+    /// Passes `call` on, returning its response.
+    pub fn run(self, call: Call) -> Response {
+        match self.chain.split_first() {
+            Some((first, rest)) => first(
+                self.request,
+                call,
+                Next {
+                    chain: rest,
+                    ..self
+                },
+            ),
+            None => (self.dispatch)(call),
+        }
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<HttpRequest>> = const { RefCell::new(None) };
+}
+
+// Runs `call` through `chain` and then `dispatch`, with the HTTP request
+// being handled on this thread.
+pub(crate) fn run(
+    chain: &[Middleware],
+    call: Call,
+    dispatch: &dyn Fn(Call) -> Response,
+) -> Response {
+    CURRENT.with(|current| {
+        let unknown = HttpRequest::default();
+        let current = current.borrow();
+        let next = Next {
+            chain,
+            request: current.as_ref().unwrap_or(&unknown),
+            dispatch,
+        };
+        next.run(call)
+    })
+}
+
+// Makes `request` the one being handled while `f` runs.
+pub(crate) fn scope<F: FnOnce() -> R, R>(request: Option<HttpRequest>, f: F) -> R {
+    struct Restore(Option<HttpRequest>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            CURRENT.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+
+    let previous = CURRENT.with(|current| current.replace(request));
+    let _restore = Restore(previous);
+    f()
+}
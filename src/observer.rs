@@ -0,0 +1,54 @@
+//! Hooks told about every call the server dispatches and every connection it
+//! opens or closes, for feeding monitoring systems other than the built-in
+//! Prometheus page, added with `Server::add_observer`.
+
+use std::time::Duration;
+
+/// How a call ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    /// The call was answered with a fault with this code, as it is when its
+    /// handler panics.
+    Fault(i32),
+}
+
+/// Told about calls and connections as they happen, from whichever thread
+/// handles them, so implementations should be quick.
+pub trait Observer: Send + Sync {
+    /// Called once a call to `method` has been answered, `duration` after
+    /// it was handed to the middleware. Every call answered is told about,
+    /// those answered from the cache as a success, and so are those to
+    /// methods registered with `register_async`. Methods not registered are
+    /// named `"unknown"`, so that callers cannot grow the set of names
+    /// without bound.
+    fn on_call(&self, method: &str, duration: Duration, outcome: Outcome);
+
+    /// Called with the number of connections open whenever one opens or
+    /// closes. Only the hyper, Tokio and SCGI backends count connections.
+    fn on_connections(&self, _open: usize) {}
+}
+
+/// Reports to the `metrics` crate's facade: `xmlrpc_calls_total` and
+/// `xmlrpc_faults_total` counters and an `xmlrpc_call_duration_seconds`
+/// histogram, labelled by method, and an `xmlrpc_open_connections` gauge.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MetricsObserver;
+
+#[cfg(feature = "metrics")]
+impl Observer for MetricsObserver {
+    fn on_call(&self, method: &str, duration: Duration, outcome: Outcome) {
+        let method = method.to_owned();
+        ::metrics_lib::counter!("xmlrpc_calls_total", "method" => method.clone()).increment(1);
+        if let Outcome::Fault(_) = outcome {
+            ::metrics_lib::counter!("xmlrpc_faults_total", "method" => method.clone()).increment(1);
+        }
+        ::metrics_lib::histogram!("xmlrpc_call_duration_seconds", "method" => method)
+            .record(duration.as_secs_f64());
+    }
+
+    fn on_connections(&self, open: usize) {
+        ::metrics_lib::gauge!("xmlrpc_open_connections").set(open as f64);
+    }
+}
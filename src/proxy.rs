@@ -0,0 +1,91 @@
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+
+use super::client::Client;
+use super::error::{Error, Result};
+use super::xmlfmt::{from_params, Params, Response, Value};
+use Url;
+
+/// Calls methods by name on one server, in the style of Python's
+/// `xmlrpc.client.ServerProxy`:
+///
+/// `proxy.method("examples.getStateName").arg(41).call::<String>()`
+pub struct ServerProxy {
+    client: Client,
+    uri: Url,
+}
+
+impl ServerProxy {
+    pub fn new(uri: Url) -> Result<ServerProxy> {
+        Ok(ServerProxy::with_client(Client::new()?, uri))
+    }
+
+    pub fn with_client(client: Client, uri: Url) -> ServerProxy {
+        ServerProxy { client, uri }
+    }
+
+    pub fn method<T: Into<String>>(&mut self, name: T) -> MethodCall<'_> {
+        MethodCall {
+            proxy: self,
+            name: name.into(),
+            params: Vec::new(),
+            deadline: None,
+        }
+    }
+}
+
+pub struct MethodCall<'a> {
+    proxy: &'a mut ServerProxy,
+    name: String,
+    params: Params,
+    deadline: Option<Duration>,
+}
+
+impl<'a> MethodCall<'a> {
+    pub fn arg<T: Into<Value>>(mut self, value: T) -> Self {
+        self.params.push(value.into());
+        self
+    }
+
+    /// Limits how long the call may wait on the server, in place of the
+    /// client's call timeout.
+    pub fn deadline(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(timeout);
+        self
+    }
+
+    pub fn call_value(self) -> Result<Response> {
+        let proxy = self.proxy;
+        match self.deadline {
+            Some(timeout) => {
+                proxy
+                    .client
+                    .call_value_within(&proxy.uri, self.name, self.params, timeout)
+            }
+            None => proxy.client.call_value(&proxy.uri, self.name, self.params),
+        }
+    }
+
+    /// Unlike `Client::call`, faults are returned as `Error::Fault`.
+    pub fn call<T: DeserializeOwned>(self) -> Result<T> {
+        let params = self.call_value()?.map_err(Error::Fault)?;
+        from_params(params).map_err(Into::into)
+    }
+}
+
+/// Calls a method through a `ServerProxy` in one go, converting each
+/// argument with `Into<Value>`:
+///
+/// `call!(proxy, "math.add", 1, 2 => i32)`
+///
+/// The result type after `=>` can be left out where it is inferred.
+/// Expands to a `Result` like `MethodCall::call`.
+#[macro_export]
+macro_rules! call {
+    ($proxy:expr, $name:expr $(, $arg:expr)* => $output:ty) => {
+        $proxy.method($name)$(.arg($arg))*.call::<$output>()
+    };
+    ($proxy:expr, $name:expr $(, $arg:expr)* $(,)*) => {
+        $proxy.method($name)$(.arg($arg))*.call()
+    };
+}
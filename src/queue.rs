@@ -0,0 +1,44 @@
+//! A fixed set of worker threads fed through a bounded queue.
+
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub(crate) struct WorkQueue<T> {
+    sender: SyncSender<T>,
+}
+
+impl<T: Send + 'static> WorkQueue<T> {
+    /// Starts `threads` workers running `work` on each queued job. At most
+    /// `capacity` jobs wait for a free worker.
+    pub fn start<F>(threads: usize, capacity: usize, work: F) -> WorkQueue<T>
+    where
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let work = Arc::new(work);
+        for _ in 0..threads.max(1) {
+            let receiver = Arc::clone(&receiver);
+            let work = Arc::clone(&work);
+            thread::spawn(move || {
+                while let Some(job) = next_job(&receiver) {
+                    work(job);
+                }
+            });
+        }
+        WorkQueue { sender }
+    }
+
+    /// Queues `job`, waiting while the queue is full.
+    pub fn push(&self, job: T) {
+        // Workers only stop once every sender is gone.
+        let _ = self.sender.send(job);
+    }
+}
+
+// The lock is held only while waiting for a job, not while working on it.
+fn next_job<T>(receiver: &Mutex<Receiver<T>>) -> Option<T> {
+    let receiver = receiver.lock().unwrap_or_else(|err| err.into_inner());
+    receiver.recv().ok()
+}
@@ -0,0 +1,115 @@
+//! Sending calls again when they fail in ways that often pass, such as a
+//! dropped connection or a 503 from a server restarting, set up with
+//! `Client::set_retry_policy`.
+//!
+//! A call that failed may still have been carried out, as when the
+//! connection dropped before the reply came. Methods that must not run
+//! twice should be named in `RetryPolicy::non_idempotent`, so that they are
+//! never sent again. Faults are answers, and are never retried.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use super::error::Error;
+use super::jobs::Backoff;
+
+/// The kinds of failure a `RetryPolicy` retries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryOn {
+    /// Connections that could not be made, or that dropped before the
+    /// reply came.
+    pub connect: bool,
+    /// Replies with a 5xx status, or 429 Too Many Requests.
+    pub server_errors: bool,
+    /// Connect, read and whole-call timeouts.
+    pub timeouts: bool,
+}
+
+impl Default for RetryOn {
+    fn default() -> RetryOn {
+        RetryOn {
+            connect: true,
+            server_errors: true,
+            timeouts: true,
+        }
+    }
+}
+
+/// When and how often a client sends a failed call again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times a call is sent at most, the first time included.
+    pub max_attempts: u32,
+    /// How long to wait before sending a call again: `backoff.initial`
+    /// before the first retry, then twice as long each time up to
+    /// `backoff.max`.
+    pub backoff: Backoff,
+    /// Waits a random part of each delay instead, from half of it to all
+    /// of it, so that clients that failed together do not retry together.
+    pub jitter: bool,
+    pub retry_on: RetryOn,
+    /// Methods never sent again, because running them twice would do harm.
+    pub non_idempotent: HashSet<String>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            backoff: Backoff::default(),
+            jitter: true,
+            retry_on: RetryOn::default(),
+            non_idempotent: HashSet::new(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Never sends `method` again, as in
+    /// `RetryPolicy::default().non_idempotent("account.charge")`.
+    pub fn non_idempotent<M: Into<String>>(mut self, method: M) -> RetryPolicy {
+        self.non_idempotent.insert(method.into());
+        self
+    }
+
+    // Whether a call to `method` may be sent again after its `attempt`th
+    // attempt fails.
+    pub(crate) fn allows(&self, method: &str, attempt: u32) -> bool {
+        attempt < self.max_attempts && !self.non_idempotent.contains(method)
+    }
+
+    // Whether `err` is worth sending the call again for.
+    pub(crate) fn retries(&self, err: &Error) -> bool {
+        match *err {
+            Error::Status(ref reply) => {
+                self.retry_on.server_errors && (reply.status == 429 || reply.status >= 500)
+            }
+            ref err if err.is_timeout() => self.retry_on.timeouts,
+            ref err if err.is_connect() => self.retry_on.connect,
+            Error::Io(_) => self.retry_on.connect && err.is_retryable(),
+            _ => false,
+        }
+    }
+
+    // How long to wait after the `attempt`th attempt failed.
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let mut delay = self.backoff.initial;
+        for _ in 1..attempt {
+            delay = self.backoff.next(delay);
+        }
+        if self.jitter {
+            delay / 2 + delay.mul_f64(random_fraction() / 2.0)
+        } else {
+            delay
+        }
+    }
+}
+
+// A number from 0 up to 1, random enough to spread retries out. Each
+// `RandomState` is seeded apart from the others.
+fn random_fraction() -> f64 {
+    let hash = RandomState::new().build_hasher().finish();
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,323 @@
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::Serialize;
+use std;
+use std::sync::Arc;
+
+use super::client::Client;
+use super::error::{self, FmtError};
+use super::server::{on_decode_fail, on_encode_fail, Server};
+use super::xmlfmt::{from_params, into_params, Fault, Response, Value};
+use Url;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Rpc(#[from] error::Error),
+    /// The reply carried a status code other than success.
+    #[error("ROS API call failed with code {code}: {message}")]
+    Api { code: i32, message: String },
+}
+
+fn bad_reply() -> Error {
+    let err = FmtError::Decoding("expected a [code, statusMessage, value] reply".into());
+    error::Error::from(err).into()
+}
+
+/// Typed client for the ROS Master API.
+pub struct Master {
+    client: Client,
+    uri: Url,
+    caller_id: String,
+}
+
+/// Publishers, subscribers and services, each listed with the nodes using it.
+pub type SystemState = (
+    Vec<(String, Vec<String>)>,
+    Vec<(String, Vec<String>)>,
+    Vec<(String, Vec<String>)>,
+);
+
+impl Master {
+    pub fn new<T: Into<String>>(uri: Url, caller_id: T) -> Result<Master> {
+        Ok(Master {
+            client: Client::new()?,
+            uri,
+            caller_id: caller_id.into(),
+        })
+    }
+
+    // Every API call replies with a single `[code, statusMessage, value]`.
+    // The value is only meaningful on success, so it is decoded last.
+    fn request<Args, T>(&mut self, method: &str, args: Args) -> Result<T>
+    where
+        Args: Serialize,
+        T: DeserializeOwned,
+    {
+        let params = into_params(&args).map_err(error::Error::from)?;
+        let mut reply = match self
+            .client
+            .call_value(&self.uri, method, params)?
+            .map_err(error::Error::from)?
+            .pop()
+        {
+            Some(Value::Array(reply)) => reply,
+            _ => return Err(bad_reply()),
+        };
+        match (reply.pop(), reply.pop(), reply.pop(), reply.is_empty()) {
+            (Some(value), _, Some(Value::Int(1)), true) => {
+                from_params(vec![value]).map_err(|err| error::Error::from(err).into())
+            }
+            (Some(_), Some(Value::String(message)), Some(Value::Int(code)), true) => {
+                Err(Error::Api { code, message })
+            }
+            _ => Err(bad_reply()),
+        }
+    }
+
+    /// Returns the APIs of the topic's current subscribers.
+    pub fn register_publisher(
+        &mut self,
+        topic: &str,
+        topic_type: &str,
+        caller_api: &str,
+    ) -> Result<Vec<String>> {
+        let args = (self.caller_id.clone(), topic, topic_type, caller_api);
+        self.request("registerPublisher", args)
+    }
+
+    pub fn unregister_publisher(&mut self, topic: &str, caller_api: &str) -> Result<i32> {
+        let args = (self.caller_id.clone(), topic, caller_api);
+        self.request("unregisterPublisher", args)
+    }
+
+    /// Returns the APIs of the topic's current publishers.
+    pub fn register_subscriber(
+        &mut self,
+        topic: &str,
+        topic_type: &str,
+        caller_api: &str,
+    ) -> Result<Vec<String>> {
+        let args = (self.caller_id.clone(), topic, topic_type, caller_api);
+        self.request("registerSubscriber", args)
+    }
+
+    pub fn unregister_subscriber(&mut self, topic: &str, caller_api: &str) -> Result<i32> {
+        let args = (self.caller_id.clone(), topic, caller_api);
+        self.request("unregisterSubscriber", args)
+    }
+
+    pub fn register_service(
+        &mut self,
+        service: &str,
+        service_api: &str,
+        caller_api: &str,
+    ) -> Result<()> {
+        let args = (self.caller_id.clone(), service, service_api, caller_api);
+        self.request::<_, IgnoredAny>("registerService", args)
+            .map(|_| ())
+    }
+
+    pub fn unregister_service(&mut self, service: &str, service_api: &str) -> Result<i32> {
+        let args = (self.caller_id.clone(), service, service_api);
+        self.request("unregisterService", args)
+    }
+
+    pub fn lookup_node(&mut self, node: &str) -> Result<String> {
+        let args = (self.caller_id.clone(), node);
+        self.request("lookupNode", args)
+    }
+
+    pub fn lookup_service(&mut self, service: &str) -> Result<String> {
+        let args = (self.caller_id.clone(), service);
+        self.request("lookupService", args)
+    }
+
+    /// Returns `(topic, type)` pairs.
+    pub fn get_published_topics(&mut self, subgraph: &str) -> Result<Vec<(String, String)>> {
+        let args = (self.caller_id.clone(), subgraph);
+        self.request("getPublishedTopics", args)
+    }
+
+    /// Returns `(topic, type)` pairs.
+    pub fn get_topic_types(&mut self) -> Result<Vec<(String, String)>> {
+        let caller_id = self.caller_id.clone();
+        self.request("getTopicTypes", caller_id)
+    }
+
+    pub fn get_system_state(&mut self) -> Result<SystemState> {
+        let caller_id = self.caller_id.clone();
+        self.request("getSystemState", caller_id)
+    }
+
+    pub fn get_uri(&mut self) -> Result<String> {
+        let caller_id = self.caller_id.clone();
+        self.request("getUri", caller_id)
+    }
+}
+
+/// A failed slave call is reported to the caller with this message.
+pub type SlaveResult<T> = std::result::Result<T, String>;
+
+/// Callbacks for the ROS Slave API, served by `register_slave`.
+pub trait Slave: Send + Sync + 'static {
+    fn get_master_uri(&self, caller_id: &str) -> SlaveResult<String>;
+
+    /// Returns `(topic, type)` pairs.
+    fn get_subscriptions(&self, caller_id: &str) -> SlaveResult<Vec<(String, String)>>;
+
+    /// Returns `(topic, type)` pairs.
+    fn get_publications(&self, caller_id: &str) -> SlaveResult<Vec<(String, String)>>;
+
+    fn publisher_update(
+        &self,
+        caller_id: &str,
+        topic: &str,
+        publishers: Vec<String>,
+    ) -> SlaveResult<()>;
+
+    /// Picks one of the offered protocols and returns its parameters, e.g.
+    /// `["TCPROS", host, port]`.
+    fn request_topic(
+        &self,
+        caller_id: &str,
+        topic: &str,
+        protocols: &[Value],
+    ) -> SlaveResult<Vec<Value>>;
+
+    fn shutdown(&self, caller_id: &str, message: &str) -> SlaveResult<()>;
+
+    fn param_update(&self, _caller_id: &str, _key: &str, _value: &Value) -> SlaveResult<()> {
+        Ok(())
+    }
+
+    fn get_pid(&self, _caller_id: &str) -> SlaveResult<i32> {
+        Ok(std::process::id() as i32)
+    }
+
+    fn get_bus_stats(&self, _caller_id: &str) -> SlaveResult<Value> {
+        Ok(Value::Array(vec![
+            Value::Array(vec![]),
+            Value::Array(vec![]),
+            Value::Array(vec![]),
+        ]))
+    }
+
+    fn get_bus_info(&self, _caller_id: &str) -> SlaveResult<Value> {
+        Ok(Value::Array(vec![]))
+    }
+}
+
+fn reply<T: Serialize>(result: SlaveResult<T>) -> Response {
+    let params = match result {
+        Ok(value) => into_params(&((1, "", value),)),
+        Err(message) => into_params(&((-1, message, 0),)),
+    };
+    params.or_else(|err| on_encode_fail(&err))
+}
+
+fn reply_value(result: SlaveResult<Value>) -> Response {
+    let reply = match result {
+        Ok(value) => vec![Value::Int(1), Value::String(String::new()), value],
+        Err(message) => vec![Value::Int(-1), Value::String(message), Value::Int(0)],
+    };
+    Ok(vec![Value::Array(reply)])
+}
+
+fn invalid_params(expected: &str) -> Response {
+    Err(Fault::new(
+        Fault::INVALID_PARAMS,
+        format!("Expected {}", expected),
+    ))
+}
+
+fn register<S, Args, T, F>(server: &mut Server, slave: &Arc<S>, name: &str, call: F)
+where
+    S: Slave,
+    Args: DeserializeOwned,
+    T: Serialize,
+    F: Fn(&S, Args) -> SlaveResult<T> + Send + Sync + 'static,
+{
+    let slave = Arc::clone(slave);
+    server.register_value(name, move |params| match from_params(params) {
+        Ok(args) => reply(call(&slave, args)),
+        Err(err) => on_decode_fail(&err),
+    });
+}
+
+fn register_raw<S, F>(server: &mut Server, slave: &Arc<S>, name: &str, call: F)
+where
+    S: Slave,
+    F: Fn(&S, Vec<Value>) -> Response + Send + Sync + 'static,
+{
+    let slave = Arc::clone(slave);
+    server.register_value(name, move |params| call(&slave, params));
+}
+
+/// Registers the whole Slave API on the server.
+pub fn register_slave<S: Slave>(server: &mut Server, slave: S) {
+    let slave = Arc::new(slave);
+    register(server, &slave, "getMasterUri", |s, caller_id: String| {
+        s.get_master_uri(&caller_id)
+    });
+    register(server, &slave, "getPid", |s, caller_id: String| {
+        s.get_pid(&caller_id)
+    });
+    register(
+        server,
+        &slave,
+        "getSubscriptions",
+        |s, caller_id: String| s.get_subscriptions(&caller_id),
+    );
+    register(server, &slave, "getPublications", |s, caller_id: String| {
+        s.get_publications(&caller_id)
+    });
+    register(
+        server,
+        &slave,
+        "publisherUpdate",
+        |s, (caller_id, topic, publishers): (String, String, Vec<String>)| {
+            s.publisher_update(&caller_id, &topic, publishers)
+                .map(|()| 0)
+        },
+    );
+    register(
+        server,
+        &slave,
+        "shutdown",
+        |s, (caller_id, message): (String, String)| s.shutdown(&caller_id, &message).map(|()| 0),
+    );
+    register_raw(server, &slave, "requestTopic", |s, params| {
+        match params.as_slice() {
+            [Value::String(caller_id), Value::String(topic), Value::Array(protocols)] => {
+                reply_value(
+                    s.request_topic(caller_id, topic, protocols)
+                        .map(Value::Array),
+                )
+            }
+            _ => invalid_params("caller ID, topic and protocol list"),
+        }
+    });
+    register_raw(server, &slave, "paramUpdate", |s, params| {
+        match params.as_slice() {
+            [Value::String(caller_id), Value::String(key), value] => reply_value(
+                s.param_update(caller_id, key, value)
+                    .map(|()| Value::Int(0)),
+            ),
+            _ => invalid_params("caller ID, parameter key and value"),
+        }
+    });
+    register_raw(server, &slave, "getBusStats", |s, params| {
+        match params.as_slice() {
+            [Value::String(caller_id)] => reply_value(s.get_bus_stats(caller_id)),
+            _ => invalid_params("caller ID"),
+        }
+    });
+    register_raw(server, &slave, "getBusInfo", |s, params| {
+        match params.as_slice() {
+            [Value::String(caller_id)] => reply_value(s.get_bus_info(caller_id)),
+            _ => invalid_params("caller ID"),
+        }
+    });
+}
@@ -0,0 +1,41 @@
+//! Plain HTTP pages served next to the XML-RPC endpoint, such as health
+//! checks for load balancers, added with `Server::add_route`.
+
+use super::middleware::HttpRequest;
+
+pub(crate) type Route = Box<dyn Fn(&HttpRequest) -> HttpResponse + Send + Sync>;
+
+/// What a route answers with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: String,
+    /// Headers besides `Content-Type` and `Content-Length`.
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    /// A `text/plain` response.
+    pub fn text<B: Into<String>>(status: u16, body: B) -> HttpResponse {
+        HttpResponse {
+            status,
+            content_type: String::from("text/plain; charset=utf-8"),
+            headers: Vec::new(),
+            body: body.into().into_bytes(),
+        }
+    }
+
+    pub fn with_header<N, V>(mut self, name: N, value: V) -> HttpResponse
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    pub(crate) fn not_found() -> HttpResponse {
+        HttpResponse::text(404, "Not Found")
+    }
+}
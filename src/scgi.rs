@@ -0,0 +1,706 @@
+//! XML-RPC over SCGI, as spoken by rTorrent and web servers' SCGI modules.
+//!
+//! Requests are a netstring of NUL-separated headers followed by the body,
+//! and replies are CGI output: `Status` and other headers, a blank line and
+//! the body. Each connection carries a single call.
+
+#[cfg(feature = "client")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "client")]
+use std;
+#[cfg(feature = "server")]
+use std::io::BufReader;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+#[cfg(feature = "server")]
+use std::net::{self, TcpListener};
+#[cfg(feature = "client")]
+use std::net::{SocketAddr, ToSocketAddrs};
+#[cfg(all(unix, feature = "client"))]
+use std::os::unix::net::UnixStream;
+#[cfg(all(unix, feature = "client"))]
+use std::path::PathBuf;
+#[cfg(feature = "server")]
+use std::sync::Arc;
+#[cfg(feature = "server")]
+use std::thread;
+#[cfg(feature = "client")]
+use std::time::Duration;
+#[cfg(feature = "server")]
+use std::time::{Instant, SystemTime};
+
+#[cfg(feature = "server")]
+use super::access_log::AccessRecord;
+#[cfg(feature = "client")]
+use super::client::summarize_reply;
+use super::error::Result;
+#[cfg(feature = "client")]
+use super::error::{ConnectFailed, Error};
+#[cfg(feature = "server")]
+use super::heads::{Pending, PendingHeads};
+#[cfg(feature = "server")]
+use super::listing;
+#[cfg(feature = "server")]
+use super::middleware::{self, HttpRequest};
+#[cfg(feature = "server")]
+use super::queue::WorkQueue;
+#[cfg(feature = "server")]
+use super::server::{Server, Timings, Verified, ALLOW};
+#[cfg(feature = "server")]
+use super::shutdown::ShutdownHandle;
+#[cfg(feature = "server")]
+use super::trace;
+use super::tracecontext;
+#[cfg(feature = "client")]
+use super::tracecontext::TraceContext;
+#[cfg(feature = "server")]
+use super::tracecontext::{TRACEPARENT, TRACESTATE};
+#[cfg(feature = "server")]
+use super::xmlfmt::parse::content_charset;
+#[cfg(feature = "server")]
+use super::xmlfmt::scratch;
+use super::xmlfmt::value::ToXml;
+#[cfg(feature = "client")]
+use super::xmlfmt::{from_params, into_params, parse, Call, Fault, Params, Response};
+
+// Headers beyond this size are rejected rather than buffered.
+#[cfg(feature = "server")]
+const MAX_HEADERS_LEN: usize = 64 * 1024;
+#[cfg(feature = "client")]
+enum Endpoint {
+    Tcp(Vec<SocketAddr>),
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+#[cfg(feature = "client")]
+trait Stream: Read + Write {}
+
+#[cfg(feature = "client")]
+impl<T: Read + Write> Stream for T {}
+
+#[cfg(feature = "client")]
+pub struct ScgiClient {
+    endpoint: Endpoint,
+    timeout: Option<Duration>,
+    trace_context: Option<TraceContext>,
+}
+
+#[cfg(feature = "client")]
+impl ScgiClient {
+    pub fn new<A: ToSocketAddrs>(addr: A) -> Result<ScgiClient> {
+        let addrs = addr.to_socket_addrs()?.collect();
+        Ok(ScgiClient::with_endpoint(Endpoint::Tcp(addrs)))
+    }
+
+    /// Connects through a Unix socket, e.g. rTorrent's `network.scgi.open_local`.
+    #[cfg(unix)]
+    pub fn unix<P: Into<PathBuf>>(path: P) -> ScgiClient {
+        ScgiClient::with_endpoint(Endpoint::Unix(path.into()))
+    }
+
+    fn with_endpoint(endpoint: Endpoint) -> ScgiClient {
+        ScgiClient {
+            endpoint,
+            timeout: None,
+            trace_context: None,
+        }
+    }
+
+    /// Limits how long any single read from or write to the server may block.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Sends `context` with every call instead of that of the request being
+    /// handled on this thread.
+    pub fn set_trace_context(&mut self, context: Option<TraceContext>) {
+        self.trace_context = context;
+    }
+
+    fn connect(&self) -> io::Result<Box<dyn Stream>> {
+        match self.endpoint {
+            Endpoint::Tcp(ref addrs) => {
+                let stream = TcpStream::connect(&addrs[..]).map_err(ConnectFailed::wrap)?;
+                stream.set_read_timeout(self.timeout)?;
+                stream.set_write_timeout(self.timeout)?;
+                Ok(Box::new(stream))
+            }
+            #[cfg(unix)]
+            Endpoint::Unix(ref path) => {
+                let stream = UnixStream::connect(path).map_err(ConnectFailed::wrap)?;
+                stream.set_read_timeout(self.timeout)?;
+                stream.set_write_timeout(self.timeout)?;
+                Ok(Box::new(stream))
+            }
+        }
+    }
+
+    pub fn call_value<Tkey>(&self, name: Tkey, params: Params) -> Result<Response>
+    where
+        Tkey: Into<String>,
+    {
+        let body = Call {
+            name: name.into(),
+            params,
+        }
+        .to_xml();
+        let content_length = body.len().to_string();
+        let mut headers = vec![
+            ("CONTENT_LENGTH", content_length),
+            ("SCGI", "1".to_owned()),
+            ("REQUEST_METHOD", "POST".to_owned()),
+            ("REQUEST_URI", "/RPC2".to_owned()),
+            ("CONTENT_TYPE", "text/xml".to_owned()),
+        ];
+        if let Some(context) = self.trace_context.clone().or_else(tracecontext::current) {
+            headers.push(("HTTP_TRACEPARENT", context.traceparent));
+            if let Some(state) = context.tracestate {
+                headers.push(("HTTP_TRACESTATE", state));
+            }
+        }
+
+        let mut stream = self.connect()?;
+        stream.write_all(&encode_request(&headers, body.as_bytes()))?;
+        stream.flush()?;
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply)?;
+        decode_reply(&reply)
+    }
+
+    /// Faults are returned as `Ok(Err(fault))`, like `Client::call`.
+    pub fn call<'a, Tkey, Treq, Tres>(
+        &self,
+        name: Tkey,
+        req: Treq,
+    ) -> Result<std::result::Result<Tres, Fault>>
+    where
+        Tkey: Into<String>,
+        Treq: Serialize,
+        Tres: Deserialize<'a>,
+    {
+        match self.call_value(name, into_params(&req)?) {
+            Ok(Ok(v)) => from_params(v).map(Ok).map_err(Into::into),
+            Ok(Err(v)) => Ok(Err(v)),
+            Err(v) => Err(v),
+        }
+    }
+}
+
+#[cfg(feature = "client")]
+fn encode_request(headers: &[(&str, String)], body: &[u8]) -> Vec<u8> {
+    let mut netstring = Vec::new();
+    for &(name, ref value) in headers {
+        netstring.extend_from_slice(name.as_bytes());
+        netstring.push(0);
+        netstring.extend_from_slice(value.as_bytes());
+        netstring.push(0);
+    }
+    let mut request = format!("{}:", netstring.len()).into_bytes();
+    request.extend_from_slice(&netstring);
+    request.push(b',');
+    request.extend_from_slice(body);
+    request
+}
+
+#[cfg(feature = "client")]
+fn split_head(reply: &[u8]) -> (&[u8], &[u8]) {
+    for (i, window) in reply.windows(2).enumerate() {
+        if window == b"\n\n" {
+            return (&reply[..i], &reply[i + 2..]);
+        }
+        if reply[i..].starts_with(b"\r\n\r\n") {
+            return (&reply[..i], &reply[i + 4..]);
+        }
+    }
+    (&[], reply)
+}
+
+#[cfg(feature = "client")]
+fn decode_reply(reply: &[u8]) -> Result<Response> {
+    let (head, body) = split_head(reply);
+    let head = String::from_utf8_lossy(head);
+    let headers = head
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => Some((name.trim(), value.trim())),
+                _ => None,
+            }
+        })
+        .collect::<Vec<_>>();
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|&&(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|&(_, value)| value.to_owned())
+    };
+    // CGI replies without a Status header succeeded.
+    let status = header("Status")
+        .and_then(|status| status.split_whitespace().next()?.parse().ok())
+        .unwrap_or(200);
+    if !(200..300).contains(&status) {
+        return Err(Error::Status(summarize_reply(status, header, body)));
+    }
+    parse::response(body).map_err(|err| Error::InvalidResponse {
+        reply: summarize_reply(status, header, body),
+        source: err,
+    })
+}
+
+#[cfg(feature = "server")]
+fn invalid_request(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(feature = "server")]
+// The headers of an SCGI request and the length of the body that follows.
+type Head = (Vec<(String, String)>, u64);
+
+#[cfg(feature = "server")]
+fn read_head<R: Read>(stream: &mut R) -> io::Result<Head> {
+    let mut len = 0usize;
+    let mut byte = [0u8];
+    loop {
+        stream.read_exact(&mut byte)?;
+        match byte[0] {
+            b':' => break,
+            digit @ b'0'..=b'9' => {
+                len = len * 10 + usize::from(digit - b'0');
+                if len > MAX_HEADERS_LEN {
+                    return Err(invalid_request("SCGI headers too long"));
+                }
+            }
+            _ => return Err(invalid_request("Malformed SCGI netstring length")),
+        }
+    }
+    let mut netstring = vec![0u8; len + 1];
+    stream.read_exact(&mut netstring)?;
+    if netstring.pop() != Some(b',') {
+        return Err(invalid_request("Malformed SCGI netstring"));
+    }
+    let mut fields = netstring
+        .split(|&b| b == 0)
+        .map(|field| String::from_utf8_lossy(field).into_owned());
+    let mut headers = Vec::new();
+    while let (Some(name), Some(value)) = (fields.next(), fields.next()) {
+        headers.push((name, value));
+    }
+    let content_length = headers
+        .iter()
+        .find(|&(name, _)| name == "CONTENT_LENGTH")
+        .and_then(|(_, value)| value.parse::<u64>().ok())
+        .ok_or_else(|| invalid_request("Missing SCGI CONTENT_LENGTH"))?;
+    Ok((headers, content_length))
+}
+
+// Reads a connection, holding the head to the server's limits on it.
+#[cfg(feature = "server")]
+struct HeadReader<'a> {
+    stream: &'a TcpStream,
+    // `None` once the head is in.
+    pending: Option<Pending>,
+}
+
+#[cfg(feature = "server")]
+impl<'a> HeadReader<'a> {
+    fn finish_head(&mut self) -> io::Result<()> {
+        if self.pending.take().is_some() {
+            self.stream.set_read_timeout(None)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "server")]
+impl<'a> Read for HeadReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(ref mut pending) = self.pending {
+            if let Some(remaining) = pending.remaining()? {
+                self.stream.set_read_timeout(Some(remaining))?;
+            }
+        }
+        self.stream.read(buf)
+    }
+}
+
+// A connection and its place among those with a head still coming in.
+#[cfg(feature = "server")]
+type Connection = (TcpStream, Pending);
+
+#[cfg(feature = "server")]
+fn serve_connection(server: &Server, connection: Connection) -> io::Result<()> {
+    server.connection_opened();
+    let served = serve_open(server, connection);
+    server.connection_closed();
+    served
+}
+
+#[cfg(feature = "server")]
+fn serve_open(server: &Server, (stream, pending): Connection) -> io::Result<()> {
+    let shutdown = match server.shutdown() {
+        Some(shutdown) => shutdown,
+        None => return serve_request(server, &stream, pending),
+    };
+    let closer = stream.try_clone()?;
+    // Connections still queued when draining began are closed unanswered.
+    if !shutdown.open(move || {
+        let _ = closer.shutdown(net::Shutdown::Both);
+    }) {
+        return Ok(());
+    }
+    let served = {
+        let _in_flight = shutdown.request();
+        serve_request(server, &stream, pending)
+    };
+    shutdown.closed();
+    served
+}
+
+#[cfg(feature = "server")]
+fn serve_request(server: &Server, stream: &TcpStream, pending: Pending) -> io::Result<()> {
+    // The netstring length is read a byte at a time.
+    let mut reader = BufReader::new(HeadReader {
+        stream,
+        pending: Some(pending),
+    });
+    let (headers, content_length) = read_head(&mut reader)?;
+    reader.get_mut().finish_head()?;
+    let received = SystemTime::now();
+    let started = Instant::now();
+    let mut method = None;
+    let peer = headers
+        .iter()
+        .find(|&(key, _)| key == "REMOTE_ADDR")
+        .map_or("-", |(_, value)| value.as_str());
+    let trace = trace::Request::start(peer);
+    let (status, sent, written) = trace.in_scope(|| {
+        respond(
+            server,
+            &headers,
+            content_length,
+            reader,
+            stream,
+            &mut method,
+        )
+    });
+    trace.finish(status);
+    if let Some(log) = server.access_log() {
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|&(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+        };
+        log.log(&AccessRecord {
+            peer: header("REMOTE_ADDR").and_then(|addr| addr.parse().ok()),
+            received,
+            http_method: header("REQUEST_METHOD").unwrap_or_else(|| String::from("POST")),
+            path: header("REQUEST_URI").unwrap_or_else(|| String::from("/")),
+            protocol: header("SERVER_PROTOCOL"),
+            method,
+            status,
+            request_bytes: Some(content_length),
+            response_bytes: sent,
+            duration: started.elapsed(),
+            referer: header("HTTP_REFERER"),
+            user_agent: header("HTTP_USER_AGENT"),
+        });
+    }
+    written
+}
+
+// The HTTP request a web server passed on as SCGI headers.
+#[cfg(feature = "server")]
+fn cgi_request(headers: &[(String, String)]) -> HttpRequest {
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|&(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+    HttpRequest {
+        method: header("REQUEST_METHOD").unwrap_or_else(|| String::from("POST")),
+        path: header("REQUEST_URI")
+            .map(|uri| uri.split('?').next().unwrap_or("").to_owned())
+            .unwrap_or_else(|| String::from("/")),
+        peer: header("REMOTE_ADDR").and_then(|addr| addr.parse().ok()),
+        // Header names come back lowercased, from the `HTTP_` variables.
+        headers: headers
+            .iter()
+            .filter_map(|(key, value)| {
+                let name = match key.as_str() {
+                    "CONTENT_TYPE" | "CONTENT_LENGTH" => key.as_str(),
+                    _ => key.get(5..).filter(|_| key.starts_with("HTTP_"))?,
+                };
+                Some((name.to_ascii_lowercase().replace('_', "-"), value.clone()))
+            })
+            .collect(),
+    }
+}
+
+// Answers a request whose head has been read. Returns the status and the
+// length of the body sent, along with whether writing them succeeded. Sets
+// `method` to the XML-RPC method called, if any.
+#[cfg(feature = "server")]
+fn respond(
+    server: &Server,
+    headers: &[(String, String)],
+    content_length: u64,
+    reader: BufReader<HeadReader>,
+    // The body stays charged to the memory budget until the reply is sent,
+    // so replies are written through a shared reference.
+    mut writer: &TcpStream,
+    method: &mut Option<String>,
+) -> (u16, u64, io::Result<()>) {
+    // Web servers pass HTTP headers on as `HTTP_` variables.
+    let http_header = |name: &str| {
+        let name = format!("HTTP_{}", name.to_ascii_uppercase().replace('-', "_"));
+        headers
+            .iter()
+            .find(|&(key, _)| *key == name)
+            .map(|(_, value)| value.clone())
+    };
+    let echoed = server
+        .echoed(http_header)
+        .into_iter()
+        .map(|(name, value)| format!("{}: {}\r\n", name, value))
+        .collect::<String>();
+    if let Some(challenge) = server.challenge(http_header) {
+        let head = format!(
+            "Status: 401 Unauthorized\r\nWWW-Authenticate: {}\r\n{}\r\n",
+            challenge, echoed
+        );
+        return (401, 0, writer.write_all(head.as_bytes()));
+    }
+    let uri = headers
+        .iter()
+        .find(|&(key, _)| key == "REQUEST_URI")
+        .map_or("/", |(_, value)| value.as_str());
+    let path = uri.split('?').next().unwrap_or("");
+    if let Some(answer) = server.route(path, || cgi_request(headers)) {
+        let reason = match answer.status {
+            200 => "OK",
+            404 => "Not Found",
+            _ => "",
+        };
+        let mut head = format!(
+            "Status: {} {}\r\n{}Content-Type: {}\r\nContent-Length: {}\r\n",
+            answer.status,
+            reason,
+            echoed,
+            answer.content_type,
+            answer.body.len()
+        );
+        for (name, value) in answer.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        let mut out = head.into_bytes();
+        out.extend_from_slice(&answer.body);
+        return (
+            answer.status,
+            answer.body.len() as u64,
+            writer.write_all(&out),
+        );
+    }
+    let is_get = headers
+        .iter()
+        .any(|(key, value)| key == "REQUEST_METHOD" && value == "GET");
+    if let Some(page) = server.listing_page(is_get) {
+        let head = format!(
+            "Status: 200 OK\r\n{}Content-Type: {}\r\nContent-Length: {}\r\n\r\n",
+            echoed,
+            listing::CONTENT_TYPE,
+            page.len()
+        );
+        let written = writer.write_all((head + &page).as_bytes());
+        return (200, page.len() as u64, written);
+    }
+    let cgi_header = |name: &str| {
+        headers
+            .iter()
+            .find(|&(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let http_method = cgi_header("REQUEST_METHOD").unwrap_or("");
+    let content_type = cgi_header("CONTENT_TYPE");
+    if let Some(status) = server.refuses_http(http_method, path, content_type) {
+        let head = match status {
+            204 => format!(
+                "Status: 204 No Content\r\n{}Allow: {}\r\n\r\n",
+                echoed, ALLOW
+            ),
+            405 => format!(
+                "Status: 405 Method Not Allowed\r\n{}Allow: {}\r\n\r\n",
+                echoed, ALLOW
+            ),
+            _ => format!("Status: 415 Unsupported Media Type\r\n{}\r\n", echoed),
+        };
+        return (status, 0, writer.write_all(head.as_bytes()));
+    }
+    // The body is parsed as it arrives, never past its declared length.
+    let refusal = |status: u16| {
+        let reason = match status {
+            413 => "Payload Too Large",
+            _ => "Service Unavailable",
+        };
+        format!("Status: {} {}\r\n{}\r\n", status, reason, echoed)
+    };
+    let mut body = match server.admit(Some(content_length), reader.take(content_length)) {
+        Ok(body) => body,
+        Err(status) => return (status, 0, writer.write_all(refusal(status).as_bytes())),
+    };
+    let signature = server.signature_header().and_then(http_header);
+    let mut verified = server.verify(signature.as_deref(), &mut body);
+    if let Some(status) = body.refusal() {
+        return (status, 0, writer.write_all(refusal(status).as_bytes()));
+    }
+    let input: &mut dyn Read = match verified {
+        Verified::Unchecked => &mut body,
+        Verified::Valid(ref mut bytes) => bytes,
+        Verified::Invalid => {
+            let head = format!("Status: 403 Forbidden\r\n{}\r\n", echoed);
+            return (403, 0, writer.write_all(head.as_bytes()));
+        }
+    };
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|&(key, _)| key == name)
+            .map(|(_, value)| value.clone())
+    };
+    let context = tracecontext::extract(|name| match name {
+        TRACEPARENT => header("HTTP_TRACEPARENT"),
+        TRACESTATE => header("HTTP_TRACESTATE"),
+        _ => None,
+    });
+    let http_request = server.http_request(|| cgi_request(headers));
+    let accept = http_header("Accept-Encoding");
+    let charset = content_charset(content_type);
+    let mut timings = Timings::default();
+    let reply = tracecontext::scope(context, || {
+        middleware::scope(http_request, || server.reply(input, charset, &mut timings))
+    });
+    *method = timings.method.clone();
+    let reply = match reply {
+        Some(reply) => reply,
+        None => {
+            server.report(&timings);
+            let head = format!("Status: 400 Bad Request\r\n{}\r\n", echoed);
+            return (400, 0, writer.write_all(head.as_bytes()));
+        }
+    };
+    let (sent, written) = scratch::with_bytes(|buf| {
+        let started = Instant::now();
+        if let Err(err) = reply.write_xml_as(buf, server.wire_profile()) {
+            return (0, Err(err));
+        }
+        let mut encoding = String::new();
+        if let Some((encoded, coding)) = server.encode_reply(accept.as_deref(), buf) {
+            buf.clear();
+            buf.extend_from_slice(&encoded);
+            if let Some(coding) = coding {
+                encoding = format!("Content-Encoding: {}\r\nVary: Accept-Encoding\r\n", coding);
+            }
+        }
+        let sent = buf.len() as u64;
+        let head = format!(
+            "Status: 200 OK\r\n{}{}Content-Type: {}\r\nContent-Length: {}\r\n\r\n",
+            echoed,
+            encoding,
+            server.xml_content_type(),
+            sent
+        );
+        // Sent in one write, so the body does not wait on Nagle's algorithm.
+        buf.splice(0..0, head.bytes());
+        timings.serialize += started.elapsed();
+        let started = Instant::now();
+        let written = writer.write_all(buf).and_then(|()| writer.flush());
+        timings.write = Some(started.elapsed());
+        (sent, written)
+    });
+    if written.is_ok() {
+        server.report(&timings);
+    }
+    (200, sent, written)
+}
+
+// Hands connections to the workers, or to a thread each without them.
+#[cfg(feature = "server")]
+fn accept(
+    server: &Arc<Server>,
+    heads: &Arc<PendingHeads>,
+    queue: Option<&WorkQueue<Connection>>,
+    listener: &TcpListener,
+) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(_) if server.shutdown().is_some_and(ShutdownHandle::is_draining) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        // Also for listeners not bound by `Server::listen`.
+        let _ = stream.set_nodelay(true);
+        // Connections from refused peers or over the limit are closed
+        // unanswered.
+        let pending = match stream
+            .peer_addr()
+            .ok()
+            .filter(|peer| server.permits(peer.ip()))
+            .and_then(|peer| PendingHeads::admit(heads, peer.ip()))
+        {
+            Some(pending) => pending,
+            None => continue,
+        };
+        match queue {
+            Some(queue) => queue.push((stream, pending)),
+            None => {
+                let server = Arc::clone(server);
+                thread::spawn(move || {
+                    if let Err(err) = serve_connection(&server, (stream, pending)) {
+                        trace::dropped(&err);
+                    }
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "server")]
+impl Server {
+    /// Answers SCGI requests from `listener`, each connection on a thread of
+    /// its own, until accepting fails on the calling thread or the server is
+    /// drained.
+    pub fn serve_scgi(self, listener: TcpListener) -> Result<()> {
+        self.drains(&listener)?;
+        let workers = self.workers();
+        let accept_threads = self.accept_threads();
+        let heads = PendingHeads::new(self.head_limits());
+        let server = Arc::new(self);
+        // A client that hung up or sent garbage gets nothing back.
+        let queue = workers.map(|(threads, capacity)| {
+            let server = Arc::clone(&server);
+            Arc::new(WorkQueue::start(threads, capacity, move |connection| {
+                if let Err(err) = serve_connection(&server, connection) {
+                    trace::dropped(&err);
+                }
+            }))
+        });
+        for _ in 1..accept_threads {
+            let listener = listener.try_clone()?;
+            let server = Arc::clone(&server);
+            let heads = Arc::clone(&heads);
+            let queue = queue.clone();
+            thread::spawn(move || {
+                if let Err(err) = accept(&server, &heads, queue.as_deref(), &listener) {
+                    trace::accept_failed(&err);
+                }
+            });
+        }
+        accept(&server, &heads, queue.as_deref(), &listener)?;
+        Ok(())
+    }
+}
@@ -1,43 +1,484 @@
+#[cfg(feature = "encoding")]
+use encoding_rs::Encoding;
+#[cfg(feature = "hyper-server")]
+use hyper;
 use rouille;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
 use std;
+use std::any::Any;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr, TcpListener};
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(all(unix, feature = "hyper-server"))]
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
-use super::error::{ErrorKind, Result};
-use super::xmlfmt::{error, from_params, into_params, parse, Call, Fault, Response, Value};
+use super::access_log::{AccessLog, AccessRecord};
+use super::auth::Auth;
+use super::budget::{Charged, MemoryBudget};
+use super::cache::{ResponseCache, Serialized};
+#[cfg(feature = "gzip")]
+use super::compression::Coding;
+use super::error::{Error, Result};
+use super::heads::HeadLimits;
+#[cfg(feature = "hyper-server")]
+use super::heads::{GuardedListener, PendingHeads};
+#[cfg(feature = "hmac")]
+use super::hmac::Verifier;
+#[cfg(feature = "hyper-server")]
+use super::ipfilter::FilteredListener;
+use super::ipfilter::IpFilter;
+use super::jobs::Jobs;
+#[cfg(feature = "jsonrpc")]
+use super::jsonrpc;
+use super::limits::ServerLimits;
+use super::listing;
+use super::metrics::Metrics;
+use super::middleware::{self, HttpRequest, Middleware, Next};
+use super::observer::{Observer, Outcome};
+use super::routes::{HttpResponse, Route};
+#[cfg(feature = "hyper-server")]
+use super::shutdown::DrainingListener;
+use super::shutdown::ShutdownHandle;
+use super::test::TestRequest;
+#[cfg(all(
+    feature = "hyper-server",
+    any(feature = "tls-native", feature = "tls-rustls")
+))]
+use super::tls::{self, TlsAcceptor, TlsStream};
+#[cfg(feature = "tokio")]
+use super::tokio::{AsyncBoundServer, AsyncHandler, HandlerFuture};
+use super::trace;
+use super::tracecontext;
+#[cfg(all(unix, feature = "hyper-server"))]
+use super::unix::{UnixHttpListener, UnixListening};
+#[cfg(feature = "encoding")]
+use super::xmlfmt::charset;
+#[cfg(feature = "chrono")]
+use super::xmlfmt::datetime::DateTimeOptions;
+use super::xmlfmt::value::ToXml;
+use super::xmlfmt::{
+    answer_multicall, error, from_params, into_params, parse, Call, Fault, IntoFault, Response,
+    Value, WireProfile, MULTICALL_METHOD,
+};
 
-type Handler = Box<Fn(Vec<Value>) -> Response + Send + Sync>;
-type HandlerMap = HashMap<String, Handler>;
+type Handler = Box<dyn Fn(Vec<Value>) -> Response + Send + Sync>;
+type HandlerMap = HashMap<String, Method>;
+pub(crate) const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+// The methods a strict server allows, as sent in `Allow`.
+pub(crate) const ALLOW: &str = "POST, OPTIONS";
+
+// Answered by the server itself with `Server::set_introspection`.
+const INTROSPECTION_METHODS: [&str; 3] = [
+    "system.listMethods",
+    "system.methodHelp",
+    "system.methodSignature",
+];
+
+type PanicHandler = Box<dyn Fn(&str, &(dyn Any + Send)) + Send + Sync>;
+
+type TimingsHandler = Box<dyn Fn(&Timings) + Send + Sync>;
+
+struct Method {
+    handler: Handler,
+    #[cfg(feature = "tokio")]
+    future: Option<AsyncHandler>,
+    info: MethodInfo,
+}
+
+/// What a registered method was documented with, for introspection.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MethodInfo {
+    /// Empty if no help was given.
+    pub help: String,
+    /// Each is the return type followed by the parameter types, named as in
+    /// XML-RPC, like `["int", "string", "boolean"]`.
+    pub signatures: Vec<Vec<String>>,
+}
+
+/// Documents a method just registered on a `Server`, as in
+/// `server.register_fn("add", add).with_help("Adds two numbers.")`.
+pub struct Registration<'a> {
+    info: &'a mut MethodInfo,
+}
+
+impl<'a> Registration<'a> {
+    pub fn with_help<S: Into<String>>(self, help: S) -> Self {
+        self.info.help = help.into();
+        self
+    }
+
+    /// Adds a signature, the return type first. Methods taking parameters
+    /// of more than one kind can be given several.
+    pub fn with_signature<I>(self, signature: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let signature = signature.into_iter().map(Into::into).collect();
+        self.info.signatures.push(signature);
+        self
+    }
+}
+
+/// Which fault codes the server uses for failures it detects itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum FaultCodes {
+    /// Codes from the XML-RPC fault code interoperability specification.
+    #[default]
+    Interop,
+    /// HTTP-like codes (400, 404, 500) used by earlier releases.
+    Legacy,
+}
+
+impl FaultCodes {
+    fn invalid_params(self) -> i32 {
+        match self {
+            FaultCodes::Interop => Fault::INVALID_PARAMS,
+            FaultCodes::Legacy => 400,
+        }
+    }
+
+    fn internal_error(self) -> i32 {
+        match self {
+            FaultCodes::Interop => Fault::INTERNAL_ERROR,
+            FaultCodes::Legacy => 500,
+        }
+    }
+
+    fn method_not_found(self) -> i32 {
+        match self {
+            FaultCodes::Interop => Fault::METHOD_NOT_FOUND,
+            FaultCodes::Legacy => 404,
+        }
+    }
+}
+
+/// How a panic inside a handler is reported to the caller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum PanicPolicy {
+    /// Answer with a generic internal error fault.
+    #[default]
+    Fault,
+    /// Answer with an internal error fault carrying the panic message.
+    FaultWithMessage,
+    /// Let the panic unwind into the HTTP layer, which aborts the request
+    /// with a bare 500 status.
+    Propagate,
+}
+
+/// How long each stage of answering one XML-RPC request took, as passed to
+/// the hook set with `Server::set_on_timings`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Timings {
+    /// The method called, or `None` if the request did not parse.
+    pub method: Option<String>,
+    /// Waiting for and reading the request body, headers excluded.
+    pub read: Duration,
+    pub parse: Duration,
+    /// Running the handler, including any cache lookup.
+    pub handler: Duration,
+    pub serialize: Duration,
+    /// Writing the reply, or `None` where the HTTP library writes it after
+    /// the server is done, as with the rouille backend and `handle_body`.
+    pub write: Option<Duration>,
+}
+
+// Adds up the time spent waiting on `inner`.
+struct TimedReader<R> {
+    inner: R,
+    spent: Duration,
+}
+
+impl<R: io::Read> io::Read for TimedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let started = Instant::now();
+        let read = self.inner.read(buf);
+        self.spent += started.elapsed();
+        read
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic payload"
+    }
+}
+
+fn error_fault(code: i32, context: &str, err: &error::Error) -> Response {
+    let mut fault = Fault::from_error(code, err);
+    fault.message = format!("{}: {}", context, fault.message);
+    Response::from(fault)
+}
+
+fn decode_fault(codes: FaultCodes, err: &error::Error) -> Response {
+    error_fault(codes.invalid_params(), "Failed to decode request", err)
+}
+
+fn encode_fault(codes: FaultCodes, err: &error::Error) -> Response {
+    error_fault(codes.internal_error(), "Failed to encode response", err)
+}
 
 pub fn on_decode_fail(err: &error::Error) -> Response {
-    Err(Fault::new(
-        400,
-        format!("Failed to decode request: {}", err),
-    ))
+    decode_fault(FaultCodes::Interop, err)
 }
 
 pub fn on_encode_fail(err: &error::Error) -> Response {
-    Err(Fault::new(
-        500,
-        format!("Failed to encode response: {}", err),
-    ))
+    encode_fault(FaultCodes::Interop, err)
+}
+
+/// A function registered with `Server::register_fn`, taking each parameter
+/// of a call as an argument of its own.
+///
+/// Implemented for functions of up to eight arguments, where `Args` is the
+/// tuple of their types.
+pub trait FnHandler<Args>: Send + Sync + 'static {
+    type Output: Serialize;
+    type Error: IntoFault;
+
+    /// Decodes `params` into arguments and calls the function.
+    fn call_with(
+        &self,
+        params: Vec<Value>,
+    ) -> error::Result<std::result::Result<Self::Output, Self::Error>>;
+}
+
+impl<F, R, E> FnHandler<()> for F
+where
+    F: Fn() -> std::result::Result<R, E> + Send + Sync + 'static,
+    R: Serialize,
+    E: IntoFault,
+{
+    type Output = R;
+    type Error = E;
+
+    fn call_with(&self, params: Vec<Value>) -> error::Result<std::result::Result<R, E>> {
+        if !params.is_empty() {
+            return Err(error::Error::Decoding(format!(
+                "expected no parameters, found {}",
+                params.len()
+            )));
+        }
+        Ok(self())
+    }
+}
+
+// A single parameter is not wrapped in an array by `from_params`, so it
+// cannot be decoded as a one-element tuple.
+impl<F, R, E, T1> FnHandler<(T1,)> for F
+where
+    F: Fn(T1) -> std::result::Result<R, E> + Send + Sync + 'static,
+    R: Serialize,
+    E: IntoFault,
+    T1: DeserializeOwned,
+{
+    type Output = R;
+    type Error = E;
+
+    fn call_with(&self, params: Vec<Value>) -> error::Result<std::result::Result<R, E>> {
+        Ok(self(from_params(params)?))
+    }
+}
+
+macro_rules! impl_fn_handler {
+    ($($arg:ident),*) => {
+        impl<F, R, E, $($arg),*> FnHandler<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> std::result::Result<R, E> + Send + Sync + 'static,
+            R: Serialize,
+            E: IntoFault,
+            $($arg: DeserializeOwned),*
+        {
+            type Output = R;
+            type Error = E;
+
+            #[allow(non_snake_case)]
+            fn call_with(&self, params: Vec<Value>) -> error::Result<std::result::Result<R, E>> {
+                let ($($arg,)*) = from_params::<($($arg,)*)>(params)?;
+                Ok(self($($arg),*))
+            }
+        }
+    };
+}
+
+impl_fn_handler!(T1, T2);
+impl_fn_handler!(T1, T2, T3);
+impl_fn_handler!(T1, T2, T3, T4);
+impl_fn_handler!(T1, T2, T3, T4, T5);
+impl_fn_handler!(T1, T2, T3, T4, T5, T6);
+impl_fn_handler!(T1, T2, T3, T4, T5, T6, T7);
+impl_fn_handler!(T1, T2, T3, T4, T5, T6, T7, T8);
+
+// Decodes the lone struct parameter of a call taking named parameters.
+fn from_named<T: DeserializeOwned>(params: Vec<Value>) -> error::Result<T> {
+    match params.first() {
+        Some(&Value::Struct(_)) if params.len() == 1 => from_params(params),
+        _ => Err(error::Error::Decoding(
+            "expected a single struct of named parameters".into(),
+        )),
+    }
+}
+
+fn rouille_request(request: &rouille::Request) -> HttpRequest {
+    HttpRequest {
+        method: request.method().to_owned(),
+        path: request.url(),
+        peer: Some(request.remote_addr().ip()),
+        headers: request
+            .headers()
+            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+            .collect(),
+    }
+}
+
+fn rouille_response(answer: HttpResponse) -> rouille::Response {
+    let mut response = rouille::Response::from_data(answer.content_type, answer.body)
+        .with_status_code(answer.status);
+    for (name, value) in answer.headers {
+        response = response.with_additional_header(name, value);
+    }
+    response
+}
+
+// The response to a request refused with `status` before it was answered.
+fn refused(status: u16) -> rouille::Response {
+    rouille::Response::text("").with_status_code(status)
+}
+
+// Whether an `Expect` header asks for `100 Continue`.
+pub(crate) fn expects_continue(expect: Option<&str>) -> bool {
+    expect.is_some_and(|value| value.trim().eq_ignore_ascii_case("100-continue"))
 }
 
-fn on_missing_method(_: Vec<Value>) -> Response {
-    Err(Fault::new(404, "Requested method does not exist"))
+#[cfg(feature = "hyper-server")]
+fn bind_error(addr: &std::net::SocketAddr, err: hyper::Error) -> Error {
+    match err {
+        hyper::Error::Io(source) => Error::Bind {
+            addr: *addr,
+            source,
+        },
+        err => Error::Http(err),
+    }
+}
+
+// A request body as far as its signature goes.
+#[cfg_attr(not(feature = "hmac"), allow(dead_code))]
+pub(crate) enum Verified {
+    // Unsigned requests are welcome.
+    Unchecked,
+    // Read in full, and signed.
+    Valid(io::Cursor<Vec<u8>>),
+    // Unsigned, wrongly signed or cut short.
+    Invalid,
+}
+
+// A reply to a request body, either just produced or serialized earlier.
+pub(crate) enum Reply {
+    Fresh(Response),
+    Cached(Arc<Serialized>),
+}
+
+impl Reply {
+    fn response(&self) -> &Response {
+        match *self {
+            Reply::Fresh(ref response) => response,
+            Reply::Cached(ref cached) => &cached.response,
+        }
+    }
+}
+
+impl ToXml for Reply {
+    fn to_xml(&self) -> String {
+        match *self {
+            Reply::Fresh(ref response) => response.to_xml(),
+            Reply::Cached(ref cached) => cached.xml.clone(),
+        }
+    }
+
+    fn write_xml<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        self.write_xml_as(out, &WireProfile::default())
+    }
+
+    // Cached replies were written in the server's profile already.
+    fn write_xml_as<W: io::Write>(&self, out: &mut W, profile: &WireProfile) -> io::Result<()> {
+        match *self {
+            Reply::Fresh(ref response) => response.write_xml_as(out, profile),
+            Reply::Cached(ref cached) => out.write_all(cached.xml.as_bytes()),
+        }
+    }
 }
 
 pub struct Server {
     handlers: HandlerMap,
     on_missing_method: Handler,
+    // The default reply to unknown methods, until `set_on_missing`,
+    // serialized once up front.
+    missing_method: Option<Arc<Serialized>>,
+    fault_codes: FaultCodes,
+    panic_policy: PanicPolicy,
+    on_panic: Option<PanicHandler>,
+    on_timings: Option<TimingsHandler>,
+    middleware: Vec<Middleware>,
+    metrics: Option<Metrics>,
+    observers: Vec<Box<dyn Observer>>,
+    // Connections open on the backends that count them.
+    open_connections: AtomicUsize,
+    method_listing: bool,
+    introspection: bool,
+    multicall: bool,
+    streaming_parse: bool,
+    strict_http: bool,
+    expectation_failed: bool,
+    #[cfg(feature = "tokio")]
+    serves_async: bool,
+    // Worker threads and queued connections.
+    workers: Option<(usize, usize)>,
+    accept_threads: usize,
+    listen_backlog: i32,
+    reuse_port: bool,
+    #[cfg(all(unix, feature = "hyper-server"))]
+    unix_mode: Option<u32>,
+    memory_budget: Option<MemoryBudget>,
+    limits: ServerLimits,
+    head_limits: HeadLimits,
+    ip_filter: Option<IpFilter>,
+    auth: Option<Auth>,
+    access_log: Option<AccessLog>,
+    echoed_headers: Vec<String>,
+    #[cfg(feature = "hmac")]
+    verifier: Option<Verifier>,
+    shutdown: Option<ShutdownHandle>,
+    jobs: Jobs,
+    cache: ResponseCache,
+    wire_profile: WireProfile,
+    #[cfg(feature = "chrono")]
+    datetimes: DateTimeOptions,
+    #[cfg(feature = "jsonrpc")]
+    jsonrpc_path: Option<String>,
+    // Where XML-RPC is served, if not on every path.
+    rpc_path: Option<String>,
+    routes: HashMap<String, Route>,
+    // The smallest reply worth compressing.
+    #[cfg(feature = "gzip")]
+    compression: Option<usize>,
+    #[cfg(feature = "encoding")]
+    output_encoding: Option<&'static Encoding>,
 }
 
 impl Default for Server {
     fn default() -> Self {
-        Server {
-            handlers: HashMap::new(),
-            on_missing_method: Box::new(on_missing_method),
-        }
+        Server::with_fault_codes(FaultCodes::default())
     }
 }
 
@@ -46,12 +487,98 @@ impl Server {
         Server::default()
     }
 
-    pub fn register_value<K, T>(&mut self, name: K, handler: T)
+    pub fn with_fault_codes(fault_codes: FaultCodes) -> Server {
+        let code = fault_codes.method_not_found();
+        Server {
+            handlers: HashMap::new(),
+            on_missing_method: Box::new(move |_| {
+                Err(Fault::new(code, "Requested method does not exist"))
+            }),
+            missing_method: Some(Arc::new(Serialized::new(
+                Err(Fault::new(code, "Requested method does not exist")),
+                &WireProfile::default(),
+            ))),
+            fault_codes,
+            panic_policy: PanicPolicy::default(),
+            on_panic: None,
+            on_timings: None,
+            middleware: Vec::new(),
+            metrics: None,
+            observers: Vec::new(),
+            open_connections: AtomicUsize::new(0),
+            method_listing: false,
+            introspection: false,
+            multicall: false,
+            streaming_parse: false,
+            strict_http: false,
+            expectation_failed: false,
+            #[cfg(feature = "tokio")]
+            serves_async: false,
+            workers: None,
+            accept_threads: 1,
+            // What the standard library asks for.
+            listen_backlog: 128,
+            reuse_port: false,
+            #[cfg(all(unix, feature = "hyper-server"))]
+            unix_mode: None,
+            memory_budget: None,
+            limits: ServerLimits::default(),
+            head_limits: HeadLimits::default(),
+            ip_filter: None,
+            auth: None,
+            access_log: None,
+            echoed_headers: Vec::new(),
+            #[cfg(feature = "hmac")]
+            verifier: None,
+            shutdown: None,
+            jobs: Jobs::default(),
+            cache: ResponseCache::default(),
+            wire_profile: WireProfile::default(),
+            #[cfg(feature = "chrono")]
+            datetimes: DateTimeOptions::default(),
+            #[cfg(feature = "jsonrpc")]
+            jsonrpc_path: None,
+            rpc_path: None,
+            routes: HashMap::new(),
+            #[cfg(feature = "gzip")]
+            compression: None,
+            #[cfg(feature = "encoding")]
+            output_encoding: None,
+        }
+    }
+
+    pub fn register_value<K, T>(&mut self, name: K, handler: T) -> Registration<'_>
     where
         K: Into<String>,
         T: Fn(Vec<Value>) -> Response + Send + Sync + 'static,
     {
-        self.handlers.insert(name.into(), Box::new(handler));
+        let method = Method {
+            handler: Box::new(handler),
+            #[cfg(feature = "tokio")]
+            future: None,
+            info: MethodInfo::default(),
+        };
+        let name = name.into();
+        self.handlers.insert(name.clone(), method);
+        let method = self
+            .handlers
+            .get_mut(&name)
+            .expect("method was just inserted");
+        Registration {
+            info: &mut method.info,
+        }
+    }
+
+    /// The documentation of method `name`, if it is registered.
+    pub fn method_info(&self, name: &str) -> Option<&MethodInfo> {
+        self.handlers.get(name).map(|method| &method.info)
+    }
+
+    /// The names of the registered methods, in order.
+    pub fn method_names(&self) -> Vec<&str> {
+        let mut names = self.handlers.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort();
+        names
     }
 
     pub fn register<'a, K, Treq, Tres, Thandler, Tef, Tdf>(
@@ -60,7 +587,8 @@ impl Server {
         handler: Thandler,
         encode_fail: Tef,
         decode_fail: Tdf,
-    ) where
+    ) -> Registration<'_>
+    where
         K: Into<String>,
         Treq: Deserialize<'a>,
         Tres: Serialize,
@@ -75,17 +603,194 @@ impl Server {
             };
             let response = handler(params)?;
             into_params(&response).or_else(|v| encode_fail(&v))
-        });
+        })
     }
 
-    pub fn register_simple<'a, K, Treq, Tres, Thandler>(&mut self, name: K, handler: Thandler)
+    pub fn register_simple<'a, K, Treq, Tres, Thandler>(
+        &mut self,
+        name: K,
+        handler: Thandler,
+    ) -> Registration<'_>
     where
         K: Into<String>,
         Treq: Deserialize<'a>,
         Tres: Serialize,
         Thandler: Fn(Treq) -> std::result::Result<Tres, Fault> + Send + Sync + 'static,
     {
-        self.register(name, handler, on_encode_fail, on_decode_fail);
+        let codes = self.fault_codes;
+        self.register(
+            name,
+            handler,
+            move |err| encode_fault(codes, err),
+            move |err| decode_fault(codes, err),
+        )
+    }
+
+    /// Like `register_simple`, for handlers failing with any error that
+    /// implements `IntoFault`, such as `anyhow::Error` with the `anyhow`
+    /// feature, so that they can use `?` without mapping errors to faults.
+    pub fn register_fallible<'a, K, Treq, Tres, Terr, Thandler>(
+        &mut self,
+        name: K,
+        handler: Thandler,
+    ) -> Registration<'_>
+    where
+        K: Into<String>,
+        Treq: Deserialize<'a>,
+        Tres: Serialize,
+        Terr: IntoFault,
+        Thandler: Fn(Treq) -> std::result::Result<Tres, Terr> + Send + Sync + 'static,
+    {
+        self.register_simple(name, move |params| {
+            handler(params).map_err(IntoFault::into_fault)
+        })
+    }
+
+    /// Registers a function taking each parameter of `name` as an argument
+    /// of its own, as in `server.register_fn("add", |a: i32, b: i32| ...)`.
+    ///
+    /// Calls with a parameter that does not decode into its argument are
+    /// answered with an invalid params fault.
+    pub fn register_fn<K, Args, T>(&mut self, name: K, handler: T) -> Registration<'_>
+    where
+        K: Into<String>,
+        T: FnHandler<Args>,
+    {
+        let codes = self.fault_codes;
+        self.register_value(name, move |params| {
+            let response = match handler.call_with(params) {
+                Ok(response) => response.map_err(IntoFault::into_fault)?,
+                Err(err) => return decode_fault(codes, &err),
+            };
+            into_params(&response).or_else(|err| encode_fault(codes, &err))
+        })
+    }
+
+    /// Registers a handler for a method taking named parameters, passed as
+    /// the members of a single struct parameter and decoded into `Treq`.
+    ///
+    /// Calls with any other parameters are answered with an invalid params
+    /// fault. `Client::call_named` makes such calls.
+    pub fn register_named<K, Treq, Tres, Terr, Thandler>(
+        &mut self,
+        name: K,
+        handler: Thandler,
+    ) -> Registration<'_>
+    where
+        K: Into<String>,
+        Treq: DeserializeOwned,
+        Tres: Serialize,
+        Terr: IntoFault,
+        Thandler: Fn(Treq) -> std::result::Result<Tres, Terr> + Send + Sync + 'static,
+    {
+        let codes = self.fault_codes;
+        self.register_value(name, move |params| {
+            let args = match from_named(params) {
+                Ok(args) => args,
+                Err(err) => return decode_fault(codes, &err),
+            };
+            let response = handler(args).map_err(IntoFault::into_fault)?;
+            into_params(&response).or_else(|err| encode_fault(codes, &err))
+        })
+    }
+
+    /// Registers `handler` as a job method, which answers with a job id at
+    /// once and runs the handler in the background, along with the methods
+    /// `name.status` and `name.result` to follow the job. See the `jobs`
+    /// module.
+    pub fn register_job<K, T>(&mut self, name: K, handler: T) -> Registration<'_>
+    where
+        K: Into<String>,
+        T: Fn(Vec<Value>) -> Response + Send + Sync + 'static,
+    {
+        let name = name.into();
+        let codes = self.fault_codes;
+        let unknown =
+            move |id: &str| Fault::new(codes.invalid_params(), format!("No job {:?}", id));
+
+        let jobs = self.jobs.clone();
+        self.register_simple(format!("{}.status", name), move |id: String| {
+            match jobs.is_done(&id) {
+                Some(true) => Ok("done"),
+                Some(false) => Ok("running"),
+                None => Err(unknown(&id)),
+            }
+        })
+        .with_help(format!("Whether {} job is \"running\" or \"done\".", name))
+        .with_signature(vec!["string", "string"]);
+
+        let jobs = self.jobs.clone();
+        self.register_value(format!("{}.result", name), move |params| {
+            let id: String = match from_params(params) {
+                Ok(id) => id,
+                Err(err) => return decode_fault(codes, &err),
+            };
+            match jobs.take(&id) {
+                Some(Some(response)) => response,
+                Some(None) => Err(Fault::new(codes.invalid_params(), "Job is still running")),
+                None => Err(unknown(&id)),
+            }
+        })
+        .with_help(format!(
+            "What a finished {} job returned. Each result is given once.",
+            name
+        ));
+
+        let jobs = self.jobs.clone();
+        let handler = Arc::new(handler);
+        let panicked = Err(Fault::new(
+            codes.internal_error(),
+            "Internal error while running job",
+        ));
+        self.register_value(name, move |params| {
+            let handler = Arc::clone(&handler);
+            let id = jobs.start(move || handler(params), panicked.clone());
+            Ok(vec![Value::String(id)])
+        })
+    }
+
+    /// Registers a handler returning a future, for methods waiting on other
+    /// async work. Only a server bound with `bind_async` awaits it; the other
+    /// backends answer calls to it with an internal error fault.
+    #[cfg(feature = "tokio")]
+    pub fn register_async<K, T>(&mut self, name: K, handler: T) -> Registration<'_>
+    where
+        K: Into<String>,
+        T: Fn(Vec<Value>) -> HandlerFuture + Send + Sync + 'static,
+    {
+        let name = name.into();
+        let code = self.fault_codes.internal_error();
+        let message = format!("Method {} is only served asynchronously", name);
+        self.register_value(name.clone(), move |_| {
+            Err(Fault::new(code, message.clone()))
+        });
+        self.serves_async = true;
+        let method = self
+            .handlers
+            .get_mut(&name)
+            .expect("method was just registered");
+        method.future = Some(Box::new(handler));
+        Registration {
+            info: &mut method.info,
+        }
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) fn has_async_handlers(&self) -> bool {
+        self.serves_async
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) fn async_handler(&self, name: &str) -> Option<&AsyncHandler> {
+        self.handlers
+            .get(name)
+            .and_then(|method| method.future.as_ref())
+    }
+
+    /// Sets how long results of finished jobs are kept waiting to be
+    /// fetched. Defaults to ten minutes.
+    pub fn set_job_retention(&mut self, retention: Duration) {
+        self.jobs.set_retention(retention);
     }
 
     pub fn set_on_missing<T>(&mut self, handler: T)
@@ -93,65 +798,1819 @@ impl Server {
         T: Fn(Vec<Value>) -> Response + Send + Sync + 'static,
     {
         self.on_missing_method = Box::new(handler);
+        self.missing_method = None;
     }
 
-    pub fn bind(
-        self,
-        uri: &std::net::SocketAddr,
-    ) -> Result<BoundServer<impl Fn(&rouille::Request) -> rouille::Response + Send + Sync + 'static>>
+    /// Runs `middleware` around every call answered, inside the middleware
+    /// added before it, including calls answered from the cache or with the
+    /// fault for unknown methods. Calls in a `system.multicall` go through
+    /// it one by one, after the multicall itself. Methods registered with
+    /// `register_async` are not run through it.
+    pub fn add_middleware<T>(&mut self, middleware: T)
+    where
+        T: Fn(&HttpRequest, Call, Next) -> Response + Send + Sync + 'static,
     {
-        rouille::Server::new(uri, move |req| self.handle_outer(req))
-            .map_err(|err| ErrorKind::BindFail(err.description().into()).into())
-            .map(BoundServer::new)
+        self.middleware.push(Box::new(middleware));
     }
 
-    fn handle_outer(&self, request: &rouille::Request) -> rouille::Response {
-        use super::xmlfmt::value::ToXml;
+    // The request `build` makes, for the middleware to be handed, or `None`
+    // if there is no middleware to hand it to.
+    pub(crate) fn http_request<F: FnOnce() -> HttpRequest>(&self, build: F) -> Option<HttpRequest> {
+        if self.middleware.is_empty() {
+            None
+        } else {
+            Some(build())
+        }
+    }
 
-        let body = match request.data() {
-            Some(data) => data,
-            None => return rouille::Response::empty_400(),
-        };
+    /// Sets the XML dialect of replies, for clients that only read some of
+    /// XML-RPC.
+    pub fn set_wire_profile(&mut self, profile: WireProfile) {
+        if let Some(canned) = self.missing_method.take() {
+            let response = canned.response.clone();
+            self.missing_method = Some(Arc::new(Serialized::new(response, &profile)));
+        }
+        self.wire_profile = profile;
+    }
+
+    pub(crate) fn wire_profile(&self) -> &WireProfile {
+        &self.wire_profile
+    }
+
+    /// Sets how the UTC offsets of datetimes in call parameters and results
+    /// are handled. Calls failing the options are answered with a fault.
+    #[cfg(feature = "chrono")]
+    pub fn set_datetimes(&mut self, options: DateTimeOptions) {
+        self.datetimes = options;
+    }
+
+    /// Answers repeated calls to `name` with the same parameters from a cache
+    /// for `ttl`, without calling the handler again. Faults are not cached.
+    ///
+    /// Only XML-RPC requests read by the server's own transports, or passed
+    /// to `handle_body`, are answered from the cache.
+    pub fn set_cacheable<K: Into<String>>(&mut self, name: K, ttl: Duration) {
+        self.cache.set_ttl(name.into(), ttl);
+    }
+
+    pub fn set_panic_policy(&mut self, policy: PanicPolicy) {
+        self.panic_policy = policy;
+    }
+
+    /// Called with the method name and panic payload whenever a handler
+    /// panics, before the panic policy is applied.
+    pub fn set_on_panic<T>(&mut self, handler: T)
+    where
+        T: Fn(&str, &(dyn Any + Send)) + Send + Sync + 'static,
+    {
+        self.on_panic = Some(Box::new(handler));
+    }
+
+    /// Called with the timings of every XML-RPC request once it has been
+    /// answered, including requests that failed to parse. JSON-RPC requests
+    /// are not timed.
+    pub fn set_on_timings<T>(&mut self, handler: T)
+    where
+        T: Fn(&Timings) + Send + Sync + 'static,
+    {
+        self.on_timings = Some(Box::new(handler));
+    }
+
+    pub(crate) fn report(&self, timings: &Timings) {
+        if let Some(ref on_timings) = self.on_timings {
+            on_timings(timings);
+        }
+    }
+
+    /// Serves Prometheus metrics on GET requests to `path`, e.g. `/metrics`.
+    pub fn set_metrics_path<P: Into<String>>(&mut self, path: P) {
+        self.metrics = Some(Metrics::new(path.into()));
+    }
+
+    /// Tells `observer` about every call dispatched and every connection
+    /// opened or closed, after the observers added before it.
+    pub fn add_observer<T: Observer + 'static>(&mut self, observer: T) {
+        self.observers.push(Box::new(observer));
+    }
+
+    pub(crate) fn connection_opened(&self) {
+        let open = self.open_connections.fetch_add(1, Ordering::SeqCst) + 1;
+        for observer in &self.observers {
+            observer.on_connections(open);
+        }
+    }
+
+    pub(crate) fn connection_closed(&self) {
+        let open = self.open_connections.fetch_sub(1, Ordering::SeqCst) - 1;
+        for observer in &self.observers {
+            observer.on_connections(open);
+        }
+    }
 
-        // TODO: use the right error type
-        let call: Call = match parse::call(body) {
-            Ok(data) => data,
-            Err(_err) => return rouille::Response::empty_400(),
+    // Counts a call answered with `response` in the metrics and tells the
+    // observers about it.
+    pub(crate) fn record(&self, method: &str, elapsed: Duration, response: &Response) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.record(method, elapsed, response.is_err());
+        }
+        let outcome = match *response {
+            Ok(_) => Outcome::Success,
+            Err(ref fault) => Outcome::Fault(fault.code),
         };
-        let res = self.handle(call);
-        let body = res.to_xml();
-        rouille::Response::from_data("text/xml", body)
+        for observer in &self.observers {
+            observer.on_call(method, elapsed, outcome);
+        }
     }
 
-    fn handle(&self, req: Call) -> Response {
-        self.handlers
-            .get(&req.name)
-            .unwrap_or(&self.on_missing_method)(req.params)
+    /// Handles connections on `threads` worker threads instead of a thread
+    /// each. Up to `queue` accepted connections wait for a free worker;
+    /// beyond that, accepting pauses until one frees up.
+    ///
+    /// Applies to `serve_scgi` and the hyper backend. Hyper's workers accept
+    /// connections themselves, so its waiting connections queue in the
+    /// listen backlog instead. The rouille backend keeps its own pool.
+    pub fn set_workers(&mut self, threads: usize, queue: usize) {
+        self.workers = Some((threads, queue));
     }
-}
 
-pub struct BoundServer<F>
-where
-    F: Send + Sync + 'static + Fn(&rouille::Request) -> rouille::Response,
-{
-    server: rouille::Server<F>,
-    // server: hyper::Server<NewService, hyper::Body>,
-}
+    pub(crate) fn workers(&self) -> Option<(usize, usize)> {
+        self.workers
+    }
 
-impl<F> BoundServer<F>
-where
-    F: Send + Sync + 'static + Fn(&rouille::Request) -> rouille::Response,
-{
-    fn new(server: rouille::Server<F>) -> Self {
-        Self { server }
+    /// Accepts SCGI connections on `threads` threads at once. The hyper
+    /// backend already accepts on each of its worker threads.
+    pub fn set_accept_threads(&mut self, threads: usize) {
+        self.accept_threads = threads.max(1);
     }
 
-    pub fn local_addr(&self) -> std::net::SocketAddr {
-        self.server.server_addr()
+    pub(crate) fn accept_threads(&self) -> usize {
+        self.accept_threads
     }
 
-    pub fn run(self) {
-        self.server.run()
+    /// How many connections the OS queues before they are accepted, for
+    /// listeners bound by `bind_hyper`, `bind_hyper_tls` and `listen`.
+    pub fn set_listen_backlog(&mut self, backlog: i32) {
+        self.listen_backlog = backlog;
+    }
+
+    /// Binds with `SO_REUSEPORT`, so that several processes or servers can
+    /// listen on the same address and the kernel spreads connections across
+    /// them. Applies to the same listeners as `set_listen_backlog`. Binding
+    /// fails on platforms without it, such as Windows.
+    pub fn set_reuse_port(&mut self, reuse: bool) {
+        self.reuse_port = reuse;
+    }
+
+    /// Gives the socket file `bind_unix` creates the permission bits `mode`,
+    /// e.g. `0o660` to only let its group connect. It is created with the
+    /// process umask applied until then.
+    #[cfg(all(unix, feature = "hyper-server"))]
+    pub fn set_unix_mode(&mut self, mode: u32) {
+        self.unix_mode = Some(mode);
+    }
+
+    /// Binds a listener with the configured backlog and port reuse, and with
+    /// Nagle's algorithm off, e.g. for `serve_scgi`.
+    pub fn listen(&self, addr: &SocketAddr) -> Result<TcpListener> {
+        self.bind_socket(addr).map_err(|source| Error::Bind {
+            addr: *addr,
+            source,
+        })
+    }
+
+    fn bind_socket(&self, addr: &SocketAddr) -> io::Result<TcpListener> {
+        let socket = Socket::new(
+            Domain::for_address(*addr),
+            Type::STREAM,
+            Some(Protocol::TCP),
+        )?;
+        // As the standard library does, so restarts can rebind at once.
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+        #[cfg(all(
+            unix,
+            not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))
+        ))]
+        socket.set_reuse_port(self.reuse_port)?;
+        #[cfg(not(all(
+            unix,
+            not(any(target_os = "solaris", target_os = "illumos", target_os = "cygwin"))
+        )))]
+        {
+            if self.reuse_port {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "SO_REUSEPORT is not supported on this platform",
+                ));
+            }
+        }
+        // Calls are small exchanges that Nagle's algorithm only delays.
+        // Connections accepted by the listener inherit the option.
+        socket.set_tcp_nodelay(true)?;
+        socket.bind(&(*addr).into())?;
+        socket.listen(self.listen_backlog)?;
+        Ok(socket.into())
+    }
+
+    /// Caps the request body bytes held in memory at once, across all
+    /// requests. A request that would go over is answered with 503 Service
+    /// Unavailable, as soon as its Content-Length shows it won't fit or,
+    /// without one, once it has read past what is left.
+    ///
+    /// Applies to every backend. The Tokio backend counts requests as their
+    /// bytes are buffered, heads included, and refuses the one that goes
+    /// over with 503 and closes its connection.
+    pub fn set_memory_budget(&mut self, bytes: usize) {
+        self.memory_budget = Some(MemoryBudget::new(bytes));
+    }
+
+    // Holds `bytes` the Tokio backend has buffered against the memory
+    // budget, or returns false if they do not fit.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn hold(&self, bytes: usize) -> bool {
+        self.memory_budget
+            .as_ref()
+            .is_none_or(|budget| budget.take(bytes))
+    }
+
+    // Gives back `bytes` held with `hold`.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn release(&self, bytes: usize) {
+        if let Some(ref budget) = self.memory_budget {
+            budget.give_back(bytes);
+        }
+    }
+
+    /// Holds requests to `limits`, refusing those that go past them.
+    ///
+    /// Applies to the rouille and hyper backends, `bind_async` and
+    /// `serve_scgi`, whose heads are bounded by the SCGI netstring instead
+    /// of the header limits. Parsing limits also apply to `handle_body`.
+    pub fn set_limits(&mut self, limits: ServerLimits) {
+        self.limits = limits;
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) fn limits(&self) -> &ServerLimits {
+        &self.limits
+    }
+
+    /// Compresses XML replies of at least `min_size` bytes with gzip or
+    /// deflate, for clients whose `Accept-Encoding` allows either. Smaller
+    /// replies gain too little to be worth the time.
+    ///
+    /// Applies to every backend. With hyper, replies are then serialized
+    /// whole before they are sent, rather than sent as they are written.
+    #[cfg(feature = "gzip")]
+    pub fn set_compression(&mut self, min_size: usize) {
+        self.compression = Some(min_size);
+    }
+
+    /// Writes XML replies in `encoding` rather than in UTF-8, for clients
+    /// that read nothing else. Characters the encoding lacks are written as
+    /// character references.
+    ///
+    /// Applies to the rouille, hyper, Tokio and SCGI backends. With hyper,
+    /// replies are then serialized whole before they are sent.
+    #[cfg(feature = "encoding")]
+    pub fn set_output_encoding(&mut self, encoding: Option<&'static Encoding>) {
+        self.output_encoding = encoding;
+    }
+
+    // Whether replies are changed after they are serialized, so that they
+    // cannot be sent as they are written.
+    #[cfg(feature = "hyper-server")]
+    fn rewrites_replies(&self) -> bool {
+        #[cfg(feature = "gzip")]
+        {
+            if self.compression.is_some() {
+                return true;
+            }
+        }
+        #[cfg(feature = "encoding")]
+        {
+            if self.output_encoding.is_some() {
+                return true;
+            }
+        }
+        false
+    }
+
+    // The body to send for the XML reply `xml`, in the output encoding and
+    // compressed for a client whose `Accept-Encoding` is `accept`, with the
+    // name of the content coding if any. `None` sends `xml` as it is.
+    pub(crate) fn encode_reply(
+        &self,
+        accept: Option<&str>,
+        xml: &[u8],
+    ) -> Option<(Vec<u8>, Option<&'static str>)> {
+        let transcoded = self.transcode(xml);
+        let body = transcoded.as_deref().unwrap_or(xml);
+        match self.compress(accept, body) {
+            Some((coding, compressed)) => Some((compressed, Some(coding))),
+            None => transcoded.map(|body| (body, None)),
+        }
+    }
+
+    #[cfg(feature = "encoding")]
+    fn transcode(&self, xml: &[u8]) -> Option<Vec<u8>> {
+        let encoding = self.output_encoding?;
+        Some(charset::encode(&String::from_utf8_lossy(xml), encoding))
+    }
+
+    #[cfg(not(feature = "encoding"))]
+    fn transcode(&self, _xml: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+
+    // `xml` compressed for a client whose `Accept-Encoding` is `accept`,
+    // with the name of the coding, or `None` to send it as it is.
+    #[cfg(feature = "gzip")]
+    fn compress(&self, accept: Option<&str>, xml: &[u8]) -> Option<(&'static str, Vec<u8>)> {
+        let min_size = self.compression?;
+        if xml.len() < min_size {
+            return None;
+        }
+        let coding = Coding::accepted(accept?)?;
+        Some((coding.name(), coding.encode(xml)))
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    fn compress(&self, _accept: Option<&str>, _xml: &[u8]) -> Option<(&'static str, Vec<u8>)> {
+        None
+    }
+
+    /// Holds XML-RPC requests to the HTTP the specification asks for.
+    /// Requests with other methods than POST are answered with 405 Method
+    /// Not Allowed, except OPTIONS, which is answered with the methods
+    /// allowed. Bodies whose Content-Type is not `text/xml` or
+    /// `application/xml` are answered with 415 Unsupported Media Type.
+    /// Replies name their charset, as `text/xml; charset=utf-8`.
+    ///
+    /// Off by default, as some clients send other content types. Applies
+    /// to the rouille, hyper, Tokio and SCGI backends, but not to routes,
+    /// metrics, the method listing or JSON-RPC.
+    pub fn set_strict_http(&mut self, strict: bool) {
+        self.strict_http = strict;
+    }
+
+    // Under strict HTTP, the status to answer an XML-RPC request with
+    // instead of reading it: 204 for OPTIONS, 405 for methods other than
+    // POST and 415 for content types other than XML.
+    pub(crate) fn refuses_http(
+        &self,
+        method: &str,
+        path: &str,
+        content_type: Option<&str>,
+    ) -> Option<u16> {
+        if !self.strict_http || self.is_jsonrpc(method == "POST", path) {
+            return None;
+        }
+        match method {
+            "OPTIONS" => return Some(204),
+            "POST" => {}
+            _ => return Some(405),
+        }
+        let media_type = content_type
+            .and_then(|value| value.split(';').next())
+            .unwrap_or("")
+            .trim();
+        if media_type.eq_ignore_ascii_case("text/xml")
+            || media_type.eq_ignore_ascii_case("application/xml")
+        {
+            None
+        } else {
+            Some(415)
+        }
+    }
+
+    // The Content-Type of XML-RPC replies.
+    pub(crate) fn xml_content_type(&self) -> Cow<'static, str> {
+        #[cfg(feature = "encoding")]
+        {
+            if let Some(encoding) = self.output_encoding {
+                let name = encoding.output_encoding().name();
+                return Cow::Owned(format!("text/xml; charset={}", name));
+            }
+        }
+        if self.strict_http {
+            Cow::Borrowed("text/xml; charset=utf-8")
+        } else {
+            Cow::Borrowed("text/xml")
+        }
+    }
+
+    /// Parses request bodies as they are read, instead of reading each one
+    /// whole first, so that calls with large `<base64>` values are not held
+    /// in memory twice over. Parse faults then no longer say where a request
+    /// is broken.
+    pub fn set_streaming_parse(&mut self, enabled: bool) {
+        self.streaming_parse = enabled;
+    }
+
+    /// Closes connections that have not sent the whole head of their request
+    /// within `timeout` of the server starting to read it.
+    ///
+    /// Applies to `bind_hyper` and `serve_scgi`. With hyper, only the first
+    /// request of a connection is held to it; those after it on a kept-alive
+    /// connection are bounded by hyper's keep-alive timeout.
+    pub fn set_head_timeout(&mut self, timeout: Duration) {
+        self.head_limits.timeout = Some(timeout);
+    }
+
+    /// Closes new connections from an IP address that already has `max`
+    /// connections whose request head is still coming in, so that a single
+    /// slow client cannot take up every thread.
+    ///
+    /// Applies to `bind_hyper` and `serve_scgi`, like `set_head_timeout`.
+    pub fn set_max_pending_heads_per_ip(&mut self, max: usize) {
+        self.head_limits.per_ip = Some(max);
+    }
+
+    /// Closes connections that have not sent a whole request head within
+    /// `timeout` of opening or of the response to their last request, so
+    /// clients holding kept-alive connections they no longer call on do
+    /// not tie up threads.
+    ///
+    /// Applies to the hyper backends, which otherwise close kept-alive
+    /// connections after 5 seconds without a byte. With `bind_hyper_tls`,
+    /// `timeout` bounds each read of a head rather than the whole wait.
+    /// Also applies to `bind_async`, whose runtime must then have its time
+    /// driver enabled, and which otherwise keeps connections open for as
+    /// long as clients do.
+    pub fn set_idle_timeout(&mut self, timeout: Duration) {
+        self.head_limits.idle = Some(timeout);
+    }
+
+    pub(crate) fn head_limits(&self) -> HeadLimits {
+        HeadLimits {
+            timeout: self.head_limits.timeout.or(self.limits.head_timeout),
+            ..self.head_limits
+        }
+    }
+
+    /// Only answers peers that `filter` permits.
+    ///
+    /// `bind_hyper`, `bind_hyper_tls`, `bind_async` and `serve_scgi` close connections
+    /// from other peers as soon as they are accepted, `bind_hyper_tls`
+    /// after the TLS handshake. The rouille backend has no say in which
+    /// connections it accepts, so it answers their requests with 403
+    /// Forbidden. With SCGI, the peer is the web server, not its client.
+    pub fn set_ip_filter(&mut self, filter: IpFilter) {
+        self.ip_filter = Some(filter);
+    }
+
+    pub(crate) fn permits(&self, ip: IpAddr) -> bool {
+        self.ip_filter
+            .as_ref()
+            .is_none_or(|filter| filter.permits(ip))
+    }
+
+    /// Requires every HTTP request to carry credentials `auth` accepts.
+    /// Requests without them are answered with 401 Unauthorized before
+    /// their body is read.
+    ///
+    /// Applies to the rouille, hyper and Tokio backends and to `serve_scgi`.
+    pub fn set_auth(&mut self, auth: Auth) {
+        self.auth = Some(auth);
+    }
+
+    // The `WWW-Authenticate` challenge to answer a request with, if its
+    // credentials are missing or wrong.
+    pub(crate) fn challenge<F>(&self, header: F) -> Option<String>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        match self.auth {
+            Some(ref auth) if !auth.check(header) => Some(auth.challenge()),
+            _ => None,
+        }
+    }
+
+    /// Logs every HTTP request once it has been answered, JSON-RPC and
+    /// metrics requests included.
+    ///
+    /// Applies to the rouille, hyper and Tokio backends and to `serve_scgi`.
+    pub fn set_access_log(&mut self, log: AccessLog) {
+        self.access_log = Some(log);
+    }
+
+    pub(crate) fn access_log(&self) -> Option<&AccessLog> {
+        self.access_log.as_ref()
+    }
+
+    /// Copies the headers named, like `X-Request-Id`, from every HTTP
+    /// request that has them onto its response.
+    ///
+    /// Applies to the rouille, hyper and Tokio backends and to `serve_scgi`.
+    pub fn set_echoed_headers<I>(&mut self, names: I)
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.echoed_headers = names.into_iter().map(Into::into).collect();
+    }
+
+    // The echoed headers a request has, given a lookup of its headers by
+    // name.
+    pub(crate) fn echoed<F>(&self, header: F) -> Vec<(String, String)>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        self.echoed_headers
+            .iter()
+            .filter_map(|name| header(name).map(|value| (name.clone(), value)))
+            .collect()
+    }
+
+    /// Requires every request body to be signed with one of the keys of
+    /// `verifier`. Bodies are read in full before being parsed, and those
+    /// without a valid signature are answered with 403 Forbidden.
+    ///
+    /// Applies to the rouille, hyper and Tokio backends and to `serve_scgi`.
+    #[cfg(feature = "hmac")]
+    pub fn set_verifier(&mut self, verifier: Verifier) {
+        self.verifier = Some(verifier);
+    }
+
+    // The header requests must carry a signature in, if any.
+    pub(crate) fn signature_header(&self) -> Option<&str> {
+        #[cfg(feature = "hmac")]
+        {
+            if let Some(ref verifier) = self.verifier {
+                return Some(verifier.header());
+            }
+        }
+        None
+    }
+
+    // Reads `body` in full and checks it against `signature` if requests
+    // must be signed.
+    #[allow(unused_variables)]
+    pub(crate) fn verify<R: io::Read>(&self, signature: Option<&str>, body: &mut R) -> Verified {
+        #[cfg(feature = "hmac")]
+        {
+            if let Some(ref verifier) = self.verifier {
+                let mut bytes = Vec::new();
+                let valid = body.read_to_end(&mut bytes).is_ok()
+                    && signature.is_some_and(|signature| verifier.verify(signature, &bytes));
+                if !valid {
+                    return Verified::Invalid;
+                }
+                return Verified::Valid(io::Cursor::new(bytes));
+            }
+        }
+        Verified::Unchecked
+    }
+
+    /// A handle to stop the server with once it is bound, draining the
+    /// requests it is answering.
+    ///
+    /// Applies to the rouille and hyper backends and to `serve_scgi`.
+    pub fn shutdown_handle(&mut self) -> ShutdownHandle {
+        self.shutdown
+            .get_or_insert_with(ShutdownHandle::new)
+            .clone()
+    }
+
+    pub(crate) fn shutdown(&self) -> Option<&ShutdownHandle> {
+        self.shutdown.as_ref()
+    }
+
+    // Closes `listener` when the server is drained.
+    pub(crate) fn drains(&self, listener: &TcpListener) -> Result<()> {
+        match self.shutdown {
+            Some(ref shutdown) => shutdown.add_listener(listener).map_err(Error::Io),
+            None => Ok(()),
+        }
+    }
+
+    /// Answers requests that send `Expect: 100-continue` and declare a body
+    /// longer than `ServerLimits::max_body` with 417 Expectation Failed
+    /// rather than 413 Payload Too Large. Either way the body is not asked
+    /// for, but some clients only give up on the body on a 417.
+    ///
+    /// Off by default. Clients waiting for `100 Continue` are sent it before
+    /// their body is read, by rouille, hyper and the Tokio backend alike.
+    pub fn set_expectation_failed(&mut self, enabled: bool) {
+        self.expectation_failed = enabled;
+    }
+
+    // The status to refuse a request whose body is too long with, given
+    // its `Expect` header.
+    pub(crate) fn too_large(&self, expect: Option<&str>) -> u16 {
+        if self.expectation_failed && expects_continue(expect) {
+            417
+        } else {
+            413
+        }
+    }
+
+    // `body`, charged against the memory budget and capped at the longest
+    // body allowed, or the status to refuse it with: 413 if its `declared`
+    // length is too long, 503 if the budget is spent.
+    pub(crate) fn admit<R: io::Read>(
+        &self,
+        declared: Option<u64>,
+        body: R,
+    ) -> std::result::Result<Charged<'_, R>, u16> {
+        if self.limits.refuses_body(declared) {
+            return Err(413);
+        }
+        let body = match self.memory_budget {
+            Some(ref budget) => budget.admit(declared, body).ok_or(503u16)?,
+            None => Charged::unbounded(body),
+        };
+        Ok(body.capped(self.limits.max_body))
+    }
+
+    /// Also serves the handlers as JSON-RPC 2.0 on POST requests to `path`.
+    #[cfg(feature = "jsonrpc")]
+    pub fn set_jsonrpc_path<P: Into<String>>(&mut self, path: P) {
+        self.jsonrpc_path = Some(path.into());
+    }
+
+    /// Only serves XML-RPC on requests to `path`, e.g. `/RPC2`. Requests to
+    /// paths nothing else is served on are answered with 404 Not Found.
+    ///
+    /// By default XML-RPC is served on every path.
+    pub fn set_rpc_path<P: Into<String>>(&mut self, path: P) {
+        self.rpc_path = Some(path.into());
+    }
+
+    /// Answers requests to `path` with `handler`, whatever their HTTP
+    /// method, as in
+    /// `server.add_route("/healthz", |_| HttpResponse::text(200, "ok"))`.
+    /// The query is not part of the path matched.
+    ///
+    /// Routes are only answered once the request has been let through by
+    /// `set_auth`, and are not limited to `set_rpc_path`.
+    pub fn add_route<P, T>(&mut self, path: P, handler: T)
+    where
+        P: Into<String>,
+        T: Fn(&HttpRequest) -> HttpResponse + Send + Sync + 'static,
+    {
+        self.routes.insert(path.into(), Box::new(handler));
+    }
+
+    // The answer to a request for `path` that is not for XML-RPC: from its
+    // route, or 404 if XML-RPC is served elsewhere and nothing else is
+    // served on `path`. `None` for requests left to the rest of the server.
+    pub(crate) fn route<F>(&self, path: &str, request: F) -> Option<HttpResponse>
+    where
+        F: FnOnce() -> HttpRequest,
+    {
+        if let Some(route) = self.routes.get(path) {
+            return Some(route(&request()));
+        }
+        let rpc_path = self.rpc_path.as_ref()?;
+        #[cfg(feature = "jsonrpc")]
+        let served = self.jsonrpc_path.as_ref().is_some_and(|p| p == path);
+        #[cfg(not(feature = "jsonrpc"))]
+        let served = false;
+        let served = served
+            || path == rpc_path
+            || self
+                .metrics
+                .as_ref()
+                .is_some_and(|metrics| metrics.path == path);
+        if served {
+            None
+        } else {
+            Some(HttpResponse::not_found())
+        }
+    }
+
+    pub fn bind(
+        mut self,
+        uri: &std::net::SocketAddr,
+    ) -> Result<BoundServer<impl Fn(&rouille::Request) -> rouille::Response + Send + Sync + 'static>>
+    {
+        let shutdown = self.shutdown_handle();
+        rouille::Server::new(uri, move |req| self.handle_rouille(req))
+            .map_err(|err| Error::Bind {
+                addr: *uri,
+                source: match err.downcast::<io::Error>() {
+                    Ok(err) => *err,
+                    Err(err) => io::Error::other(err),
+                },
+            })
+            .map(|server| BoundServer::new(server, shutdown))
+    }
+
+    /// Binds to `addr` for serving on Tokio, once `run` is polled on the
+    /// result. Settings for the hyper backend alone, like head limits and
+    /// workers, do not apply.
+    #[cfg(feature = "tokio")]
+    pub fn bind_async(self, addr: &SocketAddr) -> Result<AsyncBoundServer> {
+        let listener = self.listen(addr)?;
+        AsyncBoundServer::new(self, listener)
+    }
+
+    /// Serves the handlers with hyper instead of rouille.
+    #[cfg(feature = "hyper-server")]
+    pub fn bind_hyper(self, uri: &std::net::SocketAddr) -> Result<hyper::server::Listening> {
+        let listener = self.listen(uri)?;
+        self.drains(&listener)?;
+        let listener = hyper::net::HttpListener::from(listener);
+        let listener = FilteredListener::new(listener, self.ip_filter.clone());
+        let limits = self.head_limits();
+        if limits.timeout.is_none() && limits.per_ip.is_none() && limits.idle.is_none() {
+            return self
+                .serve_hyper(listener)
+                .map_err(|err| bind_error(uri, err));
+        }
+        let listener = GuardedListener::new(listener, PendingHeads::new(limits));
+        self.serve_hyper(listener)
+            .map_err(|err| bind_error(uri, err))
+    }
+
+    #[cfg(feature = "hyper-server")]
+    fn serve_hyper<L>(self, listener: L) -> hyper::Result<hyper::server::Listening>
+    where
+        L: hyper::net::NetworkListener + Send + 'static,
+    {
+        let listener = DrainingListener::new(listener, self.shutdown.clone());
+        let mut server = hyper::Server::new(listener);
+        if let Some(idle) = self.head_limits.idle {
+            server.keep_alive(Some(idle));
+        }
+        match self.workers {
+            Some((threads, _)) => server.handle_threads(self, threads),
+            None => server.handle(self),
+        }
+    }
+
+    /// Serves the handlers with hyper on the Unix socket at `path`, for
+    /// clients on the same host, such as `Client::unix(path)`. A socket
+    /// file left behind by a server that is gone is replaced; one a server
+    /// still listens on is not.
+    ///
+    /// Peers have no IP address, so `set_ip_filter` does not apply, and
+    /// they show as `0.0.0.0` to middleware and in access logs.
+    /// `set_max_pending_heads_per_ip` counts all of them as one peer.
+    #[cfg(all(unix, feature = "hyper-server"))]
+    pub fn bind_unix<P: AsRef<Path>>(self, path: P) -> Result<UnixListening> {
+        let path = path.as_ref();
+        let listener = UnixHttpListener::bind(path, self.unix_mode).map_err(Error::Io)?;
+        let limits = self.head_limits();
+        let listening =
+            if limits.timeout.is_none() && limits.per_ip.is_none() && limits.idle.is_none() {
+                self.serve_hyper(listener)
+            } else {
+                self.serve_hyper(GuardedListener::new(listener, PendingHeads::new(limits)))
+            };
+        Ok(UnixListening {
+            listening: listening.map_err(Error::from)?,
+            path: path.to_owned(),
+        })
+    }
+
+    /// Serves the handlers with hyper over TLS. `TlsAcceptor::from_files`
+    /// sets up the usual case of one certificate and key.
+    #[cfg(all(
+        feature = "hyper-server",
+        any(feature = "tls-native", feature = "tls-rustls")
+    ))]
+    pub fn bind_hyper_tls(
+        self,
+        uri: &std::net::SocketAddr,
+        tls: TlsAcceptor,
+    ) -> Result<hyper::server::Listening> {
+        let listener = self.listen(uri)?;
+        self.drains(&listener)?;
+        let listener = hyper::net::HttpListener::from(listener);
+        let listener = hyper::net::HttpsListener::with_listener(listener, tls);
+        let listener = FilteredListener::new(listener, self.ip_filter.clone());
+        self.serve_hyper(listener)
+            .map_err(|err| bind_error(uri, err))
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    pub(crate) fn is_jsonrpc(&self, is_post: bool, path: &str) -> bool {
+        is_post && self.jsonrpc_path.as_ref().is_some_and(|p| p == path)
+    }
+
+    #[cfg(not(feature = "jsonrpc"))]
+    fn is_jsonrpc(&self, _is_post: bool, _path: &str) -> bool {
+        false
+    }
+
+    pub(crate) fn metrics_page(&self, is_get: bool, path: &str) -> Option<String> {
+        match self.metrics {
+            Some(ref metrics) if is_get && path == metrics.path => {
+                Some(metrics.render(self.open_connections.load(Ordering::SeqCst)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Answers GET requests, other than for metrics, with an HTML page
+    /// listing the registered methods with their signatures and help, for
+    /// browsing during development. Off by default, as the page gives away
+    /// every method name.
+    pub fn set_method_listing(&mut self, enabled: bool) {
+        self.method_listing = enabled;
+    }
+
+    /// Answers `system.listMethods`, `system.methodHelp` and
+    /// `system.methodSignature` from what methods were registered with,
+    /// unless methods of those names are registered. Off by default, for
+    /// the same reason as `set_method_listing`.
+    pub fn set_introspection(&mut self, enabled: bool) {
+        self.introspection = enabled;
+    }
+
+    /// Answers calls to `system.multicall`, which carry several calls to
+    /// be answered together, unless a method of that name is registered.
+    /// Each call is dispatched as if made on its own, except that methods
+    /// registered with `register_async` are not awaited.
+    pub fn set_multicall(&mut self, enabled: bool) {
+        self.multicall = enabled;
+    }
+
+    // Whether `name` is answered by the server itself, for introspection.
+    fn introspects(&self, name: &str) -> bool {
+        self.introspection
+            && INTROSPECTION_METHODS.contains(&name)
+            && !self.handlers.contains_key(name)
+    }
+
+    fn multicalls(&self, name: &str) -> bool {
+        self.multicall && name == MULTICALL_METHOD && !self.handlers.contains_key(name)
+    }
+
+    // Whether calls to `name` reach a method rather than `on_missing_method`.
+    fn serves(&self, name: &str) -> bool {
+        self.handlers.contains_key(name) || self.introspects(name) || self.multicalls(name)
+    }
+
+    // Answers the calls carried by a call to `system.multicall`.
+    fn multicall(&self, params: Vec<Value>) -> Response {
+        let codes = self.fault_codes;
+        answer_multicall(params, codes.invalid_params(), |call| {
+            if call.name == MULTICALL_METHOD {
+                return Err(Fault::new(
+                    codes.invalid_params(),
+                    "Calls to system.multicall cannot be nested",
+                ));
+            }
+            self.handle(call)
+        })
+    }
+
+    // Answers `call` if it is to an introspection method.
+    fn introspect(&self, call: &Call) -> Option<Response> {
+        let name = call.name.as_str();
+        if !self.introspects(name) {
+            return None;
+        }
+        if name == INTROSPECTION_METHODS[0] {
+            let mut names = self.method_names();
+            names.extend(
+                INTROSPECTION_METHODS
+                    .iter()
+                    .filter(|name| self.introspects(name)),
+            );
+            if self.multicalls(MULTICALL_METHOD) {
+                names.push(MULTICALL_METHOD);
+            }
+            names.sort();
+            let names = names.into_iter().map(|name| Value::String(name.to_owned()));
+            return Some(Ok(vec![Value::Array(names.collect())]));
+        }
+        let method: String = match from_params(call.params.clone()) {
+            Ok(method) => method,
+            Err(err) => return Some(decode_fault(self.fault_codes, &err)),
+        };
+        let info = match self.method_info(&method) {
+            Some(info) => info,
+            None => {
+                return Some(Err(Fault::new(
+                    self.fault_codes.invalid_params(),
+                    format!("No method {:?}", method),
+                )))
+            }
+        };
+        let answer = if name == INTROSPECTION_METHODS[1] {
+            Value::String(info.help.clone())
+        } else if info.signatures.is_empty() {
+            // What other servers answer for methods without signatures.
+            Value::String(String::from("undef"))
+        } else {
+            let signatures = info.signatures.iter().map(|signature| {
+                Value::Array(signature.iter().cloned().map(Value::String).collect())
+            });
+            Value::Array(signatures.collect())
+        };
+        Some(Ok(vec![answer]))
+    }
+
+    pub(crate) fn listing_page(&self, is_get: bool) -> Option<String> {
+        if !is_get || !self.method_listing {
+            return None;
+        }
+        let methods = self
+            .method_names()
+            .into_iter()
+            .filter_map(|name| self.method_info(name).map(|info| (name, info)))
+            .collect::<Vec<_>>();
+        Some(listing::render(&methods))
+    }
+
+    // Counts `request` as in flight while it is answered, for draining.
+    fn handle_rouille(&self, request: &rouille::Request) -> rouille::Response {
+        let shutdown = match self.shutdown {
+            Some(ref shutdown) => shutdown,
+            None => return self.handle_outer(request),
+        };
+        // rouille has no connection to close, so a request that comes in
+        // once draining has begun is turned away instead.
+        if !shutdown.open(|| ()) {
+            return rouille::Response::text("").with_status_code(503);
+        }
+        let response = {
+            let _in_flight = shutdown.request();
+            self.handle_outer(request)
+        };
+        shutdown.closed();
+        response
+    }
+
+    fn handle_outer(&self, request: &rouille::Request) -> rouille::Response {
+        let received = SystemTime::now();
+        let started = Instant::now();
+        let trace = trace::Request::start(request.remote_addr());
+        let context = tracecontext::extract(|name| request.header(name).map(str::to_owned));
+        let http_request = self.http_request(|| rouille_request(request));
+        let mut method = None;
+        let respond = || self.respond(request, &mut method);
+        let respond = || middleware::scope(http_request, respond);
+        let mut response = trace.in_scope(|| tracecontext::scope(context, respond));
+        trace.finish(response.status_code);
+        for (name, value) in self.echoed(|name| request.header(name).map(str::to_owned)) {
+            response = response.with_unique_header(name, value);
+        }
+        if let Some(ref log) = self.access_log {
+            // Only the body's length is needed, but rouille hands it out
+            // with the body.
+            let body = std::mem::replace(&mut response.data, rouille::ResponseBody::empty());
+            let (body, length) = body.into_reader_and_size();
+            response.data = match length {
+                Some(length) => rouille::ResponseBody::from_reader_and_size(body, length),
+                None => rouille::ResponseBody::from_reader(body),
+            };
+            log.log(&AccessRecord {
+                peer: Some(request.remote_addr().ip()),
+                received,
+                http_method: request.method().to_owned(),
+                path: request.raw_url().to_owned(),
+                protocol: None,
+                method,
+                status: response.status_code,
+                request_bytes: request
+                    .header("Content-Length")
+                    .and_then(|length| length.trim().parse().ok()),
+                response_bytes: length.map_or(0, |length| length as u64),
+                duration: started.elapsed(),
+                referer: request.header("Referer").map(str::to_owned),
+                user_agent: request.header("User-Agent").map(str::to_owned),
+            });
+        }
+        response
+    }
+
+    // Sets `method` to the XML-RPC method called, if any.
+    fn respond(
+        &self,
+        request: &rouille::Request,
+        method: &mut Option<String>,
+    ) -> rouille::Response {
+        if !self.permits(request.remote_addr().ip()) {
+            return rouille::Response::text("").with_status_code(403);
+        }
+        let (count, bytes) = request
+            .headers()
+            .fold((0, 0), |(count, bytes), (name, value)| {
+                (count + 1, bytes + name.len() + value.len())
+            });
+        if self.limits.refuses_head(count, bytes) {
+            return refused(431);
+        }
+        if let Some(challenge) = self.challenge(|name| request.header(name).map(str::to_owned)) {
+            return rouille::Response::text("")
+                .with_status_code(401)
+                .with_unique_header("WWW-Authenticate", challenge);
+        }
+        if let Some(answer) = self.route(&request.url(), || rouille_request(request)) {
+            return rouille_response(answer);
+        }
+        if let Some(page) = self.metrics_page(request.method() == "GET", &request.url()) {
+            return rouille::Response::from_data(METRICS_CONTENT_TYPE, page);
+        }
+        if let Some(page) = self.listing_page(request.method() == "GET") {
+            return rouille::Response::from_data(listing::CONTENT_TYPE, page);
+        }
+        let content_type = request.header("Content-Type");
+        if let Some(status) = self.refuses_http(request.method(), &request.url(), content_type) {
+            let response = rouille::Response::text("").with_status_code(status);
+            return match status {
+                415 => response,
+                _ => response.with_additional_header("Allow", ALLOW),
+            };
+        }
+        let body = match request.data() {
+            Some(data) => data,
+            None => return rouille::Response::empty_400(),
+        };
+        let length = request
+            .header("Content-Length")
+            .and_then(|length| length.trim().parse().ok());
+        let mut body = match self.admit(length, body) {
+            Ok(body) => body,
+            Err(413) => return refused(self.too_large(request.header("Expect"))),
+            Err(status) => return refused(status),
+        };
+        let signature = self
+            .signature_header()
+            .and_then(|name| request.header(name));
+        let mut verified = self.verify(signature, &mut body);
+        if let Some(status) = body.refusal() {
+            return refused(status);
+        }
+        let input: &mut dyn io::Read = match verified {
+            Verified::Unchecked => &mut body,
+            Verified::Valid(ref mut bytes) => bytes,
+            Verified::Invalid => return rouille::Response::text("").with_status_code(403),
+        };
+        #[cfg(feature = "jsonrpc")]
+        {
+            if self.is_jsonrpc(request.method() == "POST", &request.url()) {
+                let reply = self.handle_jsonrpc(input);
+                if let Some(status) = body.refusal() {
+                    return refused(status);
+                }
+                return match reply {
+                    Some(reply) => rouille::Response::from_data(jsonrpc::CONTENT_TYPE, reply),
+                    None => rouille::Response::empty_204(),
+                };
+            }
+        }
+        let mut timings = Timings::default();
+        let charset = parse::content_charset(content_type);
+        let reply = self.answer(input, charset, &mut timings);
+        *method = timings.method;
+        if let Some(status) = body.refusal() {
+            return refused(status);
+        }
+        match reply {
+            Some(body) => {
+                let content_type = self.xml_content_type();
+                match self.encode_reply(request.header("Accept-Encoding"), body.as_bytes()) {
+                    Some((encoded, Some(coding))) => {
+                        rouille::Response::from_data(content_type, encoded)
+                            .with_additional_header("Content-Encoding", coding)
+                            .with_additional_header("Vary", "Accept-Encoding")
+                    }
+                    Some((encoded, None)) => rouille::Response::from_data(content_type, encoded),
+                    None => rouille::Response::from_data(content_type, body),
+                }
+            }
+            None => rouille::Response::empty_400(),
+        }
+    }
+
+    /// Answers a request body read from any transport, for network stacks the
+    /// server has no backend for. Returns the XML reply, or `None` if the
+    /// request should be answered with a bare 400.
+    ///
+    /// `charset` is the one the request's `Content-Type` names, if any. With
+    /// the `encoding` feature the body is read in it, as the HTTP backends
+    /// do; without it bodies are read as UTF-8.
+    ///
+    /// The crate itself still needs `std`, so stacks like smoltcp have to
+    /// bring their own HTTP handling and hand the body over here.
+    pub fn handle_body<R: io::Read>(&self, body: R, charset: Option<&str>) -> Option<String> {
+        self.answer(body, charset, &mut Timings::default())
+    }
+
+    /// Answers `request` as the default backend would, for testing handlers
+    /// and middleware without binding a socket.
+    pub fn handle_http(&self, request: &TestRequest) -> HttpResponse {
+        let response = self.handle_rouille(&request.to_rouille());
+        let mut answer = HttpResponse {
+            status: response.status_code,
+            content_type: String::new(),
+            headers: Vec::new(),
+            body: Vec::new(),
+        };
+        for (name, value) in response.headers {
+            if name.eq_ignore_ascii_case("Content-Type") {
+                answer.content_type = value.into_owned();
+            } else {
+                answer.headers.push((name.into_owned(), value.into_owned()));
+            }
+        }
+        let (mut body, _) = response.data.into_reader_and_size();
+        // The body is in memory, so reading it cannot fail.
+        let _ = body.read_to_end(&mut answer.body);
+        answer
+    }
+
+    fn answer<R: io::Read>(
+        &self,
+        body: R,
+        charset: Option<&str>,
+        timings: &mut Timings,
+    ) -> Option<String> {
+        let reply = self.reply(body, charset, timings);
+        let started = Instant::now();
+        let xml = reply.map(|res| res.to_xml_as(&self.wire_profile));
+        timings.serialize += started.elapsed();
+        self.report(timings);
+        xml
+    }
+
+    // The response to a request body, or `None` for a bare 400. Fills in
+    // the timings up to the handler. `charset` is that of the request's
+    // `Content-Type`, if it names one other than UTF-8.
+    pub(crate) fn reply<R: io::Read>(
+        &self,
+        body: R,
+        charset: Option<&str>,
+        timings: &mut Timings,
+    ) -> Option<Reply> {
+        let _in_flight = self.metrics.as_ref().map(Metrics::start);
+        let mut body = TimedReader {
+            inner: body,
+            spent: Duration::from_secs(0),
+        };
+        let started = Instant::now();
+        let parsed = trace::parse(|| {
+            let limits = self.limits.parse();
+            match charset {
+                #[cfg(feature = "encoding")]
+                Some(charset) => parse::call_in_charset(&mut body, charset, &limits),
+                _ if self.streaming_parse => {
+                    parse::call_streaming_within(io::BufReader::new(&mut body), &limits)
+                }
+                _ => parse::call_within(&mut body, &limits),
+            }
+        });
+        timings.read = body.spent;
+        timings.parse = started.elapsed().saturating_sub(body.spent);
+        let res = match parsed {
+            Ok(call) => {
+                if self.on_timings.is_some() || self.access_log.is_some() {
+                    timings.method = Some(call.name.clone());
+                }
+                let started = Instant::now();
+                let reply = self.reply_call(call, timings);
+                timings.handler = started.elapsed().saturating_sub(timings.serialize);
+                return Some(reply);
+            }
+            Err(err) => {
+                if let Some(ref metrics) = self.metrics {
+                    metrics.record_invalid();
+                }
+                match self.fault_codes {
+                    FaultCodes::Interop => {
+                        error_fault(Fault::PARSE_ERROR, "Failed to parse request", &err)
+                    }
+                    FaultCodes::Legacy => return None,
+                }
+            }
+        };
+        Some(Reply::Fresh(res))
+    }
+
+    fn reply_call(&self, call: Call, timings: &mut Timings) -> Reply {
+        let serialize = Cell::new(Duration::from_secs(0));
+        let reply = self.around(call, &|call| self.lookup(call, &serialize));
+        timings.serialize = serialize.get();
+        reply
+    }
+
+    // Answers `call` from the cache or with the canned fault for unknown
+    // methods if it can, else from its handler, adding the time spent
+    // serializing for the cache to `serialize`.
+    fn lookup(&self, call: Call, serialize: &Cell<Duration>) -> Reply {
+        if let Some(ref canned) = self.missing_method {
+            if !self.serves(&call.name) {
+                return Reply::Cached(Arc::clone(canned));
+            }
+        }
+        if !self.cache.is_cacheable(&call.name) {
+            return Reply::Fresh(self.dispatch(call));
+        }
+        let key = call.to_xml();
+        if let Some(cached) = self.cache.get(&key) {
+            return Reply::Cached(cached);
+        }
+        let name = call.name.clone();
+        let response = self.dispatch(call);
+        if response.is_err() {
+            return Reply::Fresh(response);
+        }
+        let started = Instant::now();
+        let cached = Arc::new(Serialized::new(response, &self.wire_profile));
+        serialize.set(serialize.get() + started.elapsed());
+        self.cache.insert(&name, key, Arc::clone(&cached));
+        Reply::Cached(cached)
+    }
+
+    pub(crate) fn handle(&self, req: Call) -> Response {
+        match self.around(req, &|call| Reply::Fresh(self.dispatch(call))) {
+            Reply::Fresh(response) => response,
+            Reply::Cached(cached) => cached.response.clone(),
+        }
+    }
+
+    // Runs `call` through the middleware to `inner`, traced and counted in
+    // the metrics, so that every call answered passes through here. A reply
+    // `inner` serialized earlier is kept if the middleware hands back its
+    // response unchanged.
+    fn around(&self, call: Call, inner: &dyn Fn(Call) -> Reply) -> Reply {
+        let trace = trace::Dispatch::start(&call.name);
+        // Unregistered names stay out of the labels, so callers cannot grow
+        // the metrics without bound.
+        let method = if self.metrics.is_none() && self.observers.is_empty() {
+            None
+        } else if self.serves(&call.name) {
+            Some(call.name.clone())
+        } else {
+            Some(String::from("unknown"))
+        };
+        let started = Instant::now();
+        let reply = if self.middleware.is_empty() {
+            inner(call)
+        } else {
+            let serialized = RefCell::new(None);
+            let response = middleware::run(&self.middleware, call, &|call| match inner(call) {
+                Reply::Fresh(response) => response,
+                Reply::Cached(cached) => {
+                    let response = cached.response.clone();
+                    *serialized.borrow_mut() = Some(cached);
+                    response
+                }
+            });
+            match serialized.into_inner() {
+                Some(cached) if cached.response == response => Reply::Cached(cached),
+                _ => Reply::Fresh(response),
+            }
+        };
+        if let Some(method) = method {
+            self.record(&method, started.elapsed(), reply.response());
+        }
+        trace.finish(reply.response());
+        reply
+    }
+
+    fn dispatch(&self, req: Call) -> Response {
+        if let Some(response) = self.introspect(&req) {
+            return response;
+        }
+        if self.multicalls(&req.name) {
+            return self.multicall(req.params);
+        }
+        let handler = self
+            .handlers
+            .get(&req.name)
+            .map_or(&self.on_missing_method, |method| &method.handler);
+        #[cfg(feature = "chrono")]
+        let params = {
+            let mut params = req.params;
+            if let Err(err) = self.datetimes.receive(&mut params) {
+                return decode_fault(self.fault_codes, &err);
+            }
+            params
+        };
+        #[cfg(not(feature = "chrono"))]
+        let params = req.params;
+        let payload = match panic::catch_unwind(AssertUnwindSafe(|| handler(params))) {
+            #[cfg(feature = "chrono")]
+            Ok(Ok(mut values)) => {
+                return match self.datetimes.send(&mut values) {
+                    Ok(()) => Ok(values),
+                    Err(err) => encode_fault(self.fault_codes, &err),
+                }
+            }
+            Ok(response) => return response,
+            Err(payload) => payload,
+        };
+        self.panicked(&req.name, payload)
+    }
+
+    // The answer to a call to `method` whose handler panicked with
+    // `payload`, as the panic policy has it.
+    pub(crate) fn panicked(&self, method: &str, payload: Box<dyn Any + Send>) -> Response {
+        trace::panicked(method, panic_message(&*payload));
+        if let Some(ref on_panic) = self.on_panic {
+            on_panic(method, &*payload);
+        }
+        let code = self.fault_codes.internal_error();
+        match self.panic_policy {
+            PanicPolicy::Fault => Err(Fault::new(code, "Internal error while handling request")),
+            PanicPolicy::FaultWithMessage => Err(Fault::new(
+                code,
+                format!("Handler panicked: {}", panic_message(&*payload)),
+            )),
+            PanicPolicy::Propagate => panic::resume_unwind(payload),
+        }
+    }
+}
+
+#[cfg(feature = "hyper-server")]
+impl hyper::server::Handler for Server {
+    // Refuses bodies known to be too long before the client sends them, if
+    // asked to with `set_expectation_failed`.
+    fn check_continue(
+        &self,
+        (_, _, headers): (
+            &hyper::method::Method,
+            &hyper::uri::RequestUri,
+            &hyper::header::Headers,
+        ),
+    ) -> hyper::status::StatusCode {
+        let length = headers
+            .get::<hyper::header::ContentLength>()
+            .map(|length| length.0);
+        if self.expectation_failed && self.limits.refuses_body(length) {
+            hyper::status::StatusCode::ExpectationFailed
+        } else {
+            hyper::status::StatusCode::Continue
+        }
+    }
+
+    fn handle<'a, 'k>(
+        &'a self,
+        request: hyper::server::Request<'a, 'k>,
+        mut response: hyper::server::Response<'a>,
+    ) {
+        let _in_flight = self.shutdown.as_ref().map(ShutdownHandle::request);
+        let received = SystemTime::now();
+        let started = Instant::now();
+        let trace = trace::Request::start(request.remote_addr);
+        let header = |name: &str| {
+            request
+                .headers
+                .get_raw(name)
+                .and_then(|values| values.first())
+                .map(|value| String::from_utf8_lossy(value).into_owned())
+        };
+        let context = tracecontext::extract(header);
+        for (name, value) in self.echoed(header) {
+            response
+                .headers_mut()
+                .set_raw(name, vec![value.into_bytes()]);
+        }
+        // Taken before the request is handed over and read.
+        let record = self.access_log.as_ref().map(|_| AccessRecord {
+            peer: Some(request.remote_addr.ip()),
+            received,
+            http_method: request.method.to_string(),
+            path: request.uri.to_string(),
+            protocol: Some(request.version.to_string()),
+            method: None,
+            status: 0,
+            request_bytes: request
+                .headers
+                .get::<hyper::header::ContentLength>()
+                .map(|length| length.0),
+            response_bytes: 0,
+            duration: Duration::from_secs(0),
+            referer: header("Referer"),
+            user_agent: header("User-Agent"),
+        });
+        #[cfg(any(feature = "tls-native", feature = "tls-rustls"))]
+        let peer_certificate = request
+            .ssl::<TlsStream>()
+            .and_then(TlsStream::peer_certificate);
+        let http_request = self.http_request(|| hyper_request(&request));
+        let mut method = None;
+        let respond = || self.respond_hyper(request, response, &mut method);
+        let respond = || middleware::scope(http_request, respond);
+        #[cfg(any(feature = "tls-native", feature = "tls-rustls"))]
+        let respond = || tls::scope(peer_certificate, respond);
+        let (status, sent) = trace.in_scope(|| tracecontext::scope(context, respond));
+        trace.finish(status);
+        if let (Some(log), Some(mut record)) = (self.access_log.as_ref(), record) {
+            record.method = method;
+            record.status = status;
+            record.response_bytes = sent;
+            record.duration = started.elapsed();
+            log.log(&record);
+        }
+    }
+
+    fn on_connection_start(&self) {
+        self.connection_opened();
+    }
+
+    fn on_connection_end(&self) {
+        self.connection_closed();
+        if let Some(ref shutdown) = self.shutdown {
+            shutdown.closed();
+        }
+    }
+}
+
+#[cfg(feature = "hyper-server")]
+impl Server {
+    // Returns the status code and the length of the body sent, for tracing
+    // and logging. Sets `method` to the XML-RPC method called, if any.
+    fn respond_hyper<'a, 'k>(
+        &self,
+        request: hyper::server::Request<'a, 'k>,
+        mut response: hyper::server::Response<'a>,
+        method: &mut Option<String>,
+    ) -> (u16, u64) {
+        let path = match request.uri {
+            hyper::uri::RequestUri::AbsolutePath(ref path) => {
+                Some(path.split('?').next().unwrap_or("").to_owned())
+            }
+            _ => None,
+        };
+        let path = path.as_ref().map_or("", String::as_str);
+        let (count, bytes) = request
+            .headers
+            .iter()
+            .fold((0, 0), |(count, bytes), header| {
+                let values = request.headers.get_raw(header.name()).unwrap_or(&[]);
+                (
+                    count + values.len(),
+                    bytes
+                        + values
+                            .iter()
+                            .map(|value| header.name().len() + value.len())
+                            .sum::<usize>(),
+                )
+            });
+        if self.limits.refuses_head(count, bytes) {
+            return (hyper_refused(response, 431), 0);
+        }
+        let challenge = self.challenge(|name| {
+            request
+                .headers
+                .get_raw(name)
+                .and_then(|values| values.first())
+                .map(|value| String::from_utf8_lossy(value).into_owned())
+        });
+        if let Some(challenge) = challenge {
+            *response.status_mut() = hyper::status::StatusCode::Unauthorized;
+            response
+                .headers_mut()
+                .set_raw("WWW-Authenticate", vec![challenge.into_bytes()]);
+            return (401, 0);
+        }
+        if let Some(answer) = self.route(path, || hyper_request(&request)) {
+            *response.status_mut() = hyper::status::StatusCode::from_u16(answer.status);
+            response
+                .headers_mut()
+                .set_raw("Content-Type", vec![answer.content_type.into_bytes()]);
+            for (name, value) in answer.headers {
+                response.headers_mut().append_raw(name, value.into_bytes());
+            }
+            let _ = response.send(&answer.body);
+            return (answer.status, answer.body.len() as u64);
+        }
+        if let Some(page) = self.metrics_page(request.method == hyper::method::Method::Get, path) {
+            response.headers_mut().set_raw(
+                "Content-Type",
+                vec![METRICS_CONTENT_TYPE.as_bytes().to_vec()],
+            );
+            let _ = response.send(page.as_bytes());
+            return (200, page.len() as u64);
+        }
+        if let Some(page) = self.listing_page(request.method == hyper::method::Method::Get) {
+            response
+                .headers_mut()
+                .set_raw("Content-Type", vec![listing::CONTENT_TYPE.into()]);
+            let _ = response.send(page.as_bytes());
+            return (200, page.len() as u64);
+        }
+        let content_type = request
+            .headers
+            .get_raw("Content-Type")
+            .and_then(|values| values.first())
+            .map(|value| String::from_utf8_lossy(value).into_owned());
+        let http_method = request.method.to_string();
+        if let Some(status) = self.refuses_http(&http_method, path, content_type.as_deref()) {
+            *response.status_mut() = hyper::status::StatusCode::from_u16(status);
+            if status != 415 {
+                response
+                    .headers_mut()
+                    .set_raw("Allow", vec![ALLOW.as_bytes().to_vec()]);
+            }
+            return (status, 0);
+        }
+        #[cfg(feature = "jsonrpc")]
+        let is_jsonrpc = self.is_jsonrpc(request.method == hyper::method::Method::Post, path);
+        let length = request
+            .headers
+            .get::<hyper::header::ContentLength>()
+            .map(|length| length.0);
+        let header = |name: &str| {
+            request
+                .headers
+                .get_raw(name)
+                .and_then(|values| values.first())
+                .map(|value| String::from_utf8_lossy(value).into_owned())
+        };
+        let signature = self.signature_header().and_then(header);
+        let accept = if self.rewrites_replies() {
+            Some(header("Accept-Encoding"))
+        } else {
+            None
+        };
+        let charset = header("Content-Type");
+        let charset = parse::content_charset(charset.as_deref());
+        let expect = header("Expect");
+        let mut body = match self.admit(length, request) {
+            Ok(body) => body,
+            Err(413) => {
+                let status = self.too_large(expect.as_deref());
+                return (hyper_refused(response, status), 0);
+            }
+            Err(status) => return (hyper_refused(response, status), 0),
+        };
+        let mut verified = self.verify(signature.as_deref(), &mut body);
+        if let Some(status) = body.refusal() {
+            return (hyper_refused(response, status), 0);
+        }
+        let input: &mut dyn io::Read = match verified {
+            Verified::Unchecked => &mut body,
+            Verified::Valid(ref mut bytes) => bytes,
+            Verified::Invalid => {
+                *response.status_mut() = hyper::status::StatusCode::Forbidden;
+                return (403, 0);
+            }
+        };
+        #[cfg(feature = "jsonrpc")]
+        {
+            if is_jsonrpc {
+                let reply = self.handle_jsonrpc(input);
+                if let Some(status) = body.refusal() {
+                    return (hyper_refused(response, status), 0);
+                }
+                return match reply {
+                    Some(reply) => {
+                        response
+                            .headers_mut()
+                            .set_raw("Content-Type", vec![jsonrpc::CONTENT_TYPE.into()]);
+                        let _ = response.send(reply.as_bytes());
+                        (200, reply.len() as u64)
+                    }
+                    None => {
+                        *response.status_mut() = hyper::status::StatusCode::NoContent;
+                        (204, 0)
+                    }
+                };
+            }
+        }
+        let mut timings = Timings::default();
+        let reply = self.reply(input, charset, &mut timings);
+        *method = timings.method.clone();
+        if let Some(status) = body.refusal() {
+            return (hyper_refused(response, status), 0);
+        }
+        let sent = match reply {
+            Some(res) => {
+                response.headers_mut().set_raw(
+                    "Content-Type",
+                    vec![self.xml_content_type().as_bytes().to_vec()],
+                );
+                // The client has gone away; there is nobody left to tell.
+                let sent = match accept {
+                    Some(ref accept) => {
+                        send_whole(response, &res, self, accept.as_deref(), &mut timings)
+                    }
+                    None => send_reply(response, &res, &self.wire_profile, &mut timings),
+                };
+                (200, sent.unwrap_or(0))
+            }
+            None => {
+                *response.status_mut() = hyper::status::StatusCode::BadRequest;
+                (400, 0)
+            }
+        };
+        self.report(&timings);
+        sent
+    }
+}
+
+#[cfg(feature = "hyper-server")]
+fn hyper_request(request: &hyper::server::Request) -> HttpRequest {
+    HttpRequest {
+        method: request.method.to_string(),
+        path: match request.uri {
+            hyper::uri::RequestUri::AbsolutePath(ref path) => {
+                path.split('?').next().unwrap_or("").to_owned()
+            }
+            _ => String::new(),
+        },
+        peer: Some(request.remote_addr.ip()),
+        headers: request
+            .headers
+            .iter()
+            .map(|header| (header.name().to_owned(), header.value_string()))
+            .collect(),
+    }
+}
+
+// Refuses a request with `status`. The body is left unread, so the
+// connection cannot be reused.
+#[cfg(feature = "hyper-server")]
+fn hyper_refused(mut response: hyper::server::Response, status: u16) -> u16 {
+    *response.status_mut() = hyper::status::StatusCode::from_u16(status);
+    response
+        .headers_mut()
+        .set(hyper::header::Connection::close());
+    status
+}
+
+// Serializes `res` straight into the connection. Returns the length of
+// the body sent.
+#[cfg(feature = "hyper-server")]
+fn send_reply(
+    response: hyper::server::Response,
+    res: &Reply,
+    profile: &WireProfile,
+    timings: &mut Timings,
+) -> io::Result<u64> {
+    let started = Instant::now();
+    let (writing, sent) = super::xmlfmt::scratch::with_bytes(|buf| {
+        let mut body = ReplyWriter {
+            buf,
+            fresh: Some(response),
+            streaming: None,
+            writing: Duration::from_secs(0),
+            sent: 0,
+        };
+        res.write_xml_as(&mut body, profile)?;
+        body.finish()
+    })?;
+    timings.serialize += started.elapsed().saturating_sub(writing);
+    timings.write = Some(writing);
+    Ok(sent)
+}
+
+// Serializes `res` whole, to send it in the output encoding, or compressed
+// if the client accepts `accept` and it is large enough. Returns the length
+// of the body sent.
+#[cfg(feature = "hyper-server")]
+fn send_whole(
+    mut response: hyper::server::Response,
+    res: &Reply,
+    server: &Server,
+    accept: Option<&str>,
+    timings: &mut Timings,
+) -> io::Result<u64> {
+    let started = Instant::now();
+    let xml = res.to_xml_as(&server.wire_profile);
+    let body = match server.encode_reply(accept, xml.as_bytes()) {
+        Some((body, coding)) => {
+            if let Some(coding) = coding {
+                let headers = response.headers_mut();
+                headers.set_raw("Content-Encoding", vec![coding.into()]);
+                headers.set_raw("Vary", vec![b"Accept-Encoding".to_vec()]);
+            }
+            body
+        }
+        None => xml.into_bytes(),
+    };
+    timings.serialize += started.elapsed();
+    let started = Instant::now();
+    response.send(&body)?;
+    timings.write = Some(started.elapsed());
+    Ok(body.len() as u64)
+}
+
+#[cfg(feature = "hyper-server")]
+const CHUNK_SIZE: usize = 8 * 1024;
+
+// Collects the reply in `buf`. A reply that fits is sent whole with its
+// length, so headers and body leave in a single write. Larger ones are
+// sent chunked, a buffer at a time.
+#[cfg(feature = "hyper-server")]
+struct ReplyWriter<'a, 'b> {
+    buf: &'a mut Vec<u8>,
+    fresh: Option<hyper::server::Response<'b>>,
+    streaming: Option<hyper::server::Response<'b, hyper::net::Streaming>>,
+    // Time spent handing bytes to the connection.
+    writing: Duration,
+    sent: u64,
+}
+
+#[cfg(feature = "hyper-server")]
+impl<'a, 'b> ReplyWriter<'a, 'b> {
+    fn send_chunk(&mut self) -> io::Result<()> {
+        let started = Instant::now();
+        self.sent += self.buf.len() as u64;
+        if let Some(response) = self.fresh.take() {
+            self.streaming = Some(response.start()?);
+        }
+        if let Some(ref mut response) = self.streaming {
+            io::Write::write_all(response, self.buf)?;
+        }
+        self.buf.clear();
+        self.writing += started.elapsed();
+        Ok(())
+    }
+
+    // Returns the total time spent writing and the length of the body.
+    fn finish(mut self) -> io::Result<(Duration, u64)> {
+        if let Some(response) = self.fresh.take() {
+            let started = Instant::now();
+            response.send(self.buf)?;
+            return Ok((started.elapsed(), self.buf.len() as u64));
+        }
+        self.send_chunk()?;
+        let started = Instant::now();
+        if let Some(response) = self.streaming.take() {
+            response.end()?;
+        }
+        Ok((self.writing + started.elapsed(), self.sent))
+    }
+}
+
+#[cfg(feature = "hyper-server")]
+impl<'a, 'b> io::Write for ReplyWriter<'a, 'b> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= CHUNK_SIZE {
+            self.send_chunk()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Dispatches calls on the calling task, so slow handlers block it.
+#[cfg(feature = "tower")]
+impl ::tower_service::Service<Call> for Arc<Server> {
+    type Response = Response;
+    type Error = ::std::convert::Infallible;
+    type Future = ::std::future::Ready<std::result::Result<Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        _cx: &mut ::std::task::Context,
+    ) -> ::std::task::Poll<std::result::Result<(), Self::Error>> {
+        ::std::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, call: Call) -> Self::Future {
+        ::std::future::ready(Ok(self.handle(call)))
+    }
+}
+
+pub struct BoundServer<F>
+where
+    F: Send + Sync + 'static + Fn(&rouille::Request) -> rouille::Response,
+{
+    server: rouille::Server<F>,
+    // server: hyper::Server<NewService, hyper::Body>,
+    shutdown: ShutdownHandle,
+}
+
+// How long `BoundServer::run` waits for a request before checking whether
+// the server is draining.
+const STOP_POLL: Duration = Duration::from_millis(100);
+
+impl<F> BoundServer<F>
+where
+    F: Send + Sync + 'static + Fn(&rouille::Request) -> rouille::Response,
+{
+    fn new(server: rouille::Server<F>, shutdown: ShutdownHandle) -> Self {
+        Self { server, shutdown }
+    }
+
+    pub fn local_addr(&self) -> std::net::SocketAddr {
+        self.server.server_addr()
+    }
+
+    /// A handle to stop the server with, like `Server::shutdown_handle`.
+    /// Requests that come in once it is draining are answered with 503
+    /// Service Unavailable, and `run` returns shortly after.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    /// Serves requests until the server is drained.
+    pub fn run(self) {
+        while !self.shutdown.is_draining() {
+            self.server.poll_timeout(STOP_POLL);
+        }
+    }
+
+    /// Runs the server on a thread of its own, which ends once it is
+    /// drained.
+    pub fn run_in_background(self) -> std::thread::JoinHandle<()> {
+        std::thread::spawn(move || self.run())
     }
 
     pub fn poll(&self) {
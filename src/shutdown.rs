@@ -0,0 +1,244 @@
+//! Stopping a server without cutting off the requests it is answering.
+
+#[cfg(feature = "hyper-server")]
+use hyper;
+use socket2::SockRef;
+use std::collections::HashMap;
+use std::io;
+#[cfg(feature = "hyper-server")]
+use std::net::SocketAddr;
+use std::net::{Shutdown, TcpListener};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+type Closer = Box<dyn FnMut() + Send>;
+
+/// How draining a server went.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DrainReport {
+    /// Requests in flight when draining began that were answered in time.
+    pub completed: usize,
+    /// Requests still in flight when the grace period ran out, whose
+    /// connections were closed on them.
+    pub aborted: usize,
+}
+
+// A connection, closed with `close` when draining.
+struct Connection {
+    close: Closer,
+    busy: bool,
+}
+
+#[derive(Default)]
+struct State {
+    draining: bool,
+    listeners: Vec<TcpListener>,
+    // Each thread serves a connection at a time.
+    connections: HashMap<ThreadId, Connection>,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    // Signalled as requests end while draining.
+    answered: Condvar,
+}
+
+/// Stops the server it was taken from, from another thread.
+///
+/// Taken with `Server::shutdown_handle`.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    shared: Arc<Shared>,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new() -> ShutdownHandle {
+        ShutdownHandle {
+            shared: Arc::default(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.shared
+            .state
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Stops accepting connections and closes those waiting for a request,
+    /// then waits up to `grace` for the requests in flight to be answered,
+    /// closing each connection as its request is. Connections with a request
+    /// still in flight after `grace` are closed unanswered.
+    pub fn drain(&self, grace: Duration) -> DrainReport {
+        let deadline = Instant::now() + grace;
+        let mut state = self.lock();
+        state.draining = true;
+        for listener in state.listeners.drain(..) {
+            // Wakes the threads blocked accepting, at least on Linux.
+            let _ = SockRef::from(&listener).shutdown(Shutdown::Both);
+        }
+        state.connections.retain(|_, connection| {
+            if !connection.busy {
+                (connection.close)();
+            }
+            connection.busy
+        });
+        let in_flight = state.connections.len();
+        while !state.connections.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            state = self
+                .shared
+                .answered
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|err| err.into_inner())
+                .0;
+        }
+        let aborted = state.connections.len();
+        for (_, mut connection) in state.connections.drain() {
+            (connection.close)();
+        }
+        DrainReport {
+            completed: in_flight - aborted,
+            aborted,
+        }
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.lock().draining
+    }
+
+    // Closes `listener` when draining.
+    pub(crate) fn add_listener(&self, listener: &TcpListener) -> io::Result<()> {
+        let listener = listener.try_clone()?;
+        let mut state = self.lock();
+        if state.draining {
+            let _ = SockRef::from(&listener).shutdown(Shutdown::Both);
+        } else {
+            state.listeners.push(listener);
+        }
+        Ok(())
+    }
+
+    // Registers the connection the calling thread is about to serve, or
+    // returns false if the server is draining already.
+    pub(crate) fn open<F: FnMut() + Send + 'static>(&self, close: F) -> bool {
+        let mut state = self.lock();
+        if state.draining {
+            return false;
+        }
+        state.connections.insert(
+            thread::current().id(),
+            Connection {
+                close: Box::new(close),
+                busy: false,
+            },
+        );
+        true
+    }
+
+    // The calling thread's connection has been closed.
+    pub(crate) fn closed(&self) {
+        self.lock().connections.remove(&thread::current().id());
+    }
+
+    // Marks the calling thread's connection as answering a request until
+    // the returned guard drops.
+    pub(crate) fn request(&self) -> InFlight<'_> {
+        if let Some(connection) = self.lock().connections.get_mut(&thread::current().id()) {
+            connection.busy = true;
+        }
+        InFlight { handle: self }
+    }
+}
+
+pub(crate) struct InFlight<'a> {
+    handle: &'a ShutdownHandle,
+}
+
+impl<'a> Drop for InFlight<'a> {
+    fn drop(&mut self) {
+        let mut state = self.handle.lock();
+        let id = thread::current().id();
+        if !state.draining {
+            if let Some(connection) = state.connections.get_mut(&id) {
+                connection.busy = false;
+            }
+            return;
+        }
+        // The response has been flushed, so the connection can go.
+        if let Some(mut connection) = state.connections.remove(&id) {
+            (connection.close)();
+            self.handle.shared.answered.notify_all();
+        }
+    }
+}
+
+// Registers the connections hyper accepts with a shutdown handle, and stops
+// handing any out once it is draining.
+#[cfg(feature = "hyper-server")]
+#[derive(Clone)]
+pub(crate) struct DrainingListener<L> {
+    inner: L,
+    shutdown: Option<ShutdownHandle>,
+}
+
+#[cfg(feature = "hyper-server")]
+impl<L> DrainingListener<L> {
+    pub fn new(inner: L, shutdown: Option<ShutdownHandle>) -> DrainingListener<L> {
+        DrainingListener { inner, shutdown }
+    }
+}
+
+// hyper accepts again as soon as `accept` returns, failed or not, and has
+// no way to stop its threads. Those left once draining wait here for good.
+#[cfg(feature = "hyper-server")]
+fn park_forever() -> ! {
+    loop {
+        thread::park();
+    }
+}
+
+#[cfg(feature = "hyper-server")]
+impl<L: hyper::net::NetworkListener> hyper::net::NetworkListener for DrainingListener<L>
+where
+    L::Stream: 'static,
+{
+    type Stream = L::Stream;
+
+    fn accept(&mut self) -> hyper::Result<L::Stream> {
+        let shutdown = match self.shutdown {
+            Some(ref shutdown) => shutdown,
+            None => return self.inner.accept(),
+        };
+        let stream = match self.inner.accept() {
+            Ok(stream) => stream,
+            Err(_) if shutdown.is_draining() => park_forever(),
+            Err(err) => return Err(err),
+        };
+        let mut closer = stream.clone();
+        let open = shutdown.open(move || {
+            let _ = hyper::net::NetworkStream::close(&mut closer, Shutdown::Both);
+        });
+        if !open {
+            park_forever();
+        }
+        Ok(stream)
+    }
+
+    fn local_addr(&mut self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn set_read_timeout(&mut self, duration: Option<Duration>) {
+        self.inner.set_read_timeout(duration);
+    }
+
+    fn set_write_timeout(&mut self, duration: Option<Duration>) {
+        self.inner.set_write_timeout(duration);
+    }
+}
@@ -0,0 +1,156 @@
+//! SOCKS5 proxies for the client, such as the one `ssh -D` opens.
+//!
+//! Host names are resolved by the proxy, so endpoints only known on the far
+//! side of the tunnel are reachable too.
+
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, TcpStream};
+use std::time::Duration;
+
+const VERSION: u8 = 5;
+const NO_AUTH: u8 = 0;
+const USERNAME_PASSWORD: u8 = 2;
+const NO_ACCEPTABLE_METHOD: u8 = 0xff;
+const CONNECT: u8 = 1;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+
+#[derive(Clone, Debug)]
+pub struct Socks5Proxy {
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    credentials: Option<(String, String)>,
+}
+
+impl Socks5Proxy {
+    pub fn new<H: Into<String>>(host: H, port: u16) -> Socks5Proxy {
+        Socks5Proxy {
+            host: host.into(),
+            port,
+            credentials: None,
+        }
+    }
+
+    /// Authenticates with a username and password, as per RFC 1929.
+    pub fn set_credentials<U, P>(&mut self, username: U, password: P)
+    where
+        U: Into<String>,
+        P: Into<String>,
+    {
+        self.credentials = Some((username.into(), password.into()));
+    }
+
+    // Asks the proxy, already connected to over `stream`, to connect to
+    // `host`. The handshake gets `timeout` for each read and write.
+    pub(crate) fn handshake(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+        timeout: Option<Duration>,
+    ) -> io::Result<()> {
+        stream.set_read_timeout(timeout)?;
+        stream.set_write_timeout(timeout)?;
+        self.authenticate(stream)?;
+        request_connect(stream, host, port)?;
+        stream.set_read_timeout(None)?;
+        stream.set_write_timeout(None)
+    }
+
+    fn authenticate(&self, stream: &mut TcpStream) -> io::Result<()> {
+        let method = match self.credentials {
+            Some(_) => USERNAME_PASSWORD,
+            None => NO_AUTH,
+        };
+        stream.write_all(&[VERSION, 1, method])?;
+        let mut reply = [0u8; 2];
+        stream.read_exact(&mut reply)?;
+        if reply[0] != VERSION {
+            return Err(failure("proxy sent a malformed reply"));
+        }
+        match (reply[1], &self.credentials) {
+            (NO_AUTH, &None) => Ok(()),
+            (USERNAME_PASSWORD, &Some((ref username, ref password))) => {
+                let mut request = vec![1];
+                push_field(&mut request, username.as_bytes(), "username")?;
+                push_field(&mut request, password.as_bytes(), "password")?;
+                stream.write_all(&request)?;
+                stream.read_exact(&mut reply)?;
+                if reply[1] == 0 {
+                    Ok(())
+                } else {
+                    Err(failure("proxy rejected the credentials"))
+                }
+            }
+            (NO_ACCEPTABLE_METHOD, _) => Err(failure("proxy requires other authentication")),
+            _ => Err(failure("proxy chose an authentication method not offered")),
+        }
+    }
+}
+
+fn failure(message: &str) -> io::Error {
+    io::Error::other(format!("SOCKS5 {}", message))
+}
+
+fn push_field(request: &mut Vec<u8>, field: &[u8], what: &str) -> io::Result<()> {
+    if field.len() > 255 {
+        return Err(failure(&format!("{} longer than 255 bytes", what)));
+    }
+    request.push(field.len() as u8);
+    request.extend_from_slice(field);
+    Ok(())
+}
+
+fn request_connect(stream: &mut TcpStream, host: &str, port: u16) -> io::Result<()> {
+    let mut request = vec![VERSION, CONNECT, 0];
+    // IPv6 hosts come bracketed from URLs.
+    match host
+        .trim_matches(|c| c == '[' || c == ']')
+        .parse::<IpAddr>()
+    {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(ATYP_IPV4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(ATYP_IPV6);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            request.push(ATYP_DOMAIN);
+            push_field(&mut request, host.as_bytes(), "host name")?;
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request)?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply)?;
+    if reply[0] != VERSION {
+        return Err(failure("proxy sent a malformed reply"));
+    }
+    if reply[1] != 0 {
+        return Err(failure(match reply[1] {
+            2 => "proxy ruleset forbids the connection",
+            3 => "proxy reports the network unreachable",
+            4 => "proxy reports the host unreachable",
+            5 => "proxy was refused a connection",
+            6 => "proxy timed out connecting",
+            _ => "proxy failed to connect",
+        }));
+    }
+    // The address the proxy bound, which is of no use to us.
+    let len = match reply[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN => {
+            let mut len = [0u8];
+            stream.read_exact(&mut len)?;
+            usize::from(len[0])
+        }
+        _ => return Err(failure("proxy replied with an unknown address type")),
+    };
+    let mut bound = vec![0u8; len + 2];
+    stream.read_exact(&mut bound)
+}
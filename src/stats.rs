@@ -0,0 +1,144 @@
+//! Counters of a client's connections and traffic.
+
+use hyper;
+use std::io;
+use std::net::{Shutdown, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What a client has done so far, from `Client::stats`.
+///
+/// Bytes are counted as HTTP, before any TLS.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClientStats {
+    pub connections_opened: u64,
+    /// Calls sent over a connection kept from an earlier call.
+    pub connections_reused: u64,
+    pub connections_closed: u64,
+    /// Connections still open, which between calls are those kept in the
+    /// pool.
+    pub connections_pooled: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    opened: AtomicU64,
+    reused: AtomicU64,
+    closed: AtomicU64,
+    sent: AtomicU64,
+    received: AtomicU64,
+}
+
+impl Counters {
+    pub fn snapshot(&self) -> ClientStats {
+        let opened = self.opened.load(Ordering::Relaxed);
+        let closed = self.closed.load(Ordering::Relaxed);
+        ClientStats {
+            connections_opened: opened,
+            connections_reused: self.reused.load(Ordering::Relaxed),
+            connections_closed: closed,
+            connections_pooled: opened.saturating_sub(closed),
+            bytes_sent: self.sent.load(Ordering::Relaxed),
+            bytes_received: self.received.load(Ordering::Relaxed),
+        }
+    }
+
+    // Counts a call as reusing a connection if it sent anything without
+    // opening one.
+    pub fn count_call(&self, before: &ClientStats) {
+        let after = self.snapshot();
+        if after.connections_opened == before.connections_opened
+            && after.bytes_sent > before.bytes_sent
+        {
+            self.reused.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+// Counts a connection as closed once hyper drops every copy of it.
+#[derive(Debug)]
+struct Open(Arc<Counters>);
+
+impl Drop for Open {
+    fn drop(&mut self) {
+        self.0.closed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// Counts the connections `inner` makes and the bytes through them.
+pub(crate) struct StatsConnector<C> {
+    inner: C,
+    counters: Arc<Counters>,
+}
+
+impl<C> StatsConnector<C> {
+    pub fn new(inner: C, counters: Arc<Counters>) -> StatsConnector<C> {
+        StatsConnector { inner, counters }
+    }
+}
+
+impl<C> hyper::net::NetworkConnector for StatsConnector<C>
+where
+    C: hyper::net::NetworkConnector,
+    C::Stream: hyper::net::NetworkStream + Send + Clone,
+{
+    type Stream = StatsStream<C::Stream>;
+
+    fn connect(&self, host: &str, port: u16, scheme: &str) -> hyper::Result<Self::Stream> {
+        let stream = self.inner.connect(host, port, scheme)?;
+        self.counters.opened.fetch_add(1, Ordering::Relaxed);
+        Ok(StatsStream {
+            inner: stream,
+            open: Arc::new(Open(Arc::clone(&self.counters))),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct StatsStream<S> {
+    inner: S,
+    open: Arc<Open>,
+}
+
+impl<S: io::Read> io::Read for StatsStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        let counters = &(self.open.0);
+        counters.received.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+impl<S: io::Write> io::Write for StatsStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        let counters = &(self.open.0);
+        counters.sent.fetch_add(written as u64, Ordering::Relaxed);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S: hyper::net::NetworkStream> hyper::net::NetworkStream for StatsStream<S> {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    fn set_read_timeout(&self, duration: Option<Duration>) -> io::Result<()> {
+        self.inner.set_read_timeout(duration)
+    }
+
+    fn set_write_timeout(&self, duration: Option<Duration>) -> io::Result<()> {
+        self.inner.set_write_timeout(duration)
+    }
+
+    fn close(&mut self, how: Shutdown) -> io::Result<()> {
+        self.inner.close(how)
+    }
+}
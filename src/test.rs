@@ -0,0 +1,400 @@
+//! Helpers for testing code that makes or answers XML-RPC calls, without
+//! going through the network or, for `MockServer`, through a real server.
+//!
+//! `MockServer` is a scripted server for testing client code.
+//! `TestRequest` and `Server::handle_http` answer requests with a server in
+//! the same process, and `MockClient` stands in for a `Client`, answering
+//! from a script or from a server in the same process.
+
+#[cfg(feature = "client")]
+use hyper::Url;
+#[cfg(feature = "server")]
+use rouille;
+#[cfg(feature = "client")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "client")]
+use std::collections::{HashMap, VecDeque};
+#[cfg(all(feature = "client", feature = "server"))]
+use std::io;
+#[cfg(feature = "server")]
+use std::net::SocketAddr;
+#[cfg(all(feature = "client", feature = "server"))]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(all(feature = "client", feature = "server"))]
+use std::sync::{Arc, Mutex, MutexGuard};
+#[cfg(all(feature = "client", feature = "server"))]
+use std::thread::{self, JoinHandle};
+#[cfg(all(feature = "client", feature = "server"))]
+use std::time::Duration;
+
+#[cfg(all(feature = "client", feature = "server"))]
+use super::client::summarize_reply;
+#[cfg(all(feature = "client", feature = "server"))]
+use super::error::Error;
+#[cfg(feature = "client")]
+use super::error::Result;
+#[cfg(all(feature = "client", feature = "server"))]
+use super::server::Server;
+#[cfg(all(feature = "client", feature = "server"))]
+use super::xmlfmt::parse;
+#[cfg(feature = "server")]
+use super::xmlfmt::value::ToXml;
+#[cfg(feature = "client")]
+use super::xmlfmt::{from_params, into_params, Fault, Response};
+use super::xmlfmt::{Call, Params};
+
+#[cfg(all(feature = "client", feature = "server"))]
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[cfg(feature = "client")]
+#[derive(Default)]
+struct Script {
+    calls: Vec<Call>,
+    responses: HashMap<String, VecDeque<Response>>,
+}
+
+#[cfg(feature = "client")]
+impl Script {
+    // Scripted responses are used up in order, except for the last one,
+    // which answers every call after it.
+    fn answer(&mut self, call: Call) -> Response {
+        let response = match self.responses.get_mut(&call.name) {
+            Some(queue) if queue.len() > 1 => queue.pop_front(),
+            Some(queue) => queue.front().cloned(),
+            None => None,
+        };
+        let response = response.unwrap_or_else(|| {
+            Err(Fault::new(
+                Fault::METHOD_NOT_FOUND,
+                format!("No response scripted for {}", call.name),
+            ))
+        });
+        self.calls.push(call);
+        response
+    }
+}
+
+/// Listens on an ephemeral local port and answers calls with scripted
+/// responses, recording every call it receives. Stops when dropped.
+///
+/// ```no_run
+/// # use xml_rpc::{Client, Fault, Value};
+/// # use xml_rpc::test::MockServer;
+/// let mock = MockServer::start().unwrap();
+/// mock.respond("add", Ok(vec![Value::Int(5)]));
+/// mock.respond("add", Err(Fault::new(4, "Out of range")));
+///
+/// let mut client = Client::new().unwrap();
+/// let params = vec![Value::Int(2), Value::Int(3)];
+/// assert_eq!(client.call_value(&mock.url(), "add", params).unwrap(), Ok(vec![Value::Int(5)]));
+/// assert_eq!(mock.calls()[0].name, "add");
+/// ```
+#[cfg(all(feature = "client", feature = "server"))]
+pub struct MockServer {
+    addr: SocketAddr,
+    script: Arc<Mutex<Script>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[cfg(all(feature = "client", feature = "server"))]
+impl MockServer {
+    pub fn start() -> Result<MockServer> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+        let script = Arc::new(Mutex::new(Script::default()));
+        let shared = Arc::clone(&script);
+        let server = rouille::Server::new(addr, move |request| {
+            let call = match request.data().map(parse::call) {
+                Some(Ok(call)) => call,
+                _ => return rouille::Response::empty_400(),
+            };
+            let response = lock(&shared).answer(call);
+            rouille::Response::from_data("text/xml", response.to_xml())
+        })
+        .map_err(|err| Error::Bind {
+            addr,
+            source: match err.downcast::<io::Error>() {
+                Ok(err) => *err,
+                Err(err) => io::Error::other(err),
+            },
+        })?;
+        let addr = server.server_addr();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = Arc::clone(&stop);
+        let thread = thread::spawn(move || {
+            while !stopped.load(Ordering::SeqCst) {
+                server.poll();
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+        Ok(MockServer {
+            addr,
+            script,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The URL to pass to the client under test.
+    pub fn url(&self) -> Url {
+        Url::parse(&format!("http://{}/", self.addr)).expect("Socket addresses form valid URLs")
+    }
+
+    /// Queues `response` for calls to `name`. Methods without any responses
+    /// queued answer with a `METHOD_NOT_FOUND` fault.
+    pub fn respond<K: Into<String>>(&self, name: K, response: Response) {
+        lock(&self.script)
+            .responses
+            .entry(name.into())
+            .or_default()
+            .push_back(response);
+    }
+
+    /// Every call received so far, in order, including unscripted ones.
+    pub fn calls(&self) -> Vec<Call> {
+        lock(&self.script).calls.clone()
+    }
+}
+
+#[cfg(all(feature = "client", feature = "server"))]
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+// Assertions failing in the test don't make the script unusable.
+#[cfg(all(feature = "client", feature = "server"))]
+fn lock(script: &Mutex<Script>) -> MutexGuard<'_, Script> {
+    script.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// A fake HTTP request, answered by `Server::handle_http` as if it had come
+/// in over the network, middleware, routes and limits included.
+///
+/// ```no_run
+/// # use xml_rpc::{Server, Value};
+/// # use xml_rpc::test::TestRequest;
+/// let mut server = Server::new();
+/// server.register_value("ping", |_| Ok(vec![Value::String("pong".into())]));
+/// let response = server.handle_http(&TestRequest::call("ping", vec![]));
+/// assert_eq!(response.status, 200);
+/// ```
+#[cfg(feature = "server")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestRequest {
+    method: String,
+    path: String,
+    peer: SocketAddr,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+#[cfg(feature = "server")]
+impl TestRequest {
+    /// A `POST` of `body` to `/`, as `text/xml`.
+    pub fn post<B: Into<Vec<u8>>>(body: B) -> TestRequest {
+        TestRequest {
+            method: String::from("POST"),
+            path: String::from("/"),
+            peer: SocketAddr::from(([127, 0, 0, 1], 0)),
+            headers: vec![(String::from("Content-Type"), String::from("text/xml"))],
+            body: body.into(),
+        }
+    }
+
+    /// A `POST` of a call to `name` with `params`.
+    pub fn call<K: Into<String>>(name: K, params: Params) -> TestRequest {
+        let call = Call {
+            name: name.into(),
+            params,
+        };
+        TestRequest::post(call.to_xml())
+    }
+
+    /// A `GET` of `path`, as for routes and the metrics page.
+    pub fn get<P: Into<String>>(path: P) -> TestRequest {
+        TestRequest {
+            method: String::from("GET"),
+            path: path.into(),
+            headers: Vec::new(),
+            ..TestRequest::post(Vec::new())
+        }
+    }
+
+    pub fn method<M: Into<String>>(mut self, method: M) -> TestRequest {
+        self.method = method.into();
+        self
+    }
+
+    /// Sets the path, which may include a query.
+    pub fn path<P: Into<String>>(mut self, path: P) -> TestRequest {
+        self.path = path.into();
+        self
+    }
+
+    /// Sets the address the request comes from, `127.0.0.1` by default.
+    pub fn peer(mut self, peer: SocketAddr) -> TestRequest {
+        self.peer = peer;
+        self
+    }
+
+    /// Sets header `name`, replacing any value it had.
+    pub fn header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> TestRequest {
+        let name = name.into();
+        self.headers
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(&name));
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> TestRequest {
+        self.body = body.into();
+        self
+    }
+
+    pub(crate) fn to_rouille(&self) -> rouille::Request {
+        rouille::Request::fake_http_from(
+            self.peer,
+            self.method.clone(),
+            self.path.clone(),
+            self.headers.clone(),
+            self.body.clone(),
+        )
+    }
+}
+
+/// Stands in for a `Client` in unit tests, answering calls with scripted
+/// responses, or with a `Server` in the same process, and recording every
+/// call made. It takes the same arguments as `Client`, ignoring the URL.
+///
+/// ```no_run
+/// # use xml_rpc::{Fault, Url, Value};
+/// # use xml_rpc::test::MockClient;
+/// let url = Url::parse("http://localhost/").unwrap();
+/// let mut client = MockClient::new();
+/// client.respond("add", Ok(vec![Value::Int(5)]));
+///
+/// let sum: Result<i32, Fault> = client.call(&url, "add", (2, 3)).unwrap();
+/// assert_eq!(sum, Ok(5));
+/// assert_eq!(client.calls()[0].params, vec![Value::Int(2), Value::Int(3)]);
+/// ```
+#[cfg(feature = "client")]
+#[derive(Default)]
+pub struct MockClient {
+    script: Script,
+    #[cfg(feature = "server")]
+    server: Option<Server>,
+}
+
+#[cfg(feature = "client")]
+impl MockClient {
+    pub fn new() -> MockClient {
+        MockClient::default()
+    }
+
+    /// Sends calls to `server` through `Server::handle_http`, as a `Client`
+    /// would over the network.
+    #[cfg(feature = "server")]
+    pub fn loopback(server: Server) -> MockClient {
+        MockClient {
+            server: Some(server),
+            ..MockClient::default()
+        }
+    }
+
+    /// Queues `response` for calls to `name`, used up as with
+    /// `MockServer::respond`. Ignored by loopback clients.
+    pub fn respond<K: Into<String>>(&mut self, name: K, response: Response) {
+        self.script
+            .responses
+            .entry(name.into())
+            .or_default()
+            .push_back(response);
+    }
+
+    /// Every call made so far, in order.
+    pub fn calls(&self) -> &[Call] {
+        &self.script.calls
+    }
+
+    pub fn call_value<Tkey>(&mut self, _uri: &Url, name: Tkey, params: Params) -> Result<Response>
+    where
+        Tkey: Into<String>,
+    {
+        let call = Call {
+            name: name.into(),
+            params,
+        };
+        #[cfg(feature = "server")]
+        {
+            if let Some(ref server) = self.server {
+                self.script.calls.push(call.clone());
+                return loopback(server, call);
+            }
+        }
+        Ok(self.script.answer(call))
+    }
+
+    pub fn call<'a, Tkey, Treq, Tres>(
+        &mut self,
+        uri: &Url,
+        name: Tkey,
+        req: Treq,
+    ) -> Result<std::result::Result<Tres, Fault>>
+    where
+        Tkey: Into<String>,
+        Treq: Serialize,
+        Tres: Deserialize<'a>,
+    {
+        match self.call_value(uri, name, into_params(&req)?)? {
+            Ok(v) => from_params(v).map(Ok).map_err(Into::into),
+            Err(v) => Ok(Err(v)),
+        }
+    }
+}
+
+// Answers `call` with `server`, failing as `Client` does on replies that
+// are not XML-RPC responses.
+#[cfg(all(feature = "client", feature = "server"))]
+fn loopback(server: &Server, call: Call) -> Result<Response> {
+    let response = server.handle_http(&TestRequest::post(call.to_xml()));
+    let reply = || {
+        summarize_reply(
+            response.status,
+            |name| {
+                if name.eq_ignore_ascii_case("Content-Type") {
+                    return Some(response.content_type.clone());
+                }
+                response
+                    .headers
+                    .iter()
+                    .find(|&(key, _)| key.eq_ignore_ascii_case(name))
+                    .map(|(_, value)| value.clone())
+            },
+            &response.body,
+        )
+    };
+    if response.status == 401 {
+        return Err(Error::Unauthorized(reply()));
+    }
+    if response.status < 200 || response.status >= 300 {
+        return Err(Error::Status(reply()));
+    }
+    let parsed = match parse::content_charset(Some(&response.content_type)) {
+        #[cfg(feature = "encoding")]
+        Some(charset) => parse::response_in_charset(response.body.as_slice(), charset),
+        _ => parse::response(response.body.as_slice()),
+    };
+    parsed.map_err(|err| Error::InvalidResponse {
+        reply: reply(),
+        source: err,
+    })
+}
@@ -0,0 +1,53 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::super::access_log::{AccessRecord, LogFormat};
+
+fn record() -> AccessRecord {
+    AccessRecord {
+        peer: Some("10.0.0.1".parse().unwrap()),
+        received: UNIX_EPOCH + Duration::from_secs(971_186_136),
+        http_method: String::from("POST"),
+        path: String::from("/RPC2"),
+        protocol: Some(String::from("HTTP/1.1")),
+        method: Some(String::from("system.listMethods")),
+        status: 200,
+        request_bytes: Some(104),
+        response_bytes: 2326,
+        duration: Duration::from_micros(352),
+        referer: None,
+        user_agent: Some(String::from("curl/\"8\"")),
+    }
+}
+
+#[test]
+fn writes_common_and_combined_lines() {
+    let record = record();
+    assert_eq!(
+        record.line(LogFormat::Common),
+        "10.0.0.1 - - [10/Oct/2000:13:55:36 +0000] \"POST /RPC2 HTTP/1.1\" 200 2326 \
+         \"system.listMethods\" 104 352"
+    );
+    assert_eq!(
+        record.line(LogFormat::Combined),
+        "10.0.0.1 - - [10/Oct/2000:13:55:36 +0000] \"POST /RPC2 HTTP/1.1\" 200 2326 \
+         \"-\" \"curl/\\\"8\\\"\" \"system.listMethods\" 104 352"
+    );
+}
+
+#[test]
+fn writes_unknown_fields_as_dashes() {
+    let record = AccessRecord {
+        peer: None,
+        received: SystemTime::UNIX_EPOCH,
+        protocol: None,
+        method: None,
+        status: 400,
+        request_bytes: None,
+        response_bytes: 0,
+        ..record()
+    };
+    assert_eq!(
+        record.line(LogFormat::Common),
+        "- - - [01/Jan/1970:00:00:00 +0000] \"POST /RPC2\" 400 - \"-\" - 352"
+    );
+}
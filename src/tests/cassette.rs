@@ -0,0 +1,79 @@
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::pin::Pin;
+use std::process;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use super::super::async_client::{HttpRequest, HttpResponse, Transport, TransportFuture};
+use super::super::cassette::Cassette;
+use super::super::xmlfmt::value::ToXml;
+use super::super::xmlfmt::{Call, Value};
+use super::super::Url;
+
+// Answers every request with the same reply, counting them.
+struct Counting(Arc<AtomicUsize>);
+
+impl Transport for Counting {
+    fn post(&self, _: HttpRequest) -> TransportFuture {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Box::pin(::std::future::ready(Ok(HttpResponse {
+            status: 200,
+            headers: vec![("Content-Type".to_owned(), "text/xml".to_owned())],
+            body: Ok(vec![Value::Int(5)]).to_xml().into_bytes(),
+        })))
+    }
+}
+
+// Runs a future that is ready the first time it is polled.
+fn ready<F: Future>(future: F) -> F::Output {
+    fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(ptr::null(), &VTABLE)
+    }
+    fn noop(_: *const ()) {}
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+    let waker = unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) };
+    let mut future = Box::pin(future);
+    match Pin::as_mut(&mut future).poll(&mut Context::from_waker(&waker)) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("Future was not ready"),
+    }
+}
+
+fn add(a: i32, b: i32) -> HttpRequest {
+    let call = Call {
+        name: String::from("add"),
+        params: vec![Value::Int(a), Value::Int(b)],
+    };
+    HttpRequest {
+        url: Url::parse("http://127.0.0.1:1/").unwrap(),
+        headers: Vec::new(),
+        body: call.to_xml().into_bytes(),
+    }
+}
+
+#[test]
+fn records_replies_and_replays_them() {
+    let dir = env::temp_dir().join(format!("xml-rpc-cassette-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    let count = Arc::new(AtomicUsize::new(0));
+    let cassette = Cassette::new(&dir, Counting(Arc::clone(&count)));
+    let recorded = ready(cassette.post(add(2, 3))).unwrap();
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+    let mut cassette = Cassette::new(&dir, Counting(Arc::clone(&count)));
+    cassette.set_replay_only(true);
+    let replayed = ready(cassette.post(add(2, 3))).unwrap();
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+    assert_eq!(replayed.status, recorded.status);
+    assert_eq!(replayed.headers, recorded.headers);
+    assert_eq!(replayed.body, recorded.body);
+    // Other parameters were never recorded.
+    assert!(ready(cassette.post(add(2, 4))).is_err());
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,344 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "server")]
+use super::super::auth::Auth;
+use super::super::cancel::CancelToken;
+use super::super::client::Client;
+#[cfg(feature = "gzip")]
+use super::super::compression::Coding;
+use super::super::error::Error;
+#[cfg(all(feature = "hmac", feature = "hyper-server"))]
+use super::super::hmac::{Signer, Verifier};
+use super::super::httpproxy::HttpProxy;
+use super::super::jobs::Backoff;
+use super::super::retry::RetryPolicy;
+#[cfg(feature = "server")]
+use super::super::server::Server;
+use super::super::test::MockClient;
+use super::super::xmlfmt::value::ToXml;
+use super::super::xmlfmt::{Fault, Value};
+use super::super::Url;
+
+// Accepts connections and holds them open without ever answering.
+fn stalling_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let mut held = Vec::new();
+        for stream in listener.incoming() {
+            held.push(stream);
+        }
+    });
+    addr
+}
+
+// Answers the request on each connection it accepts with the next of
+// `replies`, the last one over and over, keeping the heads of the requests.
+fn scripted_server(replies: Vec<Vec<u8>>) -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let addr = listener.local_addr().unwrap();
+    let heads = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&heads);
+    thread::spawn(move || {
+        for (count, stream) in listener.incoming().enumerate() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            seen.lock().unwrap().push(read_request(&mut stream));
+            let _ = stream.write_all(&replies[count.min(replies.len() - 1)]);
+        }
+    });
+    (addr, heads)
+}
+
+// Reads a request off `stream`, body and all, returning its head.
+fn read_request(stream: &mut TcpStream) -> String {
+    let mut data = Vec::new();
+    let mut buffer = [0; 4096];
+    loop {
+        if let Some(end) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+            let head = String::from_utf8_lossy(&data[..end]).into_owned();
+            let length = head
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+                .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+                .unwrap_or(0);
+            if data.len() >= end + 4 + length {
+                return head;
+            }
+        }
+        match stream.read(&mut buffer) {
+            Ok(0) | Err(_) => return String::from_utf8_lossy(&data).into_owned(),
+            Ok(read) => data.extend_from_slice(&buffer[..read]),
+        }
+    }
+}
+
+fn http_reply(status: &str, headers: &str, body: &[u8]) -> Vec<u8> {
+    let mut reply = format!(
+        "HTTP/1.1 {}\r\nConnection: close\r\nContent-Type: text/xml\r\n{}\
+         Content-Length: {}\r\n\r\n",
+        status,
+        headers,
+        body.len()
+    )
+    .into_bytes();
+    reply.extend_from_slice(body);
+    reply
+}
+
+fn five() -> Vec<u8> {
+    Ok(vec![Value::Int(5)]).to_xml().into_bytes()
+}
+
+fn url(addr: SocketAddr) -> Url {
+    Url::parse(&format!("http://{}/", addr)).unwrap()
+}
+
+fn retrying_client(policy: RetryPolicy) -> Client {
+    Client::builder()
+        .retry_policy(RetryPolicy {
+            backoff: Backoff {
+                initial: Duration::from_millis(10),
+                max: Duration::from_millis(10),
+            },
+            jitter: false,
+            ..policy
+        })
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn retries_server_errors() {
+    let unavailable = http_reply("503 Service Unavailable", "", b"");
+    let (addr, heads) = scripted_server(vec![unavailable, http_reply("200 OK", "", &five())]);
+    let mut client = retrying_client(RetryPolicy::default());
+    assert_eq!(client.call(&url(addr), "add", (2, 3)).unwrap(), Ok(5));
+    assert_eq!(heads.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn does_not_retry_non_idempotent_methods() {
+    let unavailable = http_reply("503 Service Unavailable", "", b"");
+    let (addr, heads) = scripted_server(vec![unavailable, http_reply("200 OK", "", &five())]);
+    let mut client = retrying_client(RetryPolicy::default().non_idempotent("add"));
+    match client.call::<_, _, i32>(&url(addr), "add", (2, 3)) {
+        Err(Error::Status(reply)) => assert_eq!(reply.status, 503),
+        result => panic!("Expected a 503, got {:?}", result),
+    }
+    assert_eq!(heads.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn calls_through_http_proxies() {
+    let (proxy_addr, heads) = scripted_server(vec![http_reply("200 OK", "", &five())]);
+    let (direct_addr, direct_heads) = scripted_server(vec![http_reply("200 OK", "", &five())]);
+    let mut proxy = HttpProxy::new("127.0.0.1", proxy_addr.port());
+    proxy.set_credentials("user", "pass");
+    proxy.bypass(".example.org");
+    let mut client = Client::new().unwrap();
+    client.set_http_proxy(Some(proxy.clone()));
+    let uri = Url::parse("http://rpc.internal:8080/RPC2").unwrap();
+    assert_eq!(client.call(&uri, "add", (2, 3)).unwrap(), Ok(5));
+    let head = heads.lock().unwrap()[0].clone();
+    assert!(
+        head.starts_with("POST http://rpc.internal:8080/RPC2 HTTP/1.1\r\n"),
+        "{}",
+        head
+    );
+    assert!(
+        head.contains("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n"),
+        "{}",
+        head
+    );
+
+    proxy.bypass("127.0.0.1");
+    client.set_http_proxy(Some(proxy));
+    assert_eq!(
+        client.call(&url(direct_addr), "add", (2, 3)).unwrap(),
+        Ok(5)
+    );
+    assert_eq!(heads.lock().unwrap().len(), 1);
+    assert!(direct_heads.lock().unwrap()[0].starts_with("POST / HTTP/1.1\r\n"));
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn decompresses_replies() {
+    let compressed = Coding::Gzip.encode(&five());
+    let reply = http_reply("200 OK", "Content-Encoding: gzip\r\n", &compressed);
+    let (addr, heads) = scripted_server(vec![reply]);
+    let mut client = Client::new().unwrap();
+    client.set_compression(true);
+    assert_eq!(client.call(&url(addr), "add", (2, 3)).unwrap(), Ok(5));
+    let head = heads.lock().unwrap()[0].to_ascii_lowercase();
+    assert!(head.contains("accept-encoding: gzip, deflate"), "{}", head);
+}
+
+#[test]
+fn cancels_calls_in_flight() {
+    let uri = url(stalling_server());
+    let token = CancelToken::new();
+    let mut client = Client::new().unwrap();
+    client.set_cancel_token(Some(token.clone()));
+    let started = Instant::now();
+    let canceller = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        token.cancel();
+    });
+    let result = client.call::<_, _, i32>(&uri, "add", (2, 3));
+    assert!(matches!(result, Err(Error::Cancelled)), "{:?}", result);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    canceller.join().unwrap();
+    // The token stays cancelled.
+    let result = client.call::<_, _, i32>(&uri, "add", (2, 3));
+    assert!(matches!(result, Err(Error::Cancelled)), "{:?}", result);
+}
+
+#[cfg(all(feature = "hmac", feature = "hyper-server"))]
+#[test]
+fn signs_calls_for_verifying_servers() {
+    let mut server = Server::new();
+    let verifier = Verifier::new();
+    verifier.add_key("key", b"secret");
+    server.set_verifier(verifier);
+    server.register_simple("add", |(a, b): (i32, i32)| Ok(a + b));
+    let mut listening = server
+        .bind_hyper(&"127.0.0.1:0".parse().unwrap())
+        .expect("Failed to bind server");
+    let uri = url(listening.socket);
+    let mut client = Client::new().unwrap();
+    let result = client.call::<_, _, i32>(&uri, "add", (2, 3));
+    assert!(matches!(result, Err(Error::Status(ref reply)) if reply.status == 403));
+    client.set_signer(Some(Signer::new("key", b"secret")));
+    assert_eq!(client.call(&uri, "add", (2, 3)).unwrap(), Ok(5));
+    listening.close().unwrap();
+}
+
+#[cfg(feature = "hyper-server")]
+#[test]
+fn waits_for_jobs_to_finish() {
+    let mut server = Server::new();
+    server.register_job("slow", |_| {
+        thread::sleep(Duration::from_millis(50));
+        Ok(vec![Value::Int(7)])
+    });
+    let mut listening = server
+        .bind_hyper(&"127.0.0.1:0".parse().unwrap())
+        .expect("Failed to bind server");
+    let uri = url(listening.socket);
+    let mut client = Client::new().unwrap();
+    let backoff = Backoff {
+        initial: Duration::from_millis(10),
+        max: Duration::from_millis(20),
+    };
+    let result = client.call_job(&uri, "slow", vec![], backoff).unwrap();
+    assert_eq!(result, Ok(vec![Value::Int(7)]));
+    let result = client.call_job(&uri, "fast", vec![], backoff).unwrap();
+    assert_eq!(result.unwrap_err().code, Fault::METHOD_NOT_FOUND);
+    listening.close().unwrap();
+}
+
+#[test]
+fn mock_clients_answer_from_their_script() {
+    let uri = url("127.0.0.1:1".parse().unwrap());
+    let mut client = MockClient::new();
+    client.respond("add", Ok(vec![Value::Int(5)]));
+    client.respond("add", Err(Fault::new(4, "Out of range")));
+    assert_eq!(client.call(&uri, "add", (2, 3)).unwrap(), Ok(5));
+    // The last response answers every call after it.
+    for _ in 0..2 {
+        let result = client.call::<_, _, i32>(&uri, "add", (2, 3)).unwrap();
+        assert_eq!(result, Err(Fault::new(4, "Out of range")));
+    }
+    let result = client.call_value(&uri, "sub", vec![]).unwrap();
+    assert_eq!(result.unwrap_err().code, Fault::METHOD_NOT_FOUND);
+    let names = client.calls().iter().map(|call| call.name.as_str());
+    assert_eq!(names.collect::<Vec<_>>(), ["add", "add", "add", "sub"]);
+    assert_eq!(client.calls()[0].params, vec![Value::Int(2), Value::Int(3)]);
+}
+
+#[cfg(feature = "server")]
+#[test]
+fn mock_clients_loop_back_to_servers() {
+    let uri = url("127.0.0.1:1".parse().unwrap());
+    let mut server = Server::new();
+    server.register_simple("add", |(a, b): (i32, i32)| Ok(a + b));
+    server.set_auth(Auth::tokens("Authorization", vec!["secret"]));
+    let mut client = MockClient::loopback(server);
+    let result = client.call::<_, _, i32>(&uri, "add", (2, 3));
+    assert!(matches!(result, Err(Error::Unauthorized(ref reply)) if reply.status == 401));
+    assert_eq!(client.calls().len(), 1);
+
+    let mut server = Server::new();
+    server.register_simple("add", |(a, b): (i32, i32)| Ok(a + b));
+    let mut client = MockClient::loopback(server);
+    assert_eq!(client.call(&uri, "add", (2, 3)).unwrap(), Ok(5));
+    let result = client.call_value(&uri, "sub", vec![]).unwrap();
+    assert_eq!(result.unwrap_err().code, Fault::METHOD_NOT_FOUND);
+}
+
+#[test]
+fn stops_retrying_at_the_call_timeout() {
+    let addr = stalling_server();
+    let mut client = Client::builder()
+        .call_timeout(Duration::from_millis(300))
+        .retry_policy(RetryPolicy {
+            max_attempts: 10,
+            backoff: Backoff {
+                initial: Duration::from_millis(50),
+                max: Duration::from_millis(50),
+            },
+            jitter: false,
+            ..RetryPolicy::default()
+        })
+        .build()
+        .unwrap();
+    let started = Instant::now();
+    let result = client.call_value(&url(addr), "stall", vec![]);
+    assert!(matches!(result, Err(Error::CallTimeout)), "{:?}", result);
+    // Ten attempts of the whole call timeout each would take three seconds.
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn overrides_the_call_timeout_for_one_call() {
+    let addr = stalling_server();
+    let mut client = Client::builder()
+        .call_timeout(Duration::from_secs(60))
+        .build()
+        .unwrap();
+    let started = Instant::now();
+    let result = client
+        .deadline(Duration::from_millis(200))
+        .call::<_, _, i32>(&url(addr), "stall", ());
+    assert!(matches!(result, Err(Error::CallTimeout)), "{:?}", result);
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
+
+#[cfg(feature = "hyper-server")]
+#[test]
+fn sends_calls_strict_servers_accept() {
+    let mut server = Server::new();
+    server.set_strict_http(true);
+    server.register_simple("add", |(a, b): (i32, i32)| Ok(a + b));
+    let mut listening = server
+        .bind_hyper(&"127.0.0.1:0".parse().unwrap())
+        .expect("Failed to bind server");
+    let uri = url(listening.socket);
+    let mut client = Client::new().unwrap();
+    assert_eq!(client.call(&uri, "add", (2, 3)).unwrap(), Ok(5));
+    #[cfg(feature = "encoding")]
+    {
+        client.set_output_encoding(Some(::encoding_rs::WINDOWS_1252));
+        assert_eq!(client.call(&uri, "add", (4, 5)).unwrap(), Ok(9));
+    }
+    listening.close().unwrap();
+}
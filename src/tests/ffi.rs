@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+use std::slice;
+
+use super::super::ffi::{
+    xmlrpc_server_free, xmlrpc_server_new, xmlrpc_server_register, XmlRpcKind, XmlRpcValue,
+};
+use super::super::xmlfmt::value::ToXml;
+use super::super::xmlfmt::{parse, Call, Fault, Response, Value};
+
+// Answers with its first parameter, as C would copy it over.
+extern "C" fn echo(
+    _: *mut c_void,
+    params: *const XmlRpcValue,
+    count: usize,
+    result: *mut XmlRpcValue,
+) -> c_int {
+    let params = unsafe { slice::from_raw_parts(params, count) };
+    unsafe { *result = params[0] };
+    0
+}
+
+// Answers with its first parameter, claiming it is of an unknown kind.
+extern "C" fn garble(
+    _: *mut c_void,
+    params: *const XmlRpcValue,
+    count: usize,
+    result: *mut XmlRpcValue,
+) -> c_int {
+    echo(ptr::null_mut(), params, count, result);
+    unsafe { (*result).kind = 42 };
+    0
+}
+
+// The response of a server with `echo` and `garble` to `name` with `param`.
+fn call(name: &str, param: Value) -> Response {
+    let server = xmlrpc_server_new();
+    for (method, function) in &[("echo", echo as _), ("garble", garble as _)] {
+        let method = CString::new(*method).unwrap();
+        let status = unsafe {
+            xmlrpc_server_register(server, method.as_ptr(), *function, None, ptr::null_mut())
+        };
+        assert_eq!(status, 0);
+    }
+    let body = Call {
+        name: String::from(name),
+        params: vec![param],
+    }
+    .to_xml();
+    let reply = unsafe { (*server).0.handle_body(body.as_bytes(), None) }.expect("No reply");
+    unsafe { xmlrpc_server_free(server) };
+    parse::response(reply.as_bytes()).expect("Failed to parse reply")
+}
+
+#[test]
+fn numbers_kinds_as_the_header_does() {
+    for kind in 0..10 {
+        let parsed = XmlRpcKind::try_from(kind).unwrap();
+        assert_eq!(parsed as c_int, kind);
+    }
+    assert_eq!(XmlRpcKind::try_from(9), Ok(XmlRpcKind::I8));
+    assert_eq!(XmlRpcKind::try_from(10), Err(10));
+    assert_eq!(XmlRpcKind::try_from(-1), Err(-1));
+}
+
+#[test]
+fn passes_values_to_c_and_back() {
+    let mut members = HashMap::new();
+    members.insert(String::from("name"), Value::String(String::from("a\0b")));
+    members.insert(String::from("none"), Value::Nil);
+    let values = vec![
+        Value::Int(-7),
+        Value::Int64(1 << 40),
+        Value::Bool(true),
+        Value::Double(2.5),
+        Value::String(String::from("text")),
+        Value::DateTime(String::from("19980717T14:08:55")),
+        Value::Base64(vec![0, 1, 2]),
+        Value::Array(vec![Value::Int(1), Value::Struct(members.clone())]),
+        Value::Struct(members),
+        Value::Nil,
+    ];
+    for value in values {
+        assert_eq!(call("echo", value.clone()), Ok(vec![value]));
+    }
+}
+
+#[test]
+fn answers_results_of_unknown_kinds_with_faults() {
+    let fault = call("garble", Value::Int(1)).unwrap_err();
+    assert_eq!(fault.code, Fault::INTERNAL_ERROR);
+    assert_eq!(fault.message, "Method returned a value of unknown kind 42");
+}
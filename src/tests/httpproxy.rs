@@ -0,0 +1,81 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use super::super::httpproxy::HttpProxy;
+
+#[test]
+fn bypasses_listed_hosts_and_their_subdomains() {
+    let mut proxy = HttpProxy::new("proxy", 3128);
+    proxy.bypass("example.org");
+    proxy.bypass(".internal:8080");
+    proxy.bypass("[::1]");
+    for host in &["example.org", "rpc.EXAMPLE.org", "rpc.internal", "[::1]"] {
+        assert!(!proxy.applies_to(host), "{}", host);
+    }
+    for host in &["example.com", "badexample.org", "internal.net", "::2"] {
+        assert!(proxy.applies_to(host), "{}", host);
+    }
+    proxy.bypass("*");
+    assert!(!proxy.applies_to("example.com"));
+}
+
+// Answers one `CONNECT` with `reply`, returning the request head and
+// echoing whatever is sent through the tunnel.
+fn proxy(reply: &'static str) -> (u16, thread::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let port = listener.local_addr().unwrap().port();
+    let handle = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut head = Vec::new();
+        let mut byte = [0];
+        while !head.ends_with(b"\r\n\r\n") {
+            stream.read_exact(&mut byte).unwrap();
+            head.push(byte[0]);
+        }
+        stream.write_all(reply.as_bytes()).unwrap();
+        let mut echoed = [0; 5];
+        if stream.read_exact(&mut echoed).is_ok() {
+            stream.write_all(&echoed).unwrap();
+        }
+        String::from_utf8(head).unwrap()
+    });
+    (port, handle)
+}
+
+#[test]
+fn opens_tunnels_with_connect() {
+    let (port, handle) = proxy("HTTP/1.1 200 Connection established\r\n\r\n");
+    let mut proxy = HttpProxy::new("127.0.0.1", port);
+    proxy.set_credentials("user", "pass");
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    proxy
+        .tunnel(&mut stream, "rpc.internal", 443, None)
+        .unwrap();
+    stream.write_all(b"hello").unwrap();
+    let mut echoed = [0; 5];
+    stream.read_exact(&mut echoed).unwrap();
+    assert_eq!(&echoed, b"hello");
+    assert_eq!(
+        handle.join().unwrap(),
+        "CONNECT rpc.internal:443 HTTP/1.1\r\nHost: rpc.internal:443\r\n\
+         Proxy-Authorization: Basic dXNlcjpwYXNz\r\n\r\n"
+    );
+}
+
+#[test]
+fn fails_refused_tunnels() {
+    let (port, handle) = proxy("HTTP/1.1 407 Proxy Authentication Required\r\n\r\n");
+    let proxy = HttpProxy::new("127.0.0.1", port);
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let err = proxy
+        .tunnel(&mut stream, "rpc.internal", 443, None)
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("requires other credentials"),
+        "{}",
+        err
+    );
+    drop(stream);
+    handle.join().unwrap();
+}
@@ -0,0 +1,73 @@
+use std::net::IpAddr;
+
+use super::super::ipfilter::{IpFilter, IpNet};
+
+fn net(text: &str) -> IpNet {
+    text.parse().expect("Failed to parse block")
+}
+
+fn ip(text: &str) -> IpAddr {
+    text.parse().expect("Failed to parse address")
+}
+
+#[test]
+fn parses_blocks() {
+    assert_eq!(net("10.0.0.0/8"), IpNet::new(ip("10.0.0.0"), 8).unwrap());
+    assert_eq!(net("10.1.2.3"), IpNet::new(ip("10.1.2.3"), 32).unwrap());
+    assert_eq!(net("fd00::"), IpNet::new(ip("fd00::"), 128).unwrap());
+    assert_eq!(net("10.0.0.0/8").to_string(), "10.0.0.0/8");
+    for text in &["10.0.0.0/33", "fd00::/129", "10.0.0.0/", "10.0.0/8", "/8"] {
+        assert!(text.parse::<IpNet>().is_err(), "{}", text);
+    }
+}
+
+#[test]
+fn matches_addresses_up_to_the_prefix() {
+    let block = net("172.16.0.0/12");
+    assert!(block.contains(ip("172.16.0.0")));
+    assert!(block.contains(ip("172.31.255.255")));
+    assert!(!block.contains(ip("172.32.0.0")));
+    assert!(!block.contains(ip("172.15.255.255")));
+    assert!(net("0.0.0.0/0").contains(ip("203.0.113.9")));
+    assert!(net("10.0.0.1/32").contains(ip("10.0.0.1")));
+    assert!(!net("10.0.0.1/32").contains(ip("10.0.0.0")));
+    assert!(net("10.0.0.0/31").contains(ip("10.0.0.1")));
+    assert!(!net("10.0.0.0/31").contains(ip("10.0.0.2")));
+}
+
+#[test]
+fn matches_ipv6_addresses() {
+    let block = net("2001:db8::/32");
+    assert!(block.contains(ip("2001:db8:ffff::1")));
+    assert!(!block.contains(ip("2001:db9::1")));
+    assert!(net("::1/128").contains(ip("::1")));
+    assert!(!net("::1/128").contains(ip("::2")));
+    assert!(!block.contains(ip("10.0.0.1")));
+    assert!(!net("10.0.0.0/8").contains(ip("2001:db8::1")));
+}
+
+#[test]
+fn treats_mapped_addresses_as_ipv4() {
+    assert_eq!(net("::ffff:10.0.0.0/104"), net("10.0.0.0/8"));
+    assert_eq!(net("::ffff:10.1.2.3"), net("10.1.2.3"));
+    let block = net("10.0.0.0/8");
+    assert!(block.contains(ip("::ffff:10.1.2.3")));
+    assert!(!block.contains(ip("::ffff:11.1.2.3")));
+    assert!(net("::ffff:10.0.0.0/104").contains(ip("10.1.2.3")));
+    assert!(net("::/0").contains(ip("10.1.2.3")));
+    assert!(!net("fd00::/8").contains(ip("10.1.2.3")));
+}
+
+#[test]
+fn denies_before_allowing() {
+    let filter = IpFilter::new()
+        .allow(net("10.0.0.0/8"))
+        .deny(net("10.9.0.0/16"));
+    assert!(filter.permits(ip("10.1.2.3")));
+    assert!(!filter.permits(ip("10.9.1.1")));
+    assert!(!filter.permits(ip("192.168.1.1")));
+    let filter = IpFilter::new().deny(net("10.9.0.0/16"));
+    assert!(filter.permits(ip("192.168.1.1")));
+    assert!(!filter.permits(ip("::ffff:10.9.1.1")));
+    assert!(IpFilter::new().permits(ip("::1")));
+}
@@ -0,0 +1,20 @@
+#[cfg(feature = "server")]
+mod access_log;
+#[cfg(feature = "client")]
+mod cassette;
+#[cfg(feature = "client")]
+mod client;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "client")]
+mod httpproxy;
+#[cfg(feature = "server")]
+mod ipfilter;
+#[cfg(feature = "server")]
+mod scgi;
+#[cfg(feature = "server")]
+mod server;
+#[cfg(all(feature = "socks", feature = "hyper-server"))]
+mod socks;
+#[cfg(all(feature = "tokio", feature = "server"))]
+mod tokio;
@@ -0,0 +1,354 @@
+use base64;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::super::access_log::AccessLog;
+use super::super::auth::Auth;
+#[cfg(feature = "gzip")]
+use super::super::compression::Coding;
+#[cfg(feature = "client")]
+use super::super::error::Error;
+#[cfg(feature = "hmac")]
+use super::super::hmac::{Signer, Verifier};
+use super::super::ipfilter::IpFilter;
+use super::super::middleware::HttpRequest;
+use super::super::routes::HttpResponse;
+#[cfg(feature = "client")]
+use super::super::scgi::ScgiClient;
+use super::super::server::Server;
+#[cfg(feature = "gzip")]
+use super::super::xmlfmt::parse;
+use super::super::xmlfmt::value::ToXml;
+#[cfg(feature = "client")]
+use super::super::xmlfmt::Fault;
+use super::super::xmlfmt::{Call, Value};
+
+// Serves `server` over SCGI for the rest of the tests.
+fn serve(server: Server) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || server.serve_scgi(listener));
+    addr
+}
+
+fn adding_server() -> Server {
+    let mut server = Server::new();
+    server.register_simple("add", |(a, b): (i32, i32)| Ok(a + b));
+    server
+}
+
+fn add_xml(a: i32, b: i32) -> String {
+    Call {
+        name: String::from("add"),
+        params: vec![Value::Int(a), Value::Int(b)],
+    }
+    .to_xml()
+}
+
+// A request for `body` with the SCGI `headers` given besides the required
+// ones, as a web server would send it. The request method and URI default
+// to `POST` and `/RPC2`.
+fn request(body: &str, headers: &[(&str, &str)]) -> String {
+    let mut netstring = format!("CONTENT_LENGTH\0{}\0SCGI\x001\0", body.len());
+    for &(name, value) in &[("REQUEST_METHOD", "POST"), ("REQUEST_URI", "/RPC2")] {
+        if !headers.iter().any(|&(key, _)| key == name) {
+            netstring.push_str(&format!("{}\0{}\0", name, value));
+        }
+    }
+    for &(name, value) in headers {
+        netstring.push_str(&format!("{}\0{}\0", name, value));
+    }
+    format!("{}:{},{}", netstring.len(), netstring, body)
+}
+
+fn connect(addr: SocketAddr) -> TcpStream {
+    let stream = TcpStream::connect(addr).expect("Failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    stream
+}
+
+// Sends `request` and reads the CGI reply.
+fn send(addr: SocketAddr, request: &str) -> String {
+    let mut stream = connect(addr);
+    stream.write_all(request.as_bytes()).unwrap();
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).unwrap();
+    reply
+}
+
+// Sends a call to `add` with `headers` and reads the CGI reply.
+fn exchange(addr: SocketAddr, headers: &[(&str, &str)]) -> String {
+    send(addr, &request(&add_xml(2, 3), headers))
+}
+
+#[test]
+fn answers_calls_from_web_servers() {
+    let addr = serve(adding_server());
+    let reply = exchange(addr, &[("CONTENT_TYPE", "text/xml")]);
+    assert!(reply.starts_with("Status: 200 OK\r\n"), "{}", reply);
+    assert!(reply.contains("<i4>5</i4>"), "{}", reply);
+}
+
+#[test]
+fn answers_routes_and_unserved_paths() {
+    let mut server = adding_server();
+    server.set_rpc_path("/RPC2");
+    server.add_route("/healthz", |request: &HttpRequest| {
+        HttpResponse::text(200, format!("ok {}", request.method))
+    });
+    let addr = serve(server);
+    let get = [
+        ("REQUEST_METHOD", "GET"),
+        ("REQUEST_URI", "/healthz?verbose"),
+    ];
+    let reply = exchange(addr, &get);
+    assert!(reply.starts_with("Status: 200 OK\r\n"), "{}", reply);
+    assert!(reply.ends_with("\r\n\r\nok GET"), "{}", reply);
+    let reply = exchange(addr, &[("REQUEST_URI", "/other")]);
+    assert!(reply.starts_with("Status: 404 Not Found\r\n"), "{}", reply);
+    let reply = exchange(addr, &[]);
+    assert!(reply.contains("<i4>5</i4>"), "{}", reply);
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn compresses_replies_for_clients_that_accept_it() {
+    let mut server = Server::new();
+    server.register_value("echo", Ok);
+    server.set_compression(1024);
+    let addr = serve(server);
+    let echo = |params: Vec<Value>| {
+        Call {
+            name: String::from("echo"),
+            params,
+        }
+        .to_xml()
+    };
+    // The head and body of the reply to `body`.
+    let reply = |body: &str, headers: &[(&str, &str)]| {
+        let mut stream = connect(addr);
+        stream.write_all(request(body, headers).as_bytes()).unwrap();
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply).unwrap();
+        let end = reply.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+        let head = String::from_utf8_lossy(&reply[..end]).into_owned();
+        (head, reply[end..].to_vec())
+    };
+    let long = echo(vec![Value::String("a".repeat(4096))]);
+    let (head, body) = reply(&long, &[("HTTP_ACCEPT_ENCODING", "gzip")]);
+    assert!(head.contains("Content-Encoding: gzip\r\n"), "{}", head);
+    assert!(body.len() < 1024);
+    let body = Coding::Gzip
+        .decode(&body, 1 << 20)
+        .unwrap_or_else(|_| panic!("Failed to decode reply"));
+    let response = parse::response(&body[..]).expect("Failed to parse reply");
+    assert_eq!(response, Ok(vec![Value::String("a".repeat(4096))]));
+    // Not for clients that do not ask, nor for short replies.
+    let (head, _) = reply(&long, &[]);
+    assert!(!head.contains("Content-Encoding"), "{}", head);
+    let (head, _) = reply(&echo(vec![]), &[("HTTP_ACCEPT_ENCODING", "gzip")]);
+    assert!(!head.contains("Content-Encoding"), "{}", head);
+}
+
+#[test]
+fn holds_requests_to_strict_http() {
+    let mut server = adding_server();
+    server.set_strict_http(true);
+    let addr = serve(server);
+    let reply = exchange(addr, &[("REQUEST_METHOD", "GET")]);
+    assert!(reply.starts_with("Status: 405 "), "{}", reply);
+    assert!(reply.contains("\r\nAllow: POST"), "{}", reply);
+    let reply = exchange(addr, &[("CONTENT_TYPE", "text/plain")]);
+    assert!(reply.starts_with("Status: 415 "), "{}", reply);
+    let reply = exchange(addr, &[("CONTENT_TYPE", "text/xml")]);
+    assert!(reply.starts_with("Status: 200 OK\r\n"), "{}", reply);
+    assert!(
+        reply.contains("\r\nContent-Type: text/xml; charset=utf-8\r\n"),
+        "{}",
+        reply
+    );
+}
+
+#[test]
+fn logs_requests_with_cgi_variables() {
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let mut server = adding_server();
+    let log = Arc::clone(&records);
+    server.set_access_log(AccessLog::callback(move |record| {
+        log.lock().unwrap().push(record.clone());
+    }));
+    let addr = serve(server);
+    let reply = exchange(
+        addr,
+        &[
+            ("REMOTE_ADDR", "10.0.0.1"),
+            ("SERVER_PROTOCOL", "HTTP/1.1"),
+            ("HTTP_USER_AGENT", "rtorrent"),
+        ],
+    );
+    let body = &reply[reply.find("\r\n\r\n").unwrap() + 4..];
+    let records = records.lock().unwrap();
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
+    assert_eq!(record.peer, Some("10.0.0.1".parse().unwrap()));
+    assert_eq!(record.http_method, "POST");
+    assert_eq!(record.path, "/RPC2");
+    assert_eq!(record.protocol.as_deref(), Some("HTTP/1.1"));
+    assert_eq!(record.method.as_deref(), Some("add"));
+    assert_eq!(record.status, 200);
+    assert_eq!(record.response_bytes, body.len() as u64);
+    assert_eq!(record.user_agent.as_deref(), Some("rtorrent"));
+}
+
+#[test]
+fn closes_connections_with_slow_heads() {
+    let mut server = adding_server();
+    server.set_head_timeout(Duration::from_millis(100));
+    let addr = serve(server);
+    let mut stream = connect(addr);
+    let started = Instant::now();
+    let request = request(&add_xml(2, 3), &[]);
+    stream.write_all(&request.as_bytes()[..10]).unwrap();
+    let mut reply = Vec::new();
+    // Closed unanswered, though perhaps with a reset.
+    let _ = stream.read_to_end(&mut reply);
+    assert!(reply.is_empty());
+    assert!(started.elapsed() < Duration::from_secs(5));
+    // Heads sent in time are answered.
+    let reply = exchange(addr, &[]);
+    assert!(reply.starts_with("Status: 200 OK\r\n"), "{}", reply);
+}
+
+#[test]
+fn limits_pending_heads_per_peer() {
+    let mut server = adding_server();
+    server.set_max_pending_heads_per_ip(1);
+    let addr = serve(server);
+    let mut pending = connect(addr);
+    let mut reply = Vec::new();
+    let _ = connect(addr).read_to_end(&mut reply);
+    assert!(reply.is_empty());
+    // The first connection is still served, and no longer counts once its
+    // head is in.
+    pending
+        .write_all(request(&add_xml(2, 3), &[]).as_bytes())
+        .unwrap();
+    pending.read_to_end(&mut reply).unwrap();
+    let reply = String::from_utf8(reply).unwrap();
+    assert!(reply.starts_with("Status: 200 OK\r\n"), "{}", reply);
+    let reply = exchange(addr, &[]);
+    assert!(reply.starts_with("Status: 200 OK\r\n"), "{}", reply);
+}
+
+#[test]
+fn closes_connections_from_refused_peers() {
+    let refused = |filter: IpFilter| {
+        let mut server = adding_server();
+        server.set_ip_filter(filter);
+        let mut reply = Vec::new();
+        let _ = connect(serve(server)).read_to_end(&mut reply);
+        reply.is_empty()
+    };
+    assert!(refused(
+        IpFilter::new().allow("10.0.0.0/8".parse().unwrap())
+    ));
+    assert!(refused(
+        IpFilter::new().deny("127.0.0.0/8".parse().unwrap())
+    ));
+    let mut server = adding_server();
+    server.set_ip_filter(IpFilter::new().allow("127.0.0.1".parse().unwrap()));
+    let reply = exchange(serve(server), &[]);
+    assert!(reply.starts_with("Status: 200 OK\r\n"), "{}", reply);
+}
+
+#[cfg(feature = "hmac")]
+#[test]
+fn refuses_bodies_without_valid_signatures() {
+    let mut server = adding_server();
+    let verifier = Verifier::new();
+    verifier.add_key("old", b"old secret");
+    verifier.add_key("new", b"new secret");
+    server.set_verifier(verifier.clone());
+    let addr = serve(server);
+    let signed = |signer: &Signer, body: &str| {
+        let signature = signer.sign(add_xml(2, 3).as_bytes());
+        send(addr, &request(body, &[("HTTP_X_SIGNATURE", &signature)]))
+    };
+    let status = |reply: String| reply[..reply.find("\r\n").unwrap()].to_owned();
+    let old = Signer::new("old", b"old secret");
+    assert_eq!(status(signed(&old, &add_xml(2, 3))), "Status: 200 OK");
+    let new = Signer::new("new", b"new secret");
+    assert_eq!(status(signed(&new, &add_xml(2, 3))), "Status: 200 OK");
+    assert_eq!(status(exchange(addr, &[])), "Status: 403 Forbidden");
+    let forged = Signer::new("new", b"guessed secret");
+    assert_eq!(
+        status(signed(&forged, &add_xml(2, 3))),
+        "Status: 403 Forbidden"
+    );
+    let tampered = signed(&new, &add_xml(2, 4));
+    assert_eq!(status(tampered), "Status: 403 Forbidden");
+    // Retired keys sign nothing any more.
+    verifier.remove_key("old");
+    assert_eq!(
+        status(signed(&old, &add_xml(2, 3))),
+        "Status: 403 Forbidden"
+    );
+}
+
+#[test]
+fn reads_credentials_from_http_variables() {
+    let mut server = adding_server();
+    server.set_auth(Auth::tokens("X-Api-Key", vec!["secret"]));
+    let addr = serve(server);
+    let reply = exchange(addr, &[]);
+    assert!(reply.starts_with("Status: 401 "), "{}", reply);
+    assert!(reply.contains("WWW-Authenticate: Bearer"), "{}", reply);
+    let reply = exchange(addr, &[("HTTP_X_API_KEY", "guess")]);
+    assert!(reply.starts_with("Status: 401 "), "{}", reply);
+    let reply = exchange(addr, &[("HTTP_X_API_KEY", "secret")]);
+    assert!(reply.starts_with("Status: 200 OK\r\n"), "{}", reply);
+}
+
+#[test]
+fn challenges_requests_without_basic_credentials() {
+    let mut server = adding_server();
+    let mut users = HashMap::new();
+    users.insert(String::from("ann"), String::from("pa:ss"));
+    let mut auth = Auth::users(users);
+    auth.set_realm("sums");
+    server.set_auth(auth);
+    let addr = serve(server);
+    let basic = |credentials: &str| format!("Basic {}", base64::encode(credentials));
+    let reply = exchange(addr, &[("HTTP_AUTHORIZATION", &basic("ann:wrong"))]);
+    assert!(reply.starts_with("Status: 401 "), "{}", reply);
+    assert!(
+        reply.contains("WWW-Authenticate: Basic realm=\"sums\"\r\n"),
+        "{}",
+        reply
+    );
+    let reply = exchange(addr, &[("HTTP_AUTHORIZATION", &basic("ann:pa:ss"))]);
+    assert!(reply.starts_with("Status: 200 OK\r\n"), "{}", reply);
+}
+
+#[cfg(feature = "client")]
+#[test]
+fn talks_to_scgi_clients() {
+    let mut server = adding_server();
+    server.set_strict_http(true);
+    let client = ScgiClient::new(serve(server)).unwrap();
+    assert_eq!(client.call("add", (2, 3)).unwrap(), Ok(5));
+    let result = client.call::<_, _, i32>("sub", (2, 3)).unwrap();
+    assert_eq!(result.unwrap_err().code, Fault::METHOD_NOT_FOUND);
+
+    let mut server = adding_server();
+    server.set_auth(Auth::tokens("X-Api-Key", vec!["secret"]));
+    let client = ScgiClient::new(serve(server)).unwrap();
+    let result = client.call::<_, _, i32>("add", (2, 3));
+    assert!(matches!(result, Err(Error::Status(ref reply)) if reply.status == 401));
+}
@@ -0,0 +1,269 @@
+use std::io;
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::super::error::Error;
+use super::super::middleware::{HttpRequest, Next};
+use super::super::observer::{Observer, Outcome};
+use super::super::server::Server;
+use super::super::test::TestRequest;
+use super::super::xmlfmt::value::ToXml;
+use super::super::xmlfmt::{parse, Call, Fault, Response, Value};
+
+// The response `server` gives to a call to `name` with `params`.
+fn call(server: &Server, name: &str, params: Vec<Value>) -> Response {
+    let body = Call {
+        name: String::from(name),
+        params,
+    }
+    .to_xml();
+    let reply = server.handle_body(body.as_bytes(), None).expect("No reply");
+    parse::response(reply.as_bytes()).expect("Failed to parse reply")
+}
+
+#[test]
+fn answers_repeated_calls_from_the_cache() {
+    let count = Arc::new(AtomicUsize::new(0));
+    let mut server = Server::new();
+    for name in &["cached", "expired", "fresh", "failing"] {
+        let count = Arc::clone(&count);
+        let fails = *name == "failing";
+        server.register_simple(*name, move |n: i32| {
+            count.fetch_add(1, Ordering::SeqCst);
+            if fails {
+                Err(Fault::new(1, "Failed"))
+            } else {
+                Ok(n)
+            }
+        });
+    }
+    server.set_cacheable("cached", Duration::from_secs(60));
+    server.set_cacheable("expired", Duration::from_secs(0));
+    server.set_cacheable("failing", Duration::from_secs(60));
+    let runs = |name: &str, n: i32| {
+        let before = count.load(Ordering::SeqCst);
+        let response = call(&server, name, vec![Value::Int(n)]);
+        (response, count.load(Ordering::SeqCst) - before)
+    };
+    assert_eq!(runs("cached", 1), (Ok(vec![Value::Int(1)]), 1));
+    assert_eq!(runs("cached", 1), (Ok(vec![Value::Int(1)]), 0));
+    assert_eq!(runs("cached", 2), (Ok(vec![Value::Int(2)]), 1));
+    for name in &["expired", "fresh"] {
+        assert_eq!(runs(name, 1), (Ok(vec![Value::Int(1)]), 1));
+        assert_eq!(runs(name, 1), (Ok(vec![Value::Int(1)]), 1));
+    }
+    // Faults are not cached.
+    for _ in 0..2 {
+        assert_eq!(runs("failing", 1), (Err(Fault::new(1, "Failed")), 1));
+    }
+}
+
+#[test]
+fn runs_jobs_in_the_background() {
+    let (release, released) = mpsc::channel::<()>();
+    let released = Mutex::new(released);
+    let mut server = Server::new();
+    server.register_job("sum", move |params| {
+        released.lock().unwrap().recv().unwrap();
+        let sum = params.iter().filter_map(|param| match *param {
+            Value::Int(n) => Some(n),
+            _ => None,
+        });
+        Ok(vec![Value::Int(sum.sum())])
+    });
+    let id = match call(&server, "sum", vec![Value::Int(2), Value::Int(3)]) {
+        Ok(ref params) if params.len() == 1 => params[0].clone(),
+        response => panic!("Expected a job id, got {:?}", response),
+    };
+    let status = || call(&server, "sum.status", vec![id.clone()]);
+    let result = || call(&server, "sum.result", vec![id.clone()]);
+    assert_eq!(status(), Ok(vec![Value::String("running".into())]));
+    assert_eq!(result().unwrap_err().code, Fault::INVALID_PARAMS);
+
+    release.send(()).unwrap();
+    let started = Instant::now();
+    while status() != Ok(vec![Value::String("done".into())]) {
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "Job never finished"
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(result(), Ok(vec![Value::Int(5)]));
+    // Results are given once.
+    assert_eq!(result().unwrap_err().code, Fault::INVALID_PARAMS);
+    assert_eq!(status().unwrap_err().code, Fault::INVALID_PARAMS);
+}
+
+// The response in the body of the reply to `request`.
+fn respond(server: &Server, request: &TestRequest) -> Response {
+    let reply = server.handle_http(request);
+    assert_eq!(reply.status, 200);
+    parse::response(&reply.body[..]).expect("Failed to parse reply")
+}
+
+fn adding_server() -> Server {
+    let mut server = Server::new();
+    server.register_simple("add", |(a, b): (i32, i32)| Ok(a + b));
+    server
+}
+
+fn add(a: i32, b: i32) -> TestRequest {
+    TestRequest::call("add", vec![Value::Int(a), Value::Int(b)])
+}
+
+#[test]
+fn answers_calls_with_registered_handlers() {
+    let mut server = adding_server();
+    server.register_fallible("parse", |text: String| {
+        text.parse::<i32>()
+            .map_err(|err| Error::Io(io::Error::new(io::ErrorKind::InvalidInput, err)))
+    });
+    assert_eq!(respond(&server, &add(2, 3)), Ok(vec![Value::Int(5)]));
+    let request = TestRequest::call("parse", vec![Value::String("12".into())]);
+    assert_eq!(respond(&server, &request), Ok(vec![Value::Int(12)]));
+    let request = TestRequest::call("parse", vec![Value::String("twelve".into())]);
+    assert_eq!(
+        respond(&server, &request).unwrap_err().code,
+        Fault::INTERNAL_ERROR
+    );
+    let request = TestRequest::call("add", vec![Value::String("two".into())]);
+    assert_eq!(
+        respond(&server, &request).unwrap_err().code,
+        Fault::INVALID_PARAMS
+    );
+    let response = respond(&server, &TestRequest::post("<methodCall>"));
+    assert_eq!(response.unwrap_err().code, Fault::PARSE_ERROR);
+}
+
+// Counts the calls it is handed before passing them on.
+fn counting(count: &Arc<AtomicUsize>) -> impl Fn(&HttpRequest, Call, Next) -> Response {
+    let count = Arc::clone(count);
+    move |_: &HttpRequest, call: Call, next: Next| {
+        count.fetch_add(1, Ordering::SeqCst);
+        next.run(call)
+    }
+}
+
+#[test]
+fn runs_cached_calls_through_middleware() {
+    let handled = Arc::new(AtomicUsize::new(0));
+    let seen = Arc::new(AtomicUsize::new(0));
+    let mut server = Server::new();
+    let counter = Arc::clone(&handled);
+    server.register_value("count", move |_| {
+        let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+        Ok(vec![Value::Int(count as i32)])
+    });
+    server.set_cacheable("count", Duration::from_secs(60));
+    server.add_middleware(counting(&seen));
+    let request = TestRequest::call("count", vec![]);
+    assert_eq!(respond(&server, &request), Ok(vec![Value::Int(1)]));
+    assert_eq!(respond(&server, &request), Ok(vec![Value::Int(1)]));
+    assert_eq!(handled.load(Ordering::SeqCst), 1);
+    assert_eq!(seen.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn lets_middleware_change_cached_responses() {
+    let mut server = Server::new();
+    server.register_value("answer", |_| Ok(vec![Value::Int(42)]));
+    server.set_cacheable("answer", Duration::from_secs(60));
+    server.add_middleware(|request: &HttpRequest, call: Call, next: Next| {
+        let response = next.run(call);
+        match request.header("X-Negate") {
+            Some(_) => Ok(vec![Value::Int(-42)]),
+            None => response,
+        }
+    });
+    let request = TestRequest::call("answer", vec![]);
+    assert_eq!(respond(&server, &request), Ok(vec![Value::Int(42)]));
+    let negated = request.clone().header("X-Negate", "1");
+    assert_eq!(respond(&server, &negated), Ok(vec![Value::Int(-42)]));
+    assert_eq!(respond(&server, &request), Ok(vec![Value::Int(42)]));
+}
+
+#[test]
+fn runs_calls_to_unknown_methods_through_middleware() {
+    let seen = Arc::new(AtomicUsize::new(0));
+    let mut server = Server::new();
+    server.add_middleware(counting(&seen));
+    server.add_middleware(|_: &HttpRequest, call: Call, next: Next| {
+        if call.name == "hidden" {
+            return Err(Fault::new(403, "Hidden"));
+        }
+        next.run(call)
+    });
+    let response = respond(&server, &TestRequest::call("missing", vec![]));
+    assert_eq!(response.unwrap_err().code, Fault::METHOD_NOT_FOUND);
+    let response = respond(&server, &TestRequest::call("hidden", vec![]));
+    assert_eq!(response, Err(Fault::new(403, "Hidden")));
+    assert_eq!(seen.load(Ordering::SeqCst), 2);
+}
+
+// Keeps what it is told about calls.
+#[derive(Default)]
+struct Calls(Mutex<Vec<(String, Outcome)>>);
+
+impl Observer for Arc<Calls> {
+    fn on_call(&self, method: &str, _duration: Duration, outcome: Outcome) {
+        self.0.lock().unwrap().push((method.to_owned(), outcome));
+    }
+}
+
+#[test]
+fn tells_observers_about_every_call() {
+    let calls = Arc::new(Calls::default());
+    let mut server = Server::new();
+    server.register_value("answer", |_| Ok(vec![Value::Int(42)]));
+    server.register_value("fail", |_| Err(Fault::new(7, "Failed")));
+    server.set_cacheable("answer", Duration::from_secs(60));
+    server.add_observer(Arc::clone(&calls));
+    let request = TestRequest::call("answer", vec![]);
+    for _ in 0..2 {
+        assert_eq!(respond(&server, &request), Ok(vec![Value::Int(42)]));
+    }
+    assert!(respond(&server, &TestRequest::call("fail", vec![])).is_err());
+    assert!(respond(&server, &TestRequest::call("missing", vec![])).is_err());
+    let calls = calls.0.lock().unwrap();
+    assert_eq!(
+        *calls,
+        vec![
+            (String::from("answer"), Outcome::Success),
+            (String::from("answer"), Outcome::Success),
+            (String::from("fail"), Outcome::Fault(7)),
+            (
+                String::from("unknown"),
+                Outcome::Fault(Fault::METHOD_NOT_FOUND)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn accepts_connections_without_nagle() {
+    let listener = Server::new()
+        .listen(&"127.0.0.1:0".parse().unwrap())
+        .expect("Failed to bind");
+    let _client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (stream, _) = listener.accept().unwrap();
+    assert!(stream.nodelay().unwrap());
+}
+
+#[cfg(feature = "encoding")]
+#[test]
+fn reads_bodies_in_the_charset_given() {
+    let mut server = Server::new();
+    server.register_value("echo", Ok);
+    let body = &b"<?xml version=\"1.0\"?><methodCall><methodName>echo</methodName>\
+                  <params><param><value><string>caf\xe9</string></value></param>\
+                  </params></methodCall>"[..];
+    let reply = server
+        .handle_body(body, Some("ISO-8859-1"))
+        .expect("No reply");
+    let response = parse::response(reply.as_bytes()).expect("Failed to parse reply");
+    assert_eq!(response, Ok(vec![Value::String(String::from("café"))]));
+}
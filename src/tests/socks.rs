@@ -0,0 +1,85 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::super::client::Client;
+use super::super::server::Server;
+use super::super::socks::Socks5Proxy;
+use super::super::Url;
+
+// The hosts and ports a proxy was asked to connect to.
+type Targets = Arc<Mutex<Vec<(String, u16)>>>;
+
+// A SOCKS5 proxy wanting `user:pass`, which connects whatever host it is
+// asked for to the same port on the loopback interface, keeping the hosts
+// and ports it was asked for.
+fn proxy() -> (SocketAddr, Targets) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let addr = listener.local_addr().unwrap();
+    let targets = Arc::new(Mutex::new(Vec::new()));
+    let seen = Arc::clone(&targets);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let seen = Arc::clone(&seen);
+            thread::spawn(move || tunnel(stream?, &seen));
+        }
+        Ok::<_, io::Error>(())
+    });
+    (addr, targets)
+}
+
+fn tunnel(mut client: TcpStream, seen: &Mutex<Vec<(String, u16)>>) -> io::Result<()> {
+    let mut greeting = [0; 3];
+    client.read_exact(&mut greeting)?;
+    assert_eq!(greeting, [5, 1, 2]);
+    client.write_all(&[5, 2])?;
+    let mut credentials = vec![0; 2 + 4 + 1 + 4];
+    client.read_exact(&mut credentials)?;
+    let accepted = credentials == b"\x01\x04user\x04pass";
+    client.write_all(&[1, if accepted { 0 } else { 1 }])?;
+    if !accepted {
+        return Ok(());
+    }
+    let mut request = [0; 5];
+    client.read_exact(&mut request)?;
+    assert_eq!(request[..4], [5, 1, 0, 3]);
+    let mut host = vec![0; usize::from(request[4]) + 2];
+    client.read_exact(&mut host)?;
+    let port = u16::from_be_bytes([host[host.len() - 2], host[host.len() - 1]]);
+    host.truncate(host.len() - 2);
+    seen.lock()
+        .unwrap()
+        .push((String::from_utf8(host).unwrap(), port));
+    let mut upstream = TcpStream::connect(("127.0.0.1", port))?;
+    client.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0])?;
+    let mut replies = upstream.try_clone()?;
+    let mut requests = client.try_clone()?;
+    thread::spawn(move || io::copy(&mut requests, &mut upstream));
+    io::copy(&mut replies, &mut client).map(|_| ())
+}
+
+#[test]
+fn calls_through_socks5_proxies() {
+    let mut server = Server::new();
+    server.register_simple("add", |(a, b): (i32, i32)| Ok(a + b));
+    let mut listening = server
+        .bind_hyper(&"127.0.0.1:0".parse().unwrap())
+        .expect("Failed to bind server");
+    let uri = Url::parse(&format!("http://rpc.internal:{}/", listening.socket.port())).unwrap();
+    let (addr, targets) = proxy();
+    let mut proxy = Socks5Proxy::new("127.0.0.1", addr.port());
+    let mut client = Client::new().unwrap();
+
+    proxy.set_credentials("user", "guess");
+    client.set_socks_proxy(Some(proxy.clone()));
+    assert!(client.call::<_, _, i32>(&uri, "add", (2, 3)).is_err());
+
+    proxy.set_credentials("user", "pass");
+    client.set_socks_proxy(Some(proxy));
+    assert_eq!(client.call(&uri, "add", (2, 3)).unwrap(), Ok(5));
+    // The proxy resolves the host, not the client.
+    let expected = (String::from("rpc.internal"), listening.socket.port());
+    assert_eq!(*targets.lock().unwrap(), [expected]);
+    listening.close().unwrap();
+}
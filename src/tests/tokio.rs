@@ -0,0 +1,397 @@
+use std::future;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::Poll;
+use std::thread;
+use std::time::Duration;
+use tokio_lib::runtime;
+
+use super::super::limits::ServerLimits;
+use super::super::observer::{Observer, Outcome};
+use super::super::server::{PanicPolicy, Server};
+use super::super::xmlfmt::{Response, Value};
+
+// Serves `server` on a runtime of its own for the rest of the tests.
+fn serve(server: Server) -> SocketAddr {
+    let bound = server
+        .bind_async(&"127.0.0.1:0".parse().unwrap())
+        .expect("Failed to bind server");
+    let addr = bound.local_addr();
+    thread::spawn(move || {
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to start runtime");
+        runtime.block_on(bound.run())
+    });
+    addr
+}
+
+// Sends `request` as is on a connection of its own, and reads until the
+// server closes it.
+fn exchange(addr: SocketAddr, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    stream.write_all(request).unwrap();
+    let mut reply = Vec::new();
+    // The server may close the connection before reading all of a request
+    // it refuses.
+    let _ = stream.read_to_end(&mut reply);
+    String::from_utf8_lossy(&reply).into_owned()
+}
+
+// Like `exchange`, sending `parts` in writes of their own, a moment apart,
+// so that the server reads them apart.
+fn exchange_parts(addr: SocketAddr, parts: &[&[u8]]) -> String {
+    let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    for part in parts {
+        stream.write_all(part).unwrap();
+        stream.flush().unwrap();
+        thread::sleep(Duration::from_millis(20));
+    }
+    let mut reply = Vec::new();
+    let _ = stream.read_to_end(&mut reply);
+    String::from_utf8_lossy(&reply).into_owned()
+}
+
+fn post(body: &str) -> Vec<u8> {
+    format!(
+        "POST / HTTP/1.1\r\nConnection: close\r\nContent-Type: text/xml\r\n\
+         Content-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    )
+    .into_bytes()
+}
+
+fn call_xml(method: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\"?><methodCall><methodName>{}</methodName>\
+         <params></params></methodCall>",
+        method
+    )
+}
+
+#[derive(Default)]
+struct Counter(AtomicUsize);
+
+impl Observer for Arc<Counter> {
+    fn on_call(&self, _method: &str, _duration: Duration, outcome: Outcome) {
+        assert_eq!(outcome, Outcome::Success);
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn answers_async_handlers() {
+    let mut server = Server::new();
+    server.register_async("hello", |_| {
+        Box::pin(future::ready(Ok(vec![Value::String("world".into())])))
+    });
+    let addr = serve(server);
+    let reply = exchange(addr, &post(&call_xml("hello")));
+    assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"), "{}", reply);
+    assert!(reply.contains("<string>world</string>"), "{}", reply);
+}
+
+#[test]
+fn answers_blocking_and_async_handlers_side_by_side() {
+    let mut server = Server::new();
+    server.register_async("hello", |_| {
+        Box::pin(future::ready(Ok(vec![Value::String("world".into())])))
+    });
+    server.register_value("echo", Ok);
+    let addr = serve(server);
+    let reply = exchange(addr, &post(&call_xml("echo")));
+    assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"), "{}", reply);
+    assert!(!reply.contains("<fault>"), "{}", reply);
+    // The name is found after the parameters too.
+    let body = "<?xml version=\"1.0\"?><methodCall><params></params>\
+                <methodName>hello</methodName></methodCall>";
+    let reply = exchange(addr, &post(body));
+    assert!(reply.contains("<string>world</string>"), "{}", reply);
+}
+
+#[test]
+fn records_async_calls() {
+    let counter = Arc::new(Counter::default());
+    let mut server = Server::new();
+    server.register_async("hello", |_| Box::pin(future::ready(Ok(vec![]))));
+    server.add_observer(Arc::clone(&counter));
+    let addr = serve(server);
+    exchange(addr, &post(&call_xml("hello")));
+    exchange(addr, &post(&call_xml("hello")));
+    assert_eq!(counter.0.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn answers_panicking_async_handlers_with_faults() {
+    let mut server = Server::new();
+    server.set_panic_policy(PanicPolicy::FaultWithMessage);
+    server.register_async("polled", |_| {
+        Box::pin(future::poll_fn(|_| -> Poll<Response> {
+            panic!("polled too far")
+        }))
+    });
+    server.register_async("called", |_| panic!("called too soon"));
+    let addr = serve(server);
+    let reply = exchange(addr, &post(&call_xml("polled")));
+    assert!(reply.contains("<name>faultCode</name>"), "{}", reply);
+    assert!(
+        reply.contains("Handler panicked: polled too far"),
+        "{}",
+        reply
+    );
+    let reply = exchange(addr, &post(&call_xml("called")));
+    assert!(
+        reply.contains("Handler panicked: called too soon"),
+        "{}",
+        reply
+    );
+}
+
+#[test]
+fn refuses_requests_over_the_memory_budget() {
+    let mut server = Server::new();
+    server.register_async("hello", |_| Box::pin(future::ready(Ok(vec![]))));
+    server.set_memory_budget(4096);
+    let addr = serve(server);
+    let padding = " ".repeat(16 * 1024);
+    let reply = exchange(addr, &post(&format!("{}{}", call_xml("hello"), padding)));
+    assert!(reply.starts_with("HTTP/1.1 503 "), "{}", reply);
+    // What the refused request held is given back.
+    let reply = exchange(addr, &post(&call_xml("hello")));
+    assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"), "{}", reply);
+}
+
+const CHUNKED_HEAD: &[u8] = b"POST / HTTP/1.1\r\nConnection: close\r\nContent-Type: text/xml\r\n\
+                              Transfer-Encoding: chunked\r\n\r\n";
+
+fn echo_server() -> Server {
+    let mut server = Server::new();
+    server.register_value("echo", Ok);
+    server
+}
+
+#[test]
+fn reads_chunks_split_across_reads() {
+    let addr = serve(echo_server());
+    let call = call_xml("echo").replace(
+        "<params></params>",
+        "<params><param><value><string>split</string></value></param></params>",
+    );
+    let (first, second) = call.as_bytes().split_at(40);
+    let first_size = format!("{:x}", first.len());
+    let second_size = format!("{:X};ext=1\r\n", second.len());
+    let reply = exchange_parts(
+        addr,
+        &[
+            CHUNKED_HEAD,
+            first_size.as_bytes(),
+            b"\r\n",
+            &first[..10],
+            &first[10..],
+            b"\r",
+            b"\n",
+            second_size.as_bytes(),
+            second,
+            b"\r\n0\r\nX-Trailer: 1\r\n",
+            b"\r\n",
+        ],
+    );
+    assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"), "{}", reply);
+    assert!(reply.contains("<string>split</string>"), "{}", reply);
+}
+
+#[test]
+fn refuses_chunks_over_the_body_limit() {
+    let mut server = echo_server();
+    server.set_limits(ServerLimits {
+        max_body: Some(1024),
+        ..ServerLimits::default()
+    });
+    let addr = serve(server);
+    // Refused on the size line, before the chunk is sent.
+    let reply = exchange_parts(addr, &[CHUNKED_HEAD, b"401\r\n"]);
+    assert!(reply.starts_with("HTTP/1.1 413 "), "{}", reply);
+    let reply = exchange_parts(addr, &[CHUNKED_HEAD, b"ffffffffffffffffffff\r\n"]);
+    assert!(reply.starts_with("HTTP/1.1 400 "), "{}", reply);
+}
+
+#[test]
+fn refuses_chunk_sizes_that_are_not_hex() {
+    let addr = serve(echo_server());
+    for size in &[&b"+5\r\n"[..], b"-0\r\n", b"\r\n", b"5g\r\n"] {
+        let reply = exchange_parts(addr, &[CHUNKED_HEAD, size]);
+        assert!(reply.starts_with("HTTP/1.1 400 "), "{}", reply);
+    }
+}
+
+#[test]
+fn refuses_endless_trailers() {
+    let addr = serve(echo_server());
+    let trailer = b"X-Trailer: and on and on\r\n".repeat(4096);
+    let reply = exchange_parts(addr, &[CHUNKED_HEAD, b"0\r\n", &trailer]);
+    assert!(reply.starts_with("HTTP/1.1 431 "), "{}", reply);
+}
+
+#[test]
+fn requires_a_length_on_posts() {
+    let addr = serve(echo_server());
+    let reply = exchange(addr, b"POST / HTTP/1.1\r\nConnection: close\r\n\r\n");
+    assert!(reply.starts_with("HTTP/1.1 411 "), "{}", reply);
+}
+
+#[test]
+fn refuses_lengths_that_disagree() {
+    let addr = serve(echo_server());
+    let body = echo_xml("twice");
+    let request = |lengths: &[String]| {
+        let mut head = String::from("POST / HTTP/1.1\r\nConnection: close\r\n");
+        for length in lengths {
+            head += &format!("Content-Length: {}\r\n", length);
+        }
+        format!("{}\r\n{}", head, body).into_bytes()
+    };
+    let length = body.len().to_string();
+    let reply = exchange(addr, &request(&[length.clone(), length.clone()]));
+    assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"), "{}", reply);
+    let reply = exchange(addr, &request(&[length.clone(), String::from("3")]));
+    assert!(reply.starts_with("HTTP/1.1 400 "), "{}", reply);
+    let reply = exchange(addr, &request(&[format!("+{}", length)]));
+    assert!(reply.starts_with("HTTP/1.1 400 "), "{}", reply);
+}
+
+fn echo_xml(text: &str) -> String {
+    call_xml("echo").replace(
+        "<params></params>",
+        &format!(
+            "<params><param><value><string>{}</string></value></param></params>",
+            text
+        ),
+    )
+}
+
+#[test]
+fn keeps_connections_alive_until_closed() {
+    let addr = serve(echo_server());
+    let first = echo_xml("first");
+    let first = format!(
+        "POST / HTTP/1.1\r\nContent-Type: text/xml\r\nContent-Length: {}\r\n\r\n{}",
+        first.len(),
+        first
+    );
+    let reply = exchange_parts(addr, &[first.as_bytes(), &post(&echo_xml("second"))]);
+    assert_eq!(reply.matches("HTTP/1.1 200 OK\r\n").count(), 2, "{}", reply);
+    assert_eq!(
+        reply.matches("Connection: close\r\n").count(),
+        1,
+        "{}",
+        reply
+    );
+    let (first, second) = reply.split_at(reply.find("<string>second").unwrap());
+    assert!(first.contains("<string>first</string>"), "{}", reply);
+    assert!(
+        second.trim_end().ends_with("</methodResponse>"),
+        "{}",
+        reply
+    );
+}
+
+#[test]
+fn answers_http_1_0_in_kind() {
+    let addr = serve(echo_server());
+    let body = echo_xml("old");
+    let request = format!(
+        "POST / HTTP/1.0\r\nContent-Type: text/xml\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    // The connection is closed after the reply, or this would not return.
+    let reply = exchange(addr, request.as_bytes());
+    assert!(reply.starts_with("HTTP/1.0 200 OK\r\n"), "{}", reply);
+    assert!(reply.contains("Connection: close\r\n"), "{}", reply);
+    assert!(reply.contains("<string>old</string>"), "{}", reply);
+}
+
+#[test]
+fn sends_100_continue_before_reading_bodies() {
+    let addr = serve(echo_server());
+    let body = echo_xml("awaited");
+    let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    let head = format!(
+        "POST / HTTP/1.1\r\nConnection: close\r\nContent-Type: text/xml\r\n\
+         Expect: 100-continue\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).unwrap();
+    let mut interim = [0; 25];
+    stream.read_exact(&mut interim).unwrap();
+    assert_eq!(&interim[..], b"HTTP/1.1 100 Continue\r\n\r\n");
+    stream.write_all(body.as_bytes()).unwrap();
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).unwrap();
+    assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"), "{}", reply);
+    assert!(reply.contains("<string>awaited</string>"), "{}", reply);
+}
+
+#[test]
+fn keeps_the_idle_timeout_running_past_100_continue() {
+    let mut server = echo_server();
+    server.set_idle_timeout(Duration::from_millis(400));
+    let addr = serve(server);
+    let body = echo_xml("late");
+    let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    thread::sleep(Duration::from_millis(300));
+    let head = format!(
+        "POST / HTTP/1.1\r\nConnection: close\r\nContent-Type: text/xml\r\n\
+         Expect: 100-continue\r\nContent-Length: {}\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).unwrap();
+    let mut interim = [0; 25];
+    stream.read_exact(&mut interim).unwrap();
+    assert_eq!(&interim[..], b"HTTP/1.1 100 Continue\r\n\r\n");
+    // Past the timeout counted from the connection, though not from the
+    // `100 Continue`.
+    thread::sleep(Duration::from_millis(300));
+    let _ = stream.write_all(body.as_bytes());
+    let mut reply = Vec::new();
+    let _ = stream.read_to_end(&mut reply);
+    assert!(reply.is_empty(), "{}", String::from_utf8_lossy(&reply));
+}
+
+#[test]
+fn refuses_long_awaited_bodies_with_417() {
+    let mut server = echo_server();
+    server.set_limits(ServerLimits {
+        max_body: Some(64),
+        ..ServerLimits::default()
+    });
+    server.set_expectation_failed(true);
+    let addr = serve(server);
+    let head = |expect: &str| {
+        format!(
+            "POST / HTTP/1.1\r\nConnection: close\r\nContent-Type: text/xml\r\n\
+             {}Content-Length: 1000\r\n\r\n",
+            expect
+        )
+    };
+    let reply = exchange(addr, head("Expect: 100-continue\r\n").as_bytes());
+    assert!(reply.starts_with("HTTP/1.1 417 "), "{}", reply);
+    let reply = exchange(addr, head("").as_bytes());
+    assert!(reply.starts_with("HTTP/1.1 413 "), "{}", reply);
+}
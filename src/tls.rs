@@ -0,0 +1,699 @@
+//! TLS for the client and the hyper server backend, on rustls
+//! (`tls-rustls`) or the platform's TLS stack (`tls-native`).
+//!
+//! With both features enabled, `TlsConnector::new` picks the native stack,
+//! since that is the one that honours system-wide CA configuration.
+
+#[cfg(feature = "tls-native")]
+use native_tls;
+#[cfg(feature = "tls-rustls")]
+use rustls;
+
+use hyper;
+use hyper::net::{HttpStream, NetworkStream, SslClient, SslServer};
+#[cfg(feature = "tls-rustls")]
+use rustls::pki_types::pem::PemObject;
+#[cfg(feature = "tls-rustls")]
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use std::cell::RefCell;
+#[cfg(feature = "tls-rustls")]
+use std::collections::HashMap;
+#[cfg(feature = "tls-rustls")]
+use std::convert::TryFrom;
+#[cfg(feature = "tls-rustls")]
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr};
+#[cfg(feature = "tls-rustls")]
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+#[cfg(feature = "tls-rustls")]
+use std::time::{Instant, SystemTime};
+
+use super::error::{Error, Result};
+
+// An established session, whichever library runs it.
+trait Session: Read + Write + Send {
+    fn socket(&mut self) -> &mut HttpStream;
+
+    // The DER of the end-entity certificate the peer presented, if any.
+    fn peer_certificate(&self) -> Option<Vec<u8>>;
+}
+
+#[cfg(feature = "tls-native")]
+impl Session for native_tls::TlsStream<HttpStream> {
+    fn socket(&mut self) -> &mut HttpStream {
+        self.get_mut()
+    }
+
+    fn peer_certificate(&self) -> Option<Vec<u8>> {
+        match native_tls::TlsStream::peer_certificate(self) {
+            Ok(Some(cert)) => cert.to_der().ok(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "tls-rustls")]
+impl Session for rustls::StreamOwned<rustls::ClientConnection, HttpStream> {
+    fn socket(&mut self) -> &mut HttpStream {
+        &mut self.sock
+    }
+
+    fn peer_certificate(&self) -> Option<Vec<u8>> {
+        let certs = self.conn.peer_certificates()?;
+        certs.first().map(|cert| cert.as_ref().to_vec())
+    }
+}
+
+#[cfg(feature = "tls-rustls")]
+impl Session for rustls::StreamOwned<rustls::ServerConnection, HttpStream> {
+    fn socket(&mut self) -> &mut HttpStream {
+        &mut self.sock
+    }
+
+    fn peer_certificate(&self) -> Option<Vec<u8>> {
+        let certs = self.conn.peer_certificates()?;
+        certs.first().map(|cert| cert.as_ref().to_vec())
+    }
+}
+
+thread_local! {
+    static PEER_CERTIFICATE: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+}
+
+/// The DER-encoded certificate presented by the client whose request is
+/// being handled on this thread, for handlers that authorize by identity.
+///
+/// It is only set by `Server::bind_hyper_tls`, and only once verified, so
+/// with an acceptor from `TlsAcceptor::require_client_cert` or another
+/// that verifies client certificates.
+pub fn peer_certificate() -> Option<Vec<u8>> {
+    PEER_CERTIFICATE.with(|current| current.borrow().clone())
+}
+
+// Makes `cert` the peer certificate while `f` runs.
+#[cfg(feature = "hyper-server")]
+pub(crate) fn scope<F: FnOnce() -> R, R>(cert: Option<Vec<u8>>, f: F) -> R {
+    struct Restore(Option<Vec<u8>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            PEER_CERTIFICATE.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+
+    let previous = PEER_CERTIFICATE.with(|current| current.replace(cert));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// A TLS stream as handed to hyper, which needs it to be cloneable.
+#[derive(Clone)]
+pub struct TlsStream(Arc<Mutex<Box<dyn Session>>>);
+
+impl TlsStream {
+    fn new<S: Session + 'static>(session: S) -> TlsStream {
+        TlsStream(Arc::new(Mutex::new(Box::new(session))))
+    }
+
+    fn with<F: FnOnce(&mut Box<dyn Session>) -> R, R>(&self, f: F) -> R {
+        // A panic while holding the lock leaves the session unusable, but
+        // hyper drops the connection in that case anyway.
+        let mut session = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        f(&mut session)
+    }
+
+    /// The DER-encoded certificate the peer presented, if any.
+    pub fn peer_certificate(&self) -> Option<Vec<u8>> {
+        self.with(|session| session.peer_certificate())
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.with(|session| session.read(buf))
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.with(|session| session.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.with(|session| session.flush())
+    }
+}
+
+impl NetworkStream for TlsStream {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        self.with(|session| session.socket().peer_addr())
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.with(|session| session.socket().set_read_timeout(dur))
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.with(|session| session.socket().set_write_timeout(dur))
+    }
+
+    fn close(&mut self, how: Shutdown) -> io::Result<()> {
+        self.with(|session| session.socket().close(how))
+    }
+}
+
+fn handshake_error<E>(err: E) -> hyper::Error
+where
+    E: ::std::error::Error + Send + Sync + 'static,
+{
+    hyper::Error::Ssl(Box::new(err))
+}
+
+#[cfg(feature = "tls-native")]
+fn native_handshake<S>(
+    result: ::std::result::Result<S, native_tls::HandshakeError<HttpStream>>,
+) -> hyper::Result<S> {
+    match result {
+        Ok(stream) => Ok(stream),
+        Err(native_tls::HandshakeError::Failure(err)) => Err(handshake_error(err)),
+        Err(native_tls::HandshakeError::WouldBlock(_)) => Err(hyper::Error::Io(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "TLS handshake timed out",
+        ))),
+    }
+}
+
+#[derive(Clone)]
+enum ClientBackend {
+    #[cfg(feature = "tls-native")]
+    Native(native_tls::TlsConnector),
+    #[cfg(feature = "tls-rustls")]
+    Rustls(Arc<rustls::ClientConfig>),
+}
+
+/// Client-side TLS settings, passed to `Client::set_tls`.
+#[derive(Clone)]
+pub struct TlsConnector(ClientBackend);
+
+impl TlsConnector {
+    /// Verifies servers against the platform's trust store with the native
+    /// stack, or the Mozilla root program with rustls.
+    pub fn new() -> Result<TlsConnector> {
+        default_connector()
+    }
+
+    #[cfg(feature = "tls-native")]
+    pub fn native(connector: native_tls::TlsConnector) -> TlsConnector {
+        TlsConnector(ClientBackend::Native(connector))
+    }
+
+    #[cfg(feature = "tls-rustls")]
+    pub fn rustls(config: Arc<rustls::ClientConfig>) -> TlsConnector {
+        TlsConnector(ClientBackend::Rustls(config))
+    }
+
+    /// Like `new`, but presents a client certificate to servers that ask
+    /// for one. `cert_pem` holds the certificate chain, leaf first, and
+    /// `key_pem` its PKCS#8 private key.
+    pub fn with_client_cert(cert_pem: &[u8], key_pem: &[u8]) -> Result<TlsConnector> {
+        client_cert_connector(cert_pem, key_pem)
+    }
+
+    /// Like `new`, but trusts only the CAs in `ca_pem`, as for servers
+    /// with certificates from a private CA.
+    pub fn with_root_certificates(ca_pem: &[u8]) -> Result<TlsConnector> {
+        private_ca_connector(ca_pem)
+    }
+
+    /// Accepts any certificate, valid or not, for any host. This gives up
+    /// the protection TLS offers against anyone in between, so it is only
+    /// fit for testing against servers with self-signed certificates.
+    pub fn danger_accept_invalid_certs() -> Result<TlsConnector> {
+        unverified_connector()
+    }
+}
+
+fn tls_error<E>(err: E) -> Error
+where
+    E: ::std::error::Error + Send + Sync + 'static,
+{
+    Error::Tls(Box::new(err))
+}
+
+#[cfg(feature = "tls-rustls")]
+fn read_certs(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>> {
+    CertificateDer::pem_slice_iter(pem)
+        .collect::<::std::result::Result<_, _>>()
+        .map_err(tls_error)
+}
+
+#[cfg(feature = "tls-rustls")]
+fn read_key(pem: &[u8]) -> Result<PrivateKeyDer<'static>> {
+    PrivateKeyDer::from_pem_slice(pem).map_err(tls_error)
+}
+
+#[cfg(all(feature = "tls-rustls", not(feature = "tls-native")))]
+fn mozilla_roots() -> rustls::RootCertStore {
+    rustls::RootCertStore {
+        roots: ::webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    }
+}
+
+#[cfg(feature = "tls-native")]
+fn default_connector() -> Result<TlsConnector> {
+    native_tls::TlsConnector::new()
+        .map(TlsConnector::native)
+        .map_err(tls_error)
+}
+
+#[cfg(not(feature = "tls-native"))]
+fn default_connector() -> Result<TlsConnector> {
+    let config = rustls::ClientConfig::builder()
+        .with_root_certificates(mozilla_roots())
+        .with_no_client_auth();
+    Ok(TlsConnector::rustls(Arc::new(config)))
+}
+
+#[cfg(feature = "tls-native")]
+fn client_cert_connector(cert_pem: &[u8], key_pem: &[u8]) -> Result<TlsConnector> {
+    let identity = native_tls::Identity::from_pkcs8(cert_pem, key_pem).map_err(tls_error)?;
+    native_tls::TlsConnector::builder()
+        .identity(identity)
+        .build()
+        .map(TlsConnector::native)
+        .map_err(tls_error)
+}
+
+#[cfg(not(feature = "tls-native"))]
+fn client_cert_connector(cert_pem: &[u8], key_pem: &[u8]) -> Result<TlsConnector> {
+    let config = rustls::ClientConfig::builder()
+        .with_root_certificates(mozilla_roots())
+        .with_client_auth_cert(read_certs(cert_pem)?, read_key(key_pem)?)
+        .map_err(tls_error)?;
+    Ok(TlsConnector::rustls(Arc::new(config)))
+}
+
+#[cfg(feature = "tls-native")]
+fn private_ca_connector(ca_pem: &[u8]) -> Result<TlsConnector> {
+    let mut builder = native_tls::TlsConnector::builder();
+    builder.disable_built_in_roots(true);
+    for cert in split_pem(ca_pem) {
+        builder.add_root_certificate(native_tls::Certificate::from_pem(cert).map_err(tls_error)?);
+    }
+    builder.build().map(TlsConnector::native).map_err(tls_error)
+}
+
+// The PEM blocks in `pem`, one per certificate, since native-tls reads
+// only the first.
+#[cfg(feature = "tls-native")]
+fn split_pem(pem: &[u8]) -> Vec<&[u8]> {
+    const END: &[u8] = b"-----END CERTIFICATE-----";
+    let mut blocks = Vec::new();
+    let mut rest = pem;
+    while let Some(at) = rest.windows(END.len()).position(|window| window == END) {
+        blocks.push(&rest[..at + END.len()]);
+        rest = &rest[at + END.len()..];
+    }
+    blocks
+}
+
+#[cfg(not(feature = "tls-native"))]
+fn private_ca_connector(ca_pem: &[u8]) -> Result<TlsConnector> {
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in read_certs(ca_pem)? {
+        roots.add(cert).map_err(tls_error)?;
+    }
+    let config = rustls::ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(TlsConnector::rustls(Arc::new(config)))
+}
+
+#[cfg(feature = "tls-native")]
+fn unverified_connector() -> Result<TlsConnector> {
+    native_tls::TlsConnector::builder()
+        .danger_accept_invalid_certs(true)
+        .danger_accept_invalid_hostnames(true)
+        .build()
+        .map(TlsConnector::native)
+        .map_err(tls_error)
+}
+
+#[cfg(not(feature = "tls-native"))]
+fn unverified_connector() -> Result<TlsConnector> {
+    let verifier = AnyCertificate(crypto_provider());
+    let config = rustls::ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    Ok(TlsConnector::rustls(Arc::new(config)))
+}
+
+// Takes any certificate for any host, though handshake signatures are
+// still checked, so that the session is at least with whoever holds the key.
+#[cfg(not(feature = "tls-native"))]
+#[derive(Debug)]
+struct AnyCertificate(Arc<rustls::crypto::CryptoProvider>);
+
+#[cfg(not(feature = "tls-native"))]
+impl rustls::client::danger::ServerCertVerifier for AnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer,
+        _intermediates: &[CertificateDer],
+        _server_name: &rustls::pki_types::ServerName,
+        _ocsp_response: &[u8],
+        _now: rustls::pki_types::UnixTime,
+    ) -> ::std::result::Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::danger::ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> ::std::result::Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> ::std::result::Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+impl SslClient for TlsConnector {
+    type Stream = TlsStream;
+
+    fn wrap_client(&self, stream: HttpStream, host: &str) -> hyper::Result<TlsStream> {
+        match self.0 {
+            #[cfg(feature = "tls-native")]
+            ClientBackend::Native(ref connector) => {
+                native_handshake(connector.connect(host, stream)).map(TlsStream::new)
+            }
+            #[cfg(feature = "tls-rustls")]
+            ClientBackend::Rustls(ref config) => {
+                let name = rustls::pki_types::ServerName::try_from(host.to_owned())
+                    .map_err(handshake_error)?;
+                let mut conn = rustls::ClientConnection::new(Arc::clone(config), name)
+                    .map_err(handshake_error)?;
+                let mut stream = stream;
+                while conn.is_handshaking() {
+                    conn.complete_io(&mut stream)?;
+                }
+                Ok(TlsStream::new(rustls::StreamOwned::new(conn, stream)))
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+enum ServerBackend {
+    #[cfg(feature = "tls-native")]
+    Native(native_tls::TlsAcceptor),
+    #[cfg(feature = "tls-rustls")]
+    Rustls(Arc<rustls::ServerConfig>),
+}
+
+/// Server-side TLS settings, passed to `Server::bind_hyper_tls`.
+#[derive(Clone)]
+pub struct TlsAcceptor(ServerBackend);
+
+impl TlsAcceptor {
+    /// Serves the certificate chain in `cert_pem`, leaf first, with its
+    /// PKCS#8 private key in `key_pem`. The native stack is used when
+    /// enabled, as for `TlsConnector::new`.
+    pub fn new(cert_pem: &[u8], key_pem: &[u8]) -> Result<TlsAcceptor> {
+        default_acceptor(cert_pem, key_pem)
+    }
+
+    /// Like `new`, reading the PEM from `cert_path` and `key_path`.
+    pub fn from_files<C, K>(cert_path: C, key_path: K) -> Result<TlsAcceptor>
+    where
+        C: AsRef<::std::path::Path>,
+        K: AsRef<::std::path::Path>,
+    {
+        let cert_pem = ::std::fs::read(cert_path)?;
+        let key_pem = ::std::fs::read(key_path)?;
+        TlsAcceptor::new(&cert_pem, &key_pem)
+    }
+
+    #[cfg(feature = "tls-native")]
+    pub fn native(acceptor: native_tls::TlsAcceptor) -> TlsAcceptor {
+        TlsAcceptor(ServerBackend::Native(acceptor))
+    }
+
+    #[cfg(feature = "tls-rustls")]
+    pub fn rustls(config: Arc<rustls::ServerConfig>) -> TlsAcceptor {
+        TlsAcceptor(ServerBackend::Rustls(config))
+    }
+
+    /// Serves `cert_pem` and `key_pem`, as for `TlsConnector::with_client_cert`,
+    /// and turns away clients without a certificate issued by one of the CAs
+    /// in `client_ca_pem`. Handlers can tell clients apart with
+    /// `tls::peer_certificate`.
+    ///
+    /// Only rustls is supported, as native-tls cannot verify clients.
+    #[cfg(feature = "tls-rustls")]
+    pub fn require_client_cert(
+        cert_pem: &[u8],
+        key_pem: &[u8],
+        client_ca_pem: &[u8],
+    ) -> Result<TlsAcceptor> {
+        let mut roots = rustls::RootCertStore::empty();
+        for cert in read_certs(client_ca_pem)? {
+            roots.add(cert).map_err(tls_error)?;
+        }
+        let verifier = rustls::server::WebPkiClientVerifier::builder(Arc::new(roots))
+            .build()
+            .map_err(tls_error)?;
+        let config = rustls::ServerConfig::builder()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(read_certs(cert_pem)?, read_key(key_pem)?)
+            .map_err(tls_error)?;
+        Ok(TlsAcceptor::rustls(Arc::new(config)))
+    }
+}
+
+#[cfg(feature = "tls-native")]
+fn default_acceptor(cert_pem: &[u8], key_pem: &[u8]) -> Result<TlsAcceptor> {
+    let identity = native_tls::Identity::from_pkcs8(cert_pem, key_pem).map_err(tls_error)?;
+    native_tls::TlsAcceptor::new(identity)
+        .map(TlsAcceptor::native)
+        .map_err(tls_error)
+}
+
+#[cfg(not(feature = "tls-native"))]
+fn default_acceptor(cert_pem: &[u8], key_pem: &[u8]) -> Result<TlsAcceptor> {
+    let config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(read_certs(cert_pem)?, read_key(key_pem)?)
+        .map_err(tls_error)?;
+    Ok(TlsAcceptor::rustls(Arc::new(config)))
+}
+
+#[cfg(feature = "tls-rustls")]
+impl TlsAcceptor {
+    /// Serves each client the certificate for the host name it asks for,
+    /// as set up in `certs`.
+    ///
+    /// Only rustls is supported, as native-tls cannot choose certificates
+    /// by host name.
+    pub fn sni(certs: SniCertificates) -> TlsAcceptor {
+        let config = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(certs));
+        TlsAcceptor::rustls(Arc::new(config))
+    }
+}
+
+// How often certificate files are checked for changes, at most.
+#[cfg(feature = "tls-rustls")]
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+#[cfg(feature = "tls-rustls")]
+fn crypto_provider() -> Arc<rustls::crypto::CryptoProvider> {
+    rustls::crypto::CryptoProvider::get_default()
+        .cloned()
+        .unwrap_or_else(|| Arc::new(rustls::crypto::ring::default_provider()))
+}
+
+#[cfg(feature = "tls-rustls")]
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+#[cfg(feature = "tls-rustls")]
+#[derive(Debug)]
+struct Loaded {
+    key: Arc<rustls::sign::CertifiedKey>,
+    modified: (Option<SystemTime>, Option<SystemTime>),
+    checked: Instant,
+}
+
+// A certificate chain and key read from files, and read again when either
+// file changes.
+#[cfg(feature = "tls-rustls")]
+#[derive(Debug)]
+struct CertFiles {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    loaded: Mutex<Loaded>,
+}
+
+#[cfg(feature = "tls-rustls")]
+impl CertFiles {
+    fn open(cert_path: PathBuf, key_path: PathBuf) -> Result<CertFiles> {
+        let modified = (modified(&cert_path), modified(&key_path));
+        let key = CertFiles::load(&cert_path, &key_path)?;
+        Ok(CertFiles {
+            cert_path,
+            key_path,
+            loaded: Mutex::new(Loaded {
+                key,
+                modified,
+                checked: Instant::now(),
+            }),
+        })
+    }
+
+    fn load(cert_path: &Path, key_path: &Path) -> Result<Arc<rustls::sign::CertifiedKey>> {
+        let certs = read_certs(&fs::read(cert_path)?)?;
+        let key = read_key(&fs::read(key_path)?)?;
+        rustls::sign::CertifiedKey::from_der(certs, key, &crypto_provider())
+            .map(Arc::new)
+            .map_err(tls_error)
+    }
+
+    // The current key, read again first if the files have changed. Files
+    // that fail to load, as while being rewritten, leave the key as it was
+    // until they are checked again.
+    fn key(&self) -> Arc<rustls::sign::CertifiedKey> {
+        let mut loaded = self.loaded.lock().unwrap_or_else(|err| err.into_inner());
+        if loaded.checked.elapsed() >= RELOAD_INTERVAL {
+            loaded.checked = Instant::now();
+            let modified = (modified(&self.cert_path), modified(&self.key_path));
+            if modified != loaded.modified {
+                if let Ok(key) = CertFiles::load(&self.cert_path, &self.key_path) {
+                    loaded.key = key;
+                    loaded.modified = modified;
+                }
+            }
+        }
+        Arc::clone(&loaded.key)
+    }
+}
+
+/// Certificates for `TlsAcceptor::sni`, chosen by the host name clients ask
+/// for with SNI.
+///
+/// They are read from PEM files, which are read again within a second of
+/// changing, so that renewed certificates are served without a restart.
+#[cfg(feature = "tls-rustls")]
+#[derive(Debug, Default)]
+pub struct SniCertificates {
+    hosts: HashMap<String, CertFiles>,
+    fallback: Option<CertFiles>,
+}
+
+#[cfg(feature = "tls-rustls")]
+impl SniCertificates {
+    pub fn new() -> SniCertificates {
+        SniCertificates::default()
+    }
+
+    /// Serves the chain in `cert_path`, leaf first, with the PKCS#8 key in
+    /// `key_path` to clients asking for `host`. A host like `*.example.com`
+    /// covers the names one label below `example.com` that have no
+    /// certificate of their own. Fails if the files cannot be read.
+    pub fn add<H, C, K>(&mut self, host: H, cert_path: C, key_path: K) -> Result<()>
+    where
+        H: Into<String>,
+        C: Into<PathBuf>,
+        K: Into<PathBuf>,
+    {
+        let files = CertFiles::open(cert_path.into(), key_path.into())?;
+        self.hosts.insert(host.into().to_ascii_lowercase(), files);
+        Ok(())
+    }
+
+    /// Serves a certificate to clients that ask for no host name, or one
+    /// without a certificate. Without one, their handshakes fail.
+    pub fn set_fallback<C, K>(&mut self, cert_path: C, key_path: K) -> Result<()>
+    where
+        C: Into<PathBuf>,
+        K: Into<PathBuf>,
+    {
+        self.fallback = Some(CertFiles::open(cert_path.into(), key_path.into())?);
+        Ok(())
+    }
+
+    fn find(&self, host: &str) -> Option<&CertFiles> {
+        let host = host.to_ascii_lowercase();
+        if let Some(files) = self.hosts.get(&host) {
+            return Some(files);
+        }
+        let parent = &host[host.find('.')? + 1..];
+        self.hosts.get(&format!("*.{}", parent))
+    }
+}
+
+#[cfg(feature = "tls-rustls")]
+impl rustls::server::ResolvesServerCert for SniCertificates {
+    fn resolve(
+        &self,
+        client_hello: rustls::server::ClientHello,
+    ) -> Option<Arc<rustls::sign::CertifiedKey>> {
+        client_hello
+            .server_name()
+            .and_then(|host| self.find(host))
+            .or(self.fallback.as_ref())
+            .map(CertFiles::key)
+    }
+}
+
+impl SslServer for TlsAcceptor {
+    type Stream = TlsStream;
+
+    fn wrap_server(&self, stream: HttpStream) -> hyper::Result<TlsStream> {
+        match self.0 {
+            #[cfg(feature = "tls-native")]
+            ServerBackend::Native(ref acceptor) => {
+                native_handshake(acceptor.accept(stream)).map(TlsStream::new)
+            }
+            #[cfg(feature = "tls-rustls")]
+            ServerBackend::Rustls(ref config) => {
+                let mut conn =
+                    rustls::ServerConnection::new(Arc::clone(config)).map_err(handshake_error)?;
+                let mut stream = stream;
+                while conn.is_handshaking() {
+                    conn.complete_io(&mut stream)?;
+                }
+                Ok(TlsStream::new(rustls::StreamOwned::new(conn, stream)))
+            }
+        }
+    }
+}
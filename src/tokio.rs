@@ -0,0 +1,929 @@
+//! A server backend on Tokio, for serving XML-RPC from an async application
+//! without a thread per connection.
+//!
+//! Methods registered with `Server::register_async` are answered on the
+//! runtime. Every other method may block, so it is answered on the
+//! runtime's blocking pool.
+
+use std::future::{self, Future};
+use std::io;
+use std::mem;
+use std::net::{self, SocketAddr};
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
+use tokio_lib::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_lib::net::{TcpListener, TcpStream};
+use tokio_lib::task::{self, JoinHandle};
+use tokio_lib::time::{self, Sleep};
+
+use super::access_log::AccessRecord;
+use super::error::{Error, Result};
+#[cfg(feature = "jsonrpc")]
+use super::jsonrpc;
+use super::listing;
+use super::middleware::{self, HttpRequest};
+use super::server::{expects_continue, Server, Timings, Verified, ALLOW, METRICS_CONTENT_TYPE};
+use super::trace;
+use super::tracecontext;
+use super::xmlfmt::value::ToXml;
+use super::xmlfmt::{parse, Response, Value};
+
+/// What methods registered with `Server::register_async` return.
+pub type HandlerFuture = Pin<Box<dyn Future<Output = Response> + Send>>;
+
+pub(crate) type AsyncHandler = Box<dyn Fn(Vec<Value>) -> HandlerFuture + Send + Sync>;
+
+type AnswerFuture = Pin<Box<dyn Future<Output = Answer> + Send>>;
+
+// Heads longer than this are refused.
+const MAX_HEAD: usize = 64 * 1024;
+
+/// A server bound with `Server::bind_async`, serving once `run` is polled.
+pub struct AsyncBoundServer {
+    server: Arc<Server>,
+    listener: net::TcpListener,
+    addr: SocketAddr,
+}
+
+impl AsyncBoundServer {
+    pub(crate) fn new(server: Server, listener: net::TcpListener) -> Result<AsyncBoundServer> {
+        let addr = listener.local_addr().map_err(Error::Io)?;
+        listener.set_nonblocking(true).map_err(Error::Io)?;
+        Ok(AsyncBoundServer {
+            server: Arc::new(server),
+            listener,
+            addr,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Serves connections, each in a task of its own, until accepting one
+    /// fails. Must be polled within a Tokio runtime.
+    pub fn run(self) -> Serve {
+        Serve {
+            server: self.server,
+            pending: Some(self.listener),
+            listener: None,
+        }
+    }
+}
+
+/// The future `AsyncBoundServer::run` returns.
+pub struct Serve {
+    server: Arc<Server>,
+    // Handed to Tokio once polled, when a runtime is sure to be around.
+    pending: Option<net::TcpListener>,
+    listener: Option<TcpListener>,
+}
+
+impl Future for Serve {
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<()>> {
+        let this = &mut *self;
+        if let Some(listener) = this.pending.take() {
+            this.listener = Some(TcpListener::from_std(listener).map_err(Error::Io)?);
+        }
+        let listener = match this.listener {
+            Some(ref listener) => listener,
+            None => return Poll::Ready(Ok(())),
+        };
+        loop {
+            let (stream, peer) = match listener.poll_accept(cx) {
+                Poll::Ready(Ok(accepted)) => accepted,
+                // The connection was lost before it could be accepted.
+                Poll::Ready(Err(ref err)) if err.kind() == io::ErrorKind::ConnectionAborted => {
+                    continue
+                }
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(Error::Io(err))),
+                Poll::Pending => return Poll::Pending,
+            };
+            if this.server.permits(peer.ip()) {
+                task::spawn(Connection::new(Arc::clone(&this.server), stream, peer));
+            }
+        }
+    }
+}
+
+struct Request {
+    method: String,
+    target: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    http11: bool,
+    keep_alive: bool,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<String> {
+        self.headers
+            .iter()
+            .find(|&(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.clone())
+    }
+
+    fn path(&self) -> &str {
+        self.target.split('?').next().unwrap_or("")
+    }
+}
+
+// What `take_request` found on the buffer.
+enum Taken {
+    Request(Request),
+    // More is to come. `wants_continue` is whether the head is in, and asks
+    // for `100 Continue` before the body is sent.
+    Partial { wants_continue: bool },
+}
+
+// Sent to clients that wait for it before sending a body.
+const CONTINUE: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\n";
+
+// A request whose head is in, while its body comes in.
+struct Head {
+    // The request, as yet without a body.
+    request: Request,
+    wants_continue: bool,
+    framing: Framing,
+}
+
+// How the body of a request is sent.
+enum Framing {
+    Length(usize),
+    Chunked(Dechunk),
+}
+
+// Takes the first request off `buffer`, once it is all there, or fails
+// with the status to refuse it with. The head of a request whose body is
+// still coming in is kept in `head`, so that it is only read once, and
+// chunked bodies are decoded as they come in.
+fn take_request(
+    buffer: &mut Vec<u8>,
+    head: &mut Option<Head>,
+    server: &Server,
+) -> ::std::result::Result<Taken, u16> {
+    let pending = match *head {
+        Some(ref mut pending) => pending,
+        None => match read_head(buffer, server)? {
+            Some(pending) => head.get_or_insert(pending),
+            None => {
+                return Ok(Taken::Partial {
+                    wants_continue: false,
+                })
+            }
+        },
+    };
+    let partial = Taken::Partial {
+        wants_continue: pending.wants_continue,
+    };
+    let body = match pending.framing {
+        Framing::Length(length) if buffer.len() < length => return Ok(partial),
+        Framing::Length(length) => {
+            let rest = buffer.split_off(length);
+            mem::replace(buffer, rest)
+        }
+        Framing::Chunked(ref mut dechunk) => {
+            let used = dechunk.feed(buffer, server.limits().max_body)?;
+            buffer.drain(..used);
+            if !dechunk.is_done() {
+                return Ok(partial);
+            }
+            mem::take(&mut dechunk.body)
+        }
+    };
+    let mut request = head.take().expect("the head is in").request;
+    request.body = body;
+    Ok(Taken::Request(request))
+}
+
+// Takes the head of the first request off `buffer`, once it is all there.
+fn read_head(buffer: &mut Vec<u8>, server: &Server) -> ::std::result::Result<Option<Head>, u16> {
+    let limits = server.limits();
+    let end = match buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+        Some(end) => end,
+        None if buffer.len() > MAX_HEAD => return Err(431),
+        None => return Ok(None),
+    };
+    let head = ::std::str::from_utf8(&buffer[..end]).map_err(|_| 400u16)?;
+    let mut lines = head.split("\r\n");
+    let mut start = lines.next().unwrap_or("").split_whitespace();
+    let (method, target, version) = match (start.next(), start.next(), start.next()) {
+        (Some(method), Some(target), Some(version)) => (method, target, version),
+        _ => return Err(400),
+    };
+    let http11 = match version {
+        "HTTP/1.1" => true,
+        "HTTP/1.0" => false,
+        _ => return Err(505),
+    };
+    let mut headers = Vec::new();
+    for line in lines {
+        let colon = line.find(':').ok_or(400u16)?;
+        headers.push((
+            line[..colon].trim().to_owned(),
+            line[colon + 1..].trim().to_owned(),
+        ));
+    }
+    let bytes = headers
+        .iter()
+        .map(|(name, value)| name.len() + value.len())
+        .sum();
+    if limits.refuses_head(headers.len(), bytes) {
+        return Err(431);
+    }
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|&(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    };
+    let expect = header("Expect");
+    let wants_continue = http11 && expects_continue(expect);
+    let chunked = match header("Transfer-Encoding") {
+        Some(coding) if coding.eq_ignore_ascii_case("chunked") => true,
+        Some(_) => return Err(501),
+        None => false,
+    };
+    let mut lengths = headers
+        .iter()
+        .filter(|&(key, _)| key.eq_ignore_ascii_case("Content-Length"))
+        .map(|(_, value)| value.as_str());
+    let length = match lengths.next() {
+        _ if chunked => 0,
+        // Lengths that disagree leave it to guess where the body ends.
+        Some(length) if lengths.any(|other| other != length) => return Err(400),
+        Some(length) => digits(length, 10).ok_or(400u16)?,
+        None if method == "POST" => return Err(411),
+        None => 0,
+    };
+    if limits.refuses_body(Some(length as u64)) {
+        return Err(server.too_large(expect));
+    }
+    let connection = |option: &str| {
+        header("Connection").is_some_and(|value| {
+            value
+                .split(',')
+                .any(|given| given.trim().eq_ignore_ascii_case(option))
+        })
+    };
+    // HTTP/1.1 connections persist unless closed, HTTP/1.0 ones only when
+    // asked to.
+    let keep_alive = if http11 {
+        !connection("close")
+    } else {
+        connection("keep-alive")
+    };
+    let framing = if chunked {
+        Framing::Chunked(Dechunk::default())
+    } else {
+        Framing::Length(length)
+    };
+    let request = Request {
+        method: method.to_owned(),
+        target: target.to_owned(),
+        http11,
+        keep_alive,
+        headers,
+        body: Vec::new(),
+    };
+    buffer.drain(..end + 4);
+    Ok(Some(Head {
+        request,
+        wants_continue,
+        framing,
+    }))
+}
+
+// Decodes a chunked body as it comes in, looking at each byte once.
+// Trailers are read past and dropped.
+#[derive(Default)]
+struct Dechunk {
+    body: Vec<u8>,
+    state: ChunkState,
+}
+
+#[derive(Default)]
+enum ChunkState {
+    // Before the size line of a chunk.
+    #[default]
+    Size,
+    // Within a chunk, with this many bytes of it to come.
+    Data(usize),
+    // Before the line break that ends a chunk.
+    DataEnd,
+    // After the last chunk, with this many bytes of trailers read.
+    Trailers(usize),
+    Done,
+}
+
+impl Dechunk {
+    fn is_done(&self) -> bool {
+        matches!(self.state, ChunkState::Done)
+    }
+
+    // Decodes what it can of `data`, returning how many bytes it used up.
+    // Bodies longer than `max` are refused as soon as a chunk size says so,
+    // and trailers longer than a head may be are refused too.
+    fn feed(&mut self, data: &[u8], max: Option<u64>) -> ::std::result::Result<usize, u16> {
+        let mut used = 0;
+        loop {
+            let rest = &data[used..];
+            match self.state {
+                ChunkState::Size => {
+                    let line = match find_line(rest) {
+                        Some(line) => line,
+                        None if rest.len() > MAX_HEAD => return Err(400),
+                        None => return Ok(used),
+                    };
+                    let size = ::std::str::from_utf8(&rest[..line])
+                        .ok()
+                        .and_then(|size| digits(size.split(';').next()?.trim(), 16))
+                        .ok_or(400u16)?;
+                    let total = (self.body.len() as u64).saturating_add(size as u64);
+                    if max.is_some_and(|max| total > max) {
+                        return Err(413);
+                    }
+                    used += line + 2;
+                    self.state = if size == 0 {
+                        ChunkState::Trailers(0)
+                    } else {
+                        ChunkState::Data(size)
+                    };
+                }
+                ChunkState::Data(_) if rest.is_empty() => return Ok(used),
+                ChunkState::Data(left) => {
+                    let taken = left.min(rest.len());
+                    self.body.extend_from_slice(&rest[..taken]);
+                    used += taken;
+                    self.state = if taken == left {
+                        ChunkState::DataEnd
+                    } else {
+                        ChunkState::Data(left - taken)
+                    };
+                }
+                ChunkState::DataEnd if rest.len() < 2 => return Ok(used),
+                ChunkState::DataEnd if &rest[..2] != b"\r\n" => return Err(400),
+                ChunkState::DataEnd => {
+                    used += 2;
+                    self.state = ChunkState::Size;
+                }
+                ChunkState::Trailers(read) => {
+                    let line = match find_line(rest) {
+                        Some(line) => line,
+                        None if read + rest.len() > MAX_HEAD => return Err(431),
+                        None => return Ok(used),
+                    };
+                    if read + line > MAX_HEAD {
+                        return Err(431);
+                    }
+                    used += line + 2;
+                    self.state = if line == 0 {
+                        ChunkState::Done
+                    } else {
+                        ChunkState::Trailers(read + line + 2)
+                    };
+                }
+                ChunkState::Done => return Ok(used),
+            }
+        }
+    }
+}
+
+// Reads a number made of digits alone, without the sign `from_str_radix`
+// would also take.
+fn digits(number: &str, radix: u32) -> Option<usize> {
+    if number.is_empty() || !number.chars().all(|digit| digit.is_digit(radix)) {
+        return None;
+    }
+    usize::from_str_radix(number, radix).ok()
+}
+
+// Where the first line of `data` ends, if it is all there.
+fn find_line(data: &[u8]) -> Option<usize> {
+    data.windows(2).position(|window| window == b"\r\n")
+}
+
+struct Answer {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    method: Option<String>,
+}
+
+impl Answer {
+    fn bare(status: u16) -> Answer {
+        Answer {
+            status,
+            headers: Vec::new(),
+            body: Vec::new(),
+            method: None,
+        }
+    }
+
+    fn with_body(content_type: &str, body: Vec<u8>) -> Answer {
+        Answer {
+            status: 200,
+            headers: vec![("Content-Type".to_owned(), content_type.to_owned())],
+            body,
+            method: None,
+        }
+    }
+
+    // An XML reply, in the output encoding, and compressed if the client
+    // accepts `accept`.
+    fn xml(server: &Server, accept: Option<&str>, xml: String) -> Answer {
+        let content_type = server.xml_content_type();
+        match server.encode_reply(accept, xml.as_bytes()) {
+            Some((body, coding)) => {
+                let mut answer = Answer::with_body(&content_type, body);
+                if let Some(coding) = coding {
+                    answer
+                        .headers
+                        .push(("Content-Encoding".to_owned(), coding.to_owned()));
+                    answer
+                        .headers
+                        .push(("Vary".to_owned(), "Accept-Encoding".to_owned()));
+                }
+                answer
+            }
+            None => Answer::with_body(&content_type, xml.into_bytes()),
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        417 => "Expectation Failed",
+        431 => "Request Header Fields Too Large",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        500 => "Internal Server Error",
+        505 => "HTTP Version Not Supported",
+        // The reason phrase may be left empty.
+        _ => "",
+    }
+}
+
+// The answer in bytes, ready to be written in one go, in the HTTP version
+// of the request.
+fn encode(answer: &Answer, echoed: &[(String, String)], http11: bool, keep_alive: bool) -> Vec<u8> {
+    let version = if http11 { "HTTP/1.1" } else { "HTTP/1.0" };
+    let mut head = format!(
+        "{} {} {}\r\n",
+        version,
+        answer.status,
+        reason(answer.status)
+    );
+    for (name, value) in &answer.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    for (name, value) in echoed {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if !keep_alive {
+        head.push_str("Connection: close\r\n");
+    } else if !http11 {
+        head.push_str("Connection: keep-alive\r\n");
+    }
+    head.push_str(&format!("Content-Length: {}\r\n\r\n", answer.body.len()));
+    let mut out = head.into_bytes();
+    out.extend_from_slice(&answer.body);
+    out
+}
+
+// Answers what can be answered without running a handler, or hands the
+// request on to `dispatch`.
+fn answer(server: &Arc<Server>, request: Request, peer: SocketAddr) -> AnswerFuture {
+    let lookup = |name: &str| request.header(name);
+    if let Some(challenge) = server.challenge(lookup) {
+        let mut answer = Answer::bare(401);
+        answer
+            .headers
+            .push(("WWW-Authenticate".to_owned(), challenge));
+        return Box::pin(future::ready(answer));
+    }
+    let http_request = || HttpRequest {
+        method: request.method.clone(),
+        path: request.path().to_owned(),
+        peer: Some(peer.ip()),
+        headers: request.headers.clone(),
+    };
+    if let Some(route) = server.route(request.path(), http_request) {
+        let mut answer = Answer::with_body(&route.content_type, route.body);
+        answer.status = route.status;
+        answer.headers.extend(route.headers);
+        return Box::pin(future::ready(answer));
+    }
+    let is_get = request.method == "GET";
+    if let Some(page) = server.metrics_page(is_get, request.path()) {
+        let answer = Answer::with_body(METRICS_CONTENT_TYPE, page.into_bytes());
+        return Box::pin(future::ready(answer));
+    }
+    if let Some(page) = server.listing_page(is_get) {
+        let answer = Answer::with_body(listing::CONTENT_TYPE, page.into_bytes());
+        return Box::pin(future::ready(answer));
+    }
+    let content_type = request.header("Content-Type");
+    if let Some(status) =
+        server.refuses_http(&request.method, request.path(), content_type.as_deref())
+    {
+        let mut answer = Answer::bare(status);
+        if status != 415 {
+            answer.headers.push(("Allow".to_owned(), ALLOW.to_owned()));
+        }
+        return Box::pin(future::ready(answer));
+    }
+    let charset = parse::content_charset(content_type.as_deref()).map(str::to_owned);
+    let context = tracecontext::extract(lookup);
+    let http_request = server.http_request(http_request);
+    #[cfg(feature = "jsonrpc")]
+    let is_jsonrpc = server.is_jsonrpc(request.method == "POST", request.path());
+    let signature = server.signature_header().and_then(lookup);
+    let accept = request.header("Accept-Encoding");
+    let body = match server.verify(signature.as_deref(), &mut &request.body[..]) {
+        Verified::Unchecked => request.body,
+        Verified::Valid(bytes) => bytes.into_inner(),
+        Verified::Invalid => return Box::pin(future::ready(Answer::bare(403))),
+    };
+    #[cfg(feature = "jsonrpc")]
+    {
+        if is_jsonrpc {
+            let server = Arc::clone(server);
+            return Box::pin(Blocking(task::spawn_blocking(
+                move || match middleware::scope(http_request, || server.handle_jsonrpc(&body[..])) {
+                    Some(reply) => Answer::with_body(jsonrpc::CONTENT_TYPE, reply.into_bytes()),
+                    None => Answer::bare(204),
+                },
+            )));
+        }
+    }
+    // Only calls to async handlers are parsed here, the others are parsed
+    // once, on the blocking pool.
+    let name = if server.has_async_handlers() {
+        match charset {
+            #[cfg(feature = "encoding")]
+            Some(ref charset) => parse::method_name_in_charset(&body[..], charset).ok(),
+            _ => parse::method_name(&body[..]).ok(),
+        }
+    } else {
+        None
+    };
+    if let Some(handler) = name.and_then(|name| server.async_handler(&name)) {
+        let limits = server.limits().parse();
+        let parsed = match charset {
+            #[cfg(feature = "encoding")]
+            Some(ref charset) => parse::call_in_charset(&body[..], charset, &limits),
+            _ => parse::call_within(&body[..], &limits),
+        };
+        if let Ok(call) = parsed {
+            let started = Instant::now();
+            let params = call.params;
+            let response = match panic::catch_unwind(AssertUnwindSafe(|| handler(params))) {
+                Ok(response) => response,
+                // Left for `Encode` to answer, like a panic while polled.
+                Err(payload) => {
+                    let mut payload = Some(payload);
+                    Box::pin(future::poll_fn(move |_| -> Poll<Response> {
+                        panic::resume_unwind(payload.take().expect("polled once"))
+                    }))
+                }
+            };
+            return Box::pin(Encode {
+                server: Arc::clone(server),
+                accept,
+                method: call.name,
+                started,
+                response,
+            });
+        }
+    }
+    let server = Arc::clone(server);
+    Box::pin(Blocking(task::spawn_blocking(move || {
+        let mut timings = Timings::default();
+        // The body is already held against the memory budget, and was
+        // checked against the longest allowed as it came in.
+        let reply = tracecontext::scope(context, || {
+            middleware::scope(http_request, || {
+                server.reply(&body[..], charset.as_deref(), &mut timings)
+            })
+        });
+        let mut answer = match reply {
+            Some(reply) => {
+                let started = Instant::now();
+                let xml = reply.to_xml_as(server.wire_profile());
+                let answer = Answer::xml(&server, accept.as_deref(), xml);
+                timings.serialize += started.elapsed();
+                answer
+            }
+            None => Answer::bare(400),
+        };
+        server.report(&timings);
+        answer.method = timings.method;
+        answer
+    })))
+}
+
+// Runs on the blocking pool.
+struct Blocking(JoinHandle<Answer>);
+
+impl Future for Blocking {
+    type Output = Answer;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Answer> {
+        match Pin::new(&mut self.0).poll(cx) {
+            Poll::Ready(Ok(answer)) => Poll::Ready(answer),
+            // The handler panicked with the panic policy set to propagate.
+            Poll::Ready(Err(_)) => Poll::Ready(Answer::bare(500)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+// Serializes what an async handler returns, once it is counted like the
+// calls answered by other handlers. A handler that panics is answered as
+// the panic policy says.
+struct Encode {
+    server: Arc<Server>,
+    accept: Option<String>,
+    method: String,
+    started: Instant,
+    response: HandlerFuture,
+}
+
+impl Future for Encode {
+    type Output = Answer;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Answer> {
+        let this = &mut *self;
+        let polled = panic::catch_unwind(AssertUnwindSafe(|| this.response.as_mut().poll(cx)));
+        let response = match polled {
+            Ok(Poll::Ready(response)) => response,
+            Ok(Poll::Pending) => return Poll::Pending,
+            Err(payload) => {
+                let (server, method) = (&this.server, &this.method);
+                match panic::catch_unwind(AssertUnwindSafe(|| server.panicked(method, payload))) {
+                    Ok(response) => response,
+                    // The panic policy is set to propagate, which ends in a
+                    // bare 500 as it does for the other handlers.
+                    Err(_) => return Poll::Ready(Answer::bare(500)),
+                }
+            }
+        };
+        this.server
+            .record(&this.method, this.started.elapsed(), &response);
+        let xml = response.to_xml_as(this.server.wire_profile());
+        let mut answer = Answer::xml(&this.server, this.accept.as_deref(), xml);
+        answer.method = Some(this.method.clone());
+        Poll::Ready(answer)
+    }
+}
+
+enum State {
+    Reading,
+    // Sending `100 Continue`, to go on reading the same request after.
+    Continuing {
+        written: usize,
+    },
+    Answering {
+        answer: AnswerFuture,
+        trace: trace::Request,
+        echoed: Vec<(String, String)>,
+        record: Option<Box<AccessRecord>>,
+        started: Instant,
+        http11: bool,
+        keep_alive: bool,
+    },
+    Writing {
+        out: Vec<u8>,
+        written: usize,
+        keep_alive: bool,
+    },
+}
+
+// Serves the requests of one connection, one at a time.
+struct Connection {
+    server: Arc<Server>,
+    stream: TcpStream,
+    peer: SocketAddr,
+    // Received and not yet answered.
+    buffer: Vec<u8>,
+    // The head of the request being read, once it is in.
+    head: Option<Head>,
+    state: State,
+    // When to give up on the next request, with `Server::set_idle_timeout`.
+    idle: Option<Pin<Box<Sleep>>>,
+    // Whether `100 Continue` has been sent for the request being read.
+    continued: bool,
+    // Bytes held against the memory budget: those buffered, and those of
+    // the request being answered.
+    held: usize,
+}
+
+impl Connection {
+    fn new(server: Arc<Server>, stream: TcpStream, peer: SocketAddr) -> Connection {
+        server.connection_opened();
+        let mut connection = Connection {
+            server,
+            stream,
+            peer,
+            buffer: Vec::new(),
+            head: None,
+            state: State::Reading,
+            idle: None,
+            continued: false,
+            held: 0,
+        };
+        connection.await_request();
+        connection
+    }
+
+    // Starts the wait for the next request, giving back what the last one
+    // held against the memory budget.
+    fn await_request(&mut self) {
+        let buffered = self.buffer.len();
+        self.server.release(self.held - buffered);
+        self.held = buffered;
+        self.state = State::Reading;
+        self.idle = self
+            .server
+            .head_limits()
+            .idle
+            .map(|timeout| Box::pin(time::sleep(timeout)));
+    }
+
+    // Starts answering `request`.
+    fn start(&mut self, request: Request) {
+        let lookup = |name: &str| request.header(name);
+        let echoed = self.server.echoed(lookup);
+        let record = self.server.access_log().map(|_| {
+            Box::new(AccessRecord {
+                peer: Some(self.peer.ip()),
+                received: SystemTime::now(),
+                http_method: request.method.clone(),
+                path: request.target.clone(),
+                protocol: None,
+                method: None,
+                status: 0,
+                request_bytes: Some(request.body.len() as u64),
+                response_bytes: 0,
+                duration: Duration::from_secs(0),
+                referer: lookup("Referer"),
+                user_agent: lookup("User-Agent"),
+            })
+        });
+        let http11 = request.http11;
+        let keep_alive = request.keep_alive;
+        self.idle = None;
+        self.continued = false;
+        let trace = trace::Request::start(self.peer);
+        self.state = State::Answering {
+            answer: trace.in_scope(|| answer(&self.server, request, self.peer)),
+            trace,
+            echoed,
+            record,
+            started: Instant::now(),
+            http11,
+            keep_alive,
+        };
+    }
+
+    // Refuses a request that could not be read, and closes the connection
+    // after.
+    fn refuse(&mut self, status: u16) {
+        self.state = State::Writing {
+            out: encode(&Answer::bare(status), &[], true, false),
+            written: 0,
+            keep_alive: false,
+        };
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.server.release(self.held);
+        self.server.connection_closed();
+    }
+}
+
+impl Future for Connection {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let this = &mut *self;
+        loop {
+            let next = match this.state {
+                State::Reading => {
+                    match take_request(&mut this.buffer, &mut this.head, &this.server) {
+                        Ok(Taken::Request(request)) => this.start(request),
+                        Ok(Taken::Partial { wants_continue })
+                            if wants_continue && !this.continued =>
+                        {
+                            this.continued = true;
+                            this.state = State::Continuing { written: 0 };
+                        }
+                        Ok(Taken::Partial { .. }) => {
+                            let mut chunk = [0; 8192];
+                            let mut read = ReadBuf::new(&mut chunk);
+                            match Pin::new(&mut this.stream).poll_read(cx, &mut read) {
+                                Poll::Ready(Ok(())) if !read.filled().is_empty() => {
+                                    let filled = read.filled();
+                                    if this.server.hold(filled.len()) {
+                                        this.held += filled.len();
+                                        this.buffer.extend_from_slice(filled);
+                                    } else {
+                                        this.refuse(503);
+                                    }
+                                }
+                                Poll::Ready(_) => return Poll::Ready(()),
+                                Poll::Pending => {
+                                    let idle =
+                                        this.idle.as_mut().map(|idle| idle.as_mut().poll(cx));
+                                    return match idle {
+                                        Some(Poll::Ready(())) => Poll::Ready(()),
+                                        _ => Poll::Pending,
+                                    };
+                                }
+                            }
+                        }
+                        Err(status) => this.refuse(status),
+                    }
+                    continue;
+                }
+                State::Continuing { ref mut written } => {
+                    while *written < CONTINUE.len() {
+                        match Pin::new(&mut this.stream).poll_write(cx, &CONTINUE[*written..]) {
+                            Poll::Ready(Ok(0)) | Poll::Ready(Err(_)) => return Poll::Ready(()),
+                            Poll::Ready(Ok(sent)) => *written += sent,
+                            Poll::Pending => return Poll::Pending,
+                        }
+                    }
+                    // Still the same request, so the budget it holds and
+                    // the time it has left stay as they are.
+                    State::Reading
+                }
+                State::Answering {
+                    ref mut answer,
+                    ref trace,
+                    ref echoed,
+                    ref mut record,
+                    started,
+                    http11,
+                    keep_alive,
+                } => {
+                    let answer = match trace.in_scope(|| answer.as_mut().poll(cx)) {
+                        Poll::Ready(answer) => answer,
+                        Poll::Pending => return Poll::Pending,
+                    };
+                    trace.finish(answer.status);
+                    if let (Some(mut record), Some(log)) = (record.take(), this.server.access_log())
+                    {
+                        record.method = answer.method.clone();
+                        record.status = answer.status;
+                        record.response_bytes = answer.body.len() as u64;
+                        record.duration = started.elapsed();
+                        log.log(&record);
+                    }
+                    State::Writing {
+                        out: encode(&answer, echoed, http11, keep_alive),
+                        written: 0,
+                        keep_alive,
+                    }
+                }
+                State::Writing {
+                    ref out,
+                    ref mut written,
+                    keep_alive,
+                } => {
+                    while *written < out.len() {
+                        match Pin::new(&mut this.stream).poll_write(cx, &out[*written..]) {
+                            Poll::Ready(Ok(0)) | Poll::Ready(Err(_)) => return Poll::Ready(()),
+                            Poll::Ready(Ok(sent)) => *written += sent,
+                            Poll::Pending => return Poll::Pending,
+                        }
+                    }
+                    if !keep_alive {
+                        return Poll::Ready(());
+                    }
+                    this.await_request();
+                    continue;
+                }
+            };
+            this.state = next;
+        }
+    }
+}
@@ -0,0 +1,139 @@
+// Spans for the life of a request: `xmlrpc.request` (peer, method, status,
+// duration) around `xmlrpc.parse` and `xmlrpc.dispatch` (method, fault).
+// Events for what the server otherwise shrugs off: handler panics (error),
+// accept loops that stop (warn) and connections dropped before they were
+// answered (debug). Without the `tracing` feature these compile to nothing.
+
+use super::xmlfmt::Response;
+
+#[cfg(feature = "tracing")]
+pub use self::enabled::*;
+
+#[cfg(not(feature = "tracing"))]
+pub use self::disabled::*;
+
+#[cfg(feature = "tracing")]
+mod enabled {
+    use std::fmt::Display;
+    use std::io;
+    use std::time::Instant;
+    use tracing::field;
+    use tracing::span::EnteredSpan;
+    use tracing::Span;
+
+    use super::Response;
+
+    pub struct Request {
+        span: Span,
+        started: Instant,
+    }
+
+    impl Request {
+        pub fn start<P: Display>(peer: P) -> Request {
+            let span = info_span!(
+                "xmlrpc.request",
+                peer = %peer,
+                method = field::Empty,
+                status = field::Empty,
+                duration_ms = field::Empty,
+            );
+            Request {
+                span,
+                started: Instant::now(),
+            }
+        }
+
+        pub fn in_scope<F: FnOnce() -> R, R>(&self, f: F) -> R {
+            self.span.in_scope(f)
+        }
+
+        pub fn finish(&self, status: u16) {
+            let elapsed = self.started.elapsed();
+            let millis = elapsed.as_secs() as f64 * 1e3 + f64::from(elapsed.subsec_nanos()) * 1e-6;
+            self.span.record("status", status);
+            self.span.record("duration_ms", millis);
+        }
+    }
+
+    pub fn parse<F: FnOnce() -> R, R>(f: F) -> R {
+        debug_span!("xmlrpc.parse").in_scope(f)
+    }
+
+    pub fn panicked(method: &str, message: &str) {
+        error!(method, message, "handler panicked");
+    }
+
+    pub fn accept_failed(err: &io::Error) {
+        warn!(error = %err, "stopped accepting connections");
+    }
+
+    pub fn dropped(err: &io::Error) {
+        debug!(error = %err, "connection dropped");
+    }
+
+    pub struct Dispatch(EnteredSpan);
+
+    impl Dispatch {
+        pub fn start(method: &str) -> Dispatch {
+            Span::current().record("method", method);
+            Dispatch(info_span!("xmlrpc.dispatch", method, fault = field::Empty).entered())
+        }
+
+        pub fn finish(self, response: &Response) {
+            if let Err(ref fault) = *response {
+                self.0.record("fault", fault.code);
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+mod disabled {
+    use std::fmt::Display;
+    use std::io;
+
+    use super::Response;
+
+    pub struct Request;
+
+    impl Request {
+        #[inline]
+        pub fn start<P: Display>(_peer: P) -> Request {
+            Request
+        }
+
+        #[inline]
+        pub fn in_scope<F: FnOnce() -> R, R>(&self, f: F) -> R {
+            f()
+        }
+
+        #[inline]
+        pub fn finish(&self, _status: u16) {}
+    }
+
+    #[inline]
+    pub fn parse<F: FnOnce() -> R, R>(f: F) -> R {
+        f()
+    }
+
+    #[inline]
+    pub fn panicked(_method: &str, _message: &str) {}
+
+    #[inline]
+    pub fn accept_failed(_err: &io::Error) {}
+
+    #[inline]
+    pub fn dropped(_err: &io::Error) {}
+
+    pub struct Dispatch;
+
+    impl Dispatch {
+        #[inline]
+        pub fn start(_method: &str) -> Dispatch {
+            Dispatch
+        }
+
+        #[inline]
+        pub fn finish(self, _response: &Response) {}
+    }
+}
@@ -0,0 +1,104 @@
+//! W3C Trace Context propagation between XML-RPC hops.
+//!
+//! The server picks up `traceparent`/`tracestate` from each request and makes
+//! them available to handlers through `current()`. Clients send the context
+//! set with `Client::set_trace_context`, falling back to `current()`, so calls
+//! made from inside a handler continue the caller's trace.
+
+use std::cell::RefCell;
+
+pub const TRACEPARENT: &str = "traceparent";
+pub const TRACESTATE: &str = "tracestate";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceContext {
+    /// `version-traceid-parentid-flags`, e.g.
+    /// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`.
+    pub traceparent: String,
+    pub tracestate: Option<String>,
+}
+
+impl TraceContext {
+    /// Returns `None` unless `traceparent` is well formed. An all-zero trace
+    /// or parent ID is invalid, as is the reserved version `ff`.
+    pub fn new<T: Into<String>>(
+        traceparent: T,
+        tracestate: Option<String>,
+    ) -> Option<TraceContext> {
+        let traceparent = traceparent.into().trim().to_ascii_lowercase();
+        if !is_valid(&traceparent) {
+            return None;
+        }
+        Some(TraceContext {
+            traceparent,
+            tracestate: tracestate.filter(|state| !state.trim().is_empty()),
+        })
+    }
+
+    pub fn trace_id(&self) -> &str {
+        &self.traceparent[3..35]
+    }
+
+    pub fn parent_id(&self) -> &str {
+        &self.traceparent[36..52]
+    }
+
+    pub fn sampled(&self) -> bool {
+        u8::from_str_radix(&self.traceparent[53..55], 16).is_ok_and(|flags| flags & 1 == 1)
+    }
+}
+
+fn is_hex(part: &str, len: usize) -> bool {
+    part.len() == len && part.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn is_valid(traceparent: &str) -> bool {
+    let parts = traceparent.split('-').collect::<Vec<_>>();
+    // Later versions may append fields, but must keep the first four.
+    let (version, trace_id, parent_id, flags) = match parts.as_slice() {
+        [version, trace_id, parent_id, flags] => (*version, *trace_id, *parent_id, *flags),
+        [version, trace_id, parent_id, flags, ..] if *version != "00" => {
+            (*version, *trace_id, *parent_id, *flags)
+        }
+        _ => return false,
+    };
+    is_hex(version, 2)
+        && version != "ff"
+        && is_hex(trace_id, 32)
+        && trace_id.bytes().any(|b| b != b'0')
+        && is_hex(parent_id, 16)
+        && parent_id.bytes().any(|b| b != b'0')
+        && is_hex(flags, 2)
+}
+
+// Reads the context from request headers, ignoring a malformed one.
+#[cfg(feature = "server")]
+pub(crate) fn extract<F: Fn(&str) -> Option<String>>(header: F) -> Option<TraceContext> {
+    header(TRACEPARENT).and_then(|parent| TraceContext::new(parent, header(TRACESTATE)))
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<TraceContext>> = const { RefCell::new(None) };
+}
+
+/// The trace context of the request being handled on this thread.
+pub fn current() -> Option<TraceContext> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+// Makes `context` current while `f` runs.
+#[cfg(feature = "server")]
+pub(crate) fn scope<F: FnOnce() -> R, R>(context: Option<TraceContext>, f: F) -> R {
+    struct Restore(Option<TraceContext>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            CURRENT.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+
+    let previous = CURRENT.with(|current| current.replace(context));
+    let _restore = Restore(previous);
+    f()
+}
@@ -0,0 +1,145 @@
+//! HTTP over Unix sockets, for clients and servers on the same host.
+//!
+//! Unix sockets have no IP addresses, so their peers stand in as `0.0.0.0`
+//! wherever hyper asks for one.
+
+#[cfg(feature = "hyper-server")]
+use hyper;
+#[cfg(feature = "hyper-server")]
+use hyper::net::NetworkListener;
+use hyper::net::NetworkStream;
+#[cfg(feature = "hyper-server")]
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr};
+#[cfg(feature = "hyper-server")]
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+#[cfg(feature = "hyper-server")]
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+#[cfg(feature = "hyper-server")]
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn unspecified() -> SocketAddr {
+    SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0))
+}
+
+// Lets hyper speak HTTP over a Unix socket.
+#[derive(Clone)]
+pub(crate) struct UnixHttpStream(Arc<UnixStream>);
+
+impl UnixHttpStream {
+    pub fn new(stream: UnixStream) -> UnixHttpStream {
+        UnixHttpStream(Arc::new(stream))
+    }
+}
+
+impl Read for UnixHttpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self.0).read(buf)
+    }
+}
+
+impl Write for UnixHttpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self.0).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self.0).flush()
+    }
+}
+
+impl NetworkStream for UnixHttpStream {
+    fn peer_addr(&mut self) -> io::Result<SocketAddr> {
+        Ok(unspecified())
+    }
+
+    fn set_read_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.set_read_timeout(dur)
+    }
+
+    fn set_write_timeout(&self, dur: Option<Duration>) -> io::Result<()> {
+        self.0.set_write_timeout(dur)
+    }
+
+    fn close(&mut self, how: Shutdown) -> io::Result<()> {
+        self.0.shutdown(how)
+    }
+}
+
+#[cfg(feature = "hyper-server")]
+#[derive(Clone)]
+pub(crate) struct UnixHttpListener(Arc<UnixListener>);
+
+#[cfg(feature = "hyper-server")]
+impl UnixHttpListener {
+    // Listens on `path`, replacing a socket file nothing listens on any
+    // more, and gives the socket file `mode` if set.
+    pub fn bind(path: &Path, mode: Option<u32>) -> io::Result<UnixHttpListener> {
+        let listener = match UnixListener::bind(path) {
+            Err(ref err) if err.kind() == io::ErrorKind::AddrInUse && is_stale(path) => {
+                fs::remove_file(path)?;
+                UnixListener::bind(path)?
+            }
+            bound => bound?,
+        };
+        if let Some(mode) = mode {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        }
+        Ok(UnixHttpListener(Arc::new(listener)))
+    }
+}
+
+// Whether `path` is a socket left behind by a server that has gone away.
+#[cfg(feature = "hyper-server")]
+fn is_stale(path: &Path) -> bool {
+    let is_socket = fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket());
+    is_socket
+        && match UnixStream::connect(path) {
+            Err(err) => err.kind() == io::ErrorKind::ConnectionRefused,
+            Ok(_) => false,
+        }
+}
+
+#[cfg(feature = "hyper-server")]
+impl NetworkListener for UnixHttpListener {
+    type Stream = UnixHttpStream;
+
+    fn accept(&mut self) -> hyper::Result<UnixHttpStream> {
+        let (stream, _) = self.0.accept()?;
+        Ok(UnixHttpStream::new(stream))
+    }
+
+    fn local_addr(&mut self) -> io::Result<SocketAddr> {
+        Ok(unspecified())
+    }
+}
+
+/// A server bound with `Server::bind_unix`, serving until it is closed.
+///
+/// Dropping it waits for the server to stop, which it never does, as with
+/// `bind_hyper`.
+#[cfg(feature = "hyper-server")]
+#[derive(Debug)]
+pub struct UnixListening {
+    pub(crate) listening: hyper::server::Listening,
+    pub(crate) path: PathBuf,
+}
+
+#[cfg(feature = "hyper-server")]
+impl UnixListening {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Removes the socket file, so that no more clients can connect, and
+    /// stops waiting for the server on drop. Connections already open are
+    /// still served.
+    pub fn close(&mut self) -> io::Result<()> {
+        let _ = self.listening.close();
+        fs::remove_file(&self.path)
+    }
+}
@@ -0,0 +1,121 @@
+//! Character encodings other than UTF-8, as some older servers still send
+//! documents in `ISO-8859-1` or `windows-1252`.
+//!
+//! Documents are read in the encoding of a byte order mark if they start
+//! with one, else in the charset of the `Content-Type` they came with, else
+//! in the encoding their XML declaration names, else in UTF-8.
+
+use super::error::{Error, Result};
+use encoding_rs::{Encoding, UTF_8};
+
+const CONTEXT: &str = "Failed to decode XML-RPC document";
+
+fn fail(details: String) -> Error {
+    Error::Parse {
+        context: CONTEXT,
+        details,
+        location: None,
+    }
+}
+
+/// Reads `bytes` into text, in the encoding found as described above, with
+/// `charset` as the charset of the `Content-Type` if there was one.
+///
+/// The XML declaration is rewritten to name UTF-8, which the text is in
+/// from then on.
+pub fn decode(bytes: Vec<u8>, charset: Option<&str>) -> Result<String> {
+    let (encoding, bom) = match Encoding::for_bom(&bytes) {
+        Some(found) => found,
+        None => (find_encoding(&bytes, charset)?, 0),
+    };
+    let mut text = if encoding == UTF_8 {
+        let mut bytes = bytes;
+        bytes.drain(..bom);
+        String::from_utf8(bytes).map_err(|err| fail(err.to_string()))?
+    } else {
+        encoding
+            .decode_without_bom_handling_and_without_replacement(&bytes[bom..])
+            .ok_or_else(|| fail(format!("malformed {} data", encoding.name())))?
+            .into_owned()
+    };
+    if let Some(range) = declared_encoding(text.as_bytes()) {
+        text.replace_range(range, "UTF-8");
+    }
+    Ok(text)
+}
+
+/// `xml` in `encoding`, with its XML declaration naming it. Characters the
+/// encoding lacks are written as character references.
+///
+/// As with `encoding_rs`, `UTF-16` documents are written in UTF-8.
+pub fn encode(xml: &str, encoding: &'static Encoding) -> Vec<u8> {
+    let encoding = encoding.output_encoding();
+    if encoding == UTF_8 {
+        return xml.as_bytes().to_vec();
+    }
+    let mut xml = xml.to_owned();
+    match declaration(xml.as_bytes()) {
+        Some(end) => match declared_encoding(xml.as_bytes()) {
+            Some(range) => xml.replace_range(range, encoding.name()),
+            None => xml.insert_str(end - 2, &format!(" encoding=\"{}\"", encoding.name())),
+        },
+        None => xml.insert_str(
+            0,
+            &format!("<?xml version=\"1.0\" encoding=\"{}\"?>", encoding.name()),
+        ),
+    }
+    encoding.encode(&xml).0.into_owned()
+}
+
+/// The encoding `label` names, as in a charset or an XML declaration.
+pub fn lookup(label: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+}
+
+fn find_encoding(bytes: &[u8], charset: Option<&str>) -> Result<&'static Encoding> {
+    let label = match charset {
+        Some(charset) => charset.to_owned(),
+        None => match declared_encoding(bytes) {
+            Some(range) => String::from_utf8_lossy(&bytes[range]).into_owned(),
+            None => return Ok(UTF_8),
+        },
+    };
+    lookup(&label).ok_or_else(|| fail(format!("unsupported encoding {:?}", label)))
+}
+
+// Where the XML declaration at the start of `bytes` ends, past its `?>`.
+fn declaration(bytes: &[u8]) -> Option<usize> {
+    if !bytes.starts_with(b"<?xml") {
+        return None;
+    }
+    bytes
+        .windows(2)
+        .position(|pair| pair == b"?>")
+        .map(|at| at + 2)
+}
+
+// Where the name of the encoding is in the XML declaration at the start of
+// `bytes`, if it names one.
+fn declared_encoding(bytes: &[u8]) -> Option<::std::ops::Range<usize>> {
+    let declaration = &bytes[..declaration(bytes)?];
+    let at = declaration
+        .windows(8)
+        .position(|word| word == b"encoding")?;
+    let skip_space = |mut at: usize| {
+        while declaration.get(at).is_some_and(u8::is_ascii_whitespace) {
+            at += 1;
+        }
+        at
+    };
+    let equals = skip_space(at + 8);
+    if declaration.get(equals) != Some(&b'=') {
+        return None;
+    }
+    let start = skip_space(equals + 1);
+    let quote = *declaration.get(start)?;
+    if quote != b'"' && quote != b'\'' {
+        return None;
+    }
+    let len = declaration[start + 1..].iter().position(|&b| b == quote)?;
+    Some(start + 1..start + 1 + len)
+}
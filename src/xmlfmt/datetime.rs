@@ -0,0 +1,292 @@
+//! `dateTime.iso8601` values as chrono datetimes, and their UTC offsets.
+//!
+//! `Value::DateTime` holds the text of a datetime as sent. `parse` reads it
+//! as a chrono datetime, `Value::from` writes one, and the `utc` module
+//! lets serde do both for `DateTime<Utc>` fields.
+//!
+//! The XML-RPC specification does not say which timezone a datetime is in,
+//! and peers disagree: some send UTC without an offset, some local time,
+//! some an ISO 8601 offset. `DateTimeOptions` settles what a server or client
+//! makes of the datetimes it receives, and how it writes those it sends.
+
+use chrono::{DateTime, FixedOffset, Local, LocalResult, NaiveDateTime, TimeZone, Utc};
+
+use super::error::{Error, Result};
+use super::ser::DATETIME_NEWTYPE;
+use super::Value;
+
+const FORMATS: &[&str] = &[
+    "%Y%m%dT%H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y%m%dT%H%M%S%.f",
+];
+
+/// How datetimes without a UTC offset, like `19980717T14:08:55`, are read.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum NaiveDateTimes {
+    /// Leave them without an offset.
+    #[default]
+    Keep,
+    /// Read them as UTC, adding a `Z`.
+    AssumeUtc,
+    /// Read them as local time, adding the local offset.
+    AssumeLocal,
+    /// Refuse them, failing the call.
+    Reject,
+}
+
+/// Whether sent datetimes carry a UTC offset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SentOffsets {
+    /// Send datetimes as given.
+    #[default]
+    AsGiven,
+    /// Give every datetime an offset, reading those without one as the
+    /// `NaiveDateTimes` option says.
+    Include,
+    /// Convert datetimes to UTC and send them without an offset, for peers
+    /// that cannot read offsets.
+    OmitAsUtc,
+}
+
+/// How a server or client handles the UTC offsets of datetimes.
+///
+/// The default passes every datetime through as it is.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DateTimeOptions {
+    pub naive: NaiveDateTimes,
+    pub sent_offsets: SentOffsets,
+    /// Rewrites sent datetimes in the format of the specification, as in
+    /// `19980717T14:08:55`, keeping any offset. A datetime that cannot be
+    /// read fails the call.
+    pub normalize: bool,
+}
+
+impl DateTimeOptions {
+    /// Applies the options to the datetimes in received `values`.
+    pub(crate) fn receive(&self, values: &mut [Value]) -> Result<()> {
+        if self.naive == NaiveDateTimes::Keep {
+            return Ok(());
+        }
+        for value in values {
+            visit(value, &mut |text| self.resolve(text, Error::Decoding))?;
+        }
+        Ok(())
+    }
+
+    /// Applies the options to the datetimes in `values` about to be sent.
+    pub(crate) fn send(&self, values: &mut [Value]) -> Result<()> {
+        if self.sent_offsets == SentOffsets::AsGiven && !self.normalize {
+            return Ok(());
+        }
+        for value in values {
+            visit(value, &mut |text| {
+                if self.sent_offsets != SentOffsets::AsGiven {
+                    self.resolve(text, Error::Encoding)?;
+                }
+                if self.sent_offsets == SentOffsets::OmitAsUtc {
+                    to_naive_utc(text)?;
+                }
+                if self.normalize {
+                    normalize(text)?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    // Adds an offset to `text` if it has none, as `naive` says.
+    fn resolve(&self, text: &mut String, error: fn(String) -> Error) -> Result<()> {
+        let local = match split_offset(text) {
+            (_, Some(_)) => return Ok(()),
+            (local, None) => local,
+        };
+        match self.naive {
+            NaiveDateTimes::Keep => {}
+            NaiveDateTimes::AssumeUtc => {
+                parse_naive(local, error)?;
+                text.push('Z');
+            }
+            NaiveDateTimes::AssumeLocal => {
+                let time = parse_naive(local, error)?;
+                let offset = match Local.from_local_datetime(&time) {
+                    LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => *time.offset(),
+                    LocalResult::None => {
+                        return Err(error(format!(
+                            "datetime `{}` does not exist in the local timezone",
+                            text
+                        )))
+                    }
+                };
+                text.push_str(&offset.to_string());
+            }
+            NaiveDateTimes::Reject => {
+                return Err(error(format!("datetime `{}` has no UTC offset", text)))
+            }
+        }
+        Ok(())
+    }
+}
+
+fn visit<F>(value: &mut Value, f: &mut F) -> Result<()>
+where
+    F: FnMut(&mut String) -> Result<()>,
+{
+    match *value {
+        Value::DateTime(ref mut text) => f(text),
+        Value::Array(ref mut values) => values.iter_mut().try_for_each(|value| visit(value, f)),
+        Value::Struct(ref mut members) => {
+            members.values_mut().try_for_each(|value| visit(value, f))
+        }
+        _ => Ok(()),
+    }
+}
+
+// Splits the offset, `Z` or one like `+02:00`, off the end of `text`.
+fn split_offset(text: &str) -> (&str, Option<&str>) {
+    if text.ends_with('Z') || text.ends_with('z') {
+        return (&text[..text.len() - 1], Some("Z"));
+    }
+    let time = match text.find(&['T', 't'][..]) {
+        Some(i) => i + 1,
+        None => return (text, None),
+    };
+    match text[time..].rfind(&['+', '-'][..]) {
+        Some(i) => (&text[..time + i], Some(&text[time + i..])),
+        None => (text, None),
+    }
+}
+
+fn parse_naive(text: &str, error: fn(String) -> Error) -> Result<NaiveDateTime> {
+    FORMATS
+        .iter()
+        .filter_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .next()
+        .ok_or_else(|| error(format!("invalid datetime `{}`", text)))
+}
+
+// Reads offsets written as `Z`, `+hh:mm`, `+hhmm` or `+hh`.
+fn parse_offset(text: &str) -> Option<FixedOffset> {
+    if text == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+    let sign = match text.chars().next() {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => return None,
+    };
+    let digits = text[1..].replace(':', "");
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let (hours, minutes) = match digits.len() {
+        2 => (&digits[..], "0"),
+        4 => (&digits[..2], &digits[2..]),
+        _ => return None,
+    };
+    let seconds = hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60;
+    FixedOffset::east_opt(sign * seconds)
+}
+
+// Rewrites a datetime with an offset as the same instant in UTC, without
+// one. Datetimes still without an offset are left alone.
+fn to_naive_utc(text: &mut String) -> Result<()> {
+    let utc = match split_offset(text) {
+        (_, None) => return Ok(()),
+        (local, Some(offset)) => {
+            let offset = parse_offset(offset)
+                .ok_or_else(|| Error::Encoding(format!("invalid datetime `{}`", text)))?;
+            parse_naive(local, Error::Encoding)? - offset
+        }
+    };
+    *text = utc.format(FORMATS[0]).to_string();
+    Ok(())
+}
+
+// Rewrites a datetime in the first of `FORMATS`, with its offset if any.
+fn normalize(text: &mut String) -> Result<()> {
+    let invalid = || Error::Encoding(format!("invalid datetime `{}`", text));
+    let (local, offset) = split_offset(text);
+    let offset = match offset {
+        None => String::new(),
+        Some("Z") => "Z".into(),
+        Some(offset) => parse_offset(offset).ok_or_else(invalid)?.to_string(),
+    };
+    let normal = format!(
+        "{}{}",
+        parse_naive(local, Error::Encoding)?.format(FORMATS[0]),
+        offset
+    );
+    *text = normal;
+    Ok(())
+}
+
+/// Reads the text of a datetime, in any of the formats peers send. One
+/// without a UTC offset is read as UTC.
+pub fn parse(text: &str) -> Result<DateTime<FixedOffset>> {
+    let invalid = || Error::Decoding(format!("invalid datetime `{}`", text));
+    let (local, offset) = match split_offset(text.trim()) {
+        (local, None) => (local, FixedOffset::east_opt(0)),
+        (local, Some(offset)) => (local, parse_offset(offset)),
+    };
+    let offset = offset.ok_or_else(invalid)?;
+    offset
+        .from_local_datetime(&parse_naive(local, Error::Decoding)?)
+        .single()
+        .ok_or_else(invalid)
+}
+
+/// Written without an offset.
+impl From<NaiveDateTime> for Value {
+    fn from(time: NaiveDateTime) -> Value {
+        Value::DateTime(time.format(FORMATS[0]).to_string())
+    }
+}
+
+/// Written without an offset, which most peers read as UTC.
+impl From<DateTime<Utc>> for Value {
+    fn from(time: DateTime<Utc>) -> Value {
+        Value::from(time.naive_utc())
+    }
+}
+
+/// Written with its offset, as in `19980717T14:08:55+02:00`.
+impl From<DateTime<FixedOffset>> for Value {
+    fn from(time: DateTime<FixedOffset>) -> Value {
+        Value::DateTime(format!(
+            "{}{}",
+            time.naive_local().format(FORMATS[0]),
+            time.offset()
+        ))
+    }
+}
+
+/// Serde support for `DateTime<Utc>` fields, as in
+/// `#[serde(with = "xml_rpc::datetime::utc")]`. They are written as
+/// `<dateTime.iso8601>` values without an offset, and read from any
+/// datetime `parse` reads, or from a string holding one.
+pub mod utc {
+    use chrono::{DateTime, Utc};
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{parse, DATETIME_NEWTYPE, FORMATS};
+
+    pub fn serialize<S: Serializer>(
+        time: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let text = time.naive_utc().format(FORMATS[0]).to_string();
+        serializer.serialize_newtype_struct(DATETIME_NEWTYPE, &text)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        parse(&text)
+            .map(|time| time.with_timezone(&Utc))
+            .map_err(D::Error::custom)
+    }
+}
@@ -1,4 +1,4 @@
-use super::error::{Error, Result};
+use super::error::{Error, PathSegment, Result};
 use super::Value;
 use serde::de::{
     DeserializeSeed, EnumAccess, MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor,
@@ -6,6 +6,7 @@ use serde::de::{
 use serde::{self, Deserializer};
 use std;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::vec;
 
 impl<'de> serde::Deserializer<'de> for Value {
@@ -18,10 +19,12 @@ impl<'de> serde::Deserializer<'de> for Value {
     {
         match self {
             Value::Int(v) => visitor.visit_i32(v),
+            Value::Int64(v) => visitor.visit_i64(v),
             Value::Bool(v) => visitor.visit_bool(v),
             Value::DateTime(v) | Value::String(v) => visitor.visit_string(v),
             Value::Double(v) => visitor.visit_f64(v),
-            Value::Base64(v) => visitor.visit_bytes(v.as_slice()),
+            Value::Base64(v) => visitor.visit_byte_buf(v),
+            Value::Nil => visitor.visit_unit(),
             Value::Array(v) => {
                 let len = v.len();
                 let mut deserializer = SeqDeserializer::new(v);
@@ -172,13 +175,14 @@ impl<'de> serde::Deserializer<'de> for Value {
         V: Visitor<'de>,
     {
         if let Value::String(v) = self {
-            if v.len() != 1 {
-                return Err(serde::de::Error::invalid_value(
+            let mut chars = v.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => visitor.visit_char(c),
+                _ => Err(serde::de::Error::invalid_value(
                     Unexpected::Str(&v),
                     &"string with a single character",
-                ));
+                )),
             }
-            visitor.visit_char(v.chars().next().unwrap())
         } else {
             Err(serde::de::Error::invalid_value(self.unexpected(), &visitor))
         }
@@ -188,10 +192,9 @@ impl<'de> serde::Deserializer<'de> for Value {
     where
         V: Visitor<'de>,
     {
-        if let Value::String(v) = self {
-            visitor.visit_str(&v)
-        } else {
-            Err(serde::de::Error::invalid_value(self.unexpected(), &visitor))
+        match self {
+            Value::String(v) | Value::DateTime(v) => visitor.visit_str(&v),
+            _ => Err(serde::de::Error::invalid_value(self.unexpected(), &visitor)),
         }
     }
 
@@ -199,10 +202,9 @@ impl<'de> serde::Deserializer<'de> for Value {
     where
         V: Visitor<'de>,
     {
-        if let Value::String(v) = self {
-            visitor.visit_string(v)
-        } else {
-            Err(serde::de::Error::invalid_value(self.unexpected(), &visitor))
+        match self {
+            Value::String(v) | Value::DateTime(v) => visitor.visit_string(v),
+            _ => Err(serde::de::Error::invalid_value(self.unexpected(), &visitor)),
         }
     }
 
@@ -232,6 +234,12 @@ impl<'de> serde::Deserializer<'de> for Value {
     where
         V: Visitor<'de>,
     {
+        if let Value::Nil = self {
+            return visitor.visit_none();
+        }
+        if cfg!(feature = "ext-nil") {
+            return visitor.visit_some(self);
+        }
         if let Value::Array(mut v) = self {
             let v1 = v.pop();
             if !v.is_empty() {
@@ -245,7 +253,8 @@ impl<'de> serde::Deserializer<'de> for Value {
                 None => visitor.visit_none(),
             }
         } else {
-            Err(serde::de::Error::invalid_value(self.unexpected(), &visitor))
+            // Peers that leave options unwrapped send the bare value.
+            visitor.visit_some(self)
         }
     }
 
@@ -253,6 +262,9 @@ impl<'de> serde::Deserializer<'de> for Value {
     where
         V: Visitor<'de>,
     {
+        if let Value::Nil = self {
+            return visitor.visit_unit();
+        }
         if let Value::Struct(v) = self {
             if !v.is_empty() {
                 return Err(serde::de::Error::invalid_value(
@@ -344,7 +356,7 @@ impl<'de> serde::Deserializer<'de> for Value {
                     if member_iter.next().is_none() {
                         return visitor.visit_enum(EnumDeserializer {
                             variant: key,
-                            value: value,
+                            value,
                         });
                     }
                 }
@@ -353,9 +365,14 @@ impl<'de> serde::Deserializer<'de> for Value {
                     &"map with a single key",
                 ))
             }
+            // The name alone stands for a unit variant.
+            Value::String(variant) => visitor.visit_enum(EnumDeserializer {
+                variant,
+                value: Value::Struct(HashMap::new()),
+            }),
             other => Err(serde::de::Error::invalid_value(
                 other.unexpected(),
-                &"map with a single key",
+                &"map with a single key, or a variant name",
             )),
         }
     }
@@ -367,12 +384,14 @@ impl<'de> serde::Deserializer<'de> for Value {
 
 struct SeqDeserializer {
     iter: vec::IntoIter<Value>,
+    index: usize,
 }
 
 impl SeqDeserializer {
     fn new(vec: Vec<Value>) -> Self {
         SeqDeserializer {
             iter: vec.into_iter(),
+            index: 0,
         }
     }
 }
@@ -386,7 +405,7 @@ impl<'de> serde::Deserializer<'de> for SeqDeserializer {
         V: Visitor<'de>,
     {
         let len = self.iter.len();
-        let ret = try!(visitor.visit_seq(&mut self));
+        let ret = visitor.visit_seq(&mut self)?;
         let remaining = self.iter.len();
         if remaining == 0 {
             Ok(ret)
@@ -413,7 +432,13 @@ impl<'de> SeqAccess<'de> for SeqDeserializer {
         T: DeserializeSeed<'de>,
     {
         match self.iter.next() {
-            Some(value) => seed.deserialize(value).map(Some),
+            Some(value) => {
+                let index = self.index;
+                self.index += 1;
+                seed.deserialize(value)
+                    .map(Some)
+                    .map_err(|err| err.within(PathSegment::Index(index)))
+            }
             None => Ok(None),
         }
     }
@@ -428,7 +453,7 @@ impl<'de> SeqAccess<'de> for SeqDeserializer {
 
 struct MapDeserializer {
     iter: <HashMap<String, Value> as IntoIterator>::IntoIter,
-    value: Option<Value>,
+    value: Option<(String, Value)>,
 }
 
 impl MapDeserializer {
@@ -449,7 +474,7 @@ impl<'de> MapAccess<'de> for MapDeserializer {
     {
         match self.iter.next() {
             Some((key, value)) => {
-                self.value = Some(value);
+                self.value = Some((key.clone(), value));
                 seed.deserialize(Value::String(key)).map(Some)
             }
             None => Ok(None),
@@ -461,7 +486,9 @@ impl<'de> MapAccess<'de> for MapDeserializer {
         T: DeserializeSeed<'de>,
     {
         match self.value.take() {
-            Some(value) => seed.deserialize(value),
+            Some((key, value)) => seed
+                .deserialize(value)
+                .map_err(|err| err.within(PathSegment::Member(key))),
             None => Err(serde::de::Error::custom("value is missing")),
         }
     }
@@ -581,11 +608,13 @@ impl FromI32 for i64 {
 
 fn handle_integer<'de, T, V>(value: Value, visitor: &V) -> Result<T>
 where
-    T: FromI32 + std::str::FromStr,
+    T: FromI32 + std::str::FromStr + TryFrom<i64>,
     V: Visitor<'de>,
 {
     match value {
         Value::Int(v) => Ok(T::from_i32(v)),
+        Value::Int64(v) => T::try_from(v)
+            .map_err(|_| serde::de::Error::invalid_value(Unexpected::Signed(v), visitor)),
         Value::String(v) => v
             .parse()
             .map_err(|_| serde::de::Error::invalid_value(Unexpected::Str(&v), visitor)),
@@ -0,0 +1,467 @@
+use super::error::Location;
+use super::Value;
+use base64;
+use quick_xml::events::Event as XmlEvent;
+use quick_xml::reader::Reader as XmlReader;
+use std::collections::HashMap;
+use std::fmt;
+
+fn is_value_type(element: &str) -> bool {
+    matches!(
+        element,
+        "i4" | "int"
+            | "i8"
+            | "boolean"
+            | "string"
+            | "double"
+            | "dateTime.iso8601"
+            | "base64"
+            | "nil"
+            | "array"
+            | "struct"
+    )
+}
+
+fn allows_child(parent: Option<&str>, child: &str) -> bool {
+    match parent {
+        None => child == "methodCall" || child == "methodResponse" || is_value_type(child),
+        Some("methodCall") => child == "methodName" || child == "params",
+        Some("methodResponse") => child == "params" || child == "fault",
+        Some("params") => child == "param",
+        Some("param") | Some("fault") | Some("data") => child == "value",
+        Some("value") => is_value_type(child),
+        Some("array") => child == "data",
+        Some("struct") => child == "member",
+        Some("member") => child == "name" || child == "value",
+        Some(_) => false,
+    }
+}
+
+fn expected_children(parent: Option<&str>) -> &'static str {
+    match parent {
+        None => "<methodCall>, <methodResponse> or a value",
+        Some("methodCall") => "<methodName> or <params>",
+        Some("methodResponse") => "<params> or <fault>",
+        Some("params") => "<param>",
+        Some("param") | Some("fault") | Some("data") => "<value>",
+        Some("value") => "a value type",
+        Some("array") => "<data>",
+        Some("struct") => "<member>",
+        Some("member") => "<name> or <value>",
+        Some(_) => "no elements",
+    }
+}
+
+fn allows_text(element: Option<&str>) -> bool {
+    match element {
+        Some("value") | Some("methodName") | Some("name") => true,
+        Some("array") | Some("struct") => false,
+        Some(element) => is_value_type(element),
+        None => false,
+    }
+}
+
+// Deliberately at least as lenient as the real parser, so a reported
+// problem is never a false alarm.
+fn check_scalar(element: &str, text: &str) -> Option<String> {
+    let text = text.trim();
+    let valid = match element {
+        "i4" | "int" | "boolean" => text.parse::<i32>().is_ok(),
+        "i8" => text.parse::<i64>().is_ok(),
+        "double" => text.parse::<f64>().is_ok(),
+        "base64" => {
+            let text = text.split_whitespace().collect::<String>();
+            base64::decode(text.as_bytes()).is_ok()
+        }
+        _ => true,
+    };
+    if valid {
+        None
+    } else {
+        Some(format!("invalid <{}> content {:?}", element, text))
+    }
+}
+
+fn locate(content: &str, at: u64, stack: &[(String, String)]) -> Location {
+    locate_in(
+        content,
+        at,
+        stack.iter().map(|(element, _)| element.as_str()),
+    )
+}
+
+// Where byte `at` of `content` is, inside `elements`.
+fn locate_in<'a, I: Iterator<Item = &'a str>>(content: &str, at: u64, elements: I) -> Location {
+    let before = &content.as_bytes()[..(at as usize).min(content.len())];
+    let line_start = before
+        .iter()
+        .rposition(|&byte| byte == b'\n')
+        .map_or(0, |newline| newline + 1);
+    let column = String::from_utf8_lossy(&before[line_start..])
+        .chars()
+        .count();
+    let mut path = elements
+        .map(|element| format!("/{}", element))
+        .collect::<String>();
+    if path.is_empty() {
+        path.push('/');
+    }
+    Location {
+        line: before.iter().filter(|&&byte| byte == b'\n').count() as u64 + 1,
+        column: column as u64 + 1,
+        path,
+    }
+}
+
+fn reader(content: &str) -> XmlReader<&[u8]> {
+    let mut reader = XmlReader::from_str(content);
+    reader.config_mut().expand_empty_elements = true;
+    reader
+}
+
+// The text of an event that carries some, or what is wrong with it.
+fn text_of(event: &XmlEvent) -> Option<Result<String, String>> {
+    let text = match *event {
+        XmlEvent::Text(ref text) => text.unescape().map_err(|err| err.to_string()),
+        XmlEvent::CData(ref data) => data.decode().map_err(|err| err.to_string()),
+        _ => return None,
+    };
+    Some(text.map(|text| text.into_owned()))
+}
+
+const UNEXPECTED_END: &str = "unexpected end of document";
+
+/// Scans `content` for the first violation of XML or XML-RPC grammar.
+///
+/// This only runs after the real parser has failed, to tell the user where
+/// the document went wrong.
+pub fn first_problem(content: &str) -> Option<(String, Location)> {
+    let mut reader = reader(content);
+    // Open elements with the text collected inside each of them.
+    let mut stack: Vec<(String, String)> = Vec::new();
+    loop {
+        let at = reader.buffer_position();
+        let event = match reader.read_event() {
+            Ok(event) => event,
+            Err(err) => {
+                let location = locate(content, reader.error_position(), &stack);
+                return Some((err.to_string(), location));
+            }
+        };
+        if let Some(text) = text_of(&event) {
+            let text = match text {
+                Ok(text) => text,
+                Err(reason) => return Some((reason, locate(content, at, &stack))),
+            };
+            if text.trim().is_empty() {
+                continue;
+            }
+            if !allows_text(stack.last().map(|(element, _)| element.as_str())) {
+                return Some((
+                    format!("unexpected text {:?}", text.trim()),
+                    locate(content, at, &stack),
+                ));
+            }
+            if let Some(&mut (_, ref mut collected)) = stack.last_mut() {
+                collected.push_str(&text);
+            }
+            continue;
+        }
+        match event {
+            XmlEvent::Start(start) => {
+                let child = String::from_utf8_lossy(start.local_name().as_ref()).into_owned();
+                let reason = match stack.last() {
+                    Some((parent, _)) if !allows_child(Some(parent), &child) => {
+                        format!("unexpected element <{}> inside <{}>", child, parent)
+                    }
+                    None if !allows_child(None, &child) => {
+                        format!("unexpected root element <{}>", child)
+                    }
+                    _ => {
+                        stack.push((child, String::new()));
+                        continue;
+                    }
+                };
+                return Some((reason, locate(content, at, &stack)));
+            }
+            XmlEvent::End(_) => {
+                let problem = stack
+                    .last()
+                    .and_then(|(element, text)| check_scalar(element, text));
+                if let Some(reason) = problem {
+                    return Some((reason, locate(content, at, &stack)));
+                }
+                stack.pop();
+            }
+            XmlEvent::Eof if stack.is_empty() => return None,
+            XmlEvent::Eof => return Some((UNEXPECTED_END.into(), locate(content, at, &stack))),
+            _ => {}
+        }
+    }
+}
+
+/// One thing wrong with a document, found by `parse::recover`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub location: Location,
+    pub expected: String,
+    pub found: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "expected {}, found {} at {}",
+            self.expected, self.found, self.location
+        )
+    }
+}
+
+/// What could be read from a document despite its problems.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Diagnosis {
+    /// The method name, for a call.
+    pub name: Option<String>,
+    /// The parameters of a call or response, the fault structure of a
+    /// fault, or the value a bare value document holds. Values with a
+    /// problem inside are left out, or for arrays and structs the items
+    /// with the problem.
+    pub values: Vec<Value>,
+    pub problems: Vec<Problem>,
+}
+
+impl Diagnosis {
+    pub fn is_healthy(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+// An open element while recovering.
+#[derive(Default)]
+struct Frame {
+    element: String,
+    text: String,
+    // Values read from the children, and whether any child failed.
+    values: Vec<Value>,
+    failed: bool,
+    name: Option<String>,
+    members: HashMap<String, Value>,
+}
+
+fn scalar(element: &str, text: &str) -> Option<Value> {
+    let trimmed = text.trim();
+    match element {
+        "i4" | "int" => trimmed.parse().ok().map(Value::Int),
+        "i8" => trimmed.parse().ok().map(Value::Int64),
+        "boolean" => trimmed.parse::<i32>().ok().map(|v| Value::Bool(v != 0)),
+        "double" => trimmed.parse().ok().map(Value::Double),
+        "string" => Some(Value::String(text.into())),
+        "nil" => Some(Value::Nil),
+        "dateTime.iso8601" => Some(Value::DateTime(trimmed.into())),
+        "base64" => {
+            let text = trimmed.split_whitespace().collect::<String>();
+            base64::decode(text.as_bytes()).ok().map(Value::Base64)
+        }
+        _ => None,
+    }
+}
+
+fn describe(element: &str) -> &'static str {
+    match element {
+        "i4" | "int" | "i8" | "boolean" => "an integer",
+        "double" => "a number",
+        "base64" => "base64",
+        _ => "a value",
+    }
+}
+
+struct Recovery<'a> {
+    content: &'a str,
+    stack: Vec<Frame>,
+    // How deep inside an element being skipped, if any.
+    skipping: usize,
+    diagnosis: Diagnosis,
+}
+
+impl<'a> Recovery<'a> {
+    // Notes a problem inside the open elements, and inside `closed` too
+    // when it is about an element just closed.
+    fn problem(&mut self, at: u64, closed: Option<&str>, expected: String, found: String) {
+        let open = self.stack.iter().map(|frame| frame.element.as_str());
+        let location = locate_in(self.content, at, open.chain(closed));
+        self.diagnosis.problems.push(Problem {
+            location,
+            expected,
+            found,
+        });
+    }
+
+    fn parent(&self) -> Option<&str> {
+        self.stack.last().map(|frame| frame.element.as_str())
+    }
+
+    // Hands what a closed element produced to the element around it.
+    fn deliver(&mut self, value: Option<Value>) {
+        match (self.stack.last_mut(), value) {
+            (Some(parent), Some(value)) => parent.values.push(value),
+            (Some(parent), None) => parent.failed = true,
+            (None, Some(value)) => self.diagnosis.values.push(value),
+            (None, None) => {}
+        }
+    }
+
+    fn start(&mut self, at: u64, element: String) {
+        if self.skipping > 0 {
+            self.skipping += 1;
+            return;
+        }
+        if !allows_child(self.parent(), &element) {
+            let expected = expected_children(self.parent()).into();
+            self.problem(at, None, expected, format!("<{}>", element));
+            if let Some(parent) = self.stack.last_mut() {
+                parent.failed = true;
+            }
+            self.skipping = 1;
+            return;
+        }
+        self.stack.push(Frame {
+            element,
+            ..Frame::default()
+        });
+    }
+
+    fn text(&mut self, at: u64, text: &str) {
+        if self.skipping > 0 {
+            return;
+        }
+        if !allows_text(self.parent()) {
+            if !text.trim().is_empty() {
+                let expected = expected_children(self.parent()).into();
+                self.problem(at, None, expected, format!("text {:?}", text.trim()));
+            }
+            return;
+        }
+        if let Some(frame) = self.stack.last_mut() {
+            frame.text.push_str(text);
+        }
+    }
+
+    fn end(&mut self, at: u64) {
+        if self.skipping > 0 {
+            self.skipping -= 1;
+            return;
+        }
+        let mut frame = match self.stack.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let value = match frame.element.as_str() {
+            "methodName" => {
+                self.diagnosis.name = Some(frame.text.trim().into());
+                return;
+            }
+            "name" => {
+                if let Some(parent) = self.stack.last_mut() {
+                    parent.name = Some(frame.text);
+                }
+                return;
+            }
+            "methodCall" | "methodResponse" => return,
+            "params" | "fault" => {
+                self.diagnosis.values.append(&mut frame.values);
+                return;
+            }
+            "param" | "data" => {
+                if let Some(parent) = self.stack.last_mut() {
+                    parent.values.append(&mut frame.values);
+                }
+                return;
+            }
+            "value" => match frame.values.pop() {
+                Some(value) => Some(value),
+                None if frame.failed => None,
+                None => Some(Value::String(frame.text)),
+            },
+            "array" => Some(Value::Array(frame.values)),
+            "struct" => Some(Value::Struct(frame.members)),
+            "member" => {
+                match (frame.name, frame.values.pop()) {
+                    (Some(name), Some(value)) => {
+                        if let Some(parent) = self.stack.last_mut() {
+                            parent.members.insert(name, value);
+                        }
+                    }
+                    (name, _) if !frame.failed => {
+                        let found = match name {
+                            Some(name) => format!("member {:?} without a <value>", name),
+                            None => "a member without a <name>".into(),
+                        };
+                        let expected = "<name> and <value>".into();
+                        self.problem(at, Some("member"), expected, found);
+                    }
+                    _ => {}
+                }
+                return;
+            }
+            element => {
+                let value = scalar(element, &frame.text);
+                if value.is_none() {
+                    let expected = describe(element).into();
+                    let found = format!("{:?}", frame.text.trim());
+                    self.problem(at, Some(element), expected, found);
+                }
+                value
+            }
+        };
+        self.deliver(value);
+    }
+}
+
+/// Reads as much of `content` as it can, noting every problem on the way
+/// rather than stopping at the first. Problems in the XML itself still end
+/// the reading, since nothing after them can be trusted.
+pub fn recover(content: &str) -> Diagnosis {
+    let mut reader = reader(content);
+    let mut recovery = Recovery {
+        content,
+        stack: Vec::new(),
+        skipping: 0,
+        diagnosis: Diagnosis::default(),
+    };
+    loop {
+        let at = reader.buffer_position();
+        let event = match reader.read_event() {
+            Ok(event) => event,
+            Err(err) => {
+                let at = reader.error_position();
+                recovery.problem(at, None, "well-formed XML".into(), err.to_string());
+                break;
+            }
+        };
+        match text_of(&event) {
+            Some(Ok(text)) => recovery.text(at, &text),
+            Some(Err(reason)) => {
+                recovery.problem(at, None, "well-formed XML".into(), reason);
+                break;
+            }
+            None => {}
+        }
+        match event {
+            XmlEvent::Start(start) => {
+                let element = String::from_utf8_lossy(start.local_name().as_ref()).into_owned();
+                recovery.start(at, element)
+            }
+            XmlEvent::End(_) => recovery.end(at),
+            XmlEvent::Eof => {
+                if !recovery.stack.is_empty() {
+                    let found = UNEXPECTED_END.into();
+                    recovery.problem(at, None, "well-formed XML".into(), found);
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+    recovery.diagnosis
+}
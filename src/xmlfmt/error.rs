@@ -1,40 +1,151 @@
-#![allow(unknown_lints, unused_doc_comments)]
 use serde::{de, ser};
 use std::fmt::{self, Display};
+use std::io;
 
-error_chain! {
-    foreign_links {
-        Fmt(fmt::Error);
-    }
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("Issue while decoding data structure: {0}")]
+    Decoding(String),
+    #[error("Issue while encoding data structure: {0}")]
+    Encoding(String),
+    #[error("Given structure is not supported: {0}")]
+    UnsupportedData(String),
+    #[error("Failed to read data source.")]
+    Io(#[from] io::Error),
+    #[error(
+        "{context}: {details}{}",
+        .location.as_ref().map(|l| format!(" at {}", l)).unwrap_or_default()
+    )]
+    Parse {
+        context: &'static str,
+        details: String,
+        location: Option<Location>,
+    },
+    #[error(
+        "Invalid {}{}: expected {expected}, found {found}",
+        .param.map(|p| format!("parameter {}", p)).unwrap_or_else(|| "value".into()),
+        .path.iter().map(ToString::to_string).collect::<String>()
+    )]
+    Mismatch {
+        /// The parameter holding the value, when decoding call parameters.
+        param: Option<usize>,
+        /// Array indices and struct members leading to the value.
+        path: Vec<PathSegment>,
+        expected: String,
+        found: String,
+    },
+}
+
+/// One step into an array or struct.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathSegment {
+    Index(usize),
+    Member(String),
+}
 
-    errors {
-        Decoding(t: String) {
-            description("Issue while decoding data structure")
-            display("Issue while decoding data structure: {}", t)
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PathSegment::Index(index) => write!(f, "[{}]", index),
+            PathSegment::Member(ref name) => write!(f, ".{}", name),
         }
-        Encoding(t: String) {
-            description("Issue while encoding data structure")
-            display("Issue while encoding data structure: {}", t)
+    }
+}
+
+impl Error {
+    pub(crate) fn within(self, segment: PathSegment) -> Error {
+        match self {
+            Error::Mismatch {
+                param,
+                mut path,
+                expected,
+                found,
+            } => {
+                path.insert(0, segment);
+                Error::Mismatch {
+                    param,
+                    path,
+                    expected,
+                    found,
+                }
+            }
+            err => err,
         }
-        UnsupportedData(t: String) {
-            description("Given structure is not supported")
-            display("Given structure is not supported: {}", t)
+    }
+
+    // Moves the leading index of the path into `param`. A lone parameter is
+    // decoded directly rather than as an array, so it has no index to move.
+    pub(crate) fn within_params(self, count: usize) -> Error {
+        match self {
+            Error::Mismatch {
+                param: None,
+                mut path,
+                expected,
+                found,
+            } => {
+                let param = if count == 1 {
+                    Some(0)
+                } else if let Some(&PathSegment::Index(index)) = path.first() {
+                    path.remove(0);
+                    Some(index)
+                } else {
+                    None
+                };
+                Error::Mismatch {
+                    param,
+                    path,
+                    expected,
+                    found,
+                }
+            }
+            err => err,
         }
     }
 }
 
+/// Where in a document parsing went wrong.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    pub line: u64,
+    pub column: u64,
+    /// The enclosing elements, e.g. `/methodCall/params/param/value`.
+    pub path: String,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}, in {}",
+            self.line, self.column, self.path
+        )
+    }
+}
+
 impl de::Error for Error {
     fn custom<T: Display>(msg: T) -> Error {
-        ErrorKind::Decoding(format!("{}", msg)).into()
+        Error::Decoding(format!("{}", msg))
+    }
+
+    fn invalid_type(unexp: de::Unexpected, exp: &dyn de::Expected) -> Self {
+        Error::Mismatch {
+            param: None,
+            path: Vec::new(),
+            expected: exp.to_string(),
+            found: unexp.to_string(),
+        }
     }
 
-    fn invalid_type(unexp: de::Unexpected, exp: &de::Expected) -> Self {
-        Error::custom(format_args!("invalid type: {}, expected {}", unexp, exp))
+    fn invalid_value(unexp: de::Unexpected, exp: &dyn de::Expected) -> Self {
+        Error::invalid_type(unexp, exp)
     }
 }
 
 impl ser::Error for Error {
     fn custom<T: Display>(msg: T) -> Error {
-        ErrorKind::Encoding(format!("{}", msg)).into()
+        Error::Encoding(format!("{}", msg))
     }
 }
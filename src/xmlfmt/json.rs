@@ -0,0 +1,99 @@
+//! Conversions between XML-RPC values and `serde_json` values, for gateways
+//! between XML-RPC and JSON APIs.
+//!
+//! Values map to JSON as follows:
+//!
+//! * `Int` and `Int64` become numbers, and `Double` a number too, except
+//!   that NaN and the infinities, which JSON cannot hold, become `null`.
+//! * `String` and `DateTime` become strings, date-times as sent, e.g.
+//!   `"19980717T14:08:55"`.
+//! * `Base64` becomes a string of the data base64-encoded.
+//! * `Array` and `Struct` become arrays and objects, and `Nil` becomes
+//!   `null`.
+//!
+//! JSON maps back as follows:
+//!
+//! * Numbers without a fraction or exponent become `Int` if they fit, else
+//!   `Int64`. Those too big for `Int64` are refused, rather than rounded to
+//!   a `Double`. Other numbers become `Double`.
+//! * Strings always become `String`, as nothing tells date-times or base64
+//!   data apart from other strings. Handlers taking `DateTime` or `Base64`
+//!   parameters from JSON have to convert them themselves.
+//! * Arrays, objects and `null` become `Array`, `Struct` and `Nil`.
+
+use base64;
+use serde_json::{self, Number, Value as Json};
+use std::convert::TryFrom;
+
+use super::error::{Error, Result};
+use super::value::Value;
+
+impl From<Value> for Json {
+    fn from(value: Value) -> Json {
+        match value {
+            Value::Int(v) => Json::from(v),
+            Value::Int64(v) => Json::from(v),
+            Value::Bool(v) => Json::Bool(v),
+            Value::String(v) | Value::DateTime(v) => Json::String(v),
+            Value::Double(v) => Number::from_f64(v).map_or(Json::Null, Json::Number),
+            Value::Base64(v) => Json::String(base64::encode(&v)),
+            Value::Array(v) => Json::Array(v.into_iter().map(Json::from).collect()),
+            Value::Struct(v) => Json::Object(
+                v.into_iter()
+                    .map(|(key, value)| (key, Json::from(value)))
+                    .collect(),
+            ),
+            Value::Nil => Json::Null,
+        }
+    }
+}
+
+impl<'a> From<&'a Value> for Json {
+    fn from(value: &'a Value) -> Json {
+        Json::from(value.clone())
+    }
+}
+
+impl TryFrom<Json> for Value {
+    type Error = Error;
+
+    fn try_from(json: Json) -> Result<Value> {
+        Ok(match json {
+            Json::Null => Value::Nil,
+            Json::Bool(v) => Value::Bool(v),
+            Json::Number(v) => match v.as_i64() {
+                Some(int) => i32::try_from(int).map_or(Value::Int64(int), Value::Int),
+                None if v.is_u64() => {
+                    return Err(Error::UnsupportedData(format!(
+                        "integer {} is too big for <i8>",
+                        v
+                    )))
+                }
+                None => Value::Double(v.as_f64().unwrap_or(0.0)),
+            },
+            Json::String(v) => Value::String(v),
+            Json::Array(v) => {
+                Value::Array(v.into_iter().map(Value::try_from).collect::<Result<_>>()?)
+            }
+            Json::Object(v) => Value::Struct(
+                v.into_iter()
+                    .map(|(key, value)| Value::try_from(value).map(|value| (key, value)))
+                    .collect::<Result<_>>()?,
+            ),
+        })
+    }
+}
+
+impl Value {
+    /// The value as JSON text.
+    pub fn to_json_string(&self) -> String {
+        Json::from(self).to_string()
+    }
+
+    /// Reads a value from JSON text.
+    pub fn from_json_str(json: &str) -> Result<Value> {
+        let json: Json =
+            serde_json::from_str(json).map_err(|err| Error::Decoding(err.to_string()))?;
+        Value::try_from(json)
+    }
+}
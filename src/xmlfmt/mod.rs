@@ -1,24 +1,139 @@
 use serde::{Deserialize, Serialize};
 
+/// Builds `Params`, converting each element with `Value::from`, as in
+/// `params![42, "name", vec![1, 2]]`.
+#[macro_export]
+macro_rules! params {
+    ($($param:expr),* $(,)*) => {
+        {
+            let params: $crate::Params = vec![$($crate::Value::from($param)),*];
+            params
+        }
+    };
+}
+
+/// Builds a `Value` from JSON-like syntax, as in
+/// `xmlrpc_value!({"name": "foo", "sizes": [1, 2], "parent": nil})`.
+///
+/// Arrays and structs nest. Struct member names are string literals, or
+/// expressions in parentheses. `nil` stands for `Value::Nil`, and anything
+/// else is converted with `Value::from`.
+#[macro_export]
+macro_rules! xmlrpc_value {
+    (@array [$($done:expr,)*]) => {
+        vec![$($done),*]
+    };
+    (@array [$($done:expr,)*] nil $(,)*) => {
+        $crate::xmlrpc_value!(@array [$($done,)* $crate::Value::Nil,])
+    };
+    (@array [$($done:expr,)*] nil, $($rest:tt)+) => {
+        $crate::xmlrpc_value!(@array [$($done,)* $crate::Value::Nil,] $($rest)+)
+    };
+    (@array [$($done:expr,)*] [$($inner:tt)*] $(,)*) => {
+        $crate::xmlrpc_value!(@array [$($done,)* $crate::xmlrpc_value!([$($inner)*]),])
+    };
+    (@array [$($done:expr,)*] [$($inner:tt)*], $($rest:tt)+) => {
+        $crate::xmlrpc_value!(@array [$($done,)* $crate::xmlrpc_value!([$($inner)*]),] $($rest)+)
+    };
+    (@array [$($done:expr,)*] {$($inner:tt)*} $(,)*) => {
+        $crate::xmlrpc_value!(@array [$($done,)* $crate::xmlrpc_value!({$($inner)*}),])
+    };
+    (@array [$($done:expr,)*] {$($inner:tt)*}, $($rest:tt)+) => {
+        $crate::xmlrpc_value!(@array [$($done,)* $crate::xmlrpc_value!({$($inner)*}),] $($rest)+)
+    };
+    (@array [$($done:expr,)*] $next:expr $(,)*) => {
+        $crate::xmlrpc_value!(@array [$($done,)* $crate::Value::from($next),])
+    };
+    (@array [$($done:expr,)*] $next:expr, $($rest:tt)+) => {
+        $crate::xmlrpc_value!(@array [$($done,)* $crate::Value::from($next),] $($rest)+)
+    };
+
+    (@struct $members:ident) => {};
+    (@struct $members:ident $name:tt : nil $(,)*) => {
+        $crate::xmlrpc_value!(@member $members $name $crate::Value::Nil);
+    };
+    (@struct $members:ident $name:tt : nil, $($rest:tt)+) => {
+        $crate::xmlrpc_value!(@member $members $name $crate::Value::Nil);
+        $crate::xmlrpc_value!(@struct $members $($rest)+);
+    };
+    (@struct $members:ident $name:tt : [$($inner:tt)*] $(,)*) => {
+        $crate::xmlrpc_value!(@member $members $name $crate::xmlrpc_value!([$($inner)*]));
+    };
+    (@struct $members:ident $name:tt : [$($inner:tt)*], $($rest:tt)+) => {
+        $crate::xmlrpc_value!(@member $members $name $crate::xmlrpc_value!([$($inner)*]));
+        $crate::xmlrpc_value!(@struct $members $($rest)+);
+    };
+    (@struct $members:ident $name:tt : {$($inner:tt)*} $(,)*) => {
+        $crate::xmlrpc_value!(@member $members $name $crate::xmlrpc_value!({$($inner)*}));
+    };
+    (@struct $members:ident $name:tt : {$($inner:tt)*}, $($rest:tt)+) => {
+        $crate::xmlrpc_value!(@member $members $name $crate::xmlrpc_value!({$($inner)*}));
+        $crate::xmlrpc_value!(@struct $members $($rest)+);
+    };
+    (@struct $members:ident $name:tt : $value:expr $(,)*) => {
+        $crate::xmlrpc_value!(@member $members $name $crate::Value::from($value));
+    };
+    (@struct $members:ident $name:tt : $value:expr, $($rest:tt)+) => {
+        $crate::xmlrpc_value!(@member $members $name $crate::Value::from($value));
+        $crate::xmlrpc_value!(@struct $members $($rest)+);
+    };
+    (@member $members:ident $name:tt $value:expr) => {
+        $members.insert(::std::string::String::from($name), $value);
+    };
+
+    (nil) => {
+        $crate::Value::Nil
+    };
+    ([ $($elements:tt)* ]) => {
+        $crate::Value::Array($crate::xmlrpc_value!(@array [] $($elements)*))
+    };
+    ({ $($members:tt)* }) => {
+        {
+            let mut members = ::std::collections::HashMap::new();
+            $crate::xmlrpc_value!(@struct members $($members)*);
+            $crate::Value::Struct(members)
+        }
+    };
+    ($other:expr) => {
+        $crate::Value::from($other)
+    };
+}
+
+#[cfg(feature = "encoding")]
+pub mod charset;
+#[cfg(feature = "chrono")]
+pub mod datetime;
 mod de;
+mod diagnose;
 pub mod error;
+#[cfg(feature = "serde_json")]
+pub mod json;
+mod multicall;
 pub mod parse;
+pub mod profile;
+pub mod scratch;
 mod ser;
 #[cfg(test)]
 mod tests;
 pub mod value;
 
-pub use self::value::{Call, Fault, Params, Response, Value};
+pub use self::multicall::Multicall;
+#[cfg(feature = "server")]
+pub(crate) use self::multicall::{answer as answer_multicall, METHOD as MULTICALL_METHOD};
+pub use self::profile::{Doubles, IntTag, Layout, WireProfile};
+pub use self::value::{
+    Call, CallBuilder, Fault, FaultCode, IntoFault, Params, Response, ResponseExt, Value,
+};
 
 pub fn from_params<'a, T: Deserialize<'a>>(mut params: Params) -> error::Result<T> {
-    use self::error::ResultExt;
+    let count = params.len();
     let data = if params.len() == 1 {
         params.pop().unwrap()
     } else {
         Value::Array(params)
     };
 
-    T::deserialize(data).chain_err(|| "Failed to convert XML-RPC to structure.")
+    T::deserialize(data).map_err(|err| err.within_params(count))
 }
 
 pub fn into_params<T: Serialize>(v: &T) -> error::Result<Params> {
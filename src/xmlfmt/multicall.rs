@@ -0,0 +1,151 @@
+//! Boxcarring: several calls sent as one call to `system.multicall`, and
+//! answered together.
+//!
+//! The calls travel as an array of structs with `methodName` and `params`
+//! members. Each is answered in the same place of the response array, with
+//! an array of its return values or with its fault struct.
+
+use std::collections::HashMap;
+
+use super::error::{Error, Result};
+use super::value::{Call, Fault, Params, Response, Value};
+
+// The name boxcarred calls are sent to.
+pub(crate) const METHOD: &str = "system.multicall";
+
+/// Calls to make in one round trip, with `Client::multicall`, as in
+/// `Multicall::new().call(first).call(second)`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Multicall {
+    calls: Vec<Call>,
+}
+
+impl Multicall {
+    pub fn new() -> Multicall {
+        Multicall::default()
+    }
+
+    /// Adds `call`, whose response comes in the same place among the
+    /// responses as the call among the calls.
+    pub fn call(mut self, call: Call) -> Multicall {
+        self.calls.push(call);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// The call to `system.multicall` carrying the calls.
+    pub fn to_call(&self) -> Call {
+        let calls = self
+            .calls
+            .iter()
+            .map(|call| {
+                let mut members = HashMap::new();
+                members.insert("methodName".into(), Value::String(call.name.clone()));
+                members.insert("params".into(), Value::Array(call.params.clone()));
+                Value::Struct(members)
+            })
+            .collect();
+        Call {
+            name: METHOD.into(),
+            params: vec![Value::Array(calls)],
+        }
+    }
+
+    /// Splits the parameters of a successful response to `to_call` into the
+    /// response to each call.
+    pub fn responses(&self, mut params: Params) -> Result<Vec<Response>> {
+        let results = match (params.pop(), params.is_empty()) {
+            (Some(Value::Array(results)), true) => results,
+            _ => return Err(malformed("expected a single array of results")),
+        };
+        if results.len() != self.calls.len() {
+            return Err(malformed(&format!(
+                "expected {} results, found {}",
+                self.calls.len(),
+                results.len()
+            )));
+        }
+        results.into_iter().map(response).collect()
+    }
+}
+
+fn malformed(details: &str) -> Error {
+    Error::Decoding(format!("Malformed {} response: {}", METHOD, details))
+}
+
+// One result of a multicall, back as the response it stands for.
+fn response(result: Value) -> Result<Response> {
+    match result {
+        Value::Array(values) => Ok(Ok(values)),
+        Value::Struct(members) => match (members.get("faultCode"), members.get("faultString")) {
+            (Some(&Value::Int(code)), Some(Value::String(message))) => {
+                Ok(Err(Fault::new(code, message.clone())))
+            }
+            _ => Err(malformed("expected a fault struct")),
+        },
+        _ => Err(malformed("expected an array of values or a fault")),
+    }
+}
+
+// The call in one element of the parameter to `system.multicall`, or what
+// is wrong with it.
+#[cfg(feature = "server")]
+fn embedded(value: Value) -> ::std::result::Result<Call, String> {
+    let mut members = match value {
+        Value::Struct(members) => members,
+        _ => return Err("Each call must be a struct".into()),
+    };
+    let name = match members.remove("methodName") {
+        Some(Value::String(name)) => name,
+        _ => return Err("Each call needs a string methodName".into()),
+    };
+    let params = match members.remove("params") {
+        Some(Value::Array(params)) => params,
+        None => Vec::new(),
+        Some(_) => return Err("The params of each call must be an array".into()),
+    };
+    Ok(Call { name, params })
+}
+
+// Answers the calls in `params` with `handle`, each in its own place.
+// Malformed calls are answered with `invalid_params` faults.
+#[cfg(feature = "server")]
+pub(crate) fn answer<F>(mut params: Params, invalid_params: i32, mut handle: F) -> Response
+where
+    F: FnMut(Call) -> Response,
+{
+    let calls = match (params.pop(), params.is_empty()) {
+        (Some(Value::Array(calls)), true) => calls,
+        _ => {
+            return Err(Fault::new(
+                invalid_params,
+                format!("{} takes a single array of calls", METHOD),
+            ))
+        }
+    };
+    let results = calls
+        .into_iter()
+        .map(|call| {
+            let response = embedded(call)
+                .map_err(|message| Fault::new(invalid_params, message))
+                .and_then(&mut handle);
+            match response {
+                Ok(values) => Value::Array(values),
+                Err(fault) => {
+                    let mut members = HashMap::new();
+                    members.insert("faultCode".into(), Value::Int(fault.code));
+                    members.insert("faultString".into(), Value::String(fault.message));
+                    Value::Struct(members)
+                }
+            }
+        })
+        .collect();
+    Ok(vec![Value::Array(results)])
+}
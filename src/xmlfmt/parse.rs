@@ -1,237 +1,693 @@
-use super::error::{Result, ResultExt};
+//! A pull parser for XML-RPC documents, building values straight from the
+//! XML events without an intermediate document tree.
+
+#[cfg(feature = "encoding")]
+use super::charset;
+use super::diagnose;
+pub use super::diagnose::{Diagnosis, Problem};
+use super::error::{Error, Result};
 use super::{Call, Fault, Response, Value};
 use base64;
-use regex::Regex;
-use serde_xml_rs::deserialize;
-use std;
+use quick_xml;
+use quick_xml::events::Event as XmlEvent;
+use quick_xml::reader::Reader as XmlReader;
+use serde::Deserialize;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt::Display;
+use std::io::{BufReader, Read};
+
+// Parsing recurses once per element, so deeper documents are refused
+// before they can overflow the stack. Real calls stay far below.
+const MAX_DEPTH: usize = 256;
+
+/// Bounds on the calls `call_within` accepts, for servers that must not
+/// spend much on hostile requests.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// How deep elements may nest, at most 256.
+    pub max_depth: usize,
+    /// How many parameters a call may have.
+    pub max_params: Option<usize>,
+}
 
-fn wrap_in_string(content: String) -> String {
-    lazy_static! {
-        static ref RE1: Regex = Regex::new(r"<value\s*/>").unwrap();
-        static ref RE2: Regex = Regex::new(r"<value\s*>\s*<string\s*/>\s*</value\s*>").unwrap();
-        static ref RE3: Regex = Regex::new(r"<value\s*>(?P<rest>[^<>]*)</value\s*>").unwrap();
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_depth: MAX_DEPTH,
+            max_params: None,
+        }
     }
-    RE3.replace_all(
-        &RE2.replace_all(
-            &RE1.replace_all(&content, "<value><string></string></value>"),
-            "<value><string></string></value>",
-        ),
-        "<value><string>$rest</string></value>",
-    )
-    .into()
 }
 
-#[allow(dead_code)]
-pub fn xml<T: std::io::Read>(mut r: T) -> Result<Value> {
-    let mut content = String::new();
-    r.read_to_string(&mut content)
-        .chain_err(|| "Failed to read data source.")?;
-    let data: XmlValue = deserialize(std::io::Cursor::new(wrap_in_string(content)))
-        .chain_err(|| "Failed to parse XML-RPC data.")?;
-    data.into()
+fn fail<E: Display>(context: &'static str, err: E) -> Error {
+    Error::Parse {
+        context,
+        details: err.to_string(),
+        location: None,
+    }
 }
 
-pub fn call<T: std::io::Read>(mut r: T) -> Result<Call> {
-    let mut content = String::new();
-    r.read_to_string(&mut content)
-        .chain_err(|| "Failed to read data source.")?;
-    let data: XmlCall = deserialize(std::io::Cursor::new(wrap_in_string(content)))
-        .chain_err(|| "Failed to parse XML-RPC call.")?;
-    data.into()
+fn with_location(err: Error, content: &str) -> Error {
+    match err {
+        Error::Parse {
+            context,
+            details,
+            location: None,
+        } => match diagnose::first_problem(content) {
+            Some((reason, location)) => Error::Parse {
+                context,
+                details: reason,
+                location: Some(location),
+            },
+            None => Error::Parse {
+                context,
+                details,
+                location: None,
+            },
+        },
+        err => err,
+    }
 }
 
-pub fn response<T: std::io::Read>(mut r: T) -> Result<Response> {
+#[cfg(not(feature = "encoding"))]
+fn read_content<T: Read>(mut r: T) -> Result<String> {
     let mut content = String::new();
-    r.read_to_string(&mut content)
-        .chain_err(|| "Failed to read data source.")?;
-    let data: XmlResponse = deserialize(std::io::Cursor::new(wrap_in_string(content)))
-        .chain_err(|| "Failed to parse XML-RPC response.")?;
-    data.into()
-}
-
-#[derive(Debug, PartialEq, Deserialize)]
-enum XmlValue {
-    #[serde(rename = "i4")]
-    I4(i32),
-    #[serde(rename = "int")]
-    Int(i32),
-    #[serde(rename = "boolean")]
-    Bool(i32),
-    #[serde(rename = "string")]
-    Str(String),
-    #[serde(rename = "double")]
-    Double(String),
-    #[serde(rename = "dateTime.iso8601")]
-    DateTime(String),
-    #[serde(rename = "base64")]
-    Base64(String),
-    #[serde(rename = "array")]
-    Array(XmlArray),
-    #[serde(rename = "struct")]
-    Struct(XmlStruct),
-}
-
-impl Into<Result<Value>> for XmlValue {
-    fn into(self) -> Result<Value> {
-        Ok(match self {
-            XmlValue::I4(v) | XmlValue::Int(v) => Value::Int(v),
-            XmlValue::Bool(v) => Value::Bool(v != 0),
-            XmlValue::Str(v) => Value::String(v),
-            XmlValue::Double(v) => Value::Double(v.parse().chain_err(|| "Failed to parse double")?),
-            XmlValue::DateTime(v) => Value::DateTime(v),
-            XmlValue::Base64(v) => {
-                Value::Base64(base64::decode(v.as_bytes()).chain_err(|| "Failed to parse base64")?)
-            }
-            XmlValue::Array(v) => {
-                let items: Result<Vec<Value>> = v.into();
-                Value::Array(items?)
-            }
-            XmlValue::Struct(v) => {
-                let items: Result<HashMap<String, Value>> = v.into();
-                Value::Struct(items?)
-            }
-        })
+    r.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+// Documents are read in the encoding they declare.
+#[cfg(feature = "encoding")]
+fn read_content<T: Read>(r: T) -> Result<String> {
+    read_content_in(r, None)
+}
+
+#[cfg(feature = "encoding")]
+fn read_content_in<T: Read>(mut r: T, charset: Option<&str>) -> Result<String> {
+    let mut bytes = Vec::new();
+    r.read_to_end(&mut bytes)?;
+    charset::decode(bytes, charset)
+}
+
+// The charset a `Content-Type` names, unless it is UTF-8, which documents
+// are read in anyway, for `call_in_charset` and `response_in_charset`.
+#[cfg(any(feature = "client", feature = "server"))]
+pub(crate) fn content_charset(content_type: Option<&str>) -> Option<&str> {
+    let charset = content_type?.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_at(param.find('=')?);
+        if name.trim().eq_ignore_ascii_case("charset") {
+            Some(value[1..].trim().trim_matches('"'))
+        } else {
+            None
+        }
+    })?;
+    if charset.eq_ignore_ascii_case("utf-8") || charset.eq_ignore_ascii_case("utf8") {
+        None
+    } else {
+        Some(charset)
+    }
+}
+
+// Text outside of markup and in CDATA sections alike, borrowed from the
+// document where nothing needs unescaping.
+fn append<'a>(content: &mut Cow<'a, str>, text: Cow<'a, str>) {
+    if content.is_empty() {
+        *content = text;
+    } else {
+        content.to_mut().push_str(&text);
+    }
+}
+
+// The text of an element, without the whitespace around it.
+fn trim(text: Cow<str>) -> Cow<str> {
+    match text {
+        Cow::Borrowed(text) => Cow::Borrowed(text.trim()),
+        Cow::Owned(text) if text.trim().len() == text.len() => Cow::Owned(text),
+        Cow::Owned(text) => Cow::Owned(text.trim().to_owned()),
+    }
+}
+
+// The elements of XML-RPC, whose names are never copied out of documents.
+const ELEMENTS: [&str; 21] = [
+    "methodCall",
+    "methodResponse",
+    "methodName",
+    "params",
+    "param",
+    "fault",
+    "value",
+    "i4",
+    "int",
+    "i8",
+    "boolean",
+    "string",
+    "double",
+    "dateTime.iso8601",
+    "nil",
+    "base64",
+    "array",
+    "data",
+    "struct",
+    "member",
+    "name",
+];
+
+// The local name of an element.
+enum Name {
+    Known(&'static str),
+    Other(String),
+}
+
+impl Name {
+    fn new(name: &[u8]) -> Name {
+        match ELEMENTS.iter().find(|known| known.as_bytes() == name) {
+            Some(known) => Name::Known(known),
+            None => Name::Other(String::from_utf8_lossy(name).into_owned()),
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        match *self {
+            Name::Known(name) => name,
+            Name::Other(ref name) => name,
+        }
+    }
+}
+
+enum Event<'a> {
+    Start(Name),
+    Text(Cow<'a, str>),
+    End,
+}
+
+// Where a `Reader` gets XML events from: a document in memory, whose
+// events borrow from it, or a stream, whose events are copied out of the
+// buffer they are read into.
+trait Source<'a> {
+    fn read(&mut self) -> quick_xml::Result<XmlEvent<'a>>;
+}
+
+impl<'a> Source<'a> for XmlReader<&'a [u8]> {
+    fn read(&mut self) -> quick_xml::Result<XmlEvent<'a>> {
+        self.read_event()
+    }
+}
+
+struct Stream<R> {
+    reader: XmlReader<BufReader<R>>,
+    buffer: Vec<u8>,
+}
+
+impl<'a, R: Read> Source<'a> for Stream<R> {
+    fn read(&mut self) -> quick_xml::Result<XmlEvent<'a>> {
+        self.buffer.clear();
+        self.reader
+            .read_event_into(&mut self.buffer)
+            .map(XmlEvent::into_owned)
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
-#[serde(rename = "methodCall")]
-struct XmlCall {
-    #[serde(rename = "methodName")]
-    pub name: String,
-    pub params: XmlParams,
+fn configure<R>(reader: &mut XmlReader<R>) {
+    reader.config_mut().expand_empty_elements = true;
+}
+
+struct Reader<S> {
+    source: S,
+    context: &'static str,
+    depth: usize,
+    limits: Limits,
+}
+
+impl<'a> Reader<XmlReader<&'a [u8]>> {
+    fn new(content: &'a str, context: &'static str) -> Reader<XmlReader<&'a [u8]>> {
+        let mut source = XmlReader::from_str(content);
+        configure(&mut source);
+        Reader::with_source(source, context)
+    }
 }
 
-impl Into<Result<Call>> for XmlCall {
-    fn into(self) -> Result<Call> {
-        let params: Result<Vec<Value>> = self.params.into();
-        Ok(Call {
-            name: self.name,
-            params: params?,
-        })
+impl<R: Read> Reader<Stream<R>> {
+    fn streaming(source: R, context: &'static str) -> Reader<Stream<R>> {
+        let mut reader = XmlReader::from_reader(BufReader::new(source));
+        configure(&mut reader);
+        let source = Stream {
+            reader,
+            buffer: Vec::new(),
+        };
+        Reader::with_source(source, context)
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
-enum XmlResponseResult {
-    #[serde(rename = "params")]
-    Success(XmlParams),
-    #[serde(rename = "fault")]
-    Failure { value: XmlValue },
+impl<S> Reader<S> {
+    fn with_source(source: S, context: &'static str) -> Reader<S> {
+        Reader {
+            source,
+            context,
+            depth: 0,
+            limits: Limits::default(),
+        }
+    }
+
+    fn within(mut self, limits: &Limits) -> Reader<S> {
+        self.limits = Limits {
+            max_depth: limits.max_depth.min(MAX_DEPTH),
+            ..*limits
+        };
+        self
+    }
+
+    fn fail<E: Display>(&self, err: E) -> Error {
+        fail(self.context, err)
+    }
 }
 
-impl Into<Result<Response>> for XmlResponseResult {
-    fn into(self) -> Result<Response> {
-        match self {
-            XmlResponseResult::Success(params) => {
-                let params: Result<Vec<Value>> = params.into();
-                Ok(Ok(params?))
+impl<'a, S: Source<'a>> Reader<S> {
+    // The next element or text. Whitespace between elements is skipped,
+    // and comments, processing instructions and declarations are ignored.
+    fn next(&mut self) -> Result<Event<'a>> {
+        loop {
+            match self.source.read().map_err(|err| fail(self.context, err))? {
+                XmlEvent::Start(start) => {
+                    self.depth += 1;
+                    if self.depth > self.limits.max_depth {
+                        return Err(self.fail(format!(
+                            "elements nested more than {} deep",
+                            self.limits.max_depth
+                        )));
+                    }
+                    return Ok(Event::Start(Name::new(start.local_name().as_ref())));
+                }
+                XmlEvent::End(_) => {
+                    self.depth -= 1;
+                    return Ok(Event::End);
+                }
+                XmlEvent::Text(ref text) if text.iter().all(u8::is_ascii_whitespace) => {}
+                XmlEvent::Text(text) => {
+                    return text
+                        .unescape()
+                        .map(Event::Text)
+                        .map_err(|err| self.fail(err))
+                }
+                XmlEvent::CData(data) => {
+                    return data.decode().map(Event::Text).map_err(|err| self.fail(err))
+                }
+                XmlEvent::Eof => return Err(self.fail("unexpected end of document")),
+                _ => {}
             }
-            XmlResponseResult::Failure { value: v } => {
-                use serde::Deserialize;
+        }
+    }
 
-                let val: Result<Value> = v.into();
+    // The root element's name.
+    fn root(&mut self) -> Result<Name> {
+        match self.next()? {
+            Event::Start(name) => Ok(name),
+            _ => Err(self.fail("expected a root element")),
+        }
+    }
 
-                Ok(Err(
-                    Fault::deserialize(val?).chain_err(|| "Failed to decode fault structure")?
-                ))
+    // The next child element of the open element, or `None` once it closes.
+    fn child(&mut self, parent: &str) -> Result<Option<Name>> {
+        match self.next()? {
+            Event::Start(name) => Ok(Some(name)),
+            Event::End => Ok(None),
+            Event::Text(text) => Err(self.fail(format!(
+                "unexpected text {:?} inside <{}>",
+                text.trim(),
+                parent
+            ))),
+        }
+    }
+
+    // The text of the open element, which must not have children.
+    fn text(&mut self, element: &str) -> Result<Cow<'a, str>> {
+        let mut content = Cow::Borrowed("");
+        loop {
+            match self.next()? {
+                Event::Text(text) => append(&mut content, text),
+                Event::End => return Ok(trim(content)),
+                Event::Start(child) => return Err(unexpected(self, &child, element)),
+            }
+        }
+    }
+
+    // Skips over the rest of the open element.
+    fn skip(&mut self) -> Result<()> {
+        let mut open = 1;
+        while open > 0 {
+            match self.next()? {
+                Event::Start(_) => open += 1,
+                Event::End => open -= 1,
+                Event::Text(_) => {}
             }
         }
+        Ok(())
+    }
+}
+
+// Points `err` at the first problem in `content`. Documents that are too
+// deep are not scanned again, as that could take long.
+fn locate<S>(reader: &Reader<S>, err: Error, content: &str) -> Error {
+    if reader.depth > reader.limits.max_depth {
+        err
+    } else {
+        with_location(err, content)
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
-enum XmlResponse {
-    #[serde(rename = "methodResponse")]
-    Response(XmlResponseResult),
+fn unexpected<S>(reader: &Reader<S>, child: &Name, parent: &str) -> Error {
+    reader.fail(format!(
+        "unexpected element <{}> inside <{}>",
+        child.as_str(),
+        parent
+    ))
+}
+
+fn int<S>(reader: &Reader<S>, text: &str) -> Result<i32> {
+    text.parse().map_err(|err| reader.fail(err))
 }
 
-impl Into<Result<Response>> for XmlResponse {
-    fn into(self) -> Result<Response> {
-        match self {
-            XmlResponse::Response(v) => v.into(),
+// Reads the contents of a `<value>`, which is either a typed element or
+// bare text standing for a string.
+fn value<'a, S: Source<'a>>(reader: &mut Reader<S>) -> Result<Value> {
+    let mut content = Cow::Borrowed("");
+    loop {
+        match reader.next()? {
+            Event::Text(text) => append(&mut content, text),
+            Event::End => return Ok(Value::String(trim(content).into_owned())),
+            Event::Start(ref kind) if content.trim().is_empty() => {
+                let value = typed_value(reader, kind)?;
+                return match reader.child("value")? {
+                    None => Ok(value),
+                    Some(child) => Err(unexpected(reader, &child, "value")),
+                };
+            }
+            Event::Start(kind) => return Err(unexpected(reader, &kind, "value")),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
-struct XmlParams {
-    #[serde(rename = "param", default)]
-    pub params: Vec<XmlParamData>,
+// Reads a value of type `kind`, whose element was just opened.
+fn typed_value<'a, S: Source<'a>>(reader: &mut Reader<S>, kind: &Name) -> Result<Value> {
+    let kind = match *kind {
+        Name::Known(kind) => kind,
+        Name::Other(ref kind) => return Err(reader.fail(format!("unknown value type <{}>", kind))),
+    };
+    Ok(match kind {
+        "i4" | "int" => {
+            let text = reader.text(kind)?;
+            Value::Int(int(reader, &text)?)
+        }
+        "i8" => {
+            let text = reader.text(kind)?;
+            Value::Int64(text.parse().map_err(|err| reader.fail(err))?)
+        }
+        "boolean" => {
+            let text = reader.text(kind)?;
+            Value::Bool(int(reader, &text)? != 0)
+        }
+        "string" => Value::String(reader.text(kind)?.into_owned()),
+        "double" => Value::Double(
+            reader
+                .text(kind)?
+                .parse()
+                .map_err(|err| fail("Failed to parse double", err))?,
+        ),
+        "dateTime.iso8601" => Value::DateTime(reader.text(kind)?.into_owned()),
+        "nil" => {
+            reader.text(kind)?;
+            Value::Nil
+        }
+        "base64" => {
+            let mut text = reader.text(kind)?;
+            // Encoders commonly wrap long lines.
+            if text.bytes().any(|byte| byte.is_ascii_whitespace()) {
+                text.to_mut().retain(|c| !c.is_ascii_whitespace());
+            }
+            Value::Base64(
+                base64::decode(text.as_bytes())
+                    .map_err(|err| fail("Failed to parse base64", err))?,
+            )
+        }
+        "array" => {
+            let mut items = Vec::new();
+            while let Some(child) = reader.child(kind)? {
+                match child.as_str() {
+                    "data" => items = values(reader, "data")?,
+                    _ => reader.skip()?,
+                }
+            }
+            Value::Array(items)
+        }
+        "struct" => {
+            let mut members = HashMap::new();
+            while let Some(child) = reader.child(kind)? {
+                match child.as_str() {
+                    "member" => {
+                        let (name, value) = member(reader)?;
+                        members.insert(name, value);
+                    }
+                    _ => reader.skip()?,
+                }
+            }
+            Value::Struct(members)
+        }
+        _ => return Err(reader.fail(format!("unknown value type <{}>", kind))),
+    })
 }
 
-impl Into<Result<Vec<Value>>> for XmlParams {
-    fn into(self) -> Result<Vec<Value>> {
-        self.params
-            .into_iter()
-            .map(Into::<Result<Value>>::into)
-            .collect()
+// Reads every `<value>` inside the open `parent`.
+fn values<'a, S: Source<'a>>(reader: &mut Reader<S>, parent: &str) -> Result<Vec<Value>> {
+    let mut items = Vec::new();
+    while let Some(child) = reader.child(parent)? {
+        match child.as_str() {
+            "value" => items.push(value(reader)?),
+            _ => reader.skip()?,
+        }
     }
+    Ok(items)
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
-struct XmlParamData {
-    pub value: XmlValue,
+// Reads the single `<value>` that `parent` must hold.
+fn single_value<'a, S: Source<'a>>(reader: &mut Reader<S>, parent: &str) -> Result<Value> {
+    let mut found = values(reader, parent)?;
+    match found.len() {
+        1 => Ok(found.remove(0)),
+        _ => Err(reader.fail(format!("expected one <value> inside <{}>", parent))),
+    }
 }
 
-impl Into<Result<Value>> for XmlParamData {
-    fn into(self) -> Result<Value> {
-        self.value.into()
+fn member<'a, S: Source<'a>>(reader: &mut Reader<S>) -> Result<(String, Value)> {
+    let mut name = None;
+    let mut value = None;
+    while let Some(child) = reader.child("member")? {
+        match child.as_str() {
+            "name" => name = Some(reader.text("name")?.into_owned()),
+            "value" => value = Some(self::value(reader)?),
+            _ => reader.skip()?,
+        }
+    }
+    match (name, value) {
+        (Some(name), Some(value)) => Ok((name, value)),
+        _ => Err(reader.fail("<member> needs both a <name> and a <value>")),
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
-struct XmlArray {
-    #[serde(rename = "data")]
-    pub data: XmlArrayData,
+fn params<'a, S: Source<'a>>(reader: &mut Reader<S>) -> Result<Vec<Value>> {
+    let mut params = Vec::new();
+    while let Some(child) = reader.child("params")? {
+        match child.as_str() {
+            "param" => {
+                if reader.limits.max_params == Some(params.len()) {
+                    return Err(reader.fail(format!("more than {} parameters", params.len())));
+                }
+                params.push(single_value(reader, "param")?)
+            }
+            _ => reader.skip()?,
+        }
+    }
+    Ok(params)
 }
 
-impl Into<Result<Vec<Value>>> for XmlArray {
-    fn into(self) -> Result<Vec<Value>> {
-        self.data.into()
+fn read_call<'a, S: Source<'a>>(reader: &mut Reader<S>) -> Result<Call> {
+    let root = reader.root()?;
+    if root.as_str() != "methodCall" {
+        return Err(reader.fail(format!("expected <methodCall>, found <{}>", root.as_str())));
     }
+    call_body(reader)
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
-struct XmlArrayData {
-    #[serde(default)]
-    pub value: Vec<XmlValue>,
+// The children of an open <methodCall>.
+fn call_body<'a, S: Source<'a>>(reader: &mut Reader<S>) -> Result<Call> {
+    let mut name = None;
+    let mut params = Vec::new();
+    while let Some(child) = reader.child("methodCall")? {
+        match child.as_str() {
+            "methodName" => name = Some(reader.text("methodName")?.into_owned()),
+            "params" => params = self::params(reader)?,
+            _ => reader.skip()?,
+        }
+    }
+    match name {
+        Some(name) => Ok(Call { name, params }),
+        None => Err(reader.fail("missing <methodName>")),
+    }
 }
 
-impl Into<Result<Vec<Value>>> for XmlArrayData {
-    fn into(self) -> Result<Vec<Value>> {
-        self.value
-            .into_iter()
-            .map(Into::<Result<Value>>::into)
-            .collect()
+fn read_response<'a, S: Source<'a>>(reader: &mut Reader<S>) -> Result<Response> {
+    let root = reader.root()?;
+    if root.as_str() != "methodResponse" {
+        return Err(reader.fail(format!(
+            "expected <methodResponse>, found <{}>",
+            root.as_str()
+        )));
     }
+    response_body(reader)
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
-struct XmlStruct {
-    #[serde(rename = "member", default)]
-    pub members: Vec<XmlStructItem>,
+// The children of an open <methodResponse>.
+fn response_body<'a, S: Source<'a>>(reader: &mut Reader<S>) -> Result<Response> {
+    let response = match reader.child("methodResponse")? {
+        Some(Name::Known("params")) => Ok(params(reader)?),
+        Some(Name::Known("fault")) => {
+            let value = single_value(reader, "fault")?;
+            Err(Fault::deserialize(value)
+                .map_err(|err| fail("Failed to decode fault structure", err))?)
+        }
+        Some(child) => return Err(unexpected(reader, &child, "methodResponse")),
+        None => return Err(reader.fail("expected <params> or <fault>")),
+    };
+    match reader.child("methodResponse")? {
+        None => Ok(response),
+        Some(child) => Err(unexpected(reader, &child, "methodResponse")),
+    }
 }
 
-impl Into<Result<HashMap<String, Value>>> for XmlStruct {
-    fn into(self) -> Result<HashMap<String, Value>> {
-        self.members
-            .into_iter()
-            .map(Into::<Result<(String, Value)>>::into)
-            .collect()
+fn read_message<'a, S: Source<'a>>(reader: &mut Reader<S>) -> Result<Message> {
+    let root = reader.root()?;
+    match root.as_str() {
+        "methodCall" => call_body(reader).map(Message::Call),
+        "methodResponse" => response_body(reader).map(Message::Response),
+        root => Err(reader.fail(format!(
+            "expected <methodCall> or <methodResponse>, found <{}>",
+            root
+        ))),
     }
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
-struct XmlStructItem {
-    pub name: String,
-    pub value: XmlValue,
+/// Either kind of XML-RPC document, as told apart by `message`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    Call(Call),
+    Response(Response),
+}
+
+#[allow(dead_code)]
+pub fn xml<T: Read>(r: T) -> Result<Value> {
+    let content = read_content(r)?;
+    let mut reader = Reader::new(&content, "Failed to parse XML-RPC data");
+    reader
+        .root()
+        .and_then(|kind| typed_value(&mut reader, &kind))
+        .map_err(|err| locate(&reader, err, &content))
+}
+
+pub fn call<T: Read>(r: T) -> Result<Call> {
+    call_within(r, &Limits::default())
+}
+
+/// Like `call`, refusing calls that go past `limits`.
+pub fn call_within<T: Read>(r: T, limits: &Limits) -> Result<Call> {
+    let content = read_content(r)?;
+    let mut reader = Reader::new(&content, "Failed to parse XML-RPC call").within(limits);
+    read_call(&mut reader).map_err(|err| locate(&reader, err, &content))
+}
+
+/// Like `call_within`, reading the call in `charset`, as named by the
+/// `Content-Type` it came with, whatever encoding it declares.
+#[cfg(feature = "encoding")]
+pub fn call_in_charset<T: Read>(r: T, charset: &str, limits: &Limits) -> Result<Call> {
+    let content = read_content_in(r, Some(charset))?;
+    let mut reader = Reader::new(&content, "Failed to parse XML-RPC call").within(limits);
+    read_call(&mut reader).map_err(|err| locate(&reader, err, &content))
+}
+
+pub fn response<T: Read>(r: T) -> Result<Response> {
+    let content = read_content(r)?;
+    let mut reader = Reader::new(&content, "Failed to parse XML-RPC response");
+    read_response(&mut reader).map_err(|err| locate(&reader, err, &content))
 }
 
-impl Into<Result<(String, Value)>> for XmlStructItem {
-    fn into(self) -> Result<(String, Value)> {
-        let value: Result<Value> = self.value.into();
-        Ok((self.name, value?))
+/// Like `response`, reading the response in `charset`, as with
+/// `call_in_charset`.
+#[cfg(feature = "encoding")]
+pub fn response_in_charset<T: Read>(r: T, charset: &str) -> Result<Response> {
+    let content = read_content_in(r, Some(charset))?;
+    let mut reader = Reader::new(&content, "Failed to parse XML-RPC response");
+    read_response(&mut reader).map_err(|err| locate(&reader, err, &content))
+}
+
+/// Like `call`, but parses the call as it is read, instead of reading it
+/// whole first. This saves holding the whole document in memory next to
+/// the values read from it, which matters for large `<base64>` values,
+/// but errors do not say where the document is broken, and the document
+/// must be in UTF-8.
+pub fn call_streaming<T: Read>(r: T) -> Result<Call> {
+    call_streaming_within(r, &Limits::default())
+}
+
+/// Like `call_streaming`, refusing calls that go past `limits`.
+pub fn call_streaming_within<T: Read>(r: T, limits: &Limits) -> Result<Call> {
+    read_call(&mut Reader::streaming(r, "Failed to parse XML-RPC call").within(limits))
+}
+
+// The name of the method a call is for, read without parsing its
+// parameters, for finding its handler before parsing the rest.
+#[cfg(feature = "tokio")]
+pub(crate) fn method_name<T: Read>(r: T) -> Result<String> {
+    let mut reader = Reader::streaming(r, "Failed to parse XML-RPC call");
+    let root = reader.root()?;
+    if root.as_str() != "methodCall" {
+        return Err(reader.fail(format!("expected <methodCall>, found <{}>", root.as_str())));
+    }
+    while let Some(child) = reader.child("methodCall")? {
+        if child.as_str() == "methodName" {
+            return Ok(reader.text("methodName")?.into_owned());
+        }
+        reader.skip()?;
     }
+    Err(reader.fail("missing <methodName>"))
+}
+
+// Like `method_name`, reading the call in `charset`, as `call_in_charset`
+// does.
+#[cfg(all(feature = "tokio", feature = "encoding"))]
+pub(crate) fn method_name_in_charset<T: Read>(r: T, charset: &str) -> Result<String> {
+    let content = read_content_in(r, Some(charset))?;
+    method_name(content.as_bytes())
+}
+
+/// Like `response`, parsing the response as it is read, as with
+/// `call_streaming`.
+pub fn response_streaming<T: Read>(r: T) -> Result<Response> {
+    read_response(&mut Reader::streaming(
+        r,
+        "Failed to parse XML-RPC response",
+    ))
+}
+
+/// Parses a call or a response, whichever the root element says it is,
+/// for code that sees traffic in both directions.
+pub fn message<T: Read>(r: T) -> Result<Message> {
+    let content = read_content(r)?;
+    let mut reader = Reader::new(&content, "Failed to parse XML-RPC message");
+    read_message(&mut reader).map_err(|err| locate(&reader, err, &content))
+}
+
+/// Reads whatever it can from a document that may be broken, listing every
+/// problem found instead of failing at the first. Meant for working out
+/// what is wrong with a payload, not for serving calls.
+pub fn recover<T: Read>(r: T) -> Result<Diagnosis> {
+    let content = read_content(r)?;
+    Ok(diagnose::recover(&content))
 }
@@ -0,0 +1,50 @@
+//! Choices in how XML-RPC is written, for peers that only read some of it.
+
+/// How elements are laid out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// All on one line.
+    #[default]
+    Compact,
+    /// One element to a line, indented by two spaces per level of nesting.
+    /// Scalars stay on the line of the `<value>` holding them.
+    Indented,
+}
+
+/// The tag of integer values. Fault codes are always written as `<int>`,
+/// as in the specification.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum IntTag {
+    #[default]
+    I4,
+    Int,
+}
+
+/// How doubles are written. Neither choice uses exponents, which XML-RPC
+/// does not allow, and both always use `.` as the decimal separator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Doubles {
+    /// With the fewest digits that read back as the same number.
+    #[default]
+    Shortest,
+    /// With this many digits after the decimal point, rounding off the rest.
+    Fixed(usize),
+}
+
+/// The XML dialect a client or server writes.
+///
+/// The default is understood by any conforming peer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WireProfile {
+    pub layout: Layout,
+    /// Writes elements without content as `<tag/>` rather than `<tag></tag>`.
+    pub self_closing: bool,
+    pub int_tag: IntTag,
+    /// Leaves out the `<string>` tag, which strings may go without.
+    pub bare_strings: bool,
+    pub doubles: Doubles,
+    /// Never writes `<i8>`, for peers that only know the types of the
+    /// specification. `Value::Int64` is written as an integer if it fits
+    /// and as a string otherwise, as serde writes `i64` by default.
+    pub no_i8: bool,
+}
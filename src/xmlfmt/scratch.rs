@@ -0,0 +1,46 @@
+//! Buffers kept per thread and reused from one message to the next, so
+//! serializing many small messages does not allocate for each of them.
+
+use std::cell::RefCell;
+
+// A buffer that grew past this is freed after use, so one huge message does
+// not leave its memory pinned to the thread.
+const MAX_RETAINED: usize = 64 * 1024;
+
+thread_local! {
+    #[cfg(any(feature = "client", feature = "server"))]
+    static BYTES: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    static TEXT: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// Runs `f` with an empty byte buffer.
+#[cfg(any(feature = "client", feature = "server"))]
+pub fn with_bytes<R, F: FnOnce(&mut Vec<u8>) -> R>(f: F) -> R {
+    BYTES.with(|cell| match cell.try_borrow_mut() {
+        Ok(mut buf) => {
+            buf.clear();
+            let result = f(&mut buf);
+            if buf.capacity() > MAX_RETAINED {
+                *buf = Vec::new();
+            }
+            result
+        }
+        // Already lent out further up the stack.
+        Err(_) => f(&mut Vec::new()),
+    })
+}
+
+/// Runs `f` with an empty string.
+pub fn with_text<R, F: FnOnce(&mut String) -> R>(f: F) -> R {
+    TEXT.with(|cell| match cell.try_borrow_mut() {
+        Ok(mut text) => {
+            text.clear();
+            let result = f(&mut text);
+            if text.capacity() > MAX_RETAINED {
+                *text = String::new();
+            }
+            result
+        }
+        Err(_) => f(&mut String::new()),
+    })
+}
@@ -1,7 +1,12 @@
-use super::error::{Error, ErrorKind};
+use super::error::Error;
 use super::Value;
 use serde::{self, Serialize};
 use std::collections::HashMap;
+use std::convert::TryFrom;
+
+// The newtype struct `datetime::utc` writes datetimes as, so that they
+// become datetime values rather than strings.
+pub(crate) const DATETIME_NEWTYPE: &str = "$xml_rpc::DateTime";
 
 pub struct Serializer;
 
@@ -33,8 +38,13 @@ impl serde::Serializer for Serializer {
         Ok(Value::Int(v))
     }
 
+    // Wider integers are strings, as not every peer takes `<i8>`.
     fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-        Ok(Value::String(v.to_string()))
+        if cfg!(feature = "ext-i8") {
+            Ok(Value::Int64(v))
+        } else {
+            Ok(Value::String(v.to_string()))
+        }
     }
 
     fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
@@ -46,11 +56,14 @@ impl serde::Serializer for Serializer {
     }
 
     fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
-        Ok(Value::String(v.to_string()))
+        self.serialize_i64(i64::from(v))
     }
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-        Ok(Value::String(v.to_string()))
+        match i64::try_from(v) {
+            Ok(v) => self.serialize_i64(v),
+            Err(_) => Ok(Value::String(v.to_string())),
+        }
     }
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
@@ -73,21 +86,44 @@ impl serde::Serializer for Serializer {
         Ok(Value::Base64(v.into()))
     }
 
+    // Without `<nil/>`, options are arrays of at most one value and the
+    // unit is an empty struct.
+    #[cfg(not(feature = "ext-nil"))]
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
         Ok(Value::Array(Vec::new()))
     }
 
-    fn serialize_some<T: ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    #[cfg(feature = "ext-nil")]
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Nil)
+    }
+
+    #[cfg(not(feature = "ext-nil"))]
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         Ok(Value::Array(vec![value.serialize(self)?]))
     }
 
+    #[cfg(feature = "ext-nil")]
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    #[cfg(not(feature = "ext-nil"))]
     fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
         Ok(Value::Struct(HashMap::new()))
     }
 
+    #[cfg(feature = "ext-nil")]
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Value::Nil)
+    }
+
     fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
         self.serialize_unit()
     }
@@ -99,22 +135,25 @@ impl serde::Serializer for Serializer {
         variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
         let mut members = HashMap::new();
-        members.insert(variant.into(), self.serialize_unit()?);
+        members.insert(variant.into(), Value::Struct(HashMap::new()));
         Ok(Value::Struct(members))
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(
+    fn serialize_newtype_struct<T>(
         self,
-        _name: &'static str,
+        name: &'static str,
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        value.serialize(self)
+        match value.serialize(self)? {
+            Value::String(text) if name == DATETIME_NEWTYPE => Ok(Value::DateTime(text)),
+            value => Ok(value),
+        }
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         _variant_index: u32,
@@ -122,7 +161,7 @@ impl serde::Serializer for Serializer {
         value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         let mut members = HashMap::new();
         members.insert(variant.into(), value.serialize(self)?);
@@ -161,9 +200,9 @@ impl serde::Serializer for Serializer {
         })
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
         Ok(SerializeMap {
-            map: HashMap::new(),
+            map: HashMap::with_capacity(len.unwrap_or(0)),
             next_key: None,
             variant: None,
         })
@@ -182,17 +221,17 @@ impl serde::Serializer for Serializer {
         _name: &'static str,
         _variant_index: u32,
         variant: &'static str,
-        _len: usize,
+        len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
         Ok(SerializeMap {
-            map: HashMap::new(),
+            map: HashMap::with_capacity(len),
             next_key: None,
             variant: Some(variant.into()),
         })
     }
 }
 
-fn to_value<T>(value: &T) -> Result<Value, Error>
+pub fn to_value<T>(value: &T) -> Result<Value, Error>
 where
     T: Serialize,
 {
@@ -209,9 +248,9 @@ impl serde::ser::SerializeSeq for SerializeVec {
     type Ok = Value;
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         self.vec.push(to_value(&value)?);
         Ok(())
@@ -234,9 +273,9 @@ impl serde::ser::SerializeTuple for SerializeVec {
     type Ok = Value;
     type Error = Error;
 
-    fn serialize_element<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         serde::ser::SerializeSeq::serialize_element(self, value)
     }
@@ -250,9 +289,9 @@ impl serde::ser::SerializeTupleStruct for SerializeVec {
     type Ok = Value;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         serde::ser::SerializeSeq::serialize_element(self, value)
     }
@@ -266,9 +305,9 @@ impl serde::ser::SerializeTupleVariant for SerializeVec {
     type Ok = Value;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         serde::ser::SerializeSeq::serialize_element(self, value)
     }
@@ -289,30 +328,37 @@ impl serde::ser::SerializeMap for SerializeMap {
     type Ok = Value;
     type Error = Error;
 
-    fn serialize_key<T: ?Sized>(&mut self, key: &T) -> Result<(), Error>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         match to_value(&key)? {
             Value::Bool(v) => self.next_key = Some(v.to_string()),
             Value::Int(v) => self.next_key = Some(v.to_string()),
+            Value::Int64(v) => self.next_key = Some(v.to_string()),
             Value::Double(v) => self.next_key = Some(v.to_string()),
             Value::String(s) => self.next_key = Some(s),
-            _ => bail!(ErrorKind::UnsupportedData(
-                "Key must be a bool, int, float, char or string.".into(),
-            )),
+            _ => {
+                return Err(Error::UnsupportedData(
+                    "Key must be a bool, int, float, char or string.".into(),
+                ))
+            }
         };
         Ok(())
     }
 
-    fn serialize_value<T: ?Sized>(&mut self, value: &T) -> Result<(), Error>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
-        let key = self.next_key.take();
-        // Panic because this indicates a bug in the program rather than an
-        // expected failure.
-        let key = key.expect("serialize_value called before serialize_key");
+        let key = match self.next_key.take() {
+            Some(key) => key,
+            None => {
+                return Err(Error::Encoding(
+                    "serialize_value called before serialize_key".into(),
+                ))
+            }
+        };
         self.map.insert(key, to_value(&value)?);
         Ok(())
     }
@@ -334,9 +380,9 @@ impl serde::ser::SerializeStruct for SerializeMap {
     type Ok = Value;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         serde::ser::SerializeMap::serialize_key(self, key)?;
         serde::ser::SerializeMap::serialize_value(self, value)
@@ -351,9 +397,9 @@ impl serde::ser::SerializeStructVariant for SerializeMap {
     type Ok = Value;
     type Error = Error;
 
-    fn serialize_field<T: ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
     where
-        T: Serialize,
+        T: ?Sized + Serialize,
     {
         serde::ser::SerializeMap::serialize_key(self, key)?;
         serde::ser::SerializeMap::serialize_value(self, value)
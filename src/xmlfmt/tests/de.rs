@@ -5,8 +5,8 @@ use std::collections::HashMap;
 
 #[test]
 fn reads_bool() {
-    assert_eq!(true, bool::deserialize(Value::Bool(true)).unwrap());
-    assert_eq!(false, bool::deserialize(Value::Bool(false)).unwrap());
+    assert!(bool::deserialize(Value::Bool(true)).unwrap());
+    assert!(!bool::deserialize(Value::Bool(false)).unwrap());
 }
 
 #[test]
@@ -21,17 +21,14 @@ fn reads_integers_as_ints_or_strings_if_too_big() {
         10_000_000_000_000_000_000u64,
         u64::deserialize(Value::String("10000000000000000000".into())).unwrap()
     );
-    assert_eq!((-42 as i8), i8::deserialize(Value::Int(-42)).unwrap());
+    assert_eq!(-42_i8, i8::deserialize(Value::Int(-42)).unwrap());
+    assert_eq!(-26_000_i16, i16::deserialize(Value::Int(-26_000)).unwrap());
     assert_eq!(
-        (-26_000 as i16),
-        i16::deserialize(Value::Int(-26_000)).unwrap()
-    );
-    assert_eq!(
-        (-2_000_000_000 as i32),
+        -2_000_000_000_i32,
         i32::deserialize(Value::Int(-2000000000)).unwrap()
     );
     assert_eq!(
-        (-8_000_000_000_000_000_000 as i64),
+        -8_000_000_000_000_000_000_i64,
         i64::deserialize(Value::String("-8000000000000000000".into())).unwrap()
     );
     assert_eq!(42i8, i8::deserialize(Value::Int(42)).unwrap());
@@ -56,6 +53,9 @@ fn reads_floats() {
 fn reads_chars_as_strings() {
     assert_eq!('A', char::deserialize(Value::String("A".into())).unwrap());
     assert_eq!(' ', char::deserialize(Value::String(" ".into())).unwrap());
+    assert_eq!('ž', char::deserialize(Value::String("ž".into())).unwrap());
+    char::deserialize(Value::String("".into())).unwrap_err();
+    char::deserialize(Value::String("ab".into())).unwrap_err();
 }
 
 #[test]
@@ -73,6 +73,25 @@ fn reads_bytes_as_base64() {
 }
 
 #[test]
+fn moves_base64_params_without_copying() {
+    use super::super::from_params;
+
+    #[derive(Deserialize)]
+    struct Upload {
+        data: serde_bytes::ByteBuf,
+    }
+
+    let data = vec![7; 1 << 20];
+    let address = data.as_ptr();
+    let mut members = HashMap::new();
+    members.insert("data".to_owned(), Value::Base64(data));
+    let upload: Upload = from_params(vec![Value::Struct(members)]).unwrap();
+    // Still the parser's allocation, so the payload was never copied.
+    assert_eq!(upload.data.as_ptr(), address);
+}
+
+#[test]
+#[cfg(not(feature = "ext-nil"))]
 fn reads_options_as_one_elem_or_empty_array() {
     let none: Option<i32> = None;
     assert_eq!(none, Option::deserialize(Value::Array(Vec::new())).unwrap());
@@ -86,6 +105,25 @@ fn reads_options_as_one_elem_or_empty_array() {
     );
 }
 
+#[test]
+fn reads_i8_into_any_integer_it_fits() {
+    assert_eq!(
+        -8_000_000_000_000_000_000i64,
+        i64::deserialize(Value::Int64(-8_000_000_000_000_000_000)).unwrap()
+    );
+    assert_eq!(42u8, u8::deserialize(Value::Int64(42)).unwrap());
+    assert!(u32::deserialize(Value::Int64(-1)).is_err());
+}
+
+#[test]
+fn reads_nil_as_none_or_unit() {
+    let none: Option<i32> = None;
+    assert_eq!(none, Option::deserialize(Value::Nil).unwrap());
+    assert_eq!((), <()>::deserialize(Value::Nil).unwrap());
+    #[cfg(feature = "ext-nil")]
+    assert_eq!(Some(33i32), Option::deserialize(Value::Int(33)).unwrap());
+}
+
 #[test]
 fn reads_units_as_empty_struct() {
     #[derive(Debug, Deserialize, PartialEq)]
@@ -163,7 +201,7 @@ fn reads_struct_as_struct() {
         bar: u64,
         baz: String,
         qux: bool,
-    };
+    }
 
     let mut members = HashMap::new();
     members.insert("foo".into(), Value::Int(4));
@@ -290,7 +328,7 @@ fn reads_variant_as_one_member_struct() {
         Bar(i32),
         Baz(bool, String),
         Qux { alpha: i32, beta: Vec<bool> },
-    };
+    }
 
     let mut members = HashMap::new();
     members.insert("Foo".into(), Value::Struct(HashMap::new()));
@@ -337,3 +375,73 @@ fn reads_variant_as_one_member_struct() {
         Helper::deserialize(Value::Struct(members)).unwrap()
     );
 }
+
+#[test]
+fn reports_path_of_mismatched_params() {
+    use super::super::error::{Error, PathSegment};
+    use super::super::from_params;
+
+    #[derive(Deserialize)]
+    struct Helper {
+        tags: Vec<i32>,
+    }
+
+    let mut members = HashMap::new();
+    members.insert(
+        "tags".into(),
+        Value::Array(vec![Value::Int(1), Value::Bool(true)]),
+    );
+    let params = vec![Value::String("first".into()), Value::Struct(members)];
+    match from_params::<(String, Helper)>(params) {
+        Err(Error::Mismatch {
+            param, path, found, ..
+        }) => {
+            assert_eq!(param, Some(1));
+            assert_eq!(
+                path,
+                vec![PathSegment::Member("tags".into()), PathSegment::Index(1)]
+            );
+            assert_eq!(found, "boolean `true`");
+        }
+        Ok((_, helper)) => panic!("Accepted bad data: {:?}", helper.tags),
+        Err(err) => panic!("Expected a mismatch, got {:?}", err),
+    }
+
+    match from_params::<i32>(vec![Value::String("x".into())]) {
+        Err(err @ Error::Mismatch { .. }) => {
+            assert_eq!(
+                err.to_string(),
+                "Invalid parameter 0: expected i32, found string \"x\""
+            );
+        }
+        v => panic!("Expected a mismatch, got {:?}", v),
+    }
+}
+
+#[test]
+fn reads_unit_variant_from_its_name() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Color {
+        Red,
+        Green,
+    }
+
+    assert_eq!(
+        vec![Color::Green, Color::Red],
+        Vec::<Color>::deserialize(Value::Array(vec![
+            Value::String("Green".into()),
+            Value::String("Red".into()),
+        ]))
+        .unwrap()
+    );
+    assert!(Color::deserialize(Value::String("Blue".into())).is_err());
+}
+
+#[test]
+fn reads_bare_value_as_some() {
+    assert_eq!(Some(33i32), Option::deserialize(Value::Int(33)).unwrap());
+    assert_eq!(
+        Some(String::from("txt")),
+        Option::deserialize(Value::String("txt".into())).unwrap()
+    );
+}
@@ -0,0 +1,144 @@
+use super::super::value::ToXml;
+use super::super::*;
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Captured from Python 3's `xmlrpc.client.dumps`.
+static PYTHON_CALL: &str = include_str!("captures/python/call.xml");
+static PYTHON_RESPONSE: &str = include_str!("captures/python/response.xml");
+static PYTHON_FAULT: &str = include_str!("captures/python/fault.xml");
+
+#[test]
+fn reads_python_call() {
+    let call = parse::call(PYTHON_CALL.as_bytes()).expect("Failed to parse Python call");
+    let mut members = HashMap::new();
+    members.insert("k".to_owned(), Value::String("v".into()));
+    members.insert("n".to_owned(), Value::Int(-3));
+    assert_eq!(call.name, "echo.all");
+    assert_eq!(
+        call.params,
+        vec![
+            Value::Int(1),
+            Value::String("a<b&c>".into()),
+            Value::Bool(true),
+            Value::Double(2.5),
+            Value::DateTime("20240102T03:04:05".into()),
+            Value::Base64(b"hello world".to_vec()),
+            Value::Array(vec![Value::Int(1), Value::String("two".into())]),
+            Value::Struct(members),
+            Value::String("".into()),
+        ]
+    );
+}
+
+#[test]
+fn reads_python_response() {
+    let response =
+        parse::response(PYTHON_RESPONSE.as_bytes()).expect("Failed to parse Python response");
+    let mut members = HashMap::new();
+    members.insert(
+        "a".to_owned(),
+        Value::Array(vec![Value::Int(1), Value::Double(2.0)]),
+    );
+    assert_eq!(response, Ok(vec![Value::Struct(members)]));
+}
+
+#[test]
+fn reads_python_fault() {
+    let response = parse::response(PYTHON_FAULT.as_bytes()).expect("Failed to parse Python fault");
+    assert_eq!(response, Err(Fault::new(4, "Too many <params>")));
+}
+
+#[test]
+fn reads_multicall_results() {
+    // As Python's `MultiCall` receives them.
+    let xml = r#"<?xml version="1.0"?>
+<methodResponse><params><param><value><array><data>
+<value><array><data><value><int>3</int></value></data></array></value>
+<value><struct>
+<member><name>faultCode</name><value><int>-32601</int></value></member>
+<member><name>faultString</name><value><string>No such method</string></value></member>
+</struct></value>
+</data></array></value></param></params></methodResponse>"#;
+    let calls = Multicall::new()
+        .call(Call::builder("add").arg(1).arg(2).build().unwrap())
+        .call(Call::builder("nope").build().unwrap());
+    let call = calls.to_call();
+    assert_eq!(call.name, "system.multicall");
+    let params = parse::response(xml.as_bytes()).unwrap().unwrap();
+    assert_eq!(
+        calls.responses(params.clone()).unwrap(),
+        vec![
+            Ok(vec![Value::Int(3)]),
+            Err(Fault::new(Fault::METHOD_NOT_FOUND, "No such method")),
+        ]
+    );
+    assert!(Multicall::new().responses(params).is_err());
+}
+
+#[test]
+fn maps_interop_fault_codes() {
+    assert_eq!(Fault::not_found("nope").kind(), FaultCode::MethodNotFound);
+    assert_eq!(FaultCode::from(-32701), FaultCode::UnsupportedEncoding);
+    assert_eq!(FaultCode::from(-32050), FaultCode::ServerError(-32050));
+    assert_eq!(FaultCode::from(4), FaultCode::Other(4));
+    assert!(!FaultCode::Other(4).is_reserved());
+    assert!(FaultCode::Other(-32768).is_reserved());
+    for code in &[
+        -32700, -32701, -32702, -32600, -32601, -32602, -32603, -32500, -32400, -32300, -32000, 7,
+    ] {
+        assert_eq!(FaultCode::from(*code).code(), *code);
+    }
+}
+
+// Prints what Python makes of `xml`, or `None` if Python is unavailable.
+fn python_loads(xml: &str) -> Option<String> {
+    let script = "import sys, xmlrpc.client\n\
+                  try:\n    print(repr(xmlrpc.client.loads(sys.stdin.read(), use_builtin_types=True)))\n\
+                  except xmlrpc.client.Fault as f:\n    print(f.faultCode, f.faultString)";
+    let mut child = Command::new("python3")
+        .args(["-c", script])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .ok()?;
+    child
+        .stdin
+        .take()
+        .expect("Stdin is piped")
+        .write_all(xml.as_bytes())
+        .expect("Failed to write to Python");
+    let output = child.wait_with_output().expect("Python did not finish");
+    assert!(output.status.success(), "Python rejected {}", xml);
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+// Needs `python3` on the path, hence ignored by default.
+#[test]
+#[ignore]
+fn python_reads_what_we_write() {
+    let call = Call {
+        name: "echo.all".into(),
+        params: vec![
+            Value::Int(1),
+            Value::String("a<b&c>".into()),
+            Value::Bool(true),
+            Value::Double(2.5),
+            Value::DateTime("20240102T03:04:05".into()),
+            Value::Base64(b"hello world".to_vec()),
+            Value::Array(vec![Value::Int(1), Value::String("two".into())]),
+        ],
+    };
+    assert_eq!(
+        python_loads(&call.to_xml()).expect("python3 is not installed"),
+        "((1, 'a<b&c>', True, 2.5, datetime.datetime(2024, 1, 2, 3, 4, 5), \
+         b'hello world', [1, 'two']), 'echo.all')"
+    );
+
+    let fault: Response = Err(Fault::new(4, "Too many <params>"));
+    assert_eq!(
+        python_loads(&fault.to_xml()).expect("python3 is not installed"),
+        "4 Too many <params>"
+    );
+}
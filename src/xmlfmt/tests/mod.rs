@@ -1,3 +1,4 @@
 mod de;
+mod interop;
 mod parsevalue;
 mod ser;
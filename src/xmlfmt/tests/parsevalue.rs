@@ -1,8 +1,9 @@
+use super::super::value::ToXml;
 use super::super::*;
 use serde::Deserialize;
 use std::collections::HashMap;
 
-static BAD_DATA: &'static str = "Bad data provided";
+static BAD_DATA: &str = "Bad data provided";
 
 #[test]
 fn reads_pod_xml_value() {
@@ -43,6 +44,22 @@ fn reads_pod_xml_value() {
     assert_eq!(data, Value::Base64("foobar".into()));
 }
 
+#[test]
+fn reads_wrapped_base64() {
+    let data = "<?xml version=\"1.0\"?><base64>Zm9v\nYmFy\r\n</base64>";
+    let data = parse::xml(data.as_bytes()).expect(BAD_DATA);
+    assert_eq!(data, Value::Base64("foobar".into()));
+}
+
+#[test]
+fn converts_base64_text() {
+    let value = Value::from_base64_text("Zm9v\nYmFy").unwrap();
+    assert_eq!(value, Value::Base64("foobar".into()));
+    assert_eq!(value.base64_text(), Some("Zm9vYmFy".into()));
+    assert_eq!(Value::Int(4).base64_text(), None);
+    assert!(Value::from_base64_text("Zm9v!").is_err());
+}
+
 #[test]
 fn reads_empty_array_xml_value() {
     let data = r#"<?xml version="1.0"?>
@@ -227,6 +244,157 @@ fn reads_call() {
     );
 }
 
+#[test]
+fn reads_and_writes_nil() {
+    let data = r#"<?xml version="1.0"?>
+<methodResponse xmlns:ex="http://ws.apache.org/xmlrpc/namespaces/extensions"><params>
+<param><value><nil/></value></param>
+<param><value><ex:nil/></value></param>
+</params></methodResponse>"#;
+    let response = parse::response(data.as_bytes()).expect(BAD_DATA);
+    assert_eq!(response, Ok(vec![Value::Nil, Value::Nil]));
+    assert!(response
+        .to_xml()
+        .contains("<param><value><nil/></value></param>"));
+}
+
+#[test]
+fn reads_and_writes_i8() {
+    let data = r#"<?xml version="1.0"?>
+<methodResponse xmlns:ex="http://ws.apache.org/xmlrpc/namespaces/extensions"><params>
+<param><value><i8>-9000000000</i8></value></param>
+<param><value><ex:i8>7</ex:i8></value></param>
+</params></methodResponse>"#;
+    let response = parse::response(data.as_bytes()).expect(BAD_DATA);
+    assert_eq!(
+        response,
+        Ok(vec![Value::Int64(-9_000_000_000), Value::Int64(7)])
+    );
+    assert!(response
+        .to_xml()
+        .contains("<param><value><i8>-9000000000</i8></value></param>"));
+}
+
+#[test]
+fn writes_i8_as_int_or_string_without_the_extension() {
+    use super::super::value::ToXml;
+    let profile = WireProfile {
+        no_i8: true,
+        ..WireProfile::default()
+    };
+    assert_eq!(
+        Value::Int64(7).to_xml_as(&profile),
+        "<value><i4>7</i4></value>"
+    );
+    assert_eq!(
+        Value::Int64(-9_000_000_000).to_xml_as(&profile),
+        "<value><string>-9000000000</string></value>"
+    );
+}
+
+#[test]
+fn parses_as_it_reads() {
+    use std::io::BufReader;
+
+    let call = r#"<?xml version="1.0"?>
+<methodCall><methodName>upload</methodName><params>
+<param><value><base64>Zm9v
+YmFy</base64></value></param>
+<param><value><struct><member><name>a</name><value><i4>1</i4></value></member></struct></value></param>
+</params></methodCall>"#;
+    assert_eq!(
+        parse::call_streaming(BufReader::new(call.as_bytes())).unwrap(),
+        parse::call(call.as_bytes()).unwrap()
+    );
+    let response = r#"<?xml version="1.0"?>
+<methodResponse><params><param><value>hi</value></param></params></methodResponse>"#;
+    assert_eq!(
+        parse::response_streaming(response.as_bytes()).unwrap(),
+        Ok(vec![Value::String("hi".into())])
+    );
+    assert!(parse::call_streaming(&call.as_bytes()[..100]).is_err());
+}
+
+#[test]
+fn refuses_calls_past_limits() {
+    let call = r#"<?xml version="1.0"?>
+<methodCall><methodName>add</methodName><params>
+<param><value><i4>1</i4></value></param>
+<param><value><array><data><value><i4>2</i4></value></data></array></value></param>
+</params></methodCall>"#;
+    let limits = parse::Limits {
+        max_params: Some(2),
+        ..parse::Limits::default()
+    };
+    assert!(parse::call_within(call.as_bytes(), &limits).is_ok());
+    let limits = parse::Limits {
+        max_params: Some(1),
+        ..parse::Limits::default()
+    };
+    assert!(parse::call_within(call.as_bytes(), &limits).is_err());
+    let limits = parse::Limits {
+        max_depth: 6,
+        ..parse::Limits::default()
+    };
+    assert!(parse::call_streaming_within(call.as_bytes(), &limits).is_err());
+}
+
+#[test]
+fn reads_either_message() {
+    let call = r#"<?xml version="1.0"?>
+<methodCall><methodName>ping</methodName></methodCall>"#;
+    assert_eq!(
+        parse::message(call.as_bytes()).expect(BAD_DATA),
+        parse::Message::Call(Call {
+            name: "ping".into(),
+            params: vec![],
+        })
+    );
+    let response = r#"<?xml version="1.0"?>
+<methodResponse><params><param><value><i4>1</i4></value></param></params></methodResponse>"#;
+    assert_eq!(
+        parse::message(response.as_bytes()).expect(BAD_DATA),
+        parse::Message::Response(Ok(vec![Value::Int(1)]))
+    );
+    assert!(parse::message("<value><i4>1</i4></value>".as_bytes()).is_err());
+}
+
+#[test]
+fn recovers_values_around_problems() {
+    let data = r#"<?xml version="1.0"?>
+<methodCall>
+    <methodName>store</methodName>
+    <params>
+        <param><value><i4>forty-two</i4></value></param>
+        <param><value><string>kept</string></value></param>
+        <param><value><array><data>
+            <value><i4>1</i4></value>
+            <value><double>x</double></value>
+            <value><int>3</int></value>
+        </data></array></value></param>
+    </params>
+</methodCall>"#;
+    let diagnosis = parse::recover(data.as_bytes()).expect(BAD_DATA);
+    assert_eq!(diagnosis.name, Some("store".into()));
+    assert_eq!(
+        diagnosis.values,
+        vec![
+            Value::String("kept".into()),
+            Value::Array(vec![Value::Int(1), Value::Int(3)]),
+        ]
+    );
+    let problems = diagnosis
+        .problems
+        .iter()
+        .map(|problem| (problem.expected.as_str(), problem.location.line))
+        .collect::<Vec<_>>();
+    assert_eq!(problems, vec![("an integer", 5), ("a number", 9)]);
+    assert_eq!(
+        diagnosis.problems[0].location.path,
+        "/methodCall/params/param/value/i4"
+    );
+}
+
 #[test]
 fn reads_array_structure_xml_value() {
     let data = r#"<?xml version="1.0"?>
@@ -242,6 +410,95 @@ fn reads_array_structure_xml_value() {
     assert_eq!(data, vec![33, -12, 44]);
 }
 
+fn parse_error_location(err: error::Error) -> (String, error::Location) {
+    match err {
+        error::Error::Parse {
+            details,
+            location: Some(location),
+            ..
+        } => (details, location),
+        err => panic!("Expected a located parse error, got {:?}", err),
+    }
+}
+
+#[test]
+fn reports_location_of_invalid_values() {
+    let data = r#"<?xml version="1.0"?>
+<methodCall>
+    <methodName>foobar</methodName>
+    <params>
+        <param><value><i4>12x</i4></value></param>
+    </params>
+</methodCall>"#;
+    let err = parse::call(data.as_bytes()).expect_err("Accepted bad data");
+    let (details, location) = parse_error_location(err);
+    assert!(details.contains("<i4>"));
+    assert_eq!(location.line, 5);
+    assert_eq!(location.path, "/methodCall/params/param/value/i4");
+}
+
+#[test]
+fn reports_location_of_unexpected_elements() {
+    let data = r#"<?xml version="1.0"?>
+<methodResponse>
+    <params>
+        <param>
+            <value><long>12</long></value>
+        </param>
+    </params>
+</methodResponse>"#;
+    let err = parse::response(data.as_bytes()).expect_err("Accepted bad data");
+    let (details, location) = parse_error_location(err);
+    assert_eq!(details, "unexpected element <long> inside <value>");
+    assert_eq!(location.line, 5);
+    assert_eq!(location.path, "/methodResponse/params/param/value");
+}
+
+#[test]
+fn reports_location_of_malformed_xml() {
+    let data = r#"<?xml version="1.0"?>
+<array>
+    <data>
+        <value><i4>33</i4></value>
+    </dat>
+</array>"#;
+    let err = parse::xml(data.as_bytes()).expect_err("Accepted bad data");
+    let (_, location) = parse_error_location(err);
+    assert_eq!(location.line, 5);
+    assert_eq!(location.path, "/array/data");
+}
+
+fn nested_arrays(depth: usize) -> String {
+    let mut data = String::new();
+    for _ in 0..depth {
+        data.push_str("<array><data><value>");
+    }
+    data.push_str("<i4>1</i4>");
+    for _ in 0..depth {
+        data.push_str("</value></data></array>");
+    }
+    data
+}
+
+#[test]
+fn rejects_deeply_nested_values() {
+    let data = parse::xml(nested_arrays(50).as_bytes()).expect(BAD_DATA);
+    let mut value = &data;
+    for _ in 0..50 {
+        value = match *value {
+            Value::Array(ref items) => &items[0],
+            ref value => panic!("Expected an array, got {:?}", value),
+        };
+    }
+    assert_eq!(*value, Value::Int(1));
+
+    let err = parse::xml(nested_arrays(100_000).as_bytes()).expect_err("Accepted bad data");
+    assert!(err.to_string().contains("nested"), "{}", err);
+    // Closing tags hidden in comments and attributes don't lower the count.
+    let data = nested_arrays(100).replace("<data>", r#"<data x="/>"><!-- </a> -->"#);
+    parse::xml(data.as_bytes()).expect_err("Accepted bad data");
+}
+
 fn ser_and_de(value: Value) {
     ser_and_de_response_value(Ok(vec![value]));
 }
@@ -274,6 +531,64 @@ fn writes_pod_xml_value() {
     ser_and_de(Value::Base64("ASDF=".into()));
 }
 
+#[test]
+fn writes_numbers_without_exponents() {
+    use super::super::value::ToXml;
+    for &value in &[1e300, -2.5e-10, 123456789012345680.0, 0.1] {
+        let xml = Value::Double(value).to_xml();
+        let number = xml["<value><double>".len()..].split('<').next().unwrap();
+        assert!(!number.contains('e'), "Wrote exponent in {}", xml);
+        ser_and_de(Value::Double(value));
+    }
+    assert_eq!(
+        Value::Int(i32::MIN).to_xml(),
+        "<value><i4>-2147483648</i4></value>"
+    );
+}
+
+#[test]
+fn writes_doubles_with_fixed_precision() {
+    use super::super::value::ToXml;
+    let profile = WireProfile {
+        doubles: Doubles::Fixed(2),
+        ..WireProfile::default()
+    };
+    assert_eq!(
+        Value::Double(0.1 + 0.2).to_xml_as(&profile),
+        "<value><double>0.30</double></value>"
+    );
+    assert_eq!(
+        Value::Double(1e21).to_xml_as(&profile),
+        "<value><double>1000000000000000000000.00</double></value>"
+    );
+}
+
+#[test]
+fn reads_back_every_wire_profile() {
+    use super::super::value::ToXml;
+    let call = Call {
+        name: "examples.echo".into(),
+        params: vec![
+            Value::Int(41),
+            Value::String("".into()),
+            Value::Array(vec![Value::String("South Dakota".into())]),
+            Value::Struct(HashMap::new()),
+        ],
+    };
+    let profile = WireProfile {
+        layout: Layout::Indented,
+        self_closing: true,
+        int_tag: IntTag::Int,
+        bare_strings: true,
+        ..WireProfile::default()
+    };
+    let xml = call.to_xml_as(&profile);
+    assert!(xml.contains("\n    <param>\n      <value><int>41</int></value>"));
+    assert!(xml.contains("<value/>"));
+    assert!(xml.contains("<struct/>"));
+    assert_eq!(parse::call(xml.as_bytes()).unwrap(), call);
+}
+
 #[test]
 fn writes_array_xml_value() {
     ser_and_de(Value::Array(vec![
@@ -319,6 +634,20 @@ fn writes_call() {
     });
 }
 
+#[test]
+fn escapes_markup_in_names_and_messages() {
+    let mut fields = HashMap::<String, Value>::new();
+    fields.insert("<foo & bar>".into(), Value::DateTime("<now>".into()));
+    ser_and_de_call_value(Call {
+        name: String::from("foo<&>bar"),
+        params: vec![Value::Struct(fields)],
+    });
+    ser_and_de_response_value(Err(Fault {
+        code: 4,
+        message: "Too many <params> & more".into(),
+    }));
+}
+
 #[test]
 fn reads_and_writes_empty_call() {
     ser_and_de_call_value(Call {
@@ -331,3 +660,74 @@ fn reads_and_writes_empty_call() {
 fn reads_and_writes_empty_response() {
     ser_and_de_response_value(Ok(vec![]))
 }
+
+#[cfg(feature = "encoding")]
+#[test]
+fn reads_documents_in_declared_and_given_encodings() {
+    let mut data = br#"<?xml version="1.0" encoding="ISO-8859-1"?><methodCall><methodName>greet</methodName><params><param><value><string>Gr"#.to_vec();
+    data.extend_from_slice(b"\xfc\xdf");
+    data.extend_from_slice(b"e</string></value></param></params></methodCall>");
+    let call = parse::call(&data[..]).expect(BAD_DATA);
+    assert_eq!(call.params, vec![Value::String("Grüße".into())]);
+
+    let data = "<?xml version=\"1.0\"?><methodResponse><params><param><value>\u{e9}t\u{e9}</value></param></params></methodResponse>";
+    let (bytes, _, _) = ::encoding_rs::WINDOWS_1252.encode(data);
+    let response = parse::response_in_charset(&bytes[..], "windows-1252").expect(BAD_DATA);
+    assert_eq!(response, Ok(vec![Value::String("été".into())]));
+    assert!(parse::response(&bytes[..]).is_err());
+}
+
+#[cfg(feature = "encoding")]
+#[test]
+fn writes_documents_in_other_encodings() {
+    let xml = Call {
+        name: "greet".into(),
+        params: vec![Value::String("Grüße, 世界".into())],
+    }
+    .to_xml();
+    let bytes = charset::encode(&xml, ::encoding_rs::WINDOWS_1252);
+    assert!(bytes.starts_with(br#"<?xml version="1.0" encoding="windows-1252"?>"#));
+    assert!(bytes.windows(2).any(|pair| pair == b"\xfc\xdf"));
+    let call = parse::call(&bytes[..]).expect(BAD_DATA);
+    assert_eq!(call.params, vec![Value::String("Grüße, 世界".into())]);
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn converts_values_to_and_from_json() {
+    let mut members = HashMap::new();
+    members.insert(
+        "when".to_owned(),
+        Value::DateTime("19980717T14:08:55".into()),
+    );
+    members.insert("data".to_owned(), Value::Base64(b"hi".to_vec()));
+    members.insert("big".to_owned(), Value::Int64(1 << 40));
+    members.insert("none".to_owned(), Value::Nil);
+    let json = ::serde_json::Value::from(Value::Struct(members));
+    assert_eq!(
+        json,
+        ::serde_json::json!({
+            "when": "19980717T14:08:55",
+            "data": "aGk=",
+            "big": 1u64 << 40,
+            "none": null,
+        })
+    );
+
+    let value = Value::from_json_str(r#"[1, 5000000000, 1.5, "aGk=", null]"#).expect(BAD_DATA);
+    assert_eq!(
+        value,
+        Value::Array(vec![
+            Value::Int(1),
+            Value::Int64(5_000_000_000),
+            Value::Double(1.5),
+            Value::String("aGk=".into()),
+            Value::Nil,
+        ])
+    );
+    assert_eq!(
+        Value::from_json_str(&value.to_json_string()).expect(BAD_DATA),
+        value
+    );
+    assert!(Value::from_json_str("18446744073709551615").is_err());
+}
@@ -1,3 +1,4 @@
+use super::super::error::Error;
 use super::super::ser::Serializer;
 use super::super::Value;
 use serde::Serialize;
@@ -11,6 +12,7 @@ fn writes_bool() {
 }
 
 #[test]
+#[cfg(not(feature = "ext-i8"))]
 fn writes_integers_as_ints_or_strings_if_too_big() {
     assert_eq!(200u8.serialize(Serializer {}).unwrap(), Value::Int(200));
     assert_eq!(
@@ -27,20 +29,17 @@ fn writes_integers_as_ints_or_strings_if_too_big() {
             .unwrap(),
         Value::String("10000000000000000000".into())
     );
+    assert_eq!((-42_i8).serialize(Serializer {}).unwrap(), Value::Int(-42));
     assert_eq!(
-        (-42 as i8).serialize(Serializer {}).unwrap(),
-        Value::Int(-42)
-    );
-    assert_eq!(
-        (-26_000 as i16).serialize(Serializer {}).unwrap(),
+        (-26_000_i16).serialize(Serializer {}).unwrap(),
         Value::Int(-26_000)
     );
     assert_eq!(
-        (-2_000_000_000 as i32).serialize(Serializer {}).unwrap(),
+        (-2_000_000_000_i32).serialize(Serializer {}).unwrap(),
         Value::Int(-2000000000)
     );
     assert_eq!(
-        (-8_000_000_000_000_000_000 as i64)
+        (-8_000_000_000_000_000_000_i64)
             .serialize(Serializer {})
             .unwrap(),
         Value::String("-8000000000000000000".into())
@@ -62,6 +61,27 @@ fn writes_integers_as_ints_or_strings_if_too_big() {
     );
 }
 
+#[test]
+#[cfg(feature = "ext-i8")]
+fn writes_wide_integers_as_i8() {
+    assert_eq!(
+        4_200_000_000u32.serialize(Serializer {}).unwrap(),
+        Value::Int64(4_200_000_000)
+    );
+    assert_eq!(
+        (-8_000_000_000_000_000_000i64)
+            .serialize(Serializer {})
+            .unwrap(),
+        Value::Int64(-8_000_000_000_000_000_000)
+    );
+    assert_eq!(
+        10_000_000_000_000_000_000u64
+            .serialize(Serializer {})
+            .unwrap(),
+        Value::String("10000000000000000000".into())
+    );
+}
+
 #[test]
 fn writes_floats() {
     assert_eq!(
@@ -109,6 +129,7 @@ fn writes_bytes_as_base64() {
 }
 
 #[test]
+#[cfg(not(feature = "ext-nil"))]
 fn writes_options_as_one_elem_or_empty_array() {
     let none: Option<i32> = None;
     assert_eq!(
@@ -126,6 +147,19 @@ fn writes_options_as_one_elem_or_empty_array() {
 }
 
 #[test]
+#[cfg(feature = "ext-nil")]
+fn writes_options_and_units_as_nil() {
+    let none: Option<i32> = None;
+    assert_eq!(none.serialize(Serializer {}).unwrap(), Value::Nil);
+    assert_eq!(
+        Some(33i32).serialize(Serializer {}).unwrap(),
+        Value::Int(33)
+    );
+    assert_eq!(().serialize(Serializer {}).unwrap(), Value::Nil);
+}
+
+#[test]
+#[cfg(not(feature = "ext-nil"))]
 fn writes_units_as_empty_struct() {
     assert_eq!(
         ().serialize(Serializer {}).unwrap(),
@@ -181,6 +215,7 @@ fn writes_vector_as_array() {
 }
 
 #[test]
+#[cfg(not(feature = "ext-i8"))]
 fn writes_tuple_as_array() {
     assert_eq!(
         (4, 1_000_000_000_000i64, "hello", true)
@@ -211,6 +246,7 @@ fn writes_tuple_as_array() {
 }
 
 #[test]
+#[cfg(not(feature = "ext-i8"))]
 fn writes_struct_as_struct() {
     #[derive(Serialize)]
     struct Helper {
@@ -218,7 +254,7 @@ fn writes_struct_as_struct() {
         bar: u64,
         baz: String,
         qux: bool,
-    };
+    }
 
     let mut members = HashMap::new();
     members.insert("foo".into(), Value::Int(4));
@@ -357,10 +393,62 @@ fn map_accepts_boolean_keys() {
 #[test]
 fn rejects_maps_with_unsupported_keys() {
     let mut data = HashMap::new();
-    data.insert(Some(4), vec![44i8, 12]);
-    data.insert(Some(3), vec![]);
-    data.insert(Some(2), vec![-3, 44, 28]);
-    data.serialize(Serializer {}).unwrap_err();
+    data.insert(vec![4], vec![44i8, 12]);
+    data.insert(vec![3], vec![]);
+    data.insert(vec![2], vec![-3, 44, 28]);
+    match data.serialize(Serializer {}) {
+        Err(Error::UnsupportedData(_)) => {}
+        other => panic!("Expected unsupported data error, got {:?}", other),
+    }
+}
+
+#[test]
+fn builds_calls_with_one_param_per_arg() {
+    use super::super::Call;
+
+    let call = Call::builder("points.move")
+        .arg(2)
+        .arg((1, "a"))
+        .value(Value::DateTime("20240102T03:04:05".into()))
+        .build()
+        .unwrap();
+    assert_eq!(call.name, "points.move");
+    assert_eq!(
+        call.params,
+        vec![
+            Value::Int(2),
+            Value::Array(vec![Value::Int(1), Value::String("a".into())]),
+            Value::DateTime("20240102T03:04:05".into()),
+        ]
+    );
+
+    let mut keys = HashMap::new();
+    keys.insert(vec![4], 1);
+    match Call::builder("points.move").arg(&keys).arg(2).build() {
+        Err(Error::UnsupportedData(_)) => {}
+        other => panic!("Expected unsupported data error, got {:?}", other),
+    }
+}
+
+#[test]
+fn builds_responses_with_one_param() {
+    use super::super::{Fault, Response, ResponseExt};
+
+    assert_eq!(
+        Response::success((1, "a")),
+        Ok(vec![Value::Array(vec![
+            Value::Int(1),
+            Value::String("a".into()),
+        ])])
+    );
+    assert_eq!(Response::empty_success(), Ok(vec![]));
+
+    let mut keys = HashMap::new();
+    keys.insert(vec![4], 1);
+    match Response::success(&keys) {
+        Err(ref fault) if fault.code == Fault::INTERNAL_ERROR => {}
+        other => panic!("Expected an internal error fault, got {:?}", other),
+    }
 }
 
 #[test]
@@ -371,7 +459,7 @@ fn writes_variant_as_one_member_struct() {
         Bar(i32),
         Baz(bool, &'static str),
         Qux { alpha: i32, beta: Vec<bool> },
-    };
+    }
 
     let mut members = HashMap::new();
     members.insert("Foo".into(), Value::Struct(HashMap::new()));
@@ -420,3 +508,233 @@ fn writes_variant_as_one_member_struct() {
         Value::Struct(members)
     );
 }
+
+#[test]
+fn converts_native_types_into_values() {
+    assert_eq!(Value::from(41), Value::Int(41));
+    assert_eq!(Value::from("foo"), Value::String("foo".into()));
+    assert_eq!(
+        Value::from(vec![1.5, 2.0]),
+        Value::Array(vec![Value::Double(1.5), Value::Double(2.0)])
+    );
+    let mut members = HashMap::new();
+    members.insert("ok".to_owned(), true);
+    let mut expected = HashMap::new();
+    expected.insert("ok".to_owned(), Value::Bool(true));
+    assert_eq!(Value::from(members), Value::Struct(expected));
+}
+
+#[test]
+fn collects_members_and_params() {
+    let mut expected = HashMap::new();
+    expected.insert("name".to_owned(), Value::String("foo".into()));
+    expected.insert("size".to_owned(), Value::Int(2));
+    let members = vec![("name", Value::from("foo")), ("size", Value::from(2))];
+    assert_eq!(
+        members.into_iter().collect::<Value>(),
+        Value::Struct(expected)
+    );
+    assert_eq!(
+        params![41, "foo", vec![true]],
+        vec![
+            Value::Int(41),
+            Value::String("foo".into()),
+            Value::Array(vec![Value::Bool(true)]),
+        ]
+    );
+    assert_eq!(params![], Vec::<Value>::new());
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn converts_sent_datetimes_to_utc() {
+    use super::super::datetime::{DateTimeOptions, NaiveDateTimes, SentOffsets};
+
+    let options = DateTimeOptions {
+        naive: NaiveDateTimes::AssumeUtc,
+        sent_offsets: SentOffsets::OmitAsUtc,
+        ..DateTimeOptions::default()
+    };
+    let mut values = vec![Value::Array(vec![
+        Value::DateTime("19980101T00:10:00+01:00".into()),
+        Value::DateTime("1998-07-17T23:30:00.250-0330".into()),
+        Value::DateTime("19980717T14:08:55".into()),
+    ])];
+    options.send(&mut values).unwrap();
+    assert_eq!(
+        values,
+        vec![Value::Array(vec![
+            Value::DateTime("19971231T23:10:00".into()),
+            Value::DateTime("19980718T03:00:00.250".into()),
+            Value::DateTime("19980717T14:08:55".into()),
+        ])]
+    );
+    let mut values = vec![Value::DateTime("19980717T14:08:55".into())];
+    options.receive(&mut values).unwrap();
+    assert_eq!(values, vec![Value::DateTime("19980717T14:08:55Z".into())]);
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn round_trips_chrono_datetimes() {
+    use super::super::datetime::{self, DateTimeOptions};
+    use super::super::{from_params, into_params};
+    use chrono::{TimeZone, Utc};
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Helper {
+        #[serde(with = "datetime::utc")]
+        at: chrono::DateTime<Utc>,
+    }
+
+    let helper = Helper {
+        at: Utc.with_ymd_and_hms(1998, 7, 17, 14, 8, 55).unwrap(),
+    };
+    let params = into_params(&helper).unwrap();
+    let mut members = HashMap::new();
+    members.insert("at".into(), Value::DateTime("19980717T14:08:55".into()));
+    assert_eq!(params, vec![Value::Struct(members)]);
+    assert_eq!(from_params::<Helper>(params).unwrap(), helper);
+
+    let options = DateTimeOptions {
+        normalize: true,
+        ..DateTimeOptions::default()
+    };
+    let mut values = vec![Value::DateTime("1998-07-17T14:08:55+0200".into())];
+    options.send(&mut values).unwrap();
+    assert_eq!(
+        values,
+        vec![Value::DateTime("19980717T14:08:55+02:00".into())]
+    );
+    let mut values = vec![Value::DateTime("yesterday".into())];
+    assert!(options.send(&mut values).is_err());
+}
+
+#[test]
+fn builds_values_with_macro() {
+    let parent = "bar";
+    let value = xmlrpc_value!({
+        "name": "foo",
+        "sizes": [1, -2, nil, [true]],
+        "owner": {"id": 7u8, "tags": []},
+        ("parent"): Some(parent),
+        "none": nil,
+    });
+    assert_eq!(value["name"].as_str(), Some("foo"));
+    assert_eq!(value["sizes"][1].as_i32(), Some(-2));
+    assert!(value["sizes"][2].is_nil());
+    assert_eq!(value["sizes"][3][0].as_bool(), Some(true));
+    assert_eq!(value["owner"]["id"].as_i64(), Some(7));
+    assert_eq!(value["owner"]["tags"], Value::Array(vec![]));
+    assert_eq!(value["parent"], Value::from("bar"));
+    assert!(value["none"].is_nil());
+    assert!(value["missing"]["deeper"][4].is_nil());
+    assert_eq!(value.get("missing"), None);
+    assert_eq!(xmlrpc_value!(nil), Value::Nil);
+    assert_eq!(xmlrpc_value!(2.5), Value::Double(2.5));
+    assert_eq!(Value::from(None::<i32>), Value::Nil);
+}
+
+#[test]
+fn round_trips_the_serde_data_model() {
+    use super::super::{from_params, into_params};
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+    use std::fmt::Debug;
+
+    fn round_trip<T>(value: T)
+    where
+        T: Serialize + for<'de> Deserialize<'de> + Debug + PartialEq,
+    {
+        let params = into_params(&value).unwrap();
+        assert_eq!(from_params::<T>(params).unwrap(), value);
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    enum External {
+        Unit,
+        Newtype(i32),
+        Tuple(i32, String),
+        Struct { ok: bool },
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(tag = "type")]
+    enum Internal {
+        Circle { radius: f64 },
+        Square { side: f64 },
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(tag = "kind", content = "data")]
+    enum Adjacent {
+        Count(i32),
+        Name(String),
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(untagged)]
+    enum Untagged {
+        Number(i32),
+        Text(String),
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Paging {
+        page: i32,
+        size: i32,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Query {
+        term: String,
+        #[serde(flatten)]
+        paging: Paging,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Optional {
+        name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parent: Option<i32>,
+    }
+
+    round_trip(vec![
+        External::Unit,
+        External::Newtype(4),
+        External::Tuple(1, "a".into()),
+        External::Struct { ok: true },
+    ]);
+    round_trip(vec![
+        Internal::Circle { radius: 1.5 },
+        Internal::Square { side: 2.0 },
+    ]);
+    round_trip(vec![Adjacent::Count(3), Adjacent::Name("x".into())]);
+    round_trip(vec![Untagged::Number(7), Untagged::Text("seven".into())]);
+    round_trip(Query {
+        term: "rust".into(),
+        paging: Paging { page: 2, size: 20 },
+    });
+    round_trip(Optional {
+        name: Some("a".into()),
+        parent: None,
+    });
+    round_trip(Optional {
+        name: None,
+        parent: Some(1),
+    });
+    round_trip(vec![vec![vec![1, 2], vec![]], vec![vec![3]]]);
+    round_trip((1u8, -2i16, 'c', 2.5f32, String::from("s")));
+
+    let mut by_id = BTreeMap::new();
+    by_id.insert(-3i64, "minus three".to_owned());
+    by_id.insert(8_000_000_000i64, "big".to_owned());
+    round_trip(by_id);
+    let mut by_flag = BTreeMap::new();
+    by_flag.insert(true, vec![1u32]);
+    by_flag.insert(false, vec![]);
+    round_trip(by_flag);
+    let mut by_char = BTreeMap::new();
+    by_char.insert('x', 1.5f64);
+    round_trip(by_char);
+}
@@ -1,12 +1,26 @@
 use base64;
+use itoa;
+use quick_xml::escape::partial_escape;
+use ryu;
 use serde::de::Unexpected;
+use serde::Serialize;
 use std;
-use std::collections::HashMap;
-use xml::escape::escape_str_pcdata;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::io;
+use std::iter::FromIterator;
+use std::ops::Index;
+
+use super::error::{Error, Result};
+use super::profile::{Doubles, IntTag, Layout, WireProfile};
+use super::scratch;
+use super::ser;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Value {
     Int(i32),
+    /// The `<i8>` extension, for integers too big for `Int`.
+    Int64(i64),
     Bool(bool),
     String(String),
     Double(f64),
@@ -14,12 +28,16 @@ pub enum Value {
     Base64(Vec<u8>),
     Array(Vec<Value>),
     Struct(HashMap<String, Value>),
+    /// The `<nil/>` extension, which Python and Apache XML-RPC among others
+    /// read and write for a missing value.
+    Nil,
 }
 
 impl Value {
-    pub fn unexpected(&self) -> Unexpected {
+    pub fn unexpected(&self) -> Unexpected<'_> {
         match *self {
             Value::Int(v) => Unexpected::Signed(i64::from(v)),
+            Value::Int64(v) => Unexpected::Signed(v),
             Value::Bool(v) => Unexpected::Bool(v),
             Value::String(ref v) => Unexpected::Str(v),
             Value::Double(v) => Unexpected::Float(v),
@@ -27,13 +45,241 @@ impl Value {
             Value::Base64(ref v) => Unexpected::Bytes(v),
             Value::Array(_) => Unexpected::Seq,
             Value::Struct(_) => Unexpected::Map,
+            Value::Nil => Unexpected::Unit,
+        }
+    }
+
+    /// The base64 text of `Base64` bytes, as it is sent, for code that used
+    /// to handle the encoded form.
+    pub fn base64_text(&self) -> Option<String> {
+        match *self {
+            Value::Base64(ref v) => Some(base64::encode(v)),
+            _ => None,
+        }
+    }
+
+    /// A `Base64` value from base64 text, which may be wrapped over lines.
+    pub fn from_base64_text(text: &str) -> Result<Value> {
+        let mut text = text.to_owned();
+        text.retain(|c| !c.is_ascii_whitespace());
+        base64::decode(&text)
+            .map(Value::Base64)
+            .map_err(|err| Error::Decoding(format!("Invalid base64: {}", err)))
+    }
+
+    /// The integer of an `Int`, or of an `Int64` small enough.
+    pub fn as_i32(&self) -> Option<i32> {
+        match *self {
+            Value::Int(v) => Some(v),
+            Value::Int64(v) => i32::try_from(v).ok(),
+            _ => None,
+        }
+    }
+
+    /// The integer of an `Int` or `Int64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Int(v) => Some(i64::from(v)),
+            Value::Int64(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::String(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Double(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// The text of a `DateTime`, as it was sent.
+    pub fn as_datetime(&self) -> Option<&str> {
+        match *self {
+            Value::DateTime(ref v) => Some(v),
+            _ => None,
         }
     }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            Value::Base64(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match *self {
+            Value::Array(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_struct(&self) -> Option<&HashMap<String, Value>> {
+        match *self {
+            Value::Struct(ref v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn is_nil(&self) -> bool {
+        *self == Value::Nil
+    }
+
+    /// The member `name` of a `Struct`.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.as_struct().and_then(|members| members.get(name))
+    }
+}
+
+static NIL: Value = Value::Nil;
+
+/// The member of a `Struct` by name, as in `value["faultCode"]`, or `Nil`
+/// if there is no such member or the value is not a `Struct`.
+impl Index<&str> for Value {
+    type Output = Value;
+
+    fn index(&self, name: &str) -> &Value {
+        self.get(name).unwrap_or(&NIL)
+    }
+}
+
+/// The element of an `Array` by position, or `Nil` if there is no such
+/// element or the value is not an `Array`.
+impl Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Value {
+        self.as_array()
+            .and_then(|elements| elements.get(index))
+            .unwrap_or(&NIL)
+    }
+}
+
+impl From<i8> for Value {
+    fn from(v: i8) -> Value {
+        Value::Int(i32::from(v))
+    }
+}
+
+impl From<u8> for Value {
+    fn from(v: u8) -> Value {
+        Value::Int(i32::from(v))
+    }
+}
+
+impl From<i16> for Value {
+    fn from(v: i16) -> Value {
+        Value::Int(i32::from(v))
+    }
+}
+
+impl From<u16> for Value {
+    fn from(v: u16) -> Value {
+        Value::Int(i32::from(v))
+    }
+}
+
+impl From<i32> for Value {
+    fn from(v: i32) -> Value {
+        Value::Int(v)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Value {
+        Value::Int64(v)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Value {
+        Value::Bool(v)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Value {
+        Value::Double(v)
+    }
+}
+
+impl From<f32> for Value {
+    fn from(v: f32) -> Value {
+        Value::Double(f64::from(v))
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Value {
+        Value::String(v)
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(v: &'a str) -> Value {
+        Value::String(v.to_owned())
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(v: Vec<T>) -> Value {
+        Value::Array(v.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<Value>> From<HashMap<String, T>> for Value {
+    fn from(v: HashMap<String, T>) -> Value {
+        Value::Struct(v.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
+
+impl<T: Into<Value>> From<BTreeMap<String, T>> for Value {
+    fn from(v: BTreeMap<String, T>) -> Value {
+        Value::Struct(v.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
+
+/// `None` becomes `Nil`.
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(v: Option<T>) -> Value {
+        v.map_or(Value::Nil, Into::into)
+    }
+}
+
+/// Collects name and value pairs into a `Value::Struct`.
+impl<K, V> FromIterator<(K, V)> for Value
+where
+    K: Into<String>,
+    V: Into<Value>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(members: I) -> Value {
+        Value::Struct(
+            members
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+        )
+    }
 }
 
 pub type Params = Vec<Value>;
 
-#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Error)]
+#[error("Fault {code}: {message}")]
 pub struct Fault {
     #[serde(rename = "faultCode")]
     pub code: i32,
@@ -42,6 +288,12 @@ pub struct Fault {
 }
 
 impl Fault {
+    pub const PARSE_ERROR: i32 = -32700;
+    pub const INVALID_REQUEST: i32 = -32600;
+    pub const METHOD_NOT_FOUND: i32 = -32601;
+    pub const INVALID_PARAMS: i32 = -32602;
+    pub const INTERNAL_ERROR: i32 = -32603;
+
     pub fn new<T>(code: i32, message: T) -> Fault
     where
         T: Into<String>,
@@ -51,85 +303,519 @@ impl Fault {
             message: message.into(),
         }
     }
+
+    /// A fault with the interoperability code for an unknown method.
+    pub fn not_found<T: Into<String>>(message: T) -> Fault {
+        Fault::new(Fault::METHOD_NOT_FOUND, message)
+    }
+
+    /// A fault with the interoperability code for bad parameters.
+    pub fn invalid_params<T: Into<String>>(message: T) -> Fault {
+        Fault::new(Fault::INVALID_PARAMS, message)
+    }
+
+    /// A fault with the interoperability code for server errors.
+    pub fn internal<T: Into<String>>(message: T) -> Fault {
+        Fault::new(Fault::INTERNAL_ERROR, message)
+    }
+
+    /// Builds a fault whose message is the error followed by each of its
+    /// sources, separated by `": "`.
+    pub fn from_error(code: i32, err: &dyn std::error::Error) -> Fault {
+        let mut message = err.to_string();
+        let mut source = err.source();
+        while let Some(cause) = source {
+            message.push_str(": ");
+            message.push_str(&cause.to_string());
+            source = cause.source();
+        }
+        Fault::new(code, message)
+    }
+
+    /// What the code of the fault stands for, for matching on.
+    pub fn kind(&self) -> FaultCode {
+        FaultCode::from(self.code)
+    }
+}
+
+/// The fault codes of the XML-RPC fault code interoperability
+/// specification, for telling faults apart without comparing numbers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FaultCode {
+    /// -32700: the request is not well-formed XML.
+    ParseError,
+    /// -32701: the request is in an encoding the server does not support.
+    UnsupportedEncoding,
+    /// -32702: the request has a character invalid in its encoding.
+    InvalidCharacter,
+    /// -32600: the request is XML, but not XML-RPC.
+    InvalidRequest,
+    /// -32601
+    MethodNotFound,
+    /// -32602
+    InvalidParams,
+    /// -32603: the server failed while handling the call.
+    InternalError,
+    /// -32500: the method itself failed.
+    ApplicationError,
+    /// -32400: the system the server runs on failed.
+    SystemError,
+    /// -32300: the transport failed.
+    TransportError,
+    /// -32099 to -32000, which servers define for themselves.
+    ServerError(i32),
+    /// Any other code, as the codes services pick for their own faults.
+    Other(i32),
+}
+
+impl FaultCode {
+    pub fn code(self) -> i32 {
+        match self {
+            FaultCode::ParseError => Fault::PARSE_ERROR,
+            FaultCode::UnsupportedEncoding => -32701,
+            FaultCode::InvalidCharacter => -32702,
+            FaultCode::InvalidRequest => Fault::INVALID_REQUEST,
+            FaultCode::MethodNotFound => Fault::METHOD_NOT_FOUND,
+            FaultCode::InvalidParams => Fault::INVALID_PARAMS,
+            FaultCode::InternalError => Fault::INTERNAL_ERROR,
+            FaultCode::ApplicationError => -32500,
+            FaultCode::SystemError => -32400,
+            FaultCode::TransportError => -32300,
+            FaultCode::ServerError(code) | FaultCode::Other(code) => code,
+        }
+    }
+
+    /// Whether the code is one of those the specification reserves, from
+    /// -32768 to -32000.
+    pub fn is_reserved(self) -> bool {
+        match self {
+            FaultCode::Other(code) => (-32768..=-32000).contains(&code),
+            _ => true,
+        }
+    }
+}
+
+impl From<i32> for FaultCode {
+    fn from(code: i32) -> FaultCode {
+        match code {
+            Fault::PARSE_ERROR => FaultCode::ParseError,
+            -32701 => FaultCode::UnsupportedEncoding,
+            -32702 => FaultCode::InvalidCharacter,
+            Fault::INVALID_REQUEST => FaultCode::InvalidRequest,
+            Fault::METHOD_NOT_FOUND => FaultCode::MethodNotFound,
+            Fault::INVALID_PARAMS => FaultCode::InvalidParams,
+            Fault::INTERNAL_ERROR => FaultCode::InternalError,
+            -32500 => FaultCode::ApplicationError,
+            -32400 => FaultCode::SystemError,
+            -32300 => FaultCode::TransportError,
+            -32099..=-32000 => FaultCode::ServerError(code),
+            code => FaultCode::Other(code),
+        }
+    }
+}
+
+impl From<FaultCode> for i32 {
+    fn from(code: FaultCode) -> i32 {
+        code.code()
+    }
+}
+
+/// Errors that handlers registered with `Server::register_fallible` may
+/// return in place of a `Fault`.
+pub trait IntoFault {
+    fn into_fault(self) -> Fault;
+}
+
+impl IntoFault for Fault {
+    fn into_fault(self) -> Fault {
+        self
+    }
+}
+
+/// A `Fault` carried inside the error is returned as is. Anything else
+/// becomes an internal error describing the whole context chain.
+#[cfg(feature = "anyhow")]
+impl IntoFault for ::anyhow::Error {
+    fn into_fault(self) -> Fault {
+        match self.downcast::<Fault>() {
+            Ok(fault) => fault,
+            Err(err) => Fault::from_error(Fault::INTERNAL_ERROR, &*err),
+        }
+    }
 }
 
 pub type Response = std::result::Result<Params, Fault>;
 
+impl From<Fault> for Response {
+    fn from(fault: Fault) -> Response {
+        Err(fault)
+    }
+}
+
+/// Shorthands for successful responses, as in `Response::success(&total)`.
+pub trait ResponseExt {
+    /// A response whose single parameter is `value` serialized, or an
+    /// internal error fault if it cannot be.
+    fn success<T: Serialize>(value: T) -> Response;
+
+    /// A response without parameters.
+    fn empty_success() -> Response;
+}
+
+impl ResponseExt for Response {
+    fn success<T: Serialize>(value: T) -> Response {
+        match ser::to_value(&value) {
+            Ok(value) => Ok(vec![value]),
+            Err(err) => {
+                let mut fault = Fault::from_error(Fault::INTERNAL_ERROR, &err);
+                fault.message = format!("Failed to encode response: {}", fault.message);
+                Err(fault)
+            }
+        }
+    }
+
+    fn empty_success() -> Response {
+        Ok(Vec::new())
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Call {
     pub name: String,
     pub params: Params,
 }
 
+impl Call {
+    /// Starts a call to `name`, whose parameters are added one at a time.
+    pub fn builder<T: Into<String>>(name: T) -> CallBuilder {
+        CallBuilder {
+            call: Call {
+                name: name.into(),
+                params: Vec::new(),
+            },
+            error: None,
+        }
+    }
+}
+
+/// Builds a `Call` from parameters of mixed types, as in
+/// `Call::builder("add").arg(2).arg(&point).build()?`.
+///
+/// Every `arg` adds exactly one parameter, so an array or tuple stays a
+/// single array parameter, unlike with `into_params`.
+pub struct CallBuilder {
+    call: Call,
+    // The first serialization error, reported by `build`.
+    error: Option<Error>,
+}
+
+impl CallBuilder {
+    /// Adds `value` serialized as a parameter.
+    pub fn arg<T: Serialize>(mut self, value: T) -> CallBuilder {
+        if self.error.is_none() {
+            match ser::to_value(&value) {
+                Ok(value) => self.call.params.push(value),
+                Err(err) => self.error = Some(err),
+            }
+        }
+        self
+    }
+
+    /// Adds a parameter that is already a `Value`, such as a dateTime.
+    pub fn value<T: Into<Value>>(mut self, value: T) -> CallBuilder {
+        self.call.params.push(value.into());
+        self
+    }
+
+    pub fn build(self) -> Result<Call> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.call),
+        }
+    }
+}
+
 pub trait ToXml {
     fn to_xml(&self) -> String;
+
+    /// Writes the same XML as `to_xml` to `out`, without building it in
+    /// memory first. Small pieces are written at a time, so `out` should be
+    /// buffered.
+    fn write_xml<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(self.to_xml().as_bytes())
+    }
+
+    /// Like `write_xml`, in the dialect of `profile`. Types with no choices
+    /// to make write the same XML as `write_xml`.
+    fn write_xml_as<W: io::Write>(&self, out: &mut W, _profile: &WireProfile) -> io::Result<()> {
+        self.write_xml(out)
+    }
+
+    /// Like `to_xml`, in the dialect of `profile`.
+    fn to_xml_as(&self, profile: &WireProfile) -> String {
+        let mut out = Vec::new();
+        self.write_xml_as(&mut out, profile)
+            .expect("Writing to a Vec cannot fail");
+        String::from_utf8(out).expect("Serialized XML is valid UTF-8")
+    }
+}
+
+// Serializes through `write_xml_as`, so all forms produce the same output.
+fn xml_string<T: ToXml + ?Sized>(value: &T) -> String {
+    value.to_xml_as(&WireProfile::default())
+}
+
+// Writes XML in the layout of a profile. Indented, each element starts a
+// line, except the contents of scalar values, which stay on the line of their
+// `<value>`.
+struct XmlWriter<'a, W: 'a> {
+    out: &'a mut W,
+    profile: &'a WireProfile,
+    depth: usize,
+    // Nothing written yet, so there is no line to break.
+    start: bool,
+}
+
+impl<'a, W: io::Write> XmlWriter<'a, W> {
+    fn new(out: &'a mut W, profile: &'a WireProfile) -> Self {
+        XmlWriter {
+            out,
+            profile,
+            depth: 0,
+            start: true,
+        }
+    }
+
+    fn declaration(&mut self) -> io::Result<()> {
+        self.line()?;
+        self.out.write_all(b"<?xml version=\"1.0\"?>")
+    }
+
+    fn line(&mut self) -> io::Result<()> {
+        if self.start {
+            self.start = false;
+        } else if self.profile.layout == Layout::Indented {
+            self.out.write_all(b"\n")?;
+            for _ in 0..self.depth {
+                self.out.write_all(b"  ")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn open(&mut self, tag: &str) -> io::Result<()> {
+        self.line()?;
+        write!(self.out, "<{}>", tag)?;
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn close(&mut self, tag: &str) -> io::Result<()> {
+        self.depth -= 1;
+        self.line()?;
+        write!(self.out, "</{}>", tag)
+    }
+
+    // An element holding others, written by `items`.
+    fn list<F>(&mut self, tag: &str, empty: bool, items: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut Self) -> io::Result<()>,
+    {
+        if empty && self.profile.self_closing {
+            self.line()?;
+            return write!(self.out, "<{}/>", tag);
+        }
+        self.open(tag)?;
+        items(self)?;
+        self.close(tag)
+    }
+
+    // An element holding escaped `text`.
+    fn text(&mut self, tag: &str, text: &str) -> io::Result<()> {
+        self.line()?;
+        if text.is_empty() && self.profile.self_closing {
+            return write!(self.out, "<{}/>", tag);
+        }
+        write!(self.out, "<{}>{}</{}>", tag, text, tag)
+    }
+
+    // A `<value>` holding escaped `text`, inside `tag` unless it is `None`.
+    fn scalar(&mut self, tag: Option<&str>, text: &str) -> io::Result<()> {
+        let tag = match tag {
+            Some(tag) => tag,
+            None => return self.text("value", text),
+        };
+        self.line()?;
+        self.out.write_all(b"<value><")?;
+        self.out.write_all(tag.as_bytes())?;
+        if text.is_empty() && self.profile.self_closing {
+            self.out.write_all(b"/>")?;
+        } else {
+            self.out.write_all(b">")?;
+            self.out.write_all(text.as_bytes())?;
+            self.out.write_all(b"</")?;
+            self.out.write_all(tag.as_bytes())?;
+            self.out.write_all(b">")?;
+        }
+        self.out.write_all(b"</value>")
+    }
+
+    fn member<F>(&mut self, name: &str, value: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut Self) -> io::Result<()>,
+    {
+        self.open("member")?;
+        self.text("name", &partial_escape(name))?;
+        value(self)?;
+        self.close("member")
+    }
+
+    fn params(&mut self, params: &[Value]) -> io::Result<()> {
+        self.list("params", params.is_empty(), |w| {
+            for param in params {
+                w.open("param")?;
+                w.value(param)?;
+                w.close("param")?;
+            }
+            Ok(())
+        })
+    }
+
+    fn string(&mut self, text: &str) -> io::Result<()> {
+        let tag = if self.profile.bare_strings {
+            None
+        } else {
+            Some("string")
+        };
+        self.scalar(tag, &partial_escape(text))
+    }
+
+    fn value(&mut self, value: &Value) -> io::Result<()> {
+        match *value {
+            Value::Int(v) => {
+                let tag = match self.profile.int_tag {
+                    IntTag::I4 => "i4",
+                    IntTag::Int => "int",
+                };
+                self.scalar(Some(tag), itoa::Buffer::new().format(v))
+            }
+            Value::Int64(v) if self.profile.no_i8 => match i32::try_from(v) {
+                Ok(v) => self.value(&Value::Int(v)),
+                Err(_) => self.string(itoa::Buffer::new().format(v)),
+            },
+            Value::Int64(v) => self.scalar(Some("i8"), itoa::Buffer::new().format(v)),
+            Value::Bool(v) => self.scalar(Some("boolean"), if v { "1" } else { "0" }),
+            Value::String(ref v) => self.string(v),
+            Value::Double(v) => match self.profile.doubles {
+                Doubles::Shortest => {
+                    let mut buffer = ryu::Buffer::new();
+                    let text = buffer.format(v);
+                    // XML-RPC has no exponent notation, which ryu uses for
+                    // very large and small magnitudes.
+                    if text.contains('e') {
+                        self.scalar(Some("double"), &v.to_string())
+                    } else {
+                        self.scalar(Some("double"), text)
+                    }
+                }
+                Doubles::Fixed(digits) => self.scalar(Some("double"), &format!("{:.*}", digits, v)),
+            },
+            Value::DateTime(ref v) => self.scalar(Some("dateTime.iso8601"), &partial_escape(v)),
+            Value::Base64(ref v) => scratch::with_text(|text| {
+                base64::encode_config_buf(v, base64::STANDARD, text);
+                self.scalar(Some("base64"), text)
+            }),
+            Value::Array(ref v) => {
+                self.open("value")?;
+                self.open("array")?;
+                self.list("data", v.is_empty(), |w| {
+                    v.iter().try_for_each(|item| w.value(item))
+                })?;
+                self.close("array")?;
+                self.close("value")
+            }
+            Value::Struct(ref v) => {
+                self.open("value")?;
+                self.list("struct", v.is_empty(), |w| {
+                    for (name, value) in v {
+                        w.member(name, |w| w.value(value))?;
+                    }
+                    Ok(())
+                })?;
+                self.close("value")
+            }
+            Value::Nil => {
+                self.line()?;
+                self.out.write_all(b"<value><nil/></value>")
+            }
+        }
+    }
 }
 
 impl ToXml for Call {
     fn to_xml(&self) -> String {
-        format!(
-            include_str!("templates/call.xml"),
-            name = self.name,
-            params = self
-                .params
-                .iter()
-                .map(|param| format!("<param>{}</param>", param.to_xml()))
-                .collect::<String>()
-        )
+        xml_string(self)
+    }
+
+    fn write_xml<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        self.write_xml_as(out, &WireProfile::default())
+    }
+
+    fn write_xml_as<W: io::Write>(&self, out: &mut W, profile: &WireProfile) -> io::Result<()> {
+        let mut w = XmlWriter::new(out, profile);
+        w.declaration()?;
+        w.open("methodCall")?;
+        w.text("methodName", &partial_escape(&self.name))?;
+        w.params(&self.params)?;
+        w.close("methodCall")?;
+        w.out.write_all(b"\n")
     }
 }
 
 impl ToXml for Response {
     fn to_xml(&self) -> String {
+        xml_string(self)
+    }
+
+    fn write_xml<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        self.write_xml_as(out, &WireProfile::default())
+    }
+
+    fn write_xml_as<W: io::Write>(&self, out: &mut W, profile: &WireProfile) -> io::Result<()> {
+        let mut w = XmlWriter::new(out, profile);
+        w.declaration()?;
+        w.open("methodResponse")?;
         match *self {
-            Ok(ref params) => format!(
-                include_str!("templates/response_success.xml"),
-                params = params
-                    .iter()
-                    .map(|param| format!("<param>{}</param>", param.to_xml()))
-                    .collect::<String>()
-            ),
-            Err(Fault { code, ref message }) => format!(
-                include_str!("templates/response_fault.xml"),
-                code = code,
-                message = message
-            ),
+            Ok(ref params) => w.params(params)?,
+            Err(Fault { code, ref message }) => {
+                w.open("fault")?;
+                w.open("value")?;
+                w.open("struct")?;
+                w.member("faultCode", |w| {
+                    w.scalar(Some("int"), itoa::Buffer::new().format(code))
+                })?;
+                w.member("faultString", |w| w.string(message))?;
+                w.close("struct")?;
+                w.close("value")?;
+                w.close("fault")?;
+            }
         }
+        w.close("methodResponse")?;
+        w.out.write_all(b"\n")
     }
 }
 
 impl ToXml for Value {
     fn to_xml(&self) -> String {
-        match *self {
-            Value::Int(v) => format!("<value><i4>{}</i4></value>", v),
-            Value::Bool(v) => format!(
-                "<value><boolean>{}</boolean></value>",
-                if v { 1 } else { 0 }
-            ),
-            Value::String(ref v) => {
-                format!("<value><string>{}</string></value>", escape_str_pcdata(v))
-            }
-            Value::Double(v) => format!("<value><double>{}</double></value>", v),
-            Value::DateTime(ref v) => {
-                format!("<value><dateTime.iso8601>{}</dateTime.iso8601></value>", v)
-            }
-            Value::Base64(ref v) => {
-                format!("<value><base64>{}</base64></value>", base64::encode(v))
-            }
-            Value::Array(ref v) => format!(
-                "<value><array><data>{}</data></array></value>",
-                v.iter().map(Value::to_xml).collect::<String>()
-            ),
-            Value::Struct(ref v) => format!(
-                "<value><struct>{}</struct></value>",
-                v.iter()
-                    .map(|(key, value)| format!(
-                        "<member><name>{}</name>{}</member>",
-                        key,
-                        value.to_xml()
-                    ))
-                    .collect::<String>()
-            ),
-        }
+        xml_string(self)
+    }
+
+    fn write_xml<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        self.write_xml_as(out, &WireProfile::default())
+    }
+
+    fn write_xml_as<W: io::Write>(&self, out: &mut W, profile: &WireProfile) -> io::Result<()> {
+        XmlWriter::new(out, profile).value(self)
     }
 }
@@ -0,0 +1,313 @@
+//! The `#[xmlrpc_service]` attribute, re-exported by `xml-rpc` with its
+//! `macros` feature.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as Tokens};
+use syn::spanned::Spanned;
+use syn::{
+    Error, FnArg, GenericArgument, Ident, ItemTrait, LitStr, Pat, PathArguments, ReturnType,
+    TraitItem, TraitItemFn, Type,
+};
+
+/// Turns a trait into an XML-RPC service.
+///
+/// Every method must take `&self` and owned arguments, and return a
+/// `Result<T, E>` where `E: IntoFault + From<xml_rpc::error::Error>`, such
+/// as `xml_rpc::error::Error` itself. For a trait `Calculator` this adds:
+///
+/// * `register_calculator(&mut Server, service)`, which registers each
+///   method of `service` with the server;
+/// * `CalculatorClient`, which implements the trait by calling a remote
+///   service through a `Client`.
+///
+/// Methods are called by their Rust name, prefixed with `namespace.` if
+/// given as in `#[xmlrpc_service(namespace = "calc")]`. A method can be
+/// given another name with `#[xmlrpc(name = "getValue")]`.
+///
+/// Each argument travels as a parameter of its own, and the result as the
+/// single parameter of the response.
+#[proc_macro_attribute]
+pub fn xmlrpc_service(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut namespace = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("namespace") {
+            namespace = Some(meta.value()?.parse::<LitStr>()?.value());
+            Ok(())
+        } else {
+            Err(meta.error("expected `namespace = \"...\"`"))
+        }
+    });
+    if let Err(err) = syn::parse::Parser::parse(parser, attr) {
+        return err.to_compile_error().into();
+    }
+    let service = match syn::parse::<ItemTrait>(item) {
+        Ok(service) => service,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    match expand(namespace, service) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+struct Method {
+    ident: Ident,
+    wire_name: String,
+    args: Vec<(Ident, Type)>,
+    output: Type,
+    error: Type,
+}
+
+// `HTTPService` becomes `http_service`.
+fn snake_case(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut snake = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let after_lower = !chars[i - 1].is_uppercase();
+            let ends_acronym = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            if after_lower || ends_acronym {
+                snake.push('_');
+            }
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+// Takes the `#[xmlrpc(name = "...")]` attribute off `method`.
+fn take_name(method: &mut TraitItemFn) -> Result<Option<String>, Error> {
+    let mut name = None;
+    let mut result = Ok(());
+    method.attrs.retain(|attr| {
+        if !attr.path().is_ident("xmlrpc") {
+            return true;
+        }
+        let parsed = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("expected `name = \"...\"`"))
+            }
+        });
+        if let Err(err) = parsed {
+            result = Err(err);
+        }
+        false
+    });
+    result.map(|()| name)
+}
+
+// Splits `Result<T, E>` into `T` and `E`.
+fn result_types(output: &ReturnType) -> Result<(Type, Type), Error> {
+    let invalid = |span: Span| Error::new(span, "XML-RPC methods must return `Result<T, E>`");
+    let ty = match *output {
+        ReturnType::Type(_, ref ty) => ty,
+        ReturnType::Default => return Err(invalid(output.span())),
+    };
+    let segment = match **ty {
+        Type::Path(ref path) => path.path.segments.last(),
+        _ => None,
+    };
+    let args = match segment {
+        Some(segment) if segment.ident == "Result" => match segment.arguments {
+            PathArguments::AngleBracketed(ref args) => &args.args,
+            _ => return Err(invalid(ty.span())),
+        },
+        _ => return Err(invalid(ty.span())),
+    };
+    let mut types = args.iter().filter_map(|arg| match *arg {
+        GenericArgument::Type(ref ty) => Some(ty.clone()),
+        _ => None,
+    });
+    match (types.next(), types.next()) {
+        (Some(output), Some(error)) => Ok((output, error)),
+        _ => Err(invalid(ty.span())),
+    }
+}
+
+fn parse_method(namespace: &Option<String>, method: &mut TraitItemFn) -> Result<Method, Error> {
+    let renamed = take_name(method)?;
+    let sig = &method.sig;
+    if !sig.generics.params.is_empty() || sig.asyncness.is_some() {
+        return Err(Error::new(
+            sig.span(),
+            "XML-RPC methods cannot be generic or async",
+        ));
+    }
+    let mut inputs = sig.inputs.iter();
+    match inputs.next() {
+        Some(FnArg::Receiver(receiver))
+            if receiver.reference.is_some() && receiver.mutability.is_none() => {}
+        _ => return Err(Error::new(sig.span(), "XML-RPC methods must take `&self`")),
+    }
+    let mut args = Vec::new();
+    for input in inputs {
+        let arg = match *input {
+            FnArg::Typed(ref arg) => arg,
+            FnArg::Receiver(ref receiver) => {
+                return Err(Error::new(receiver.span(), "unexpected receiver"))
+            }
+        };
+        let ident = match *arg.pat {
+            Pat::Ident(ref pat) => pat.ident.clone(),
+            _ => return Err(Error::new(arg.pat.span(), "expected an argument name")),
+        };
+        if let Type::Reference(_) = *arg.ty {
+            return Err(Error::new(
+                arg.ty.span(),
+                "XML-RPC arguments must be owned types",
+            ));
+        }
+        args.push((ident, (*arg.ty).clone()));
+    }
+    let (output, error) = result_types(&sig.output)?;
+    let name = renamed.unwrap_or_else(|| sig.ident.to_string());
+    let wire_name = match *namespace {
+        Some(ref namespace) => format!("{}.{}", namespace, name),
+        None => name,
+    };
+    Ok(Method {
+        ident: sig.ident.clone(),
+        wire_name,
+        args,
+        output,
+        error,
+    })
+}
+
+fn registration(method: &Method) -> Tokens {
+    let ident = &method.ident;
+    let wire_name = &method.wire_name;
+    let names = method.args.iter().map(|(name, _)| name).collect::<Vec<_>>();
+    let types = method.args.iter().map(|(_, ty)| ty).collect::<Vec<_>>();
+    // A single parameter is not wrapped in an array by `from_params`.
+    let decode = match names.len() {
+        0 => quote! { let _ = params; },
+        1 => quote! {
+            let #(#names)* = match ::xml_rpc::from_params::<#(#types)*>(params) {
+                Ok(value) => value,
+                Err(err) => return ::xml_rpc::server::on_decode_fail(&err),
+            };
+        },
+        _ => quote! {
+            let (#(#names,)*) = match ::xml_rpc::from_params::<(#(#types,)*)>(params) {
+                Ok(values) => values,
+                Err(err) => return ::xml_rpc::server::on_decode_fail(&err),
+            };
+        },
+    };
+    quote! {
+        {
+            let service = ::std::sync::Arc::clone(&service);
+            server.register_value(#wire_name, move |params| {
+                #decode
+                let value = service
+                    .#ident(#(#names),*)
+                    .map_err(::xml_rpc::IntoFault::into_fault)?;
+                ::xml_rpc::into_params(&(value,))
+                    .or_else(|err| ::xml_rpc::server::on_encode_fail(&err))
+            });
+        }
+    }
+}
+
+fn client_method(method: &Method) -> Tokens {
+    let ident = &method.ident;
+    let wire_name = &method.wire_name;
+    let output = &method.output;
+    let error = &method.error;
+    let names = method.args.iter().map(|(name, _)| name).collect::<Vec<_>>();
+    let types = method.args.iter().map(|(_, ty)| ty);
+    let params = if names.is_empty() {
+        quote! { ::std::vec::Vec::new() }
+    } else {
+        quote! {
+            ::xml_rpc::into_params(&(#(#names,)*)).map_err(::xml_rpc::error::Error::from)?
+        }
+    };
+    quote! {
+        fn #ident(&self, #(#names: #types),*) -> ::std::result::Result<#output, #error> {
+            let params = #params;
+            let response = self
+                .client
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .call_value(&self.url, #wire_name, params)?;
+            match response {
+                Ok(params) => ::xml_rpc::from_params(params)
+                    .map_err(|err| ::xml_rpc::error::Error::from(err).into()),
+                Err(fault) => Err(::xml_rpc::error::Error::Fault(fault).into()),
+            }
+        }
+    }
+}
+
+fn expand(namespace: Option<String>, mut service: ItemTrait) -> Result<Tokens, Error> {
+    if !service.generics.params.is_empty() {
+        return Err(Error::new(
+            service.generics.span(),
+            "XML-RPC services cannot be generic",
+        ));
+    }
+    let mut methods = Vec::new();
+    for item in &mut service.items {
+        if let TraitItem::Fn(ref mut method) = *item {
+            methods.push(parse_method(&namespace, method)?);
+        }
+    }
+    let vis = &service.vis;
+    let trait_ident = &service.ident;
+    let register = Ident::new(
+        &format!("register_{}", snake_case(&trait_ident.to_string())),
+        trait_ident.span(),
+    );
+    let client = Ident::new(&format!("{}Client", trait_ident), trait_ident.span());
+    let registrations = methods.iter().map(registration);
+    let client_methods = methods.iter().map(client_method);
+    let register_doc = format!("Serves the methods of `{}` on `server`.", trait_ident);
+    let client_doc = format!("Calls a remote `{}` service.", trait_ident);
+
+    Ok(quote! {
+        #service
+
+        #[doc = #register_doc]
+        #vis fn #register<S>(server: &mut ::xml_rpc::Server, service: S)
+        where
+            S: #trait_ident + Send + Sync + 'static,
+        {
+            let service = ::std::sync::Arc::new(service);
+            #(#registrations)*
+        }
+
+        #[doc = #client_doc]
+        #vis struct #client {
+            client: ::std::sync::Mutex<::xml_rpc::Client>,
+            url: ::xml_rpc::Url,
+        }
+
+        impl #client {
+            #vis fn new(url: ::xml_rpc::Url) -> ::xml_rpc::error::Result<#client> {
+                Ok(#client::with_client(::xml_rpc::Client::new()?, url))
+            }
+
+            #vis fn with_client(client: ::xml_rpc::Client, url: ::xml_rpc::Url) -> #client {
+                #client {
+                    client: ::std::sync::Mutex::new(client),
+                    url,
+                }
+            }
+        }
+
+        impl #trait_ident for #client {
+            #(#client_methods)*
+        }
+    })
+}